@@ -0,0 +1,298 @@
+//! Arithmetic for the built-in `calculate` tool.
+//!
+//! A small recursive-descent evaluator over `f64`: `+ - * / %`, `^` (or `**`, right
+//! associative), parentheses, unary signs, the constants `pi` and `e`, and the usual
+//! math functions. Errors name the offending token and its position so the model can
+//! fix its expression and retry.
+
+use anyhow::{anyhow, Result};
+
+/// Deepest nesting of parentheses, function calls, signs and powers accepted, so a
+/// pathological expression fails with an error instead of overflowing the stack
+const MAX_NESTING: usize = 100;
+
+/// Evaluate `expression`, failing on syntax errors, unknown names and results that aren't
+/// finite numbers (division by zero, `sqrt(-1)`, overflow)
+pub fn evaluate(expression: &str) -> Result<f64> {
+    let mut parser = Parser { chars: expression.chars().collect(), pos: 0, depth: 0 };
+    parser.skip_whitespace();
+    if parser.peek().is_none() {
+        return Err(anyhow!("Expression is empty"));
+    }
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    if let Some(c) = parser.peek() {
+        return Err(anyhow!("Unexpected '{}' at position {}", c, parser.pos + 1));
+    }
+    if !value.is_finite() {
+        return Err(anyhow!("Result is not a finite number (division by zero or out of range?)"));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `token` (after whitespace) if it comes next
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = token.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    fn unexpected(&self) -> anyhow::Error {
+        match self.peek() {
+            Some(c) => anyhow!("Unexpected '{}' at position {}", c, self.pos + 1),
+            None => anyhow!("Expression ends unexpectedly"),
+        }
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat("+") {
+                value += self.term()?;
+            } else if self.eat("-") {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+        loop {
+            // `**` never reaches here: `power` consumes it first
+            if self.eat("*") {
+                value *= self.unary()?;
+            } else if self.eat("/") {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err(anyhow!("Division by zero"));
+                }
+                value /= divisor;
+            } else if self.eat("%") {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err(anyhow!("Division by zero"));
+                }
+                value %= divisor;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// unary := ('-' | '+') unary | power
+    ///
+    /// Every nested sub-expression passes through here, so this is where depth is counted.
+    fn unary(&mut self) -> Result<f64> {
+        if self.depth == MAX_NESTING {
+            return Err(anyhow!("Expression is nested more than {} levels deep at position {}", MAX_NESTING, self.pos + 1));
+        }
+        self.depth += 1;
+        let value = if self.eat("-") {
+            self.unary().map(|v| -v)
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.power()
+        };
+        self.depth -= 1;
+        value
+    }
+
+    /// power := primary (('^' | '**') unary)?, so `2^3^2` is `2^9` and `-2^2` is `-4`
+    fn power(&mut self) -> Result<f64> {
+        let base = self.primary()?;
+        if self.eat("^") || self.eat("**") {
+            Ok(base.powf(self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// primary := number | constant | function '(' args ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<f64> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expression()?;
+                if !self.eat(")") {
+                    return Err(anyhow!("Missing ')' at position {}", self.pos + 1));
+                }
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect::<String>().to_lowercase();
+                if self.eat("(") {
+                    let args = self.arguments()?;
+                    call(&name, &args)
+                } else {
+                    match name.as_str() {
+                        "pi" => Ok(std::f64::consts::PI),
+                        "e" => Ok(std::f64::consts::E),
+                        _ => Err(anyhow!("Unknown name '{}' at position {}", name, start + 1)),
+                    }
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Comma-separated arguments after an opening '(' up to and including the ')'
+    fn arguments(&mut self) -> Result<Vec<f64>> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            if self.eat(")") {
+                return Ok(args);
+            }
+            if !self.eat(",") {
+                return Err(anyhow!("Expected ',' or ')' at position {}", self.pos + 1));
+            }
+        }
+    }
+
+    /// Decimal number with optional fraction and exponent: `12`, `.5`, `1.5e-3`
+    fn number(&mut self) -> Result<f64> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        // Exponent only when digits follow, so `2e` stays an error rather than swallowing `e`
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let mut end = self.pos + 1;
+            if matches!(self.chars.get(end), Some('+' | '-')) {
+                end += 1;
+            }
+            if self.chars.get(end).is_some_and(char::is_ascii_digit) {
+                self.pos = end;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>().map_err(|_| anyhow!("Invalid number '{}' at position {}", text, start + 1))
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64> {
+    let one = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => Err(anyhow!("{}() takes 1 argument, got {}", name, args.len())),
+    };
+    match name {
+        "sqrt" => one(f64::sqrt),
+        "abs" => one(f64::abs),
+        "exp" => one(f64::exp),
+        "ln" => one(f64::ln),
+        "log" | "log10" => one(f64::log10),
+        "log2" => one(f64::log2),
+        "sin" => one(f64::sin),
+        "cos" => one(f64::cos),
+        "tan" => one(f64::tan),
+        "asin" => one(f64::asin),
+        "acos" => one(f64::acos),
+        "atan" => one(f64::atan),
+        "floor" => one(f64::floor),
+        "ceil" => one(f64::ceil),
+        "round" => one(f64::round),
+        "pow" => match args {
+            [base, exponent] => Ok(base.powf(*exponent)),
+            _ => Err(anyhow!("pow() takes 2 arguments, got {}", args.len())),
+        },
+        "min" | "max" if args.is_empty() => Err(anyhow!("{}() needs at least 1 argument", name)),
+        "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        _ => Err(anyhow!(
+            "Unknown function '{}' (available: sqrt, abs, exp, ln, log, log2, sin, cos, tan, asin, acos, atan, floor, ceil, round, pow, min, max)",
+            name
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> f64 {
+        evaluate(expression).unwrap_or_else(|e| panic!("{}: {}", expression, e))
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("2 ** 10 / 4"), 256.0);
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("7 % 4 * -1"), -3.0);
+        assert_eq!(eval("1.5e3 + .5"), 1500.5);
+    }
+
+    #[test]
+    fn test_functions_and_constants() {
+        assert_eq!(eval("sqrt(16) + abs(-2)"), 6.0);
+        assert_eq!(eval("max(1, 5, 3) - min(4, 2)"), 3.0);
+        assert_eq!(eval("pow(2, 8)"), 256.0);
+        assert_eq!(eval("log(1000)"), 3.0);
+        assert!((eval("2 * PI") - std::f64::consts::TAU).abs() < 1e-12);
+        assert!((eval("ln(e)") - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_malformed_expressions() {
+        let error = |expression: &str| evaluate(expression).unwrap_err().to_string();
+        assert_eq!(error(""), "Expression is empty");
+        assert_eq!(error("2 +"), "Expression ends unexpectedly");
+        assert_eq!(error("(1 + 2"), "Missing ')' at position 7");
+        assert_eq!(error("3 $ 4"), "Unexpected '$' at position 3");
+        assert_eq!(error("1 / (2 - 2)"), "Division by zero");
+        assert!(error("foo(1)").starts_with("Unknown function 'foo'"));
+        assert_eq!(error("x + 1"), "Unknown name 'x' at position 1");
+        assert_eq!(error("sqrt(1, 2)"), "sqrt() takes 1 argument, got 2");
+        assert!(error("sqrt(-1)").starts_with("Result is not a finite number"));
+    }
+
+    #[test]
+    fn test_nesting_depth_is_capped() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(eval(&nested(MAX_NESTING - 1)), 1.0);
+        let error = evaluate(&nested(100_000)).unwrap_err().to_string();
+        assert!(error.starts_with("Expression is nested more than"), "{}", error);
+        assert!(evaluate(&"-".repeat(100_000)).is_err());
+        assert!(evaluate(&"2^".repeat(100_000)).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use crate::embeddings::EmbeddingBackend;
+use crate::ollama::{ApiFlavor, BackoffStrategy, ModelOptions};
 
 fn default_max_tool_turns() -> usize {
     5
@@ -46,11 +48,17 @@ fn default_embedding_dimensions() -> usize {
     768
 }
 
+fn default_local_embedding_model() -> String {
+    "nomic-ai/nomic-embed-text-v1.5".to_string()
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub ollama: OllamaConfig,
     #[serde(default)]
     pub research: ResearchConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
 }
 
 fn default_vision_model() -> String {
@@ -73,6 +81,14 @@ fn default_summarization_threshold_research() -> usize {
     50000  // 50K chars = ~12.5K tokens per worker result before summarization
 }
 
+fn default_max_attempts() -> usize {
+    10
+}
+
+fn default_retry_base_delay_secs() -> u64 {
+    2
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OllamaConfig {
     pub host: String,
@@ -87,6 +103,10 @@ pub struct OllamaConfig {
     pub embedding_model: String,
     #[serde(default = "default_embedding_dimensions")]
     pub embedding_dimensions: usize,
+    #[serde(default)]
+    pub embedding_backend: EmbeddingBackend,  // "ollama" (embedding_model on host) or "local" (fastembed)
+    #[serde(default = "default_local_embedding_model")]
+    pub local_embedding_model: String,  // fastembed model code used when embedding_backend = "local"
     #[serde(default = "default_max_refinement_iterations")]
     pub max_refinement_iterations: usize,
     #[serde(default = "default_max_document_iterations")]
@@ -105,6 +125,31 @@ pub struct OllamaConfig {
     pub summarization_threshold: usize,
     #[serde(default = "default_summarization_threshold_research")]
     pub summarization_threshold_research: usize,
+    #[serde(default)]
+    pub options: ModelOptions,
+    #[serde(default)]
+    pub research_options: ModelOptions,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: usize,
+    #[serde(default)]
+    pub retry_backoff: BackoffStrategy,
+    #[serde(default = "default_retry_base_delay_secs")]
+    pub retry_base_delay_secs: u64,
+    #[serde(default)]
+    pub api_flavor: ApiFlavor,  // "ollama" or "openai" (OpenAI-compatible chat completions)
+    #[serde(default)]
+    pub api_key: Option<String>,  // Bearer token for the server, if it requires one
+    #[serde(default)]
+    pub system_prompt: Option<String>,  // Persona/focus put before every chat query
+    #[serde(default)]
+    pub chat_instructions: Option<String>,  // Replaces the built-in formatting instructions for chat
+}
+
+impl OllamaConfig {
+    /// Options for research agents: `research_options` layered over the main `options`
+    pub fn research_model_options(&self) -> ModelOptions {
+        self.options.merged_with(&self.research_options)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -115,18 +160,233 @@ pub struct ResearchConfig {
     pub max_worker_count: usize,
     #[serde(default = "default_export_memories")]
     pub export_memories: bool,
+    #[serde(default = "default_keep_debate_transcript")]
+    pub keep_debate_transcript: bool,
+    #[serde(default = "default_include_run_details")]
+    pub include_run_details: bool,
+    #[serde(default = "default_semantic_source_dedup")]
+    pub semantic_source_dedup: bool,
+    #[serde(default = "default_verify_sources")]
+    pub verify_sources: bool,
+    #[serde(default = "default_interactive_plan")]
+    pub interactive_plan: bool,
+    #[serde(default = "default_plan_only")]
+    pub plan_only: bool,  // Stop after planning and show the sub-questions
+    #[serde(default)]
+    pub depth: crate::research::ResearchDepth,  // Initial depth preset; the UI can switch it per query
+    #[serde(default = "default_source_similarity_threshold")]
+    pub source_similarity_threshold: f32,
+    #[serde(default = "default_memory_dedup_threshold")]
+    pub memory_dedup_threshold: f32,
+    #[serde(default = "default_max_concurrent_workers")]
+    pub max_concurrent_workers: usize,  // 0 = unlimited
+    #[serde(default = "default_worker_timeout_secs")]
+    pub worker_timeout_secs: u64,  // 0 = no timeout
+    #[serde(default = "default_early_refinement_after")]
+    pub early_refinement_after: usize,  // 0 = no follow-up questions
+    #[serde(default = "default_gap_detection_at_fraction")]
+    pub gap_detection_at_fraction: f32,
+    #[serde(default = "default_repair_document_tables")]
+    pub repair_document_tables: bool,  // Fix table rows whose cell count doesn't match the header
 }
 
 fn default_export_memories() -> bool {
     false
 }
 
+fn default_keep_debate_transcript() -> bool {
+    false
+}
+
+fn default_include_run_details() -> bool {
+    true
+}
+
+fn default_semantic_source_dedup() -> bool {
+    false
+}
+
+fn default_verify_sources() -> bool {
+    false
+}
+
+fn default_interactive_plan() -> bool {
+    false
+}
+
+fn default_plan_only() -> bool {
+    false
+}
+
+fn default_source_similarity_threshold() -> f32 {
+    0.92
+}
+
+fn default_memory_dedup_threshold() -> f32 {
+    crate::shared_memory::DEFAULT_DEDUP_THRESHOLD
+}
+
+fn default_max_concurrent_workers() -> usize {
+    4
+}
+
+fn default_worker_timeout_secs() -> u64 {
+    600
+}
+
+fn default_early_refinement_after() -> usize {
+    2
+}
+
+fn default_gap_detection_at_fraction() -> f32 {
+    0.5
+}
+
+fn default_repair_document_tables() -> bool {
+    true
+}
+
 impl Default for ResearchConfig {
     fn default() -> Self {
         ResearchConfig {
             min_worker_count: 3,
             max_worker_count: 10,
             export_memories: false,
+            keep_debate_transcript: false,
+            include_run_details: true,
+            semantic_source_dedup: false,
+            verify_sources: false,
+            interactive_plan: false,
+            plan_only: false,
+            depth: crate::research::ResearchDepth::Deep,
+            source_similarity_threshold: 0.92,
+            memory_dedup_threshold: 0.95,
+            max_concurrent_workers: 4,
+            worker_timeout_secs: 600,
+            early_refinement_after: 2,
+            gap_detection_at_fraction: 0.5,
+            repair_document_tables: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UiConfig {
+    #[serde(default = "default_theme")]
+    pub theme: String,  // Name of a built-in iced theme, e.g. "Dark", "Nord", "Catppuccin Mocha"
+    #[serde(default = "default_text_scale")]
+    pub text_scale: f32,  // Multiplier for response text sizes, adjusted with Ctrl+= / Ctrl+-
+    #[serde(default)]
+    pub table_overflow: TableOverflow,  // What to do with table cells wider than the column limit
+    #[serde(default)]
+    pub archive_dir: Option<String>,  // When set, every response is also written here as markdown
+    #[serde(default = "default_history_page_size")]
+    pub history_page_size: usize,  // Sidebar entries loaded at a time ("Load more" fetches the next page)
+    #[serde(default)]
+    pub notifications: bool,  // Desktop notification when a query finishes while bob-bar isn't focused
+    #[serde(default)]
+    pub completion_alert: CompletionAlert,  // Flash/chime when a long query finishes while bob-bar isn't focused
+    #[serde(default = "default_completion_alert_after_secs")]
+    pub completion_alert_after_secs: u64,  // Queries shorter than this finish without an alert
+}
+
+/// How markdown table cells longer than the maximum column width are rendered
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TableOverflow {
+    /// Cut the cell off with an ellipsis
+    #[default]
+    Truncate,
+    /// Continue the cell on extra lines within the same row
+    Wrap,
+}
+
+/// How a long query that finishes in the background gets the user's attention
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionAlert {
+    #[default]
+    None,
+    /// Ask the window manager to flash/bounce the window
+    Flash,
+    /// Play a short tone (needs the `sound` cargo feature)
+    Chime,
+    /// Flash and chime
+    Both,
+}
+
+impl CompletionAlert {
+    pub fn flash(self) -> bool {
+        matches!(self, CompletionAlert::Flash | CompletionAlert::Both)
+    }
+
+    pub fn chime(self) -> bool {
+        matches!(self, CompletionAlert::Chime | CompletionAlert::Both)
+    }
+}
+
+fn default_completion_alert_after_secs() -> u64 {
+    60
+}
+
+fn default_theme() -> String {
+    "TokyoNight".to_string()
+}
+
+fn default_text_scale() -> f32 {
+    1.0
+}
+
+fn default_history_page_size() -> usize {
+    50
+}
+
+/// Set `key = value` in the `[ui]` table of a config.toml source, leaving every other
+/// line (including comments) untouched. The table is appended if it doesn't exist.
+fn set_ui_value(contents: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let new_line = format!("{} = {}", key, value);
+
+    let Some(header) = lines.iter().position(|l| l.trim() == "[ui]") else {
+        if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push("[ui]".to_string());
+        lines.push(new_line);
+        return lines.join("\n") + "\n";
+    };
+
+    let section_end = lines.iter().skip(header + 1)
+        .position(|l| l.trim_start().starts_with('['))
+        .map_or(lines.len(), |i| header + 1 + i);
+    let existing = (header + 1..section_end).find(|&i| {
+        lines[i].split('=').next().is_some_and(|k| k.trim() == key) && !lines[i].trim_start().starts_with('#')
+    });
+    match existing {
+        Some(i) => lines[i] = new_line,
+        None => {
+            // Keep it inside the table, before any blank lines that separate the next one
+            let mut insert_at = section_end;
+            while insert_at > header + 1 && lines[insert_at - 1].trim().is_empty() {
+                insert_at -= 1;
+            }
+            lines.insert(insert_at, new_line);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            theme: "TokyoNight".to_string(),
+            text_scale: 1.0,
+            table_overflow: TableOverflow::Truncate,
+            archive_dir: None,
+            history_page_size: 50,
+            notifications: false,
+            completion_alert: CompletionAlert::None,
+            completion_alert_after_secs: 60,
         }
     }
 }
@@ -142,6 +402,8 @@ impl Default for Config {
                 summarization_model: None,
                 embedding_model: "nomic-embed-text".to_string(),
                 embedding_dimensions: 768,
+                embedding_backend: EmbeddingBackend::Ollama,
+                local_embedding_model: default_local_embedding_model(),
                 max_refinement_iterations: 5,
                 max_document_iterations: 3,
                 max_debate_rounds: 2,
@@ -151,15 +413,26 @@ impl Default for Config {
                 max_tool_turns: 5,
                 summarization_threshold: 5000,
                 summarization_threshold_research: 50000,
+                options: ModelOptions::default(),
+                research_options: ModelOptions::default(),
+                max_attempts: 10,
+                retry_backoff: BackoffStrategy::Progressive,
+                retry_base_delay_secs: 2,
+                api_flavor: ApiFlavor::Ollama,
+                api_key: None,
+                system_prompt: None,
+                chat_instructions: None,
             },
             research: ResearchConfig::default(),
+            ui: UiConfig::default(),
         }
     }
 }
 
 impl Config {
-    pub fn load() -> Self {
-        let config_path = Self::get_config_path();
+    /// config.toml from config directory `dir`, or the defaults if it is missing or broken
+    pub fn load(dir: &Path) -> Self {
+        let config_path = Self::config_path(dir);
 
         if config_path.exists() {
             match fs::read_to_string(&config_path) {
@@ -181,19 +454,101 @@ impl Config {
         Config::default()
     }
 
-    pub fn get_config_path() -> PathBuf {
-        if let Some(home) = std::env::var_os("HOME") {
-            PathBuf::from(home).join(".config/bob-bar/config.toml")
+    /// Persist the response text scale to config.toml without disturbing the rest of the file
+    pub fn save_text_scale(dir: &Path, scale: f32) -> anyhow::Result<()> {
+        let config_path = Self::config_path(dir);
+        let contents = if config_path.exists() {
+            set_ui_value(&fs::read_to_string(&config_path)?, "text_scale", &format!("{:.1}", scale))
         } else {
-            PathBuf::from("config.toml")
+            // No config yet: write the defaults so the file stays loadable
+            let mut config = Config::default();
+            config.ui.text_scale = scale;
+            toml::to_string_pretty(&config)?
+        };
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        fs::write(&config_path, contents)?;
+        Ok(())
+    }
+
+    pub fn config_path(dir: &Path) -> PathBuf {
+        dir.join("config.toml")
     }
 
-    pub fn get_config_dir() -> PathBuf {
+    /// `~/.config/bob-bar`, used unless `--config-dir` names another directory. Everything
+    /// bob-bar reads or writes (config files, history, sessions, memory database) lives
+    /// in the config directory.
+    pub fn default_config_dir() -> PathBuf {
         if let Some(home) = std::env::var_os("HOME") {
             PathBuf::from(home).join(".config/bob-bar")
         } else {
             PathBuf::from(".")
         }
     }
-}
\ No newline at end of file
+}
+
+/// Describe a JSON config file that failed to parse: where, what, and what to look for
+pub fn json_error(file: &str, err: &serde_json::Error) -> anyhow::Error {
+    let message = err.to_string();
+    let position = format!(" at line {} column {}", err.line(), err.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message);
+    let hint = match err.classify() {
+        serde_json::error::Category::Syntax => "look for a missing comma, quote or bracket",
+        serde_json::error::Category::Eof => "the file ends early; check for an unclosed bracket or brace",
+        serde_json::error::Category::Data => "a field is missing, misspelled or has the wrong type",
+        serde_json::error::Category::Io => "the file could not be read",
+    };
+    anyhow::anyhow!("{} line {}, column {}: {} ({})", file, err.line(), err.column(), message, hint)
+}
+
+/// Fail with every problem found in a config file, one per line
+pub fn ensure_valid(file: &str, problems: Vec<String>) -> anyhow::Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = problems.iter().map(|p| format!("  • {}", p)).collect();
+    Err(anyhow::anyhow!("{} has {} problem{}:\n{}",
+        file, problems.len(), if problems.len() == 1 { "" } else { "s" }, list.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_error_names_file_position_and_hint() {
+        let err = serde_json::from_str::<serde_json::Value>("{\n  \"a\": 1\n  \"b\": 2\n}").unwrap_err();
+        assert_eq!(
+            json_error("tools.json", &err).to_string(),
+            "tools.json line 3, column 3: expected `,` or `}` (look for a missing comma, quote or bracket)"
+        );
+    }
+
+    #[test]
+    fn test_ensure_valid_lists_every_problem() {
+        assert!(ensure_valid("agents.json", Vec::new()).is_ok());
+        let err = ensure_valid("agents.json", vec!["first".to_string(), "second".to_string()]).unwrap_err();
+        assert_eq!(err.to_string(), "agents.json has 2 problems:\n  • first\n  • second");
+    }
+
+    #[test]
+    fn test_set_ui_value_replaces_key_in_ui_table() {
+        let source = "[ollama]\ntext_scale = 9\n\n[ui]\n# text_scale = 3.0\ntheme = \"Nord\"\ntext_scale = 1.0\n";
+        let updated = set_ui_value(source, "text_scale", "1.2");
+        assert_eq!(updated, "[ollama]\ntext_scale = 9\n\n[ui]\n# text_scale = 3.0\ntheme = \"Nord\"\ntext_scale = 1.2\n");
+    }
+
+    #[test]
+    fn test_set_ui_value_inserts_key_or_table() {
+        let source = "[ui]\ntheme = \"Nord\"\n\n[research]\nmax_worker_count = 3\n";
+        assert_eq!(
+            set_ui_value(source, "text_scale", "1.5"),
+            "[ui]\ntheme = \"Nord\"\ntext_scale = 1.5\n\n[research]\nmax_worker_count = 3\n"
+        );
+        assert_eq!(
+            set_ui_value("[ollama]\nmodel = \"m\"\n", "text_scale", "0.8"),
+            "[ollama]\nmodel = \"m\"\n\n[ui]\ntext_scale = 0.8\n"
+        );
+    }
+}
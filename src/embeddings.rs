@@ -1,67 +1,96 @@
-use anyhow::{anyhow, Result};
-use serde::{Deserialize, Serialize};
+use std::path::Path;
 
-#[derive(Debug, Serialize)]
-struct EmbeddingRequest {
-    model: String,
-    prompt: String,
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use crate::config::OllamaConfig;
+use crate::ollama::OllamaClient;
+
+/// Where memory and source-dedup embeddings are computed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingBackend {
+    /// `embedding_model` served by the configured Ollama/OpenAI host
+    #[default]
+    Ollama,
+    /// `local_embedding_model` run in-process with fastembed (ONNX); needs the
+    /// `local-embeddings` cargo feature
+    Local,
 }
 
-#[derive(Debug, Deserialize)]
-struct EmbeddingResponse {
-    embedding: Vec<f32>,
+/// Text embedder selected by `embedding_backend`
+pub enum Embedder {
+    Ollama(OllamaClient),
+    #[cfg(feature = "local-embeddings")]
+    Local(std::sync::Arc<fastembed::TextEmbedding>),
 }
 
-/// Generate an embedding vector for the given text using Ollama's embedding API
-pub async fn generate_embedding(
-    ollama_host: &str,
-    embedding_model: &str,
-    text: &str,
-) -> Result<Vec<f32>> {
-    let url = format!("{}/api/embeddings", ollama_host);
-
-    let request = EmbeddingRequest {
-        model: embedding_model.to_string(),
-        prompt: text.to_string(),
-    };
-
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(anyhow!("Embedding API error ({}): {}", status, body));
+impl Embedder {
+    /// Build the configured backend and check that it produces `embedding_dimensions`-sized
+    /// vectors. For the local backend the model is downloaded on first use and the
+    /// dimensions are known up front; Ollama models are checked on the first embedding.
+    /// Local models are cached under `models/` in config directory `config_dir`.
+    pub fn from_config(config: &OllamaConfig, config_dir: &Path) -> Result<Self> {
+        match config.embedding_backend {
+            EmbeddingBackend::Ollama => {
+                let mut client = OllamaClient::with_config(config.host.clone(), config.embedding_model.clone());
+                client.set_api(config.api_flavor, config.api_key.clone());
+                Ok(Embedder::Ollama(client))
+            }
+            EmbeddingBackend::Local => Self::local(&config.local_embedding_model, config.embedding_dimensions, config_dir),
+        }
     }
 
-    let embedding_response: EmbeddingResponse = response.json().await?;
+    #[cfg(feature = "local-embeddings")]
+    fn local(model_code: &str, embedding_dimensions: usize, config_dir: &Path) -> Result<Self> {
+        use fastembed::{InitOptions, TextEmbedding};
 
-    Ok(embedding_response.embedding)
-}
+        let info = TextEmbedding::list_supported_models()
+            .into_iter()
+            .find(|m| m.model_code.eq_ignore_ascii_case(model_code))
+            .ok_or_else(|| anyhow::anyhow!("Unknown local embedding model '{}'", model_code))?;
+        check_dimensions(model_code, info.dim, embedding_dimensions)?;
+
+        let options = InitOptions::new(info.model)
+            .with_cache_dir(config_dir.join("models"))
+            .with_show_download_progress(false);
+        Ok(Embedder::Local(std::sync::Arc::new(TextEmbedding::try_new(options)?)))
+    }
+
+    #[cfg(not(feature = "local-embeddings"))]
+    fn local(model_code: &str, _embedding_dimensions: usize, _config_dir: &Path) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "embedding_backend = \"local\" ({}) requires building with --features local-embeddings",
+            model_code
+        ))
+    }
 
-/// Generate embeddings for multiple texts in batch
-#[allow(dead_code)]
-pub async fn generate_embeddings_batch(
-    ollama_host: &str,
-    embedding_model: &str,
-    texts: &[String],
-) -> Result<Vec<Vec<f32>>> {
-    let mut embeddings = Vec::new();
-
-    for text in texts {
-        let embedding = generate_embedding(ollama_host, embedding_model, text).await?;
-        embeddings.push(embedding);
+    /// Embed `texts`, one vector per input in the same order
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        match self {
+            Embedder::Ollama(client) => client.embed(texts).await,
+            #[cfg(feature = "local-embeddings")]
+            Embedder::Local(model) => {
+                // ONNX inference is CPU-bound; keep it off the async runtime
+                let model = std::sync::Arc::clone(model);
+                let texts = texts.to_vec();
+                tokio::task::spawn_blocking(move || model.embed(texts, None)).await?
+            }
+        }
     }
+}
 
-    Ok(embeddings)
+/// Error unless a backend producing `actual`-sized vectors fits the `expected` vec table width
+pub fn check_dimensions(model: &str, actual: usize, expected: usize) -> Result<()> {
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "Embedding model '{}' produces {}-dimensional vectors but embedding_dimensions is {}",
+            model, actual, expected
+        ));
+    }
+    Ok(())
 }
 
 /// Calculate cosine similarity between two embedding vectors
-#[allow(dead_code)]
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() {
         return 0.0;
@@ -92,4 +121,11 @@ mod tests {
         let d = vec![0.0, 1.0, 0.0];
         assert!((cosine_similarity(&c, &d) - 0.0).abs() < 0.001);
     }
+
+    #[test]
+    fn test_check_dimensions() {
+        assert!(check_dimensions("nomic-embed-text", 768, 768).is_ok());
+        let err = check_dimensions("bge-small", 384, 768).unwrap_err().to_string();
+        assert!(err.contains("384") && err.contains("768"));
+    }
 }
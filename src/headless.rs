@@ -0,0 +1,196 @@
+//! Headless mode: `bob-bar --query "..."` answers one query on stdout and exits
+//! without opening a window, for use from scripts.
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{config, ollama, research, tools};
+
+/// What to run, parsed from the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub query: String,
+    pub research: bool,  // `--research`: run the multi-agent research pipeline
+    pub json: bool,  // `--json`: wrap the answer with model, elapsed time and token counts
+}
+
+impl Options {
+    /// `--query "..."` (or `--query=...`) plus optional `--research` and `--json`.
+    /// None when no query was given, i.e. the normal windowed app should start.
+    pub fn from_args(args: &[String]) -> anyhow::Result<Option<Self>> {
+        let mut query = None;
+        let mut query_value_index = None;
+        for (i, arg) in args.iter().enumerate() {
+            if arg == "--query" {
+                let value = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("--query needs the question to ask"))?;
+                if value.starts_with("--") {
+                    anyhow::bail!("--query needs the question to ask, got the flag {}", value);
+                }
+                query = Some(value.clone());
+                query_value_index = Some(i + 1);
+            } else if let Some(value) = arg.strip_prefix("--query=") {
+                query = Some(value.to_string());
+            }
+        }
+        // The question after `--query` is never itself a flag
+        let has_flag = |flag: &str| args.iter().enumerate().any(|(i, arg)| Some(i) != query_value_index && arg == flag);
+        let research = has_flag("--research");
+        let json = has_flag("--json");
+
+        match query {
+            Some(query) if query.trim().is_empty() => anyhow::bail!("--query needs a non-empty question"),
+            Some(query) => Ok(Some(Self { query, research, json })),
+            None if research || json => anyhow::bail!("--research and --json need --query \"...\""),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Answer the query and print it, returning the process exit code. Tools and agents come
+/// from config directory `config_dir`.
+pub fn run(config: config::Config, config_dir: &std::path::Path, options: Options) -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: could not start the async runtime: {}", e);
+            return 1;
+        }
+    };
+    match runtime.block_on(answer(&config, config_dir, &options)) {
+        Ok(output) => {
+            println!("{}", output.trim_end());
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+async fn answer(config: &config::Config, config_dir: &std::path::Path, options: &Options) -> anyhow::Result<String> {
+    let started = std::time::Instant::now();
+    ollama::reset_session_usage();
+
+    let tool_executor = load_tools(config_dir).await;
+    let mut client = crate::build_chat_client(&config.ollama);
+    if let Some(ref executor) = tool_executor {
+        client.set_tool_executor(executor.clone());
+    }
+
+    let (model, response) = if options.research {
+        let agents_path = config_dir.join("agents.json");
+        if !agents_path.exists() {
+            anyhow::bail!("--research needs {}", agents_path.display());
+        }
+        let research_model = config.ollama.research_model.clone()
+            .unwrap_or_else(|| config.ollama.model.clone());
+        let mut orchestrator = research::ResearchOrchestrator::from_file(
+            config_dir,
+            config.ollama.clone(),
+            Arc::new(Mutex::new(client)),
+            config.ollama.context_window,
+            research_model.clone(),
+            config.ollama.max_tool_turns,
+        )?;
+        orchestrator.override_config(&config.research);
+        if crate::PLAN_ONLY.load(std::sync::atomic::Ordering::Relaxed) {
+            orchestrator.set_plan_only(true);
+        }
+        if let Some(executor) = tool_executor {
+            orchestrator.set_tool_executor(executor);
+        }
+        (research_model, orchestrator.research(&options.query).await?)
+    } else {
+        let (model, question) = match crate::parse_model_override(&options.query) {
+            Some((model, question)) => (model.to_string(), question),
+            None => (config.ollama.model.clone(), options.query.as_str()),
+        };
+        client.set_model(model.clone());
+        let prompt = format!("{}\n\n{}", crate::TABLE_PLAIN_TEXT_RULES, question);
+        (model, client.query_streaming(&prompt, |_| {}).await?)
+    };
+
+    if !options.json {
+        return Ok(response);
+    }
+    Ok(serde_json::to_string_pretty(&json_output(options, &model, &response, started.elapsed(), &ollama::session_usage()))?)
+}
+
+/// tools.json from the config directory with its MCP servers started; None (with a
+/// warning) if it's missing or broken, so the query still runs without tools
+async fn load_tools(config_dir: &std::path::Path) -> Option<Arc<Mutex<tools::ToolExecutor>>> {
+    let tools_path = config_dir.join("tools.json");
+    if !tools_path.exists() {
+        return None;
+    }
+    match tools::ToolExecutor::from_file(config_dir) {
+        Ok(executor) => {
+            if let Err(e) = executor.initialize_mcp_servers().await {
+                tracing::warn!("Failed to initialize MCP servers: {}", e);
+            }
+            Some(Arc::new(Mutex::new(executor)))
+        }
+        Err(e) => {
+            tracing::warn!("Could not load tools config: {}", e);
+            None
+        }
+    }
+}
+
+fn json_output(options: &Options, model: &str, response: &str, elapsed: std::time::Duration, usage: &ollama::TokenUsage) -> serde_json::Value {
+    serde_json::json!({
+        "query": options.query,
+        "answer": response,
+        "model": model,
+        "research": options.research,
+        "elapsed_secs": (elapsed.as_secs_f64() * 10.0).round() / 10.0,
+        "usage": {
+            "prompt_tokens": usage.prompt_tokens,
+            "completion_tokens": usage.completion_tokens,
+            "calls": usage.calls,
+            "tokens_per_second": usage.tokens_per_second(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("bob-bar").chain(list.iter().copied()).map(String::from).collect()
+    }
+
+    #[test]
+    fn test_options_from_args() {
+        assert_eq!(Options::from_args(&args(&[])).unwrap(), None);
+        assert_eq!(Options::from_args(&args(&["--debug"])).unwrap(), None);
+        assert_eq!(
+            Options::from_args(&args(&["--query", "What is Rust?"])).unwrap(),
+            Some(Options { query: "What is Rust?".to_string(), research: false, json: false })
+        );
+        assert_eq!(
+            Options::from_args(&args(&["--json", "--query=@llama3 hi", "--research"])).unwrap(),
+            Some(Options { query: "@llama3 hi".to_string(), research: true, json: true })
+        );
+        assert!(Options::from_args(&args(&["--query"])).is_err());
+        assert!(Options::from_args(&args(&["--query", "  "])).is_err());
+        assert!(Options::from_args(&args(&["--research"])).is_err());
+        // A flag right after --query is not taken as the question
+        assert!(Options::from_args(&args(&["--query", "--research"])).is_err());
+        assert!(Options::from_args(&args(&["--query", "--json", "hi"])).is_err());
+    }
+
+    #[test]
+    fn test_json_output() {
+        let options = Options { query: "q".to_string(), research: false, json: true };
+        let usage = ollama::TokenUsage { prompt_tokens: 10, completion_tokens: 20, eval_duration_ns: 2_000_000_000, calls: 1 };
+        let value = json_output(&options, "llama3", "answer", std::time::Duration::from_millis(1234), &usage);
+        assert_eq!(value["answer"], "answer");
+        assert_eq!(value["model"], "llama3");
+        assert_eq!(value["elapsed_secs"], 1.2);
+        assert_eq!(value["usage"]["completion_tokens"], 20);
+        assert_eq!(value["usage"]["tokens_per_second"], 10.0);
+    }
+}
@@ -0,0 +1,188 @@
+//! Lightweight syntax highlighting for fenced code blocks.
+//!
+//! A single-pass tokenizer that recognises keywords, strings, comments and numbers for a
+//! handful of common languages. It is deliberately approximate: good enough to make code
+//! in answers readable, without pulling in a full grammar engine.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+struct Language {
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+        "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+        "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    // Single quotes are left alone: lifetimes would otherwise swallow the rest of the line
+    quotes: &['"'],
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+        "return", "try", "while", "with", "yield",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+};
+
+const JSON: Language = Language {
+    keywords: &["true", "false", "null"],
+    line_comment: None,
+    block_comment: None,
+    quotes: &['"'],
+};
+
+const BASH: Language = Language {
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for",
+        "function", "if", "in", "local", "read", "return", "set", "then", "until", "while",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+};
+
+/// Language from a fence info string such as `rust`, `py` or `rust,ignore`
+fn language(info: &str) -> Option<&'static Language> {
+    let name = info.split(|c: char| c.is_whitespace() || c == ',').next().unwrap_or("");
+    match name.to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "python" | "py" | "python3" => Some(&PYTHON),
+        "json" | "jsonc" => Some(&JSON),
+        "bash" | "sh" | "shell" | "zsh" | "console" => Some(&BASH),
+        _ => None,
+    }
+}
+
+/// Split `code` into colored tokens, or `None` when the language isn't recognised.
+/// Adjacent tokens of the same kind are merged, and concatenating the token text always
+/// gives back `code`.
+pub fn highlight(code: &str, info: &str) -> Option<Vec<(TokenKind, String)>> {
+    let lang = language(info)?;
+    let mut tokens: Vec<(TokenKind, String)> = Vec::new();
+    let mut push = |kind: TokenKind, text: &str| match tokens.last_mut() {
+        Some((last, existing)) if *last == kind => existing.push_str(text),
+        _ => tokens.push((kind, text.to_string())),
+    };
+
+    let mut i = 0;
+    while i < code.len() {
+        let rest = &code[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        let at_word_start = code[..i].chars().next_back().is_none_or(|p| !is_word_char(p));
+
+        let (kind, len) = if let Some((open, close)) = lang.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+            let end = rest[open.len()..].find(close).map_or(rest.len(), |e| open.len() + e + close.len());
+            (TokenKind::Comment, end)
+        } else if let Some(marker) = lang.line_comment.filter(|m| rest.starts_with(m)) {
+            // `#` only starts a comment at a word boundary, so `$#` and `a#b` stay code
+            if marker != "#" || code[..i].chars().next_back().is_none_or(char::is_whitespace) {
+                (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+            } else {
+                (TokenKind::Plain, c.len_utf8())
+            }
+        } else if lang.quotes.contains(&c) {
+            (TokenKind::String, string_len(rest, c))
+        } else if c.is_ascii_digit() && at_word_start {
+            (TokenKind::Number, rest.find(|ch: char| !(is_word_char(ch) || ch == '.')).unwrap_or(rest.len()))
+        } else if is_word_char(c) {
+            let len = rest.find(|ch: char| !is_word_char(ch)).unwrap_or(rest.len());
+            let kind = if lang.keywords.contains(&&rest[..len]) { TokenKind::Keyword } else { TokenKind::Plain };
+            (kind, len)
+        } else {
+            (TokenKind::Plain, c.len_utf8())
+        };
+
+        push(kind, &rest[..len]);
+        i += len;
+    }
+    Some(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte length of the string literal starting at the quote `rest[0]`, including both
+/// quotes. Unterminated strings run to the end of the block.
+fn string_len(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (idx, ch) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == quote {
+            return idx + ch.len_utf8();
+        }
+    }
+    rest.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str, info: &str) -> Vec<(TokenKind, String)> {
+        highlight(code, info).expect("known language")
+    }
+
+    #[test]
+    fn test_highlight_rust() {
+        let tokens = kinds("fn main() { let s = \"a \\\" b\"; // done\n}", "rust");
+        assert!(tokens.contains(&(TokenKind::Keyword, "fn".to_string())));
+        assert!(tokens.contains(&(TokenKind::Keyword, "let".to_string())));
+        assert!(tokens.contains(&(TokenKind::String, "\"a \\\" b\"".to_string())));
+        assert!(tokens.contains(&(TokenKind::Comment, "// done".to_string())));
+        assert_eq!(tokens.last(), Some(&(TokenKind::Plain, "\n}".to_string())));
+    }
+
+    #[test]
+    fn test_highlight_round_trips_text() {
+        let code = "x = 1.5  # note\nprint('it''s', $#, a#b) /* 😀 */\n\"unterminated";
+        for lang in ["python", "bash", "json", "rs"] {
+            let joined: String = kinds(code, lang).into_iter().map(|(_, t)| t).collect();
+            assert_eq!(joined, code, "{}", lang);
+        }
+    }
+
+    #[test]
+    fn test_highlight_hash_comments_and_numbers() {
+        let tokens = kinds("echo $# 42 # count", "sh");
+        assert!(tokens.contains(&(TokenKind::Keyword, "echo".to_string())));
+        assert!(tokens.contains(&(TokenKind::Number, "42".to_string())));
+        assert_eq!(tokens.last(), Some(&(TokenKind::Comment, "# count".to_string())));
+        assert!(!tokens.iter().any(|(k, t)| *k == TokenKind::Comment && t.contains("42")));
+
+        let json = kinds("{\"ok\": true, \"n\": 3}", "json");
+        assert!(json.contains(&(TokenKind::String, "\"ok\"".to_string())));
+        assert!(json.contains(&(TokenKind::Keyword, "true".to_string())));
+    }
+
+    #[test]
+    fn test_highlight_unknown_language() {
+        assert!(highlight("SELECT 1", "sql").is_none());
+        assert!(highlight("plain", "").is_none());
+        assert!(highlight("fn x() {}", "rust,ignore").is_some());
+    }
+}
@@ -1,73 +1,130 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use rusqlite::{params, Connection};
+use serde::Serialize;
 
-use crate::config::Config;
+use crate::timezone;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct HistoryEntry {
     pub id: i64,
     pub prompt: String,
     pub response: String,
-    #[allow(dead_code)]
     pub created_at: i64,
+    // Not recorded for entries saved before these were tracked
+    pub model: Option<String>,
+    pub research_mode: Option<bool>,
+    pub duration_ms: Option<i64>,
 }
 
-fn db_path() -> PathBuf {
-    Config::get_config_dir().join("history.sqlite")
+/// How a response was produced, stored alongside it
+#[derive(Debug, Clone, Default)]
+pub struct EntryDetails<'a> {
+    pub model: Option<&'a str>,
+    pub research_mode: bool,
+    pub duration_ms: Option<i64>,
 }
 
-fn ensure_dir() -> std::io::Result<()> {
-    let dir = Config::get_config_dir();
-    std::fs::create_dir_all(dir)
+/// Columns added after the first release, with their types. Existing rows get NULL,
+/// which reads back as "not recorded".
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("model", "TEXT"),
+    ("research_mode", "INTEGER"),
+    ("duration_ms", "INTEGER"),
+];
+
+const SELECT_COLUMNS: &str = "id, prompt, response, created_at, model, research_mode, duration_ms";
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        prompt: row.get(1)?,
+        response: row.get(2)?,
+        created_at: row.get(3)?,
+        model: row.get(4)?,
+        research_mode: row.get(5)?,
+        duration_ms: row.get(6)?,
+    })
+}
+
+/// The history database in config directory `dir`, creating the directory if needed
+fn open(dir: &Path) -> anyhow::Result<Connection> {
+    std::fs::create_dir_all(dir)?;
+    Ok(Connection::open(dir.join("history.sqlite"))?)
 }
 
-pub fn init() -> anyhow::Result<()> {
-    ensure_dir()?;
-    let conn = Connection::open(db_path())?;
+pub fn init(dir: &Path) -> anyhow::Result<()> {
+    let conn = open(dir)?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS history (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             prompt TEXT NOT NULL,
             response TEXT NOT NULL,
-            created_at INTEGER NOT NULL
+            created_at INTEGER NOT NULL,
+            model TEXT,
+            research_mode INTEGER,
+            duration_ms INTEGER
         )",
         [],
     )?;
+    migrate(&conn)?;
+    Ok(())
+}
+
+/// Bring databases created by older versions up to the current schema
+fn migrate(conn: &Connection) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('history')")?;
+    let columns: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+    for (name, kind) in ADDED_COLUMNS {
+        if !columns.iter().any(|c| c == name) {
+            conn.execute(&format!("ALTER TABLE history ADD COLUMN {} {}", name, kind), [])?;
+        }
+    }
     Ok(())
 }
 
-pub fn add_entry(prompt: &str, response: &str) -> anyhow::Result<()> {
-    ensure_dir()?;
-    let conn = Connection::open(db_path())?;
+pub fn add_entry(dir: &Path, prompt: &str, response: &str, details: &EntryDetails) -> anyhow::Result<()> {
+    let conn = open(dir)?;
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs() as i64;
     conn.execute(
-        "INSERT INTO history (prompt, response, created_at) VALUES (?1, ?2, ?3)",
-        params![prompt, response, now],
+        "INSERT INTO history (prompt, response, created_at, model, research_mode, duration_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![prompt, response, now, details.model, details.research_mode, details.duration_ms],
     )?;
     Ok(())
 }
 
-pub fn list_entries(limit: usize) -> anyhow::Result<Vec<HistoryEntry>> {
-    ensure_dir()?;
-    let conn = Connection::open(db_path())?;
-    let mut stmt = conn.prepare(
-        "SELECT id, prompt, response, created_at
+/// SQL `LIKE` pattern matching `query` anywhere, with `%`, `_` and `\` escaped
+fn like_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Most recent entries, newest first, skipping the first `offset`; optionally only those
+/// whose prompt or response contains `query` (case-insensitive for ASCII)
+pub fn list_entries(dir: &Path, limit: usize, offset: usize, query: Option<&str>) -> anyhow::Result<Vec<HistoryEntry>> {
+    let conn = open(dir)?;
+    let pattern = query
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(like_pattern);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
          FROM history
-         ORDER BY created_at DESC
-         LIMIT ?1",
-    )?;
-    let rows = stmt.query_map([limit as i64], |row| {
-        Ok(HistoryEntry {
-            id: row.get(0)?,
-            prompt: row.get(1)?,
-            response: row.get(2)?,
-            created_at: row.get(3)?,
-        })
-    })?;
+         WHERE ?2 IS NULL
+            OR prompt LIKE ?2 ESCAPE '\\'
+            OR response LIKE ?2 ESCAPE '\\'
+         ORDER BY created_at DESC, id DESC
+         LIMIT ?1 OFFSET ?3",
+        SELECT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![limit as i64, pattern, offset as i64], entry_from_row)?;
 
     let mut entries = Vec::new();
     for r in rows {
@@ -77,28 +134,158 @@ pub fn list_entries(limit: usize) -> anyhow::Result<Vec<HistoryEntry>> {
 }
 
 #[allow(dead_code)]
-pub fn get_entry(id: i64) -> anyhow::Result<Option<HistoryEntry>> {
-    ensure_dir()?;
-    let conn = Connection::open(db_path())?;
-    let mut stmt = conn.prepare(
-        "SELECT id, prompt, response, created_at FROM history WHERE id = ?1"
-    )?;
+pub fn get_entry(dir: &Path, id: i64) -> anyhow::Result<Option<HistoryEntry>> {
+    let conn = open(dir)?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM history WHERE id = ?1", SELECT_COLUMNS))?;
     let mut rows = stmt.query([id])?;
-    if let Some(row) = rows.next()? {
-        Ok(Some(HistoryEntry {
-            id: row.get(0)?,
-            prompt: row.get(1)?,
-            response: row.get(2)?,
-            created_at: row.get(3)?,
-        }))
-    } else {
-        Ok(None)
+    match rows.next()? {
+        Some(row) => Ok(Some(entry_from_row(row)?)),
+        None => Ok(None),
     }
 }
 
-pub fn delete_entry(id: i64) -> anyhow::Result<()> {
-    ensure_dir()?;
-    let conn = Connection::open(db_path())?;
+pub fn delete_entry(dir: &Path, id: i64) -> anyhow::Result<()> {
+    let conn = open(dir)?;
     conn.execute("DELETE FROM history WHERE id = ?1", params![id])?;
     Ok(())
 }
+
+/// File format for [export]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// Every stored entry, oldest first, as a single document
+pub fn export(dir: &Path, format: ExportFormat) -> anyhow::Result<String> {
+    let conn = open(dir)?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM history ORDER BY created_at ASC, id ASC", SELECT_COLUMNS))?;
+    let entries = stmt.query_map([], entry_from_row)?.collect::<Result<Vec<_>, _>>()?;
+    render_export(&entries, format)
+}
+
+fn render_export(entries: &[HistoryEntry], format: ExportFormat) -> anyhow::Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(entries)?),
+        ExportFormat::Markdown => Ok(entries.iter().map(markdown_entry).collect::<Vec<_>>().join("\n")),
+    }
+}
+
+/// `## prompt`, a metadata line, then the response in a fence long enough that code
+/// blocks inside it can't close it early
+fn markdown_entry(entry: &HistoryEntry) -> String {
+    let title = entry.prompt.lines().next().map(str::trim).filter(|t| !t.is_empty()).unwrap_or("(empty prompt)");
+    let mut meta = vec![utc_timestamp(entry.created_at)];
+    if let Some(research) = entry.research_mode {
+        meta.push(if research { "research" } else { "chat" }.to_string());
+    }
+    if let Some(model) = &entry.model {
+        meta.push(format!("model: {}", model));
+    }
+    if let Some(ms) = entry.duration_ms {
+        meta.push(format!("took {:.1}s", ms as f64 / 1000.0));
+    }
+
+    let mut doc = format!("## {}\n\n*{}*\n\n", title, meta.join(" · "));
+    // Multi-line prompts keep their full text; the heading only has room for the first line
+    if entry.prompt.trim().lines().nth(1).is_some() {
+        doc.push_str(&format!("> {}\n\n", entry.prompt.trim().replace('\n', "\n> ")));
+    }
+    let longest_run = entry.response.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    doc.push_str(&format!("{}markdown\n{}\n{}\n", fence, entry.response.trim_end(), fence));
+    doc
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ`
+pub fn utc_timestamp(unix_secs: i64) -> String {
+    let (year, month, day) = timezone::civil_from_days(unix_secs.div_euclid(86400));
+    let secs = unix_secs.rem_euclid(86400);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like_pattern_escapes_wildcards() {
+        assert_eq!(like_pattern("rust"), "%rust%");
+        assert_eq!(like_pattern("100%_done"), "%100\\%\\_done%");
+        assert_eq!(like_pattern("a\\b"), "%a\\\\b%");
+    }
+
+    #[test]
+    fn test_migrate_adds_missing_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY AUTOINCREMENT, prompt TEXT NOT NULL,
+             response TEXT NOT NULL, created_at INTEGER NOT NULL)",
+            [],
+        ).unwrap();
+        conn.execute("INSERT INTO history (prompt, response, created_at) VALUES ('q', 'a', 1)", []).unwrap();
+
+        migrate(&conn).unwrap();
+        migrate(&conn).unwrap();
+        let entry = conn.query_row(&format!("SELECT {} FROM history", SELECT_COLUMNS), [], entry_from_row).unwrap();
+        assert_eq!((entry.model, entry.research_mode, entry.duration_ms), (None, None, None));
+
+        conn.execute(
+            "INSERT INTO history (prompt, response, created_at, model, research_mode, duration_ms)
+             VALUES ('q2', 'a2', 2, 'llama3', ?1, 1500)",
+            params![true],
+        ).unwrap();
+        let entry = conn.query_row(&format!("SELECT {} FROM history WHERE id = 2", SELECT_COLUMNS), [], entry_from_row).unwrap();
+        assert_eq!((entry.model.as_deref(), entry.research_mode, entry.duration_ms), (Some("llama3"), Some(true), Some(1500)));
+    }
+
+    fn sample_entries() -> Vec<HistoryEntry> {
+        vec![
+            HistoryEntry {
+                id: 1,
+                prompt: "What is Rust?".to_string(),
+                response: "A language.\n\n```rust\nfn main() {}\n```\n".to_string(),
+                created_at: 1_759_579_200,
+                model: Some("llama3".to_string()),
+                research_mode: Some(true),
+                duration_ms: Some(1500),
+            },
+            HistoryEntry {
+                id: 2,
+                prompt: "old\nsecond line".to_string(),
+                response: "ok".to_string(),
+                created_at: 0,
+                model: None,
+                research_mode: None,
+                duration_ms: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_export_markdown() {
+        let doc = render_export(&sample_entries(), ExportFormat::Markdown).unwrap();
+        assert!(doc.starts_with("## What is Rust?\n\n*2025-10-04T12:00:00Z · research · model: llama3 · took 1.5s*\n\n````markdown\nA language."));
+        assert!(doc.contains("```\n````\n\n## old\n\n*1970-01-01T00:00:00Z*\n\n> old\n> second line\n\n```markdown\nok\n```\n"));
+    }
+
+    #[test]
+    fn test_render_export_json() {
+        let doc = render_export(&sample_entries(), ExportFormat::Json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&doc).unwrap();
+        assert_eq!(parsed[0]["prompt"], "What is Rust?");
+        assert_eq!(parsed[0]["created_at"], 1_759_579_200);
+        assert_eq!(parsed[0]["model"], "llama3");
+        assert_eq!(parsed[1]["research_mode"], serde_json::Value::Null);
+    }
+}
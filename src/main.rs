@@ -10,10 +10,18 @@ mod research;
 mod embeddings;
 mod shared_memory;
 mod dynamic_context;
+mod timezone;
+mod window_state;
+mod highlight;
+mod calculator;
+mod headless;
+mod markdown_check;
+#[cfg(test)]
+mod test_http;
 
 use iced::{
-    widget::{column, row, container, scrollable, text, text_input, button, text_input::Id, rich_text, span, text_editor, Space},
-    Element, Length, Task, Theme, Font, Subscription,
+    widget::{column, row, container, scrollable, text, text_input, button, rich_text, span, text_editor, progress_bar, stack, Space},
+    Element, Length, Task, Theme, Font, Subscription, font,
     time, clipboard,
     keyboard::{self, Key},
     event::{self, Event as IcedEvent},
@@ -25,22 +33,23 @@ use iced::widget::text as text_widget;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
-use pulldown_cmark::{Parser, Event as MarkdownEvent, Tag, HeadingLevel, Options, Alignment};
+use pulldown_cmark::{Parser, Event as MarkdownEvent, Tag, HeadingLevel, Options, Alignment, CodeBlockKind};
 use notify_rust::Notification;
 use iced::widget::scrollable::{Direction, Scrollbar};
 use std::sync::atomic::{AtomicBool, Ordering};
 use unicode_width::{UnicodeWidthStr, UnicodeWidthChar};
-use once_cell::sync::Lazy;
 use std::sync::Mutex as StdMutex;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 static DEBUG_MODE: AtomicBool = AtomicBool::new(false);
-const ENABLE_NOTIFICATIONS: bool = false;
-const TABLE_MAX_COL_WIDTH: usize = 80; // clamp overly wide columns to reduce horizontal scrolling
+static PLAN_ONLY: AtomicBool = AtomicBool::new(false);  // `--plan-only`: research stops after planning
+const TABLE_TARGET_WIDTH: usize = 120; // display columns a table may span before its widest columns shrink
+const TABLE_MIN_COL_WIDTH: usize = 8; // columns are never shrunk below this to fit TABLE_TARGET_WIDTH
 const INPUT_HEIGHT: f32 = 48.0; // approximate height to align sidebar header with input
 const TABLE_PLAIN_TEXT_RULES: &str = "When including Markdown tables in your response: 1) do not apply any styling (no bold, italics, code formatting) to table headers or table cell values; 2) do not use Unicode symbols or emoji inside any table cells — use plain ASCII text only (letters, numbers, basic punctuation).";
 
-// Global research progress store updated from background task, polled by Tick
-static RESEARCH_PROGRESS_GLOBAL: Lazy<StdMutex<Option<String>>> = Lazy::new(|| StdMutex::new(None));
 
 fn extract_hostname(url: &str) -> String {
     // Trim leading/trailing whitespace
@@ -64,25 +73,855 @@ fn extract_hostname(url: &str) -> String {
     host.to_string()
 }
 
-fn render_markdown(markdown: String) -> Element<'static, Message> {
+type MarkdownSpan = text_widget::Span<'static, Message>;
+
+/// A chunk of rendered markdown: a run of inline spans, a pre-formatted table (with its
+/// raw cell text for CSV export), a code block (with its source for copying), or quoted
+/// spans with their blockquote nesting depth
+#[derive(Clone)]
+enum MarkdownBlock {
+    Text(Vec<MarkdownSpan>),
+    Table(Vec<MarkdownSpan>, Vec<Vec<String>>),
+    Code(Vec<MarkdownSpan>, String),
+    Quote(usize, Vec<MarkdownSpan>),
+}
+
+/// Parsed markdown memoized by a hash of its source, so `view()` only re-parses
+/// when the text actually changes (new response, history selection, streamed chunk)
+#[derive(Default)]
+struct MarkdownCache {
+    entry: RefCell<Option<(u64, Vec<MarkdownBlock>)>>,
+    table_overflow: config::TableOverflow,  // How table cells wider than their fitted column are shown
+}
+
+impl MarkdownCache {
+    fn new(table_overflow: config::TableOverflow) -> Self {
+        MarkdownCache { table_overflow, ..Default::default() }
+    }
+
+    fn render(&self, markdown: &str, scale: f32) -> Element<'static, Message> {
+        let mut hasher = DefaultHasher::new();
+        markdown.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut entry = self.entry.borrow_mut();
+        if entry.as_ref().map(|(h, _)| *h) != Some(hash) {
+            *entry = Some((hash, markdown_blocks(markdown, self.table_overflow)));
+        }
+        render_blocks(entry.as_ref().map(|(_, blocks)| blocks.as_slice()).unwrap_or_default(), scale)
+    }
+
+    /// Header and body rows of the `index`-th table in the last rendered document
+    fn table_rows(&self, index: usize) -> Option<Vec<Vec<String>>> {
+        let entry = self.entry.borrow();
+        entry.as_ref()?.1.iter()
+            .filter_map(|block| match block {
+                MarkdownBlock::Table(_, rows) => Some(rows.clone()),
+                _ => None,
+            })
+            .nth(index)
+    }
+}
+
+/// Rows as CSV, quoting fields that contain commas, quotes or line breaks
+fn table_csv(rows: &[Vec<String>]) -> String {
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Smallest and largest response text scale reachable with the zoom shortcuts
+const MIN_TEXT_SCALE: f32 = 0.5;
+const MAX_TEXT_SCALE: f32 = 2.5;
+const TEXT_SCALE_STEP: f32 = 0.1;
+
+/// Next text scale after a zoom step (`None` resets), rounded to one decimal and clamped
+fn step_text_scale(scale: f32, zoom_in: Option<bool>) -> f32 {
+    let next = match zoom_in {
+        Some(true) => scale + TEXT_SCALE_STEP,
+        Some(false) => scale - TEXT_SCALE_STEP,
+        None => 1.0,
+    };
+    ((next * 10.0).round() / 10.0).clamp(MIN_TEXT_SCALE, MAX_TEXT_SCALE)
+}
+
+/// Copies of `spans` scaled for display; the cached spans are left as parsed
+fn scale_spans(spans: &[MarkdownSpan], scale: f32) -> Vec<MarkdownSpan> {
+    spans
+        .iter()
+        .map(|s| {
+            let mut s = s.clone();
+            s.size = s.size.map(|size| iced::Pixels(size.0 * scale));
+            s
+        })
+        .collect()
+}
+
+fn render_blocks(blocks: &[MarkdownBlock], scale: f32) -> Element<'static, Message> {
+    if blocks.is_empty() {
+        return text("").into();
+    }
+
+    let mut table_index = 0;
+    let mut elements: Vec<Element<'static, Message>> = blocks
+        .iter()
+        .map(|block| match block {
+            MarkdownBlock::Text(spans) => rich_text(scale_spans(spans, scale)).width(Length::Fill).into(),
+            MarkdownBlock::Table(spans, _) => {
+                let copy_csv = button(text("csv").size(12))
+                    .on_press(Message::CopyTableCsv(table_index))
+                    .padding([2, 6]);
+                table_index += 1;
+                column![
+                    row![Space::with_width(Length::Fill), copy_csv],
+                    container(rich_text(scale_spans(spans, scale))).padding(4).width(Length::Fill),
+                ]
+                .spacing(2)
+                .into()
+            }
+            MarkdownBlock::Code(spans, code) => {
+                let copy = button(text("copy").size(12))
+                    .on_press(Message::CopyCode(code.clone()))
+                    .padding([2, 6]);
+                stack![
+                    container(rich_text(scale_spans(spans, scale)))
+                        .padding(Padding { top: 8.0, right: 56.0, bottom: 8.0, left: 8.0 })
+                        .width(Length::Fill)
+                        .style(|_theme| container::Style {
+                            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.25).into()),
+                            ..container::Style::default()
+                        }),
+                    container(copy).align_right(Length::Fill).padding(4),
+                ]
+                .into()
+            }
+            MarkdownBlock::Quote(depth, spans) => container(rich_text(scale_spans(spans, scale)))
+                .padding(Padding { top: 4.0, right: 8.0, bottom: 4.0, left: 14.0 * *depth as f32 })
+                .width(Length::Fill)
+                .style(|_theme| container::Style {
+                    text_color: Some(Color::from_rgb(0.62, 0.66, 0.78)),
+                    background: Some(Color::from_rgba(0.6, 0.7, 1.0, 0.05).into()),
+                    ..container::Style::default()
+                })
+                .into(),
+        })
+        .collect();
+
+    if elements.len() == 1 {
+        elements.remove(0)
+    } else {
+        column(elements).spacing(6).into()
+    }
+}
+
+/// Split a table cell into lines of at most `width` display columns, breaking at spaces
+/// where possible and splitting words that are longer than a whole line
+fn wrap_cell(s: &str, width: usize) -> Vec<String> {
+    if width == 0 {
+        return vec![String::new()];
+    }
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0usize;
+    for word in s.split_whitespace() {
+        let word_width = UnicodeWidthStr::width(word);
+        if line_width > 0 && line_width + 1 + word_width <= width {
+            line.push(' ');
+            line.push_str(word);
+            line_width += 1 + word_width;
+            continue;
+        }
+        if line_width > 0 {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        for ch in word.chars() {
+            let cw = UnicodeWidthChar::width(ch).unwrap_or(0);
+            if line_width + cw > width && line_width > 0 {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(ch);
+            line_width += cw;
+        }
+    }
+    if line_width > 0 || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Prefix for a list item: two spaces per nesting level, then the ordinal or a bullet
+fn list_item_prefix(list_stack: &mut [Option<u64>]) -> String {
+    let indent = "  ".repeat(list_stack.len().saturating_sub(1));
+    match list_stack.last_mut() {
+        Some(Some(n)) => {
+            let prefix = format!("{}{}. ", indent, n);
+            *n += 1;
+            prefix
+        }
+        _ => format!("{}• ", indent),
+    }
+}
+
+/// Body text span carrying the active inline styles. Strikethrough is also dimmed so it
+/// stays distinguishable where the renderer draws no line.
+fn styled_span(content: String, bold: bool, italic: bool, strikethrough: bool) -> MarkdownSpan {
+    let mut text_span = span(content).size(15);
+    if italic {
+        let weight = if bold { font::Weight::Bold } else { font::Weight::Normal };
+        text_span = text_span.font(Font { weight, style: font::Style::Italic, ..Font::MONOSPACE });
+    }
+    match (bold, strikethrough) {
+        (true, true) => text_span.strikethrough(true).color(Color::from_rgb(0.72, 0.72, 0.78)),
+        (false, true) => text_span.strikethrough(true).color(Color::from_rgb(0.55, 0.55, 0.62)),
+        (true, false) => text_span.color(Color::from_rgb(1.0, 1.0, 1.0)),
+        (false, false) => text_span,
+    }
+}
+
+/// Monospace spans for a code block, colored by token when the fence names a language
+/// the highlighter knows and in the uniform code color otherwise
+fn code_block_spans(code: &str, lang: &str) -> Vec<MarkdownSpan> {
+    let code_span = |content: String, color: Color| span(content).font(Font::MONOSPACE).size(14).color(color);
+    let plain = Color::from_rgb(0.8, 0.9, 0.8);
+    match highlight::highlight(code, lang) {
+        Some(tokens) => tokens
+            .into_iter()
+            .map(|(kind, content)| {
+                let color = match kind {
+                    highlight::TokenKind::Plain => plain,
+                    highlight::TokenKind::Keyword => Color::from_rgb(0.8, 0.6, 1.0),
+                    highlight::TokenKind::String => Color::from_rgb(0.95, 0.8, 0.5),
+                    highlight::TokenKind::Comment => Color::from_rgb(0.55, 0.6, 0.55),
+                    highlight::TokenKind::Number => Color::from_rgb(0.6, 0.8, 1.0),
+                };
+                code_span(content, color)
+            })
+            .collect(),
+        None => vec![code_span(code.to_string(), plain)],
+    }
+}
+
+/// Only absolute web/mail links are opened; relative paths and anchors have nowhere to go
+fn is_openable_link(url: &str) -> bool {
+    let lower = url.trim().to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("mailto:")
+}
+
+/// Open a URL in the system's default handler without blocking the UI
+fn open_link(url: &str) {
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut c = std::process::Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    if let Err(e) = command.arg(url).spawn() {
+        tracing::warn!("Failed to open link {}: {}", url, e);
+    }
+}
+
+/// Local calendar date, seconds since local midnight and the zone in effect
+fn local_time_parts(unix_secs: i64) -> ((i64, u32, u32), i64, timezone::ResolvedOffset) {
+    let zone = timezone::resolve(None, unix_secs).unwrap_or(timezone::ResolvedOffset {
+        name: "UTC".to_string(),
+        offset_secs: 0,
+    });
+    let local = unix_secs + zone.offset_secs;
+    (timezone::civil_from_days(local.div_euclid(86400)), local.rem_euclid(86400), zone)
+}
+
+/// Local wall-clock time as `YYYY-MM-DD HH:MM (UTC±hh:mm)`
+fn format_local_timestamp(unix_secs: i64) -> String {
+    let ((year, month, day), seconds_today, zone) = local_time_parts(unix_secs);
+    format!("{:04}-{:02}-{:02} {:02}:{:02} (UTC{})",
+        year, month, day, seconds_today / 3600, (seconds_today % 3600) / 60, zone.offset_string())
+}
+
+/// Age of a timestamp for compact lists: "just now", "5m ago", "3h ago", "yesterday",
+/// "4 days ago", then the local date
+fn format_relative_time(unix_secs: i64, now: i64) -> String {
+    let elapsed = now - unix_secs;
+    if elapsed < 60 {
+        return "just now".to_string();
+    }
+    if elapsed < 3600 {
+        return format!("{}m ago", elapsed / 60);
+    }
+    if elapsed < 86400 {
+        return format!("{}h ago", elapsed / 3600);
+    }
+    let day_of = |secs: i64| {
+        let ((y, m, d), _, _) = local_time_parts(secs);
+        (y, m, d)
+    };
+    let (then_date, today) = (day_of(unix_secs), day_of(now));
+    let days_between = |(y1, m1, d1): (i64, u32, u32), (y2, m2, d2): (i64, u32, u32)| {
+        timezone::days_from_civil(y2, m2, d2) - timezone::days_from_civil(y1, m1, d1)
+    };
+    match days_between(then_date, today) {
+        ..=1 => "yesterday".to_string(),
+        days @ 2..=6 => format!("{} days ago", days),
+        _ => format!("{:04}-{:02}-{:02}", then_date.0, then_date.1, then_date.2),
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Status line under a response, e.g. `Tokens: 812 prompt · 240 completion · 31.4 tok/s`
+fn format_token_usage(usage: &ollama::TokenUsage, research: bool) -> String {
+    let mut line = if research {
+        format!("Session tokens ({} call{}): ", usage.calls, if usage.calls == 1 { "" } else { "s" })
+    } else {
+        "Tokens: ".to_string()
+    };
+    line.push_str(&format!("{} prompt · {} completion", usage.prompt_tokens, usage.completion_tokens));
+    if let Some(rate) = usage.tokens_per_second() {
+        line.push_str(&format!(" · {:.1} tok/s", rate));
+    }
+    line
+}
+
+/// Split a `@model-name question` prompt into the model to use for this one query and
+/// the question itself. Returns None when the prompt doesn't start with a model override.
+fn parse_model_override(input: &str) -> Option<(&str, &str)> {
+    let rest = input.trim_start().strip_prefix('@')?;
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let model = &rest[..end];
+    if model.is_empty() {
+        return None;
+    }
+    Some((model, rest[end..].trim_start()))
+}
+
+/// How often tools.json and agents.json are checked for edits
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Modification time of a watched config file, None if it doesn't exist
+fn modified_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Banner text for agents that list tools tools.json doesn't define, if any
+fn unknown_tools_warning(agents: &research::AgentsConfig, tools: &tools::ToolsConfig) -> Option<String> {
+    config::ensure_valid("agents.json", agents.unknown_tools(tools))
+        .err()
+        .map(|e| format!("{}\nThose agents run without the missing tools.", e))
+}
+
+/// Re-read tools.json into the running executor. Returns a line for the progress log and
+/// the problem to show, if any.
+async fn reload_tools(path: std::path::PathBuf, executor: Option<Arc<Mutex<tools::ToolExecutor>>>) -> (String, Option<String>) {
+    let config = match tools::ToolsConfig::load(&path) {
+        Ok(config) => config,
+        Err(e) => return (format!("Could not reload tools.json, keeping the previous tools: {}", e), Some(e.to_string())),
+    };
+    let Some(executor) = executor else {
+        return ("tools.json changed; restart bob-bar to enable tools".to_string(), None);
+    };
+
+    let restarted = executor.lock().await.apply_config(config).await;
+    if restarted.is_empty() {
+        ("Reloaded tools.json".to_string(), None)
+    } else {
+        (format!("Reloaded tools.json (restarted MCP servers: {})", restarted.join(", ")), None)
+    }
+}
+
+/// Re-read agents.json into the research orchestrator. A running research query holds
+/// the orchestrator, so the new agents apply from the next run.
+async fn reload_agents(
+    path: std::path::PathBuf,
+    orchestrator: Option<Arc<Mutex<research::ResearchOrchestrator>>>,
+    executor: Option<Arc<Mutex<tools::ToolExecutor>>>,
+) -> (String, Option<String>) {
+    let config = match research::ResearchOrchestrator::load_agents_config(&path) {
+        Ok(config) => config,
+        Err(e) => return (format!("Could not reload agents.json, keeping the previous agents: {}", e), Some(e.to_string())),
+    };
+    let Some(orchestrator) = orchestrator else {
+        return ("agents.json changed; restart bob-bar to enable research mode".to_string(), None);
+    };
+
+    let warning = match executor {
+        Some(executor) => unknown_tools_warning(&config, &executor.lock().await.config),
+        None => None,
+    };
+    orchestrator.lock().await.replace_agents_config(config);
+    ("Reloaded agents.json".to_string(), warning)
+}
+
+/// Startup check that every configured model is pulled; reports via `ModelsChecked`
+fn check_models_task(ollama: &config::OllamaConfig) -> Task<Message> {
+    let mut client = ollama::OllamaClient::with_config(ollama.host.clone(), ollama.model.clone());
+    client.set_api(ollama.api_flavor, ollama.api_key.clone());
+    let mut required = vec![ollama.model.clone(), ollama.vision_model.clone()];
+    if ollama.embedding_backend == embeddings::EmbeddingBackend::Ollama {
+        required.push(ollama.embedding_model.clone());
+    }
+    if let Some(research_model) = &ollama.research_model {
+        required.push(research_model.clone());
+    }
+    Task::perform(
+        async move {
+            client.list_models().await
+                .map(|available| ollama::missing_models(&required, &available))
+                .map_err(|e| e.to_string())
+        },
+        Message::ModelsChecked,
+    )
+}
+
+/// Markdown document written by [Save]: the query as an H1, an export timestamp, then the output
+fn export_markdown(query: &str, body: &str, timestamp: &str) -> String {
+    let title = query.lines().next().map(str::trim).filter(|t| !t.is_empty()).unwrap_or("bob-bar export");
+    format!("# {}\n\n*Exported {}*\n\n{}\n", title, timestamp, body.trim_end())
+}
+
+/// Stylesheet embedded in [render_to_html] pages
+const HTML_EXPORT_STYLE: &str = "\
+body { max-width: 52rem; margin: 2rem auto; padding: 0 1rem; font: 16px/1.6 -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; color: #1f2328; }
+h1, h2, h3 { line-height: 1.25; margin-top: 1.6em; }
+h1, h2 { border-bottom: 1px solid #d0d7de; padding-bottom: 0.3em; }
+a { color: #0969da; word-break: break-word; }
+table { border-collapse: collapse; margin: 1em 0; display: block; overflow-x: auto; }
+th, td { border: 1px solid #d0d7de; padding: 6px 12px; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+tr:nth-child(even) td { background: #fafbfc; }
+code { background: #f6f8fa; padding: 0.1em 0.3em; border-radius: 4px; font-size: 90%; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; border-radius: 6px; }
+pre code { background: none; padding: 0; }
+blockquote { margin: 0; padding: 0 1em; color: #59636e; border-left: 4px solid #d0d7de; }
+hr { border: 0; border-top: 1px solid #d0d7de; }
+";
+
+/// Self-contained HTML page for a markdown document (as written by [export_markdown]), so
+/// tables, headings and links render in any browser. The first H1 becomes the page title.
+fn render_to_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let mut body = String::new();
+    // Raw HTML in a model's answer is shown as text, never run by the browser
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        MarkdownEvent::Html(html) => MarkdownEvent::Text(html),
+        event => event,
+    });
+    pulldown_cmark::html::push_html(&mut body, events);
+
+    let title = markdown.lines().find_map(|line| line.strip_prefix("# ")).map(str::trim).unwrap_or("bob-bar export");
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        html_escape(title), HTML_EXPORT_STYLE, body
+    )
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Lowercase, dash-separated prefix of a query for use in file names; empty if the
+/// query has no alphanumeric characters
+fn query_slug(query: &str) -> String {
+    let slug: String = query
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = slug.chars().take(48).collect();
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Suggested file name for a query, e.g. "rust-async-runtimes.md"
+fn default_export_name(query: &str) -> String {
+    let slug = query_slug(query);
+    if slug.is_empty() { "bob-bar-export.md".to_string() } else { format!("{}.md", slug) }
+}
+
+/// Archived response: YAML frontmatter describing the query, then the response itself
+fn archive_markdown(query: &str, response: &str, model: &str, research: bool, unix_secs: i64) -> String {
+    let ((year, month, day), seconds_today, zone) = local_time_parts(unix_secs);
+    let timestamp = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year, month, day, seconds_today / 3600, (seconds_today % 3600) / 60, seconds_today % 60, zone.offset_string());
+    // JSON strings are valid YAML scalars and take care of quotes and newlines
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    format!("---\nquery: {}\nmodel: {}\nresearch: {}\ntimestamp: {}\n---\n\n{}\n",
+        quote(query), quote(model), research, timestamp, response.trim_end())
+}
+
+/// Whether a query that ran for `elapsed` deserves a completion alert: it took at least
+/// `threshold` and bob-bar isn't the focused window
+fn completion_alert_due(elapsed: Option<Duration>, threshold: Duration, window_focused: bool) -> bool {
+    !window_focused && elapsed.is_some_and(|elapsed| elapsed >= threshold)
+}
+
+/// Short two-tone chime on the default audio output, played off the UI thread
+#[cfg(feature = "sound")]
+fn play_chime() {
+    std::thread::spawn(|| {
+        use rodio::Source;
+
+        let Ok((_stream, handle)) = rodio::OutputStream::try_default() else {
+            tracing::warn!("No audio output for the completion chime");
+            return;
+        };
+        let Ok(sink) = rodio::Sink::try_new(&handle) else {
+            return;
+        };
+        for frequency in [660.0, 880.0] {
+            sink.append(rodio::source::SineWave::new(frequency)
+                .take_duration(Duration::from_millis(150))
+                .amplify(0.2));
+        }
+        sink.sleep_until_end();
+    });
+}
+
+#[cfg(not(feature = "sound"))]
+fn play_chime() {
+    tracing::warn!("ui.completion_alert wants a chime, but bob-bar was built without the `sound` feature");
+}
+
+/// Write a response to `archive_dir/YYYY-MM-DD/HH-MM-SS-<slug>.md`, creating the day's
+/// directory as needed. Never overwrites: a numeric suffix is added on collisions.
+fn archive_response(archive_dir: &std::path::Path, query: &str, response: &str, model: &str, research: bool, unix_secs: i64)
+    -> anyhow::Result<std::path::PathBuf>
+{
+    let ((year, month, day), seconds_today, _) = local_time_parts(unix_secs);
+    let dir = archive_dir.join(format!("{:04}-{:02}-{:02}", year, month, day));
+    std::fs::create_dir_all(&dir)?;
+
+    let slug = query_slug(query);
+    let stem = format!("{:02}-{:02}-{:02}-{}",
+        seconds_today / 3600, (seconds_today % 3600) / 60, seconds_today % 60,
+        if slug.is_empty() { "response" } else { slug.as_str() });
+    let mut path = dir.join(format!("{}.md", stem));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.md", stem, n));
+        n += 1;
+    }
+    std::fs::write(&path, archive_markdown(query, response, model, research, unix_secs))?;
+    Ok(path)
+}
+
+/// Ask the user where to save, using the platform's native dialog; files are filtered by
+/// the extension of `default_name`, which is also added to a chosen name that has none.
+/// Returns None if the dialog was cancelled.
+async fn choose_save_path(default_name: &str) -> Option<std::path::PathBuf> {
+    let extension = std::path::Path::new(default_name).extension().and_then(|e| e.to_str()).unwrap_or("md");
+    let handle = rfd::AsyncFileDialog::new()
+        .set_file_name(default_name)
+        .add_filter(extension, &[extension])
+        .save_file()
+        .await?;
+    let mut path = handle.path().to_path_buf();
+    if path.extension().is_none() {
+        path.set_extension(extension);
+    }
+    Some(path)
+}
+
+/// Prompt for a destination and write `contents` there
+async fn save_with_dialog(default_name: &str, contents: String) -> Result<Option<std::path::PathBuf>, String> {
+    let Some(path) = choose_save_path(default_name).await else {
+        return Ok(None);
+    };
+    tokio::fs::write(&path, contents).await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(Some(path))
+}
+
+/// Prompt for a destination and write the exported document there
+async fn save_output(query: String, body: String) -> Result<Option<std::path::PathBuf>, String> {
+    let markdown = export_markdown(&query, &body, &format_local_timestamp(unix_now()));
+    save_with_dialog(&default_export_name(&query), markdown).await
+}
+
+/// Prompt for a destination and write the output there as a standalone HTML page
+async fn save_output_html(query: String, body: String) -> Result<Option<std::path::PathBuf>, String> {
+    let default_name = std::path::Path::new(&default_export_name(&query)).with_extension("html");
+    let markdown = export_markdown(&query, &body, &format_local_timestamp(unix_now()));
+    save_with_dialog(&default_name.to_string_lossy(), render_to_html(&markdown)).await
+}
+
+/// Prompt for a destination and write the whole history there
+async fn save_history_export(config_dir: std::path::PathBuf, format: history::ExportFormat) -> Result<Option<std::path::PathBuf>, String> {
+    let document = tokio::task::spawn_blocking(move || history::export(&config_dir, format))
+        .await
+        .map_err(|e| format!("Export task failed: {}", e))?
+        .map_err(|e| format!("Failed to read history: {}", e))?;
+    save_with_dialog(&format!("bob-bar-history.{}", format.extension()), document).await
+}
+
+/// Prompt for a destination and write the research run's tool-call audit there
+async fn save_tool_audit(document: String, format: history::ExportFormat) -> Result<Option<std::path::PathBuf>, String> {
+    save_with_dialog(&format!("bob-bar-tool-audit.{}", format.extension()), document).await
+}
+
+/// Match a configured theme name against iced's built-in themes, ignoring case and spaces
+/// ("CatppuccinMocha", "catppuccin mocha"). Unknown names fall back to Tokyo Night.
+fn parse_theme(name: &str) -> Theme {
+    let normalize = |s: &str| s.chars().filter(|c| !c.is_whitespace() && *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    let wanted = normalize(name);
+    Theme::ALL
+        .iter()
+        .find(|theme| normalize(&theme.to_string()) == wanted)
+        .cloned()
+        .unwrap_or_else(|| {
+            tracing::warn!("Unknown theme '{}', using TokyoNight", name);
+            Theme::TokyoNight
+        })
+}
+
+/// The built-in theme after `current`, wrapping around
+fn next_theme(current: &Theme) -> Theme {
+    let index = Theme::ALL.iter().position(|theme| theme == current).unwrap_or(0);
+    Theme::ALL[(index + 1) % Theme::ALL.len()].clone()
+}
+
+/// Focuses the first focusable widget inside the container with the given id and unfocuses
+/// everything else. `text_editor` has no id of its own, so the prompt editor is wrapped in
+/// an identified container instead.
+struct FocusInside {
+    target: iced::advanced::widget::Id,
+    inside: bool,
+    focused: bool,
+}
+
+impl<T> iced::advanced::widget::Operation<T> for FocusInside {
+    fn container(
+        &mut self,
+        id: Option<&iced::advanced::widget::Id>,
+        _bounds: iced::Rectangle,
+        operate_on_children: &mut dyn FnMut(&mut dyn iced::advanced::widget::Operation<T>),
+    ) {
+        let entering = id == Some(&self.target);
+        if entering {
+            self.inside = true;
+        }
+        operate_on_children(self);
+        if entering {
+            self.inside = false;
+        }
+    }
+
+    fn focusable(&mut self, state: &mut dyn iced::advanced::widget::operation::Focusable, _id: Option<&iced::advanced::widget::Id>) {
+        if self.inside && !self.focused {
+            state.focus();
+            self.focused = true;
+        } else {
+            state.unfocus();
+        }
+    }
+}
+
+fn focus_input(id: &container::Id) -> Task<Message> {
+    iced::advanced::widget::operate(FocusInside {
+        target: id.clone().into(),
+        inside: false,
+        focused: false,
+    })
+}
+
+/// Enter submits the prompt; Shift+Enter falls through to the default binding (newline)
+fn input_key_binding(key_press: text_editor::KeyPress, input_empty: bool) -> Option<text_editor::Binding<Message>> {
+    let focused = key_press.status == text_editor::Status::Focused;
+    let is_enter = matches!(key_press.key.as_ref(), Key::Named(keyboard::key::Named::Enter));
+    if is_enter && !key_press.modifiers.shift() && focused {
+        return Some(text_editor::Binding::Custom(Message::Submit));
+    }
+    // Pasting into an empty prompt checks the clipboard for an image first
+    let is_paste = matches!(key_press.key.as_ref(), Key::Character("v")) && key_press.modifiers.command();
+    if is_paste && input_empty && focused {
+        return Some(text_editor::Binding::Custom(Message::PasteImage));
+    }
+    text_editor::Binding::from_key_press(key_press)
+}
+
+/// Receiver for the current run's progress, taken by the subscription stream on first poll
+type ProgressReceiver = Arc<StdMutex<Option<tokio::sync::mpsc::UnboundedReceiver<research::ResearchProgress>>>>;
+
+/// Forward every progress event of a research run, in order, as `Message::ResearchProgress`.
+/// `subscription()` rebuilds this stream on every call but iced only runs the first one per
+/// id, so the receiver is taken lazily rather than moved in.
+fn research_progress_stream(slot: ProgressReceiver) -> impl futures_util::Stream<Item = Message> {
+    futures_util::stream::unfold((slot, None), |(slot, mut receiver)| async move {
+        if receiver.is_none() {
+            receiver = slot.lock().ok().and_then(|mut guard| guard.take());
+        }
+        let progress = receiver.as_mut()?.recv().await?;
+        Some((Message::ResearchProgress(progress), (slot, receiver)))
+    })
+}
+
+/// `3m 12s`, or just `42s` under a minute
+fn format_duration_secs(secs: u64) -> String {
+    if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Remaining time if the run keeps its pace so far; `None` until there's enough signal
+fn format_eta(elapsed: Duration, fraction: f32) -> Option<String> {
+    if !(0.05..1.0).contains(&fraction) {
+        return None;
+    }
+    let remaining = (elapsed.as_secs_f32() * (1.0 - fraction) / fraction).round() as u64;
+    Some(format_duration_secs(remaining))
+}
+
+/// Detail line for a reopened history entry, e.g.
+/// `Research · llama3 · 2026-10-14 09:12 (UTC+02:00) · took 3m 12s`. Details that
+/// weren't recorded for older entries are left out.
+fn format_history_details(entry: &history::HistoryEntry) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    match entry.research_mode {
+        Some(true) => parts.push("Research".to_string()),
+        Some(false) => parts.push("Chat".to_string()),
+        None => {}
+    }
+    if let Some(model) = &entry.model {
+        parts.push(model.clone());
+    }
+    parts.push(format_local_timestamp(entry.created_at));
+    if let Some(ms) = entry.duration_ms {
+        parts.push(format!("took {}", format_duration_secs((ms.max(0) as u64 + 500) / 1000)));
+    }
+    parts.join(" · ")
+}
+
+/// Render a plan as editable `Worker: question` lines
+fn format_plan_for_editing(plan: &[research::SubQuestion]) -> String {
+    plan.iter()
+        .map(|q| format!("{}: {}", q.assigned_worker, q.question))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse edited plan lines back into sub-questions. A line is `Worker: question` when
+/// the prefix names a configured worker; otherwise the whole line is the question and
+/// it's assigned to the plan's workers (or, for an empty plan, the configured ones) in
+/// rotation. Fails when a line can't be given to any worker.
+fn parse_plan_edits(edited: &str, original: &[research::SubQuestion], configured: &[String])
+    -> Result<Vec<research::SubQuestion>, String>
+{
+    let mut workers: Vec<&str> = Vec::new();
+    for q in original {
+        if !workers.contains(&q.assigned_worker.as_str()) && configured.contains(&q.assigned_worker) {
+            workers.push(&q.assigned_worker);
+        }
+    }
+    if workers.is_empty() {
+        workers = configured.iter().map(String::as_str).collect();
+    }
+
+    let mut next_worker = 0;
+    edited
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if let Some((prefix, rest)) = line.split_once(':') {
+                let prefix = prefix.trim();
+                if configured.iter().any(|w| w == prefix) && !rest.trim().is_empty() {
+                    return Ok(research::SubQuestion {
+                        question: rest.trim().to_string(),
+                        assigned_worker: prefix.to_string(),
+                    });
+                }
+            }
+            if workers.is_empty() {
+                return Err(format!("No research worker to assign \"{}\" to; check the workers in agents.json", line));
+            }
+            let assigned_worker = workers[next_worker % workers.len()].to_string();
+            next_worker += 1;
+            Ok(research::SubQuestion { question: line.to_string(), assigned_worker })
+        })
+        .collect()
+}
+
+/// Move the input-recall cursor through history (newest first). `older` steps back in
+/// time; stepping newer past the most recent entry returns to an empty prompt (`None`).
+fn step_history_cursor(cursor: Option<usize>, len: usize, older: bool) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    match (cursor, older) {
+        (None, true) => Some(0),
+        (Some(i), true) => Some((i + 1).min(len - 1)),
+        (None, false) | (Some(0), false) => None,
+        (Some(i), false) => Some((i - 1).min(len - 1)),
+    }
+}
+
+fn ends_with_newline(spans: &[MarkdownSpan]) -> bool {
+    spans.last().map(|s| s.text.ends_with('\n')).unwrap_or(false)
+}
+
+fn markdown_blocks(markdown: &str, table_overflow: config::TableOverflow) -> Vec<MarkdownBlock> {
     let mut md_options = Options::empty();
     md_options.insert(Options::ENABLE_TABLES);
-    let parser = Parser::new_ext(&markdown, md_options);
-    let mut spans = Vec::new();
-    let mut blocks: Vec<Element<'static, Message>> = Vec::new();
-    let flush_spans = |spans: &mut Vec<_>, blocks: &mut Vec<Element<'static, Message>>| {
-        if !spans.is_empty() {
-            blocks.push(rich_text(spans.clone()).width(Length::Fill).into());
-            spans.clear();
+    md_options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext(markdown, md_options);
+    let mut spans: Vec<MarkdownSpan> = Vec::new();
+    let mut blocks: Vec<MarkdownBlock> = Vec::new();
+    let flush_spans = |spans: &mut Vec<MarkdownSpan>, blocks: &mut Vec<MarkdownBlock>, quote_depth: usize| {
+        if quote_depth > 0 {
+            // Trailing paragraph breaks would only pad the bottom of the quote
+            while spans.last().map(|s| s.text.trim().is_empty()).unwrap_or(false) {
+                spans.pop();
+            }
+            if !spans.is_empty() {
+                blocks.push(MarkdownBlock::Quote(quote_depth, std::mem::take(spans)));
+            }
+        } else if !spans.is_empty() {
+            blocks.push(MarkdownBlock::Text(std::mem::take(spans)));
         }
     };
     let mut current_text = String::new();
     let mut in_code_block = false;
     let mut code_block_content = String::new();
+    let mut code_block_lang = String::new();
     let mut in_bold = false;
-    let mut _in_italic = false;
+    let mut in_strikethrough = false;
+    let mut in_italic = false;
     let mut heading_level: Option<HeadingLevel> = None;
-    let mut in_list = false;
+    // One entry per open list: Some(next ordinal) for ordered lists, None for bullets
+    let mut list_stack: Vec<Option<u64>> = Vec::new();
+    let mut quote_depth = 0usize;
+    // Destination of the link being parsed and the index of its first span
+    let mut link_target: Option<(String, usize)> = None;
     // Table state
     let mut in_table = false;
     let mut in_table_head = false;
@@ -98,7 +937,7 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                 match tag {
                     Tag::Table(aligns) => {
                         // Flush any pending inline content before starting a table
-                        flush_spans(&mut spans, &mut blocks);
+                        flush_spans(&mut spans, &mut blocks, quote_depth);
                         in_table = true;
                         in_table_head = false;
                         header_rows.clear();
@@ -126,59 +965,93 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                         }
                         heading_level = Some(level);
                     }
-                    Tag::CodeBlock(_) => {
-                        // Flush current text before code block
+                    Tag::CodeBlock(kind) => {
+                        // Flush current text before code block; the block gets its own entry
                         if !current_text.is_empty() {
                             spans.push(span(current_text.clone()));
                             current_text.clear();
                         }
+                        flush_spans(&mut spans, &mut blocks, quote_depth);
                         in_code_block = true;
+                        code_block_lang = match kind {
+                            CodeBlockKind::Fenced(lang) => lang.to_string(),
+                            CodeBlockKind::Indented => String::new(),
+                        };
                     }
                     Tag::Strong => {
                         // Flush text before bold starts
                         if !current_text.is_empty() {
-                            spans.push(span(current_text.clone()).size(15));
+                            spans.push(styled_span(current_text.clone(), false, in_italic, in_strikethrough));
                             current_text.clear();
                         }
                         in_bold = true;
                     }
+                    Tag::Strikethrough => {
+                        // Flush text before strikethrough starts
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, false));
+                            current_text.clear();
+                        }
+                        in_strikethrough = true;
+                    }
+                    Tag::Link(_, dest, _) if !in_table => {
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
+                            current_text.clear();
+                        }
+                        link_target = Some((dest.to_string(), spans.len()));
+                    }
+                    Tag::BlockQuote => {
+                        // Quoted content gets its own indented block
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
+                            current_text.clear();
+                        }
+                        flush_spans(&mut spans, &mut blocks, quote_depth);
+                        quote_depth += 1;
+                    }
                     Tag::Emphasis => {
-                        _in_italic = true;
+                        // Flush text before italics start
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, false, in_strikethrough));
+                            current_text.clear();
+                        }
+                        in_italic = true;
                     }
                     Tag::Paragraph => {
                         // Flush text at paragraph start
                         if !current_text.is_empty() {
-                            let mut text_span = span(current_text.clone()).size(15);
-                            if in_bold {
-                                text_span = text_span.color(Color::from_rgb(1.0, 1.0, 1.0));
-                            }
-                            spans.push(text_span);
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
                             current_text.clear();
                         }
                     }
-                    Tag::List(_) => {
-                        // Add spacing before list only if not nested
-                        if !in_list {
-                            if !current_text.is_empty() {
-                                spans.push(span(current_text.clone()));
-                                current_text.clear();
-                            }
-                            if !spans.is_empty() {
+                    Tag::List(start) => {
+                        if !current_text.is_empty() {
+                            spans.push(span(current_text.clone()).size(15));
+                            current_text.clear();
+                        }
+                        if list_stack.is_empty() {
+                            // Add spacing before a top-level list
+                            if !spans.is_empty() && !ends_with_newline(&spans) {
                                 spans.push(span("\n"));
                             }
+                        } else if !ends_with_newline(&spans) {
+                            // Nested list starts on its own line below the parent item
+                            spans.push(span("\n"));
                         }
-                        in_list = true;
+                        list_stack.push(start);
                     }
                     Tag::Item => {
                         // Flush any pending text
                         if !current_text.is_empty() {
-                            let mut text_span = span(current_text.clone()).size(15);
-                            if in_bold {
-                                text_span = text_span.color(Color::from_rgb(1.0, 1.0, 1.0));
-                            }
-                            spans.push(text_span);
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
                             current_text.clear();
                         }
+                        spans.push(
+                            span(list_item_prefix(&mut list_stack))
+                                .size(15)
+                                .color(Color::from_rgb(0.6, 0.8, 1.0))
+                        );
                     }
                     _ => {}
                 }
@@ -209,11 +1082,11 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                         let mut col_widths = vec![0usize; cols];
                         for r in &rows {
                             for (i, cell) in r.iter().enumerate() {
-                                let len = UnicodeWidthStr::width(cell.as_str()).min(TABLE_MAX_COL_WIDTH);
+                                let len = UnicodeWidthStr::width(cell.as_str());
                                 col_widths[i] = col_widths[i].max(len);
                             }
                         }
-                        let eff_widths = col_widths.clone();
+                        let eff_widths = fit_column_widths(&col_widths, TABLE_TARGET_WIDTH);
 
                         const CELL_PAD: usize = 1;
 
@@ -265,22 +1138,41 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                             acc
                         };
 
+                        // One output line per cell line; with wrapping a row is as tall as its
+                        // tallest cell and shorter cells are padded with blank lines
+                        let render_row = |r: &[String]| -> Vec<String> {
+                            let cells: Vec<Vec<String>> = eff_widths.iter().enumerate()
+                                .map(|(i, &width)| {
+                                    let raw = r.get(i).map(|s| s.as_str()).unwrap_or("");
+                                    match table_overflow {
+                                        config::TableOverflow::Truncate => vec![truncate_to(raw, width)],
+                                        config::TableOverflow::Wrap => wrap_cell(raw, width),
+                                    }
+                                })
+                                .collect();
+                            let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+                            (0..height).map(|line_idx| {
+                                let mut line = String::new();
+                                line.push('│');
+                                for (i, cell) in cells.iter().enumerate() {
+                                    let s = cell.get(line_idx).map(|s| s.as_str()).unwrap_or("");
+                                    let align = table_alignments.get(i).cloned().unwrap_or(Alignment::Left);
+                                    for _ in 0..CELL_PAD { line.push(' '); }
+                                    line.push_str(&pad_cell(s, eff_widths[i], align));
+                                    for _ in 0..CELL_PAD { line.push(' '); }
+                                    line.push('│');
+                                }
+                                line
+                            }).collect()
+                        };
+
                         let mut table_lines: Vec<(String, &'static str)> = Vec::new();
 
                         table_lines.push((top_border.clone(), "border"));
                         for r in &header_rows {
-                            let mut line = String::new();
-                            line.push('│');
-                            for i in 0..cols {
-                                let raw = r.get(i).map(|s| s.as_str()).unwrap_or("");
-                                let s = truncate_to(raw, eff_widths[i]);
-                                let align = table_alignments.get(i).cloned().unwrap_or(Alignment::Left);
-                                for _ in 0..CELL_PAD { line.push(' '); }
-                                line.push_str(&pad_cell(&s, eff_widths[i], align));
-                                for _ in 0..CELL_PAD { line.push(' '); }
-                                line.push('│');
+                            for line in render_row(r) {
+                                table_lines.push((line, "header"));
                             }
-                            table_lines.push((line, "header"));
                         }
 
                         if !header_rows.is_empty() {
@@ -288,18 +1180,9 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                         }
 
                         for (idx, r) in body_rows.iter().enumerate() {
-                            let mut line = String::new();
-                            line.push('│');
-                            for i in 0..cols {
-                                let raw = r.get(i).map(|s| s.as_str()).unwrap_or("");
-                                let s = truncate_to(raw, eff_widths[i]);
-                                let align = table_alignments.get(i).cloned().unwrap_or(Alignment::Left);
-                                for _ in 0..CELL_PAD { line.push(' '); }
-                                line.push_str(&pad_cell(&s, eff_widths[i], align));
-                                for _ in 0..CELL_PAD { line.push(' '); }
-                                line.push('│');
+                            for line in render_row(r) {
+                                table_lines.push((line, "body"));
                             }
-                            table_lines.push((line, "body"));
                             if idx + 1 < body_rows.len() { table_lines.push((row_sep.clone(), "border")); }
                         }
 
@@ -319,7 +1202,7 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                             );
                         }
 
-                        blocks.push(container(rich_text(table_spans)).padding(4).width(Length::Fill).into());
+                        blocks.push(MarkdownBlock::Table(table_spans, rows));
 
                         // Reset table state
                         in_table = false;
@@ -382,62 +1265,92 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
                     }
                     Tag::CodeBlock(_) => {
                         if !code_block_content.is_empty() {
-                            spans.push(span("\n"));
-                            spans.push(
-                                span(code_block_content.clone())
-                                    .font(Font::MONOSPACE)
-                                    .size(14)
-                                    .color(Color::from_rgb(0.8, 0.9, 0.8))
-                            );
-                            spans.push(span("\n\n"));
-                            code_block_content.clear();
+                            blocks.push(MarkdownBlock::Code(
+                                code_block_spans(&code_block_content, &code_block_lang),
+                                std::mem::take(&mut code_block_content),
+                            ));
                         }
                         in_code_block = false;
                     }
                     Tag::Strong => {
                         // Flush bold text when exiting bold
                         if !current_text.is_empty() {
-                            spans.push(
-                                span(current_text.clone())
-                                    .size(15)
-                                    .color(Color::from_rgb(1.0, 1.0, 1.0))
-                            );
+                            spans.push(styled_span(current_text.clone(), true, in_italic, in_strikethrough));
                             current_text.clear();
                         }
                         in_bold = false;
                     }
+                    Tag::Link(_, _, _) => {
+                        if let Some((url, start)) = link_target.take() {
+                            if !current_text.is_empty() {
+                                spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
+                                current_text.clear();
+                            }
+                            // Links without a label show their destination instead
+                            let has_label = spans[start..].iter().any(|s| !s.text.trim().is_empty());
+                            if !has_label {
+                                spans.truncate(start);
+                                spans.push(span(url.clone()).size(15));
+                            }
+                            let openable = is_openable_link(&url);
+                            for link_span in spans[start..].iter_mut() {
+                                link_span.underline = true;
+                                link_span.color = Some(Color::from_rgb(0.45, 0.65, 1.0));
+                                if openable {
+                                    link_span.link = Some(Message::OpenLink(url.clone()));
+                                }
+                            }
+                        }
+                    }
+                    Tag::BlockQuote => {
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
+                            current_text.clear();
+                        }
+                        flush_spans(&mut spans, &mut blocks, quote_depth);
+                        quote_depth = quote_depth.saturating_sub(1);
+                    }
+                    Tag::Strikethrough => {
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, true));
+                            current_text.clear();
+                        }
+                        in_strikethrough = false;
+                    }
                     Tag::Emphasis => {
-                        _in_italic = false;
+                        if !current_text.is_empty() {
+                            spans.push(styled_span(current_text.clone(), in_bold, true, in_strikethrough));
+                            current_text.clear();
+                        }
+                        in_italic = false;
                     }
                     Tag::Paragraph => {
                         if !current_text.is_empty() {
-                            let mut text_span = span(current_text.clone()).size(15);
-                            if in_bold {
-                                text_span = text_span.color(Color::from_rgb(1.0, 1.0, 1.0));
-                            }
-                            spans.push(text_span);
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
                             current_text.clear();
                         }
                         // Only add newlines if not in a list
-                        if !in_list {
+                        if list_stack.is_empty() {
                             spans.push(span("\n\n"));
                         }
                     }
                     Tag::List(_) => {
+                        list_stack.pop();
                         // Add spacing after list only for top-level lists
-                        in_list = false;
+                        if list_stack.is_empty() {
+                            spans.push(span("\n"));
+                        }
                     }
                     Tag::Item => {
                         // Add newline after each list item
                         if !current_text.is_empty() {
-                            let mut text_span = span(current_text.clone()).size(15);
-                            if in_bold {
-                                text_span = text_span.color(Color::from_rgb(1.0, 1.0, 1.0));
-                            }
-                            spans.push(text_span);
+                            spans.push(styled_span(current_text.clone(), in_bold, in_italic, in_strikethrough));
                             current_text.clear();
                         }
-                        spans.push(span("\n"));
+                        // A nested list already ended the line for its parent item
+                        if !ends_with_newline(&spans) {
+                            spans.push(span("\n"));
+                        }
                     }
                     _ => {}
                 }
@@ -454,7 +1367,8 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
             MarkdownEvent::Code(code) => {
                 // Flush current text
                 if in_table {
-                    current_cell.push_str(&format!("`{}`", code));
+                    // The table is monospace already, so the backticks would only be noise
+                    current_cell.push_str(&code);
                 } else {
                     if !current_text.is_empty() {
                         spans.push(span(current_text.clone()));
@@ -479,98 +1393,250 @@ fn render_markdown(markdown: String) -> Element<'static, Message> {
         }
     }
 
-    // Add any remaining text
-    if !current_text.is_empty() {
-        spans.push(span(current_text));
+    // Add any remaining text
+    if !current_text.is_empty() {
+        spans.push(span(current_text));
+    }
+    flush_spans(&mut spans, &mut blocks, quote_depth);
+    // A block still streaming in has no closing fence yet
+    if !code_block_content.is_empty() {
+        blocks.push(MarkdownBlock::Code(code_block_spans(&code_block_content, &code_block_lang), code_block_content));
+    }
+    blocks
+}
+
+fn main() -> iced::Result {
+    // Parse command line arguments
+    let args: Vec<String> = std::env::args().collect();
+    let capture_mode = match screenshot_capture_mode(&args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let debug_mode = args.iter().any(|arg| arg == "--debug" || arg == "-debug");
+
+    // Set debug mode globally
+    DEBUG_MODE.store(debug_mode, Ordering::Relaxed);
+    init_logging(debug_mode);
+    PLAN_ONLY.store(args.iter().any(|arg| arg == "--plan-only"), Ordering::Relaxed);
+
+    let config_dir = match config_dir_arg(&args) {
+        Ok(dir) => dir.unwrap_or_else(config::Config::default_config_dir),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let headless_options = match headless::Options::from_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Get screen dimensions to calculate center
+    let config = config::Config::load(&config_dir);
+
+    if let Some(options) = headless_options {
+        // Answer on stdout without opening a window
+        std::process::exit(headless::run(config, &config_dir, options));
+    } else if let Some(mode) = capture_mode {
+        // Run in screenshot mode
+        run_screenshot_mode(config_dir, mode)
+    } else {
+        // Normal mode
+        iced::application("bob-bar", App::update, App::view)
+            .theme(App::theme)
+            .subscription(App::subscription)
+            .window(window_settings(&config_dir))
+            .default_font(Font::MONOSPACE)
+            .run_with(move || App::new(config_dir))
+    }
+}
+
+/// Log to stderr, filtered by `RUST_LOG` (e.g. `RUST_LOG=bob_bar::research=debug`) when set,
+/// otherwise at info level, or debug for all of bob-bar with `--debug`
+fn init_logging(debug_mode: bool) {
+    let default_filter = if debug_mode { "warn,bob_bar=debug" } else { "warn,bob_bar=info" };
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(default_filter));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// Column widths for a bordered table at most `max_total` display columns wide (borders
+/// and padding included): the widest columns are lowered to a common cap until the table
+/// fits, so narrow columns keep their natural width. Columns already at or under
+/// TABLE_MIN_COL_WIDTH aren't touched, so very many columns can still exceed `max_total`.
+fn fit_column_widths(widths: &[usize], max_total: usize) -> Vec<usize> {
+    // Each column also takes a space of padding either side and one border
+    let available = max_total.saturating_sub(widths.len() * 3 + 1);
+    if widths.iter().sum::<usize>() <= available {
+        return widths.to_vec();
     }
-    if !code_block_content.is_empty() {
-        spans.push(
-            span(code_block_content)
-                .font(Font::MONOSPACE)
-                .size(14)
-                .color(Color::from_rgb(0.8, 0.9, 0.8))
-        );
+    let fits = |cap: usize| widths.iter().map(|&w| w.min(cap)).sum::<usize>() <= available;
+    let mut cap = widths.iter().copied().max().unwrap_or(0);
+    while cap > TABLE_MIN_COL_WIDTH && !fits(cap) {
+        cap -= 1;
     }
-
-    if !blocks.is_empty() {
-        flush_spans(&mut spans, &mut blocks);
-        return column(blocks).spacing(6).into();
+    let mut fitted: Vec<usize> = widths.iter().map(|&w| w.min(cap)).collect();
+    // Spread what's left under the target over the capped columns, left to right
+    let mut spare = available.saturating_sub(fitted.iter().sum());
+    for (fitted, &natural) in fitted.iter_mut().zip(widths) {
+        if spare == 0 {
+            break;
+        }
+        if natural > *fitted {
+            *fitted += 1;
+            spare -= 1;
+        }
     }
+    fitted
+}
 
-    if spans.is_empty() {
-        text("").into()
-    } else {
-        rich_text(spans).width(Length::Fill).into()
+/// Lines of streamed output shown per research worker
+const WORKER_TEXT_LINES: usize = 3;
+/// Bytes of streamed output kept per research worker, enough for a few lines
+const WORKER_TEXT_TAIL: usize = 2000;
+
+/// Add a streamed chunk to the worker's buffer, keeping only its tail
+fn append_worker_text(buffers: &mut Vec<(String, String)>, worker: String, chunk: &str) {
+    let index = match buffers.iter().position(|(name, _)| *name == worker) {
+        Some(index) => index,
+        None => {
+            buffers.push((worker, String::new()));
+            buffers.len() - 1
+        }
+    };
+    let buffer = &mut buffers[index].1;
+    buffer.push_str(chunk);
+    if buffer.len() > WORKER_TEXT_TAIL {
+        let mut cut = buffer.len() - WORKER_TEXT_TAIL;
+        while !buffer.is_char_boundary(cut) {
+            cut += 1;
+        }
+        buffer.drain(..cut);
     }
 }
 
-fn main() -> iced::Result {
-    // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    let screenshot_mode = args.iter().any(|arg| arg == "--screenshot" || arg == "-screenshot");
-    let debug_mode = args.iter().any(|arg| arg == "--debug" || arg == "-debug");
+/// The last `n` non-blank lines of `text`
+fn last_lines(text: &str, n: usize) -> String {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
 
-    // Set debug mode globally
-    DEBUG_MODE.store(debug_mode, Ordering::Relaxed);
-    if debug_mode {
-        std::env::set_var("BOBBAR_DEBUG", "1");
+/// Client for normal chat queries, configured from `[ollama]` (tools are attached separately)
+fn build_chat_client(ollama_config: &config::OllamaConfig) -> ollama::OllamaClient {
+    let mut client = ollama::OllamaClient::with_config(
+        ollama_config.host.clone(),
+        ollama_config.model.clone(),
+    );
+    client.set_max_tool_turns(ollama_config.max_tool_turns);
+    client.set_options(ollama_config.options.clone());
+    client.set_context_window(ollama_config.context_window);
+    client.set_api(ollama_config.api_flavor, ollama_config.api_key.clone());
+    client.set_chat_prompts(ollama_config.system_prompt.clone(), ollama_config.chat_instructions.clone());
+    client.set_retry_policy(
+        ollama_config.max_attempts,
+        ollama_config.retry_backoff,
+        ollama_config.retry_base_delay_secs,
+    );
+    client.set_summarization_config(
+        ollama_config.summarization_model.clone(),
+        ollama_config.summarization_threshold,
+        false // Not research mode for main client
+    );
+    client
+}
+
+/// Window settings shared by both modes, restoring the size and position saved by the last run
+fn window_settings(config_dir: &std::path::Path) -> window::Settings {
+    let geometry = window_state::load(config_dir).unwrap_or_default();
+    let position = match (geometry.x, geometry.y) {
+        (Some(x), Some(y)) => window::Position::Specific(iced::Point::new(x, y)),
+        _ => window::Position::Centered,
+    };
+    window::Settings {
+        size: iced::Size::new(geometry.width, geometry.height),
+        position,
+        // Use a normal window level so it does not stay above others
+        level: Level::Normal,
+        decorations: true,
+        resizable: true,
+        // Closing goes through `WindowCloseRequested` so the geometry is saved first
+        exit_on_close_request: false,
+        platform_specific: PlatformSpecific {
+            ..Default::default()
+        },
+        ..Default::default()
     }
+}
 
-    // Get screen dimensions to calculate center
-    let config = config::Config::load();
+/// Config directory override from `--config-dir <path>` (also `--config-dir=<path>`, or
+/// `--config-path`), used for config.toml, tools.json, agents.json and api_keys.toml
+fn config_dir_arg(args: &[String]) -> anyhow::Result<Option<std::path::PathBuf>> {
+    for (i, arg) in args.iter().enumerate() {
+        let value = if arg == "--config-dir" || arg == "--config-path" {
+            Some(args.get(i + 1).ok_or_else(|| anyhow::anyhow!("{} needs a directory", arg))?.as_str())
+        } else {
+            arg.strip_prefix("--config-dir=").or_else(|| arg.strip_prefix("--config-path="))
+        };
+        if let Some(value) = value {
+            let dir = std::path::PathBuf::from(value);
+            if dir.is_file() {
+                anyhow::bail!("--config-dir must be a directory, but {} is a file", dir.display());
+            }
+            return Ok(Some(dir));
+        }
+    }
+    Ok(None)
+}
 
-    if screenshot_mode {
-        // Run in screenshot mode
-        run_screenshot_mode(config)
+/// Screenshot capture requested on the command line: `--screenshot` for the full screen,
+/// `--select-region` to drag a rectangle, or `--region x,y,w,h` (also `--region=x,y,w,h`)
+fn screenshot_capture_mode(args: &[String]) -> anyhow::Result<Option<screenshot::CaptureMode>> {
+    for (i, arg) in args.iter().enumerate() {
+        let spec = if arg == "--region" {
+            Some(args.get(i + 1).ok_or_else(|| anyhow::anyhow!("--region needs a value like 0,0,1280,720"))?.as_str())
+        } else {
+            arg.strip_prefix("--region=")
+        };
+        if let Some(spec) = spec {
+            return Ok(Some(screenshot::CaptureMode::Region(screenshot::Region::parse(spec)?)));
+        }
+    }
+    if args.iter().any(|arg| arg == "--select-region") {
+        Ok(Some(screenshot::CaptureMode::Select))
+    } else if args.iter().any(|arg| arg == "--screenshot" || arg == "-screenshot") {
+        Ok(Some(screenshot::CaptureMode::FullScreen))
     } else {
-        // Normal mode
-        iced::application("bob-bar", App::update, App::view)
-            .theme(App::theme)
-            .subscription(App::subscription)
-            .window(window::Settings {
-                size: iced::Size::new(1200.0, 1200.0),
-                position: window::Position::Centered,
-                // Use a normal window level so it does not stay above others
-                level: Level::Normal,
-                decorations: true,
-                resizable: true,
-                platform_specific: PlatformSpecific {
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .default_font(Font::MONOSPACE)
-            .run_with(App::new)
+        Ok(None)
     }
 }
 
-fn run_screenshot_mode(_config: config::Config) -> iced::Result {
+fn run_screenshot_mode(config_dir: std::path::PathBuf, capture_mode: screenshot::CaptureMode) -> iced::Result {
     iced::application("bob-bar", App::update, App::view)
         .theme(App::theme)
         .subscription(App::subscription)
-        .window(window::Settings {
-            size: iced::Size::new(1200.0, 1200.0),
-            position: window::Position::Centered,
-            // Screenshot mode should also not force always-on-top
-            level: Level::Normal,
-            decorations: true,
-            resizable: true,
-            platform_specific: PlatformSpecific {
-                ..Default::default()
-            },
-            ..Default::default()
-        })
+        // Screenshot mode should also not force always-on-top
+        .window(window_settings(&config_dir))
         .default_font(Font::MONOSPACE)
-        .run_with(|| {
-            let (mut app, task) = App::new();
+        .run_with(move || {
+            let (mut app, task) = App::new(config_dir);
             app.screenshot_mode = true;
 
             // Capture screenshot after a small delay to allow window to be hidden
-            let screenshot_task = Task::future(async {
+            let screenshot_task = Task::future(async move {
                 // Small delay to let the app window minimize/hide
                 tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
-                match screenshot::capture_screenshot() {
+                match screenshot::capture(capture_mode) {
                     Ok(path) => Message::ScreenshotCaptured(Ok(path)),
                     Err(e) => Message::ScreenshotCaptured(Err(e.to_string())),
                 }
@@ -582,82 +1648,270 @@ fn run_screenshot_mode(_config: config::Config) -> iced::Result {
 
 #[derive(Debug, Clone)]
 enum Message {
-    InputChanged(String),
+    InputAction(text_editor::Action),
     Submit,
+    Regenerate,
     NewQuery,
+    NewConversation,
+    ToggleConversationMode,
     ResponseReceived(String),
     Error(String),
     Tick,
     CopyOutput,
+    CopyTableCsv(usize),
+    CopyCode(String),
+    SaveOutput,
+    SaveOutputHtml,
+    ExportHistory(history::ExportFormat),
+    ExportToolAudit(history::ExportFormat),
+    OutputSaved(Result<Option<std::path::PathBuf>, String>),
     Exit,
     ScreenshotCaptured(Result<std::path::PathBuf, String>),
     ToggleFullscreen,
     HistorySelect(usize),
     HistoryDelete(usize),
+    HistoryLoadMore,
+    HistorySearch(String),
+    HistoryRecall(bool),
     ToggleSelectMode,
     OutputEditorAction(text_editor::Action),
     ToggleResearchMode,
-    #[allow(dead_code)]
+    CycleResearchDepth,
+    PlanEditorAction(text_editor::Action),
+    ApprovePlan(Vec<research::SubQuestion>),
+    ShowSessions,
+    OpenSession(std::path::PathBuf),
+    ResumeSession(std::path::PathBuf),
     ResearchProgress(research::ResearchProgress),
     CancelQuery,
+    OpenLink(String),
+    CycleTheme,
+    StreamChunk(String),
+    ReasoningReceived(String),
+    ToggleReasoning,
+    ModelsChecked(Result<Vec<String>, String>),
+    CheckConfigFiles,
+    ConfigReloaded(&'static str, String, Option<String>),  // file, log line, problem to show
+    PasteImage,
+    PasteText(Option<String>),
+    WindowMoved(iced::Point),
+    WindowResized(iced::Size),
+    WindowCloseRequested,
+    WindowFocusChanged(bool),
+    ZoomText(Option<bool>),
+    DismissModelWarning,
+    DismissConfigWarning,
 }
 
 struct App {
     input_text: String,
+    input_editor: text_editor::Content,
     response_text: String,
     streaming_text: String,
+    response_markdown: MarkdownCache,
+    streaming_markdown: MarkdownCache,
+    reasoning_text: String,
+    show_reasoning: bool,
     is_loading: bool,
     is_fullscreen: bool,
     loading_frame: usize,
     ollama_client: Arc<Mutex<ollama::OllamaClient>>,
-    input_id: Id,
+    input_id: container::Id,
     screenshot_mode: bool,
     screenshot_path: Option<std::path::PathBuf>,
     vision_model: String,
     history: Vec<history::HistoryEntry>,
+    history_page_size: usize,
+    history_has_more: bool,  // Older entries exist beyond those loaded
     selected_history: Option<usize>,
     select_mode: bool,
     output_editor: text_editor::Content,
     research_mode: bool,
+    research_depth: research::ResearchDepth,
     research_orchestrator: Option<Arc<Mutex<research::ResearchOrchestrator>>>,
+    tool_executor: Option<Arc<Mutex<tools::ToolExecutor>>>,
+    tools_modified: Option<std::time::SystemTime>,  // tools.json mtime when last (re)loaded
+    agents_modified: Option<std::time::SystemTime>,  // agents.json mtime when last (re)loaded
     research_progress: Option<String>,
     research_start_time: Option<std::time::Instant>,
+    pending_plan: Option<Vec<research::SubQuestion>>,
+    plan_workers: Vec<String>,  // Configured worker names the pending plan's lines may name
+    plan_editor: text_editor::Content,
+    plan_approval_tx: Option<tokio::sync::mpsc::UnboundedSender<Vec<research::SubQuestion>>>,
+    session_list: Option<Vec<(std::path::PathBuf, research::ResearchSession)>>,
+    research_run: u64,  // Bumped per run so each gets a fresh progress subscription
+    research_fraction: Option<f32>,  // Latest `Overall` estimate for the running research
+    worker_text: Vec<(String, String)>,  // Tail of each running worker's streamed answer, in start order
+    research_progress_rx: Option<ProgressReceiver>,
     current_query_cancel: Option<tokio_util::sync::CancellationToken>,
+    save_status: Option<String>,
+    theme: Theme,
+    history_filter: String,
+    history_cursor: Option<usize>,
+    model_warning: Option<String>,  // Startup preflight problem (missing models, Ollama unreachable)
+    config_warning: Option<String>,  // Problems found in tools.json / agents.json
+    window_geometry: window_state::WindowGeometry,  // Latest windowed size/position, saved on exit
+    text_scale: f32,  // Multiplier applied to response span sizes (Ctrl+= / Ctrl+- / Ctrl+0)
+    archive_dir: Option<std::path::PathBuf>,  // `ui.archive_dir`: every response is also saved here
+    notifications: bool,  // `ui.notifications`: desktop notification when a query finishes unfocused
+    completion_alert: config::CompletionAlert,  // `ui.completion_alert`: flash/chime after long unfocused queries
+    completion_alert_after: Duration,  // `ui.completion_alert_after_secs`
+    window_focused: bool,  // Tracked from window focus events
+    chat_model: String,
+    research_model: String,
+    query_model: String,  // Model answering the running query, recorded in the archive
+    query_research: bool,  // Whether the running query is a research run
+    token_usage: Option<ollama::TokenUsage>,  // Reported by the server for the last response
+    query_started: Option<std::time::Instant>,  // When the running query was submitted
+    history_details: Option<String>,  // Mode/model/timing line for a reopened history entry
+    conversation_mode: bool,  // Chat queries carry the earlier turns of `conversation`
+    conversation: Vec<ollama::ChatTurn>,  // Exchanges so far, trimmed to `conversation_budget` tokens
+    conversation_budget: usize,
+    pending_question: Option<String>,  // Question of the running conversation query, added once answered
+    last_query: Option<LastQuery>,  // What Regenerate replays; exports are named after it
+    config_dir: std::path::PathBuf,  // Where config files, history and window geometry live
+}
+
+/// The input of the most recent query, kept so Regenerate can send it again
+#[derive(Debug, Clone)]
+enum LastQuery {
+    Prompt(String),
+    Image(std::path::PathBuf),
+}
+
+impl LastQuery {
+    /// The typed prompt; screenshot queries have none
+    fn prompt(&self) -> &str {
+        match self {
+            LastQuery::Prompt(prompt) => prompt,
+            LastQuery::Image(_) => "",
+        }
+    }
 }
 
 impl App {
-    fn new() -> (Self, Task<Message>) {
+    fn save_window_geometry(&self) {
+        if let Err(e) = window_state::save(&self.config_dir, &self.window_geometry) {
+            tracing::warn!("Could not save window geometry: {}", e);
+        }
+    }
+
+    /// Replace the prompt text, keeping the editor widget in sync
+    fn set_input(&mut self, value: String) {
+        self.input_editor = text_editor::Content::with_text(&value);
+        self.input_text = value;
+    }
+
+    /// Launch a research run on the current input (or resume a saved session) with
+    /// progress monitoring and cancellation
+    fn start_research(&mut self, resume: Option<research::ResearchSession>) -> Task<Message> {
+        self.query_model = self.research_model.clone();
+        self.query_research = true;
+        ollama::reset_session_usage();
+        self.query_started = Some(std::time::Instant::now());
+        self.research_start_time = Some(std::time::Instant::now());
+        self.research_progress = Some("Starting research...".to_string());
+
+        // Create cancellation token for this query
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        self.current_query_cancel = Some(cancel_token.clone());
+
+        let query = self.input_text.clone();
+        let depth = self.research_depth;
+        let orchestrator = self.research_orchestrator.clone().unwrap();
+
+        // Progress events are delivered in order by `research_progress_subscription`
+        use tokio::sync::mpsc;
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        self.research_run += 1;
+        self.research_fraction = None;
+        self.worker_text.clear();
+        self.research_progress_rx = Some(Arc::new(StdMutex::new(Some(progress_rx))));
+        let (plan_tx, plan_rx) = mpsc::unbounded_channel();
+        self.plan_approval_tx = Some(plan_tx);
+
+        // Run the research task with cancellation support
+        Task::perform(
+            async move {
+                tokio::select! {
+                    result = async {
+                        let mut orch = orchestrator.lock().await;
+                        orch.set_progress_channel(progress_tx);
+                        orch.set_plan_approval_channel(plan_rx);
+                        orch.set_depth(depth);
+                        match resume {
+                            Some(session) => orch.resume_session(session).await,
+                            None => orch.research(&query).await,
+                        }
+                    } => result,
+                    _ = cancel_token.cancelled() => {
+                        Err(anyhow::anyhow!("Query cancelled by user"))
+                    }
+                }
+            },
+            |result| match result {
+                Ok(response) => Message::ResponseReceived(response),
+                Err(e) => Message::Error(format!("Research error: {}", e)),
+            }
+        )
+    }
+
+    /// Drop any plan awaiting approval along with the channel back to the orchestrator
+    fn clear_pending_plan(&mut self) {
+        self.pending_plan = None;
+        self.plan_approval_tx = None;
+    }
+
+    /// Reload the sidebar list, applying the current search filter
+    /// Keeps at least as many entries as were already loaded, so pages fetched with
+    /// "Load more" survive a refresh.
+    fn reload_history(&mut self) {
+        let limit = self.history.len().max(self.history_page_size);
+        self.history.clear();
+        self.load_history_page(limit);
+        self.history_cursor = None;
+    }
+
+    /// Append up to `limit` older entries to the sidebar list
+    fn load_history_page(&mut self, limit: usize) {
+        // One extra row tells us whether another page exists
+        let mut page = history::list_entries(&self.config_dir, limit + 1, self.history.len(), Some(&self.history_filter)).unwrap_or_default();
+        self.history_has_more = page.len() > limit;
+        page.truncate(limit);
+        self.history.extend(page);
+    }
+
+    /// Whether the prompt is empty or still holds the prompt recalled at `history_cursor`
+    fn input_is_unedited(&self) -> bool {
+        if self.input_text.is_empty() {
+            return true;
+        }
+        self.history_cursor
+            .and_then(|i| self.history.get(i))
+            .is_some_and(|entry| entry.prompt == self.input_text)
+    }
+
+    fn new(config_dir: std::path::PathBuf) -> (Self, Task<Message>) {
         // Load config
-        let config = config::Config::load();
+        let config = config::Config::load(&config_dir);
+
+        // Problems in tools.json / agents.json, shown in a banner so they're not only on stderr
+        let mut config_problems: Vec<String> = Vec::new();
 
         // Load tools from config directory
-        let tools_path = config::Config::get_config_dir().join("tools.json");
+        let tools_path = config_dir.join("tools.json");
+        let mut tools_config = None;  // Kept to check agents.json's tool references
         let tool_executor = if tools_path.exists() {
-            match tools::ToolExecutor::from_file(&tools_path) {
+            match tools::ToolExecutor::from_file(&config_dir) {
                 Ok(executor) => {
-                    // Print tool configuration on startup (only in debug mode)
-                    if DEBUG_MODE.load(Ordering::Relaxed) {
-                        eprintln!("=== Tool Configuration ===");
-                        eprintln!("Loaded from: {}", tools_path.display());
-
-                        // Print HTTP tools
-                        if !executor.config.tools.http.is_empty() {
-                            eprintln!("\nHTTP Tools ({}):", executor.config.tools.http.len());
-                            for tool in &executor.config.tools.http {
-                                eprintln!("  • {} - {}", tool.name, tool.description);
-                            }
-                        }
-
-                        // Print MCP tools
-                        if !executor.config.tools.mcp.is_empty() {
-                            eprintln!("\nMCP Servers ({}):", executor.config.tools.mcp.len());
-                            for server in &executor.config.tools.mcp {
-                                eprintln!("  • {} - {}", server.name, server.command);
-                            }
-                        }
-                        eprintln!("==========================\n");
-                    }
+                    tracing::debug!(
+                        "Tools loaded from {}: HTTP [{}], MCP [{}]",
+                        tools_path.display(),
+                        executor.config.tools.http.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", "),
+                        executor.config.tools.mcp.iter().map(|s| format!("{} ({})", s.name, s.command)).collect::<Vec<_>>().join(", "),
+                    );
 
+                    tools_config = Some(executor.config.clone());
                     let executor_arc = Arc::new(Mutex::new(executor));
 
                     // Initialize MCP servers in background
@@ -668,7 +1922,7 @@ impl App {
                             .block_on(async {
                                 let exec = executor_clone.lock().await;
                                 if let Err(e) = exec.initialize_mcp_servers().await {
-                                    eprintln!("Warning: Failed to initialize MCP servers: {}", e);
+                                    tracing::warn!("Failed to initialize MCP servers: {}", e);
                                 }
                             });
                     });
@@ -676,7 +1930,8 @@ impl App {
                     Some(executor_arc)
                 }
                 Err(e) => {
-                    eprintln!("Warning: Could not load tools config: {}", e);
+                    tracing::warn!("Could not load tools config: {}", e);
+                    config_problems.push(format!("{}\nTools are disabled until it is fixed.", e));
                     None
                 }
             }
@@ -686,18 +1941,10 @@ impl App {
 
         // Clone ollama config early for research orchestrator
         let ollama_config = config.ollama.clone();
+        let preflight_task = check_models_task(&config.ollama);
 
         // Create Ollama client
-        let mut ollama_client = ollama::OllamaClient::with_config(
-            config.ollama.host,
-            config.ollama.model.clone(),
-        );
-        ollama_client.set_max_tool_turns(config.ollama.max_tool_turns);
-        ollama_client.set_summarization_config(
-            config.ollama.summarization_model.clone(),
-            config.ollama.summarization_threshold,
-            false // Not research mode for main client
-        );
+        let mut ollama_client = build_chat_client(&config.ollama);
 
         let tool_executor_clone = if let Some(ref executor) = tool_executor {
             ollama_client.set_tool_executor(executor.clone());
@@ -706,20 +1953,20 @@ impl App {
             None
         };
 
-        let input_id = Id::unique();
+        let input_id = container::Id::unique();
 
         let vision_model = config.ollama.vision_model.clone();
 
         let ollama_client_arc = Arc::new(Mutex::new(ollama_client));
 
         // Initialize research orchestrator
-        let agents_path = config::Config::get_config_dir().join("agents.json");
+        let agents_path = config_dir.join("agents.json");
         let research_model = config.ollama.research_model.clone()
             .unwrap_or_else(|| config.ollama.model.clone());
 
         let research_orchestrator = if agents_path.exists() {
             match research::ResearchOrchestrator::from_file(
-                &agents_path,
+                &config_dir,
                 ollama_config,
                 ollama_client_arc.clone(),
                 config.ollama.context_window,
@@ -729,36 +1976,49 @@ impl App {
                 Ok(mut orchestrator) => {
                     // Override with config.toml settings
                     orchestrator.override_config(&config.research);
+                    if PLAN_ONLY.load(Ordering::Relaxed) {
+                        orchestrator.set_plan_only(true);
+                    }
+                    if let Some(warning) = tools_config.as_ref().and_then(|tools| unknown_tools_warning(orchestrator.agents_config(), tools)) {
+                        tracing::warn!("{}", warning);
+                        config_problems.push(warning);
+                    }
 
                     if let Some(executor) = tool_executor_clone {
                         orchestrator.set_tool_executor(executor);
                     }
-                    if DEBUG_MODE.load(Ordering::Relaxed) {
-                        eprintln!("=== Research Mode ===");
-                        eprintln!("Research orchestrator initialized from: {}", agents_path.display());
-                        eprintln!("Research model: {}", research_model);
-                        eprintln!("Context window: {} tokens", config.ollama.context_window);
-                        eprintln!("Max refinement iterations: {}", config.ollama.max_refinement_iterations);
-                        eprintln!("Max debate rounds: {}", config.ollama.max_debate_rounds);
-                        eprintln!("Worker count range: {}-{}", config.research.min_worker_count, config.research.max_worker_count);
-                        eprintln!("=====================\n");
-                    }
+                    tracing::debug!(
+                        "Research orchestrator initialized from {} (model {}, context window {} tokens, {} refinement iterations, {} debate rounds, {}-{} workers)",
+                        agents_path.display(),
+                        research_model,
+                        config.ollama.context_window,
+                        config.ollama.max_refinement_iterations,
+                        config.ollama.max_debate_rounds,
+                        config.research.min_worker_count,
+                        config.research.max_worker_count,
+                    );
                     Some(Arc::new(Mutex::new(orchestrator)))
                 }
                 Err(e) => {
-                    eprintln!("Warning: Could not load research config: {}", e);
+                    tracing::warn!("Could not load research config: {}", e);
+                    config_problems.push(format!("{}\nResearch mode is disabled until it is fixed.", e));
                     None
                 }
             }
         } else {
-            eprintln!("Info: agents.json not found. Research mode will be unavailable.");
+            tracing::info!("agents.json not found. Research mode will be unavailable.");
             None
         };
 
-        let app = App {
+        let mut app = App {
             input_text: String::new(),
+            input_editor: text_editor::Content::new(),
             response_text: String::new(),
             streaming_text: String::new(),
+            response_markdown: MarkdownCache::new(config.ui.table_overflow),
+            streaming_markdown: MarkdownCache::new(config.ui.table_overflow),
+            reasoning_text: String::new(),
+            show_reasoning: false,
             is_loading: false,
             is_fullscreen: false,
             loading_frame: 0,
@@ -768,148 +2028,231 @@ impl App {
             screenshot_path: None,
             vision_model,
             history: {
-                let _ = history::init();
-                history::list_entries(100).unwrap_or_default()
+                let _ = history::init(&config_dir);
+                Vec::new()
             },
+            history_page_size: config.ui.history_page_size.max(1),
+            history_has_more: false,
             selected_history: None,
             select_mode: false,
             output_editor: text_editor::Content::with_text(""),
             research_mode: false,
+            research_depth: config.research.depth,
             research_orchestrator,
+            tool_executor,
+            tools_modified: modified_time(&tools_path),
+            agents_modified: modified_time(&agents_path),
             research_progress: None,
             research_start_time: None,
+            pending_plan: None,
+            plan_workers: Vec::new(),
+            plan_editor: text_editor::Content::new(),
+            plan_approval_tx: None,
+            session_list: None,
+            research_run: 0,
+            research_fraction: None,
+            worker_text: Vec::new(),
+            research_progress_rx: None,
             current_query_cancel: None,
+            save_status: None,
+            theme: parse_theme(&config.ui.theme),
+            history_filter: String::new(),
+            history_cursor: None,
+            model_warning: None,
+            config_warning: (!config_problems.is_empty()).then(|| config_problems.join("\n\n")),
+            window_geometry: window_state::load(&config_dir).unwrap_or_default(),
+            text_scale: config.ui.text_scale.clamp(MIN_TEXT_SCALE, MAX_TEXT_SCALE),
+            archive_dir: config.ui.archive_dir.as_ref().filter(|d| !d.trim().is_empty()).map(std::path::PathBuf::from),
+            notifications: config.ui.notifications,
+            completion_alert: config.ui.completion_alert,
+            completion_alert_after: Duration::from_secs(config.ui.completion_alert_after_secs),
+            window_focused: true,
+            chat_model: config.ollama.model.clone(),
+            research_model,
+            query_model: String::new(),
+            query_research: false,
+            token_usage: None,
+            query_started: None,
+            history_details: None,
+            conversation_mode: false,
+            conversation: Vec::new(),
+            conversation_budget: ollama::conversation_budget(config.ollama.options.num_ctx.unwrap_or(config.ollama.context_window)),
+            pending_question: None,
+            last_query: None,
+            config_dir,
         };
 
-        let focus_task = text_input::focus(input_id);
+        app.reload_history();
+
+        let focus_task = focus_input(&input_id);
         // Do not force always-on-top; keep normal stacking behavior
-        (app, Task::batch([focus_task]))
+        (app, Task::batch([focus_task, preflight_task]))
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::InputChanged(value) => {
+            Message::InputAction(action) => {
+                if self.is_loading {
+                    return Task::none();
+                }
+                if action.is_edit() {
+                    self.history_cursor = None;
+                }
+                self.input_editor.perform(action);
+                // Content::text() always ends in a newline; the prompt itself shouldn't
+                let mut value = self.input_editor.text();
+                if value.ends_with('\n') {
+                    value.pop();
+                }
                 self.input_text = value;
                 Task::none()
             }
+            Message::Regenerate => {
+                if self.response_text.is_empty() || self.is_loading {
+                    return Task::none();
+                }
+                match self.last_query.clone() {
+                    Some(LastQuery::Prompt(prompt)) => {
+                        // Same path as Submit, so research mode and cancellation apply unchanged.
+                        // In a conversation the answer being replaced mustn't stay in the context.
+                        let replaced = if self.conversation.last().is_some_and(|turn| turn.role == "assistant" && turn.content == self.response_text) {
+                            self.conversation.split_off(self.conversation.len().saturating_sub(2))
+                        } else {
+                            Vec::new()
+                        };
+                        self.set_input(prompt);
+                        let task = self.update(Message::Submit);
+                        if !self.is_loading {
+                            // Submit refused the prompt; the old answer still stands
+                            self.conversation.extend(replaced);
+                        }
+                        task
+                    }
+                    Some(LastQuery::Image(path)) => self.update(Message::ScreenshotCaptured(Ok(path))),
+                    None => Task::none(),
+                }
+            }
             Message::Submit => {
                 if self.input_text.trim().is_empty() || self.is_loading {
                     return Task::none();
                 }
 
+                let model_override = parse_model_override(&self.input_text)
+                    .map(|(model, question)| (model.to_string(), question.to_string()));
+                if let Some((model, question)) = &model_override {
+                    let problem = if question.trim().is_empty() {
+                        Some(format!("Add a question after @{}", model))
+                    } else if self.research_mode && self.research_orchestrator.is_some() {
+                        Some("@model overrides only apply to chat queries; turn off research mode to use one".to_string())
+                    } else {
+                        None
+                    };
+                    if let Some(problem) = problem {
+                        self.response_text = problem;
+                        return Task::none();
+                    }
+                }
+
                 self.is_loading = true;
+                self.last_query = Some(LastQuery::Prompt(self.input_text.clone()));
                 self.response_text = String::new();
                 self.streaming_text = String::new();
-
-                if ENABLE_NOTIFICATIONS {
-                    std::thread::spawn(|| {
-                        let _ = Notification::new()
-                            .summary("bob-bar")
-                            .body("Processing your query...")
-                            .show();
-                    });
-                }
+                self.reasoning_text.clear();
+                self.token_usage = None;
+                self.history_details = None;
+                self.show_reasoning = false;
+                self.save_status = None;
+                self.session_list = None;
 
                 // Check if research mode is enabled
                 if self.research_mode && self.research_orchestrator.is_some() {
-                    self.research_start_time = Some(std::time::Instant::now());
-                    self.research_progress = Some("Starting research...".to_string());
-                    if let Ok(mut g) = RESEARCH_PROGRESS_GLOBAL.lock() {
-                        *g = Some("Starting research...".to_string());
-                    }
-
-                    // Create cancellation token for this query
+                    self.start_research(None)
+                } else {
+                    // Normal mode
+                    let (override_model, question) = match model_override {
+                        Some((model, question)) => (Some(model), question),
+                        None => (None, self.input_text.clone()),
+                    };
+                    self.query_model = override_model.clone().unwrap_or_else(|| self.chat_model.clone());
+                    self.query_research = false;
+                    ollama::reset_session_usage();
+                    self.query_started = Some(std::time::Instant::now());
+                    let prompt = format!("{}\n\n{}", TABLE_PLAIN_TEXT_RULES, question);
+                    let conversation = if self.conversation_mode { self.conversation.clone() } else { Vec::new() };
+                    self.pending_question = self.conversation_mode.then_some(question);
+                    let client = self.ollama_client.clone();
                     let cancel_token = tokio_util::sync::CancellationToken::new();
                     self.current_query_cancel = Some(cancel_token.clone());
 
-                    let query = self.input_text.clone();
-                    let orchestrator = self.research_orchestrator.clone().unwrap();
-
-                    // For now, research progress updates are visible in terminal via eprintln
-                    // Future enhancement: implement subscription-based progress streaming to UI
-                    use tokio::sync::mpsc;
-                    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
-
-                    // Spawn task to monitor progress and print to terminal
-                    tokio::spawn(async move {
-                        use research::ResearchProgress;
-                        while let Some(progress) = progress_rx.recv().await {
-                            let msg = match progress {
-                                ResearchProgress::Started => "🚀 Starting research...".to_string(),
-                                ResearchProgress::Decomposing => "🔍 Decomposing query into sub-questions...".to_string(),
-                                ResearchProgress::PlanningIteration(i, max) => format!("📋 Planning iteration {}/{}", i, max),
-                                ResearchProgress::PlanGenerated(n) => format!("✓ Generated plan with {} sub-questions", n),
-                                ResearchProgress::PlanCriticReviewing(i, max) => format!("🔎 Plan critic reviewing (iteration {}/{})", i, max),
-                                ResearchProgress::PlanApproved => "✅ Plan approved, starting research".to_string(),
-                                ResearchProgress::WorkersStarted(n) => format!("👥 Dispatching {} research workers...", n),
-                                ResearchProgress::WorkerStarted { worker, question } => format!("→ {}: researching — {}", worker, question),
-                                ResearchProgress::WorkerStatus { worker, status } => format!("  {}: {}", worker, status),
-                                ResearchProgress::WorkerCompleted(name) => format!("✓ {} completed", name),
-                                ResearchProgress::SupervisorAnalyzing => "👁️ Supervisor analyzing progress...".to_string(),
-                                ResearchProgress::FollowUpQuestionsGenerated(n) => format!("📝 Generated {} follow-up questions", n),
-                                ResearchProgress::Combining => "🔗 Combining research results...".to_string(),
-                                ResearchProgress::Summarizing => "📊 Summarizing worker results...".to_string(),
-                                ResearchProgress::CriticReviewing => "🔎 Critic reviewing output...".to_string(),
-                                ResearchProgress::DebateRound(current, max) => format!("💬 Debate round {}/{} in progress...", current, max),
-                                ResearchProgress::Refining(current, max) => format!("✨ Refining output (iteration {}/{})", current, max),
-                                ResearchProgress::WritingDocument(current, max) => format!("✍️ Writing document (iteration {}/{})", current, max),
-                                ResearchProgress::DocumentReviewing => "📝 Document critic reviewing...".to_string(),
-                                ResearchProgress::ExportingMemories => "💾 Exporting research memories...".to_string(),
-                                ResearchProgress::Completed => "🎉 Research complete!".to_string(),
-                            };
-                            if let Ok(mut g) = RESEARCH_PROGRESS_GLOBAL.lock() {
-                                *g = Some(msg);
-                            }
-                        }
-                    });
+                    // Use Iced's Task system to run async work non-blocking
+                    // This spawns the async work on Iced's tokio runtime thread pool,
+                    // keeping the main GUI thread responsive to Wayland events.
+                    // Partial text is forwarded as StreamChunk messages while the query runs.
+                    Task::stream(iced::stream::channel(64, move |mut output| async move {
+                        use iced::futures::SinkExt;
+
+                        let mut chunk_output = output.clone();
+                        let (result, reasoning) = {
+                            let mut client_guard = client.lock().await;
 
-                    // Run the research task with cancellation support
-                    Task::perform(
-                        async move {
-                            tokio::select! {
-                                result = async {
-                                    let mut orch = orchestrator.lock().await;
-                                    orch.set_progress_channel(progress_tx);
-                                    orch.research(&query).await
-                                } => result,
-                                _ = cancel_token.cancelled() => {
-                                    Err(anyhow::anyhow!("Query cancelled by user"))
+                            // Temporarily switch to the model named with `@model`, if it exists
+                            let original_model = client_guard.get_model().to_string();
+                            if let Some(model) = override_model {
+                                let available = match client_guard.list_models().await {
+                                    Ok(available) => available,
+                                    Err(e) => {
+                                        let _ = output.send(Message::Error(format!("Error: could not check model @{}: {}", model, e))).await;
+                                        return;
+                                    }
+                                };
+                                if !ollama::missing_models(std::slice::from_ref(&model), &available).is_empty() {
+                                    let _ = output.send(Message::Error(format!(
+                                        "Error: model '{}' is not available. Available models: {}", model, available.join(", ")
+                                    ))).await;
+                                    return;
                                 }
+                                client_guard.set_model(model);
                             }
-                        },
-                        |result| match result {
-                            Ok(response) => Message::ResponseReceived(response),
-                            Err(e) => Message::Error(format!("Research error: {}", e)),
+
+                            client_guard.set_conversation(conversation);
+                            let result = client_guard.query_streaming_cancellable(&prompt, &cancel_token, move |text| {
+                                // Each chunk carries the full text so far, so a dropped
+                                // chunk under backpressure is simply superseded by the next
+                                let _ = chunk_output.try_send(Message::StreamChunk(text));
+                            }).await;
+
+                            // Restore original model; the client is shared, so later queries start fresh
+                            client_guard.set_model(original_model);
+                            client_guard.set_conversation(Vec::new());
+                            (result, client_guard.last_reasoning().to_string())
+                        };
+
+                        // CancelQuery has already reset the UI; a late result would overwrite it
+                        if cancel_token.is_cancelled() {
+                            return;
                         }
-                    )
-                } else {
-                    // Normal mode
-                    let prompt = format!("{}\n\n{}", TABLE_PLAIN_TEXT_RULES, self.input_text.clone());
-                    let client = self.ollama_client.clone();
 
-                    // Use Iced's Task system to run async work non-blocking
-                    // This spawns the async work on Iced's tokio runtime thread pool,
-                    // keeping the main GUI thread responsive to Wayland events
-                    Task::perform(
-                        async move {
-                            let mut client_guard = client.lock().await;
-                            client_guard.query_streaming(&prompt, |_text| {
-                                // For now, we'll skip streaming updates to keep it simple
-                                // We could implement this with a subscription if needed
-                            }).await
-                        },
-                        |result| match result {
+                        if !reasoning.is_empty() {
+                            let _ = output.send(Message::ReasoningReceived(reasoning)).await;
+                        }
+
+                        let final_message = match result {
                             Ok(response) => Message::ResponseReceived(response),
                             Err(e) => Message::Error(format!("Error: {}", e)),
-                        }
-                    )
+                        };
+                        let _ = output.send(final_message).await;
+                    }))
                 }
             }
             Message::NewQuery => {
                 if self.is_loading || self.research_progress.is_some() { return Task::none(); }
-                self.input_text.clear();
+                self.session_list = None;
+                self.set_input(String::new());
                 self.response_text.clear();
                 self.streaming_text.clear();
+                self.reasoning_text.clear();
+                self.token_usage = None;
+                self.history_details = None;
                 self.screenshot_path = None;
                 self.selected_history = None;
                 self.output_editor = text_editor::Content::with_text("");
@@ -918,18 +2261,36 @@ impl App {
                 crate::progress::clear();
                 Task::none()
             }
+            Message::NewConversation => {
+                if self.is_loading || self.research_progress.is_some() { return Task::none(); }
+                self.conversation.clear();
+                self.update(Message::NewQuery)
+            }
+            Message::ToggleConversationMode => {
+                self.conversation_mode = !self.conversation_mode;
+                Task::none()
+            }
             Message::ResponseReceived(response) => {
+                if let Some(question) = self.pending_question.take() {
+                    self.conversation.push(ollama::ChatTurn::user(question));
+                    self.conversation.push(ollama::ChatTurn::assistant(response.clone()));
+                    ollama::trim_conversation(&mut self.conversation, self.conversation_budget);
+                }
                 self.response_text = response;
+                let usage = ollama::session_usage();
+                self.token_usage = (usage.calls > 0).then_some(usage);
                 self.streaming_text = String::new();
                 self.is_loading = false;
                 self.research_progress = None;
                 self.research_start_time = None;
                 self.current_query_cancel = None;  // Clear cancellation token
-                if let Ok(mut g) = RESEARCH_PROGRESS_GLOBAL.lock() { *g = None; }
+                self.research_progress_rx = None;
+                self.clear_pending_plan();
                 crate::tools::clear_current_sources();
                 crate::progress::clear();
                 
-                if ENABLE_NOTIFICATIONS {
+                // Only worth a ping if the user has moved on to another window
+                if self.notifications && !self.window_focused {
                     std::thread::spawn(|| {
                         // On Linux, set urgency and timeout; other OSes may not support these
                         #[cfg(target_os = "linux")]
@@ -952,19 +2313,50 @@ impl App {
                     });
                 }
 
+                let elapsed = self.query_started.take().map(|start| start.elapsed());
+                let alert = if completion_alert_due(elapsed, self.completion_alert_after, self.window_focused) {
+                    self.completion_alert
+                } else {
+                    config::CompletionAlert::None
+                };
+                if alert.chime() {
+                    play_chime();
+                }
+
                 // Save to history and refresh list
-                let _ = history::add_entry(&self.input_text, &self.response_text);
-                self.history = history::list_entries(100).unwrap_or_default();
+                let details = history::EntryDetails {
+                    model: Some(self.query_model.as_str()).filter(|m| !m.is_empty()),
+                    research_mode: self.query_research,
+                    duration_ms: elapsed.map(|d| d.as_millis() as i64),
+                };
+                let _ = history::add_entry(&self.config_dir, &self.input_text, &self.response_text, &details);
+                self.reload_history();
+
+                if let Some(dir) = &self.archive_dir {
+                    if let Err(e) = archive_response(dir, &self.input_text, &self.response_text, &self.query_model, self.query_research, unix_now()) {
+                        tracing::warn!("Could not archive response to {}: {}", dir.display(), e);
+                    }
+                }
 
                 // Also request window focus immediately
-                window::get_latest().and_then(|id| window::gain_focus(id))
+                let focus = window::get_latest().and_then(|id| window::gain_focus(id));
+                if alert.flash() {
+                    // Focus requests are often refused for background windows; flash it as well
+                    let attention = window::get_latest()
+                        .and_then(|id| window::request_user_attention(id, Some(window::UserAttention::Informational)));
+                    Task::batch([attention, focus])
+                } else {
+                    focus
+                }
             }
             Message::Error(error) => {
+                self.pending_question = None;
                 self.response_text = error;
                 self.streaming_text = String::new();
                 self.is_loading = false;
                 self.current_query_cancel = None;  // Clear cancellation token
-                if let Ok(mut g) = RESEARCH_PROGRESS_GLOBAL.lock() { *g = None; }
+                self.research_progress_rx = None;
+                self.clear_pending_plan();
                 crate::tools::clear_current_sources();
                 crate::progress::clear();
                 Task::none()
@@ -973,13 +2365,15 @@ impl App {
                 // Cancel the current query/research if one is running
                 if let Some(cancel_token) = &self.current_query_cancel {
                     cancel_token.cancel();
+                    self.pending_question = None;
                     self.response_text = "Query cancelled by user".to_string();
                     self.streaming_text = String::new();
                     self.is_loading = false;
                     self.research_progress = None;
                     self.research_start_time = None;
                     self.current_query_cancel = None;
-                    if let Ok(mut g) = RESEARCH_PROGRESS_GLOBAL.lock() { *g = None; }
+                    self.research_progress_rx = None;
+                    self.clear_pending_plan();
                     crate::tools::clear_current_sources();
                     crate::progress::clear();
                 }
@@ -988,24 +2382,84 @@ impl App {
             Message::Tick => {
                 if self.is_loading {
                     self.loading_frame = (self.loading_frame + 1) % 80; // 10 frames * 8 messages
-                    // Pull latest research progress from global store
-                    if let Ok(g) = RESEARCH_PROGRESS_GLOBAL.lock() {
-                        if let Some(ref s) = *g {
-                            self.research_progress = Some(s.clone());
-                        }
-                    }
                 }
                 Task::none()
             }
             Message::HistorySelect(idx) => {
                 if let Some(entry) = self.history.get(idx).cloned() {
-                    self.input_text = entry.prompt;
+                    self.history_details = Some(format_history_details(&entry));
+                    self.set_input(entry.prompt);
                     self.response_text = entry.response;
+                    self.reasoning_text.clear();
+                    self.token_usage = None;
                     self.selected_history = Some(idx);
                     self.is_loading = false;
                 }
                 Task::none()
             }
+            Message::ShowSessions => {
+                if self.is_loading {
+                    return Task::none();
+                }
+                self.session_list = match self.session_list {
+                    Some(_) => None,
+                    None => Some(research::list_sessions(&self.config_dir)),
+                };
+                Task::none()
+            }
+            Message::OpenSession(_) | Message::ResumeSession(_) if self.is_loading => Task::none(),
+            Message::OpenSession(path) => {
+                self.session_list = None;
+                self.reasoning_text.clear();
+                self.token_usage = None;
+                self.history_details = None;
+                self.save_status = None;
+                self.selected_history = None;
+                match research::ResearchOrchestrator::load_session(&path) {
+                    Ok(session) => {
+                        self.set_input(session.query.clone());
+                        self.response_text = session.to_markdown();
+                    }
+                    Err(e) => self.response_text = format!("Failed to open session {}: {}", path.display(), e),
+                }
+                Task::none()
+            }
+            Message::ResumeSession(path) => {
+                self.session_list = None;
+                let session = match research::ResearchOrchestrator::load_session(&path) {
+                    Ok(session) if self.research_orchestrator.is_some() => session,
+                    Ok(_) => {
+                        self.response_text = "Research mode is not available to resume this session".to_string();
+                        return Task::none();
+                    }
+                    Err(e) => {
+                        self.response_text = format!("Failed to open session {}: {}", path.display(), e);
+                        return Task::none();
+                    }
+                };
+                self.set_input(session.query.clone());
+                self.is_loading = true;
+                self.response_text.clear();
+                self.streaming_text.clear();
+                self.reasoning_text.clear();
+                self.token_usage = None;
+                self.history_details = None;
+                self.save_status = None;
+                self.selected_history = None;
+                self.start_research(Some(session))
+            }
+            Message::PlanEditorAction(action) => {
+                self.plan_editor.perform(action);
+                Task::none()
+            }
+            Message::ApprovePlan(questions) => {
+                if let Some(tx) = self.plan_approval_tx.take() {
+                    let _ = tx.send(questions);
+                }
+                self.pending_plan = None;
+                self.research_progress = Some("✅ Plan approved, starting research".to_string());
+                Task::none()
+            }
             Message::OutputEditorAction(action) => {
                 // Allow selection and navigation; if the user types, it will edit the ephemeral view only
                 self.output_editor.perform(action);
@@ -1024,9 +2478,34 @@ impl App {
                 }
                 Task::none()
             }
+            Message::CycleResearchDepth => {
+                self.research_depth = self.research_depth.next();
+                Task::none()
+            }
             Message::ResearchProgress(progress) => {
                 use research::ResearchProgress;
 
+                // Late events from a finished or cancelled run
+                if self.research_progress_rx.is_none() {
+                    return Task::none();
+                }
+                if let ResearchProgress::Overall(fraction) = progress {
+                    self.research_fraction = Some(fraction);
+                    return Task::none();
+                }
+                if let ResearchProgress::WorkerText { worker, chunk } = progress {
+                    append_worker_text(&mut self.worker_text, worker, &chunk);
+                    return Task::none();
+                }
+                if let ResearchProgress::WorkerCompleted(ref name) = progress {
+                    self.worker_text.retain(|(worker, _)| worker != name);
+                }
+                if let ResearchProgress::PlanAwaitingApproval { ref plan, ref workers } = progress {
+                    self.plan_editor = text_editor::Content::with_text(&format_plan_for_editing(plan));
+                    self.pending_plan = Some(plan.clone());
+                    self.plan_workers = workers.clone();
+                }
+
                 let progress_text = match progress {
                     ResearchProgress::Started => "🚀 Starting research...".to_string(),
                     ResearchProgress::Decomposing => "🔍 Decomposing query into sub-questions...".to_string(),
@@ -1034,6 +2513,7 @@ impl App {
                     ResearchProgress::PlanGenerated(n) => format!("✓ Generated plan with {} sub-questions", n),
                     ResearchProgress::PlanCriticReviewing(i, max) => format!("🔎 Plan critic reviewing (iteration {}/{})", i, max),
                     ResearchProgress::PlanApproved => "✅ Plan approved, starting research".to_string(),
+                    ResearchProgress::PlanAwaitingApproval { plan, .. } => format!("📝 Review the plan ({} sub-questions)", plan.len()),
                     ResearchProgress::WorkersStarted(n) => format!("👥 Dispatching {} research workers...", n),
                     ResearchProgress::WorkerStarted { worker, question } => format!("→ {}: researching — {}", worker, question),
                     ResearchProgress::WorkerStatus { worker, status } => format!("  {}: {}", worker, status),
@@ -1049,16 +2529,38 @@ impl App {
                     ResearchProgress::DocumentReviewing => "📝 Document critic reviewing...".to_string(),
                     ResearchProgress::ExportingMemories => "💾 Exporting research memories...".to_string(),
                     ResearchProgress::Completed => "🎉 Research complete!".to_string(),
+                    ResearchProgress::WorkerText { .. } | ResearchProgress::Overall(_) => return Task::none(),
                 };
 
                 self.research_progress = Some(progress_text);
                 Task::none()
             }
+            Message::HistorySearch(filter) => {
+                self.history_filter = filter;
+                // A new filter starts again from the first page
+                self.history.clear();
+                self.reload_history();
+                // Indices refer to the filtered list, so any previous selection is stale
+                self.selected_history = None;
+                Task::none()
+            }
+            Message::HistoryRecall(older) => {
+                if self.is_loading || self.research_progress.is_some() || !self.input_is_unedited() {
+                    return Task::none();
+                }
+                self.history_cursor = step_history_cursor(self.history_cursor, self.history.len(), older);
+                let prompt = self.history_cursor
+                    .and_then(|i| self.history.get(i))
+                    .map(|entry| entry.prompt.clone())
+                    .unwrap_or_default();
+                self.set_input(prompt);
+                Task::none()
+            }
             Message::HistoryDelete(idx) => {
                 if let Some(entry) = self.history.get(idx) {
-                    let _ = history::delete_entry(entry.id);
+                    let _ = history::delete_entry(&self.config_dir, entry.id);
                 }
-                self.history = history::list_entries(100).unwrap_or_default();
+                self.reload_history();
                 if let Some(sel) = self.selected_history {
                     if sel == idx || sel >= self.history.len() {
                         self.selected_history = None;
@@ -1066,16 +2568,218 @@ impl App {
                 }
                 Task::none()
             }
-            Message::CopyOutput => {
-                clipboard::write(self.response_text.clone())
+            Message::HistoryLoadMore => {
+                self.load_history_page(self.history_page_size);
+                Task::none()
+            }
+            Message::CopyOutput => {
+                clipboard::write(self.response_text.clone())
+            }
+            Message::CopyTableCsv(index) => {
+                // Tables are numbered per document, so look in the one on screen
+                let cache = if self.response_text.is_empty() { &self.streaming_markdown } else { &self.response_markdown };
+                match cache.table_rows(index) {
+                    Some(rows) => {
+                        self.save_status = Some("Copied table as CSV".to_string());
+                        clipboard::write(table_csv(&rows))
+                    }
+                    None => Task::none(),
+                }
+            }
+            Message::CopyCode(code) => {
+                self.save_status = Some("Copied code".to_string());
+                clipboard::write(code)
+            }
+            Message::SaveOutput => {
+                let query = self.last_query.as_ref().map(LastQuery::prompt).unwrap_or_default().to_string();
+                let body = self.response_text.clone();
+                self.save_status = None;
+                Task::perform(save_output(query, body), Message::OutputSaved)
+            }
+            Message::SaveOutputHtml => {
+                let query = self.last_query.as_ref().map(LastQuery::prompt).unwrap_or_default().to_string();
+                let body = self.response_text.clone();
+                self.save_status = None;
+                Task::perform(save_output_html(query, body), Message::OutputSaved)
+            }
+            Message::ExportHistory(format) => {
+                self.save_status = None;
+                Task::perform(save_history_export(self.config_dir.clone(), format), Message::OutputSaved)
+            }
+            Message::ExportToolAudit(format) => {
+                let Some(orchestrator) = self.research_orchestrator.clone() else {
+                    return Task::none();
+                };
+                self.save_status = None;
+                Task::perform(
+                    async move {
+                        let document = orchestrator.lock().await.export_tool_audit(format).await
+                            .map_err(|e| format!("Failed to build tool audit: {}", e))?;
+                        save_tool_audit(document, format).await
+                    },
+                    Message::OutputSaved,
+                )
+            }
+            Message::OutputSaved(result) => {
+                self.save_status = Some(match result {
+                    Ok(Some(path)) => format!("Saved to {}", path.display()),
+                    Ok(None) => "Save cancelled".to_string(),
+                    Err(e) => {
+                        tracing::warn!("Save failed: {}", e);
+                        format!("Save failed: {}", e)
+                    }
+                });
+                Task::none()
+            }
+            Message::StreamChunk(text) => {
+                // Ignore chunks that arrive after the query finished or was cancelled,
+                // and don't flash raw tool-call JSON while the model is invoking a tool
+                let trimmed = text.trim_start();
+                let looks_like_tool_call = trimmed.starts_with('{') || trimmed.starts_with('[') || trimmed.starts_with("```json");
+                if self.is_loading && !looks_like_tool_call {
+                    self.streaming_text = text;
+                }
+                Task::none()
+            }
+            Message::ReasoningReceived(reasoning) => {
+                self.reasoning_text = reasoning;
+                Task::none()
+            }
+            Message::ToggleReasoning => {
+                self.show_reasoning = !self.show_reasoning;
+                Task::none()
+            }
+            Message::CheckConfigFiles => {
+                let config_dir = &self.config_dir;
+                let tools_path = config_dir.join("tools.json");
+                let agents_path = config_dir.join("agents.json");
+                let mut reloads = Vec::new();
+
+                let tools_modified = modified_time(&tools_path);
+                if tools_modified.is_some() && tools_modified != self.tools_modified {
+                    self.tools_modified = tools_modified;
+                    reloads.push(Task::perform(reload_tools(tools_path, self.tool_executor.clone()),
+                        |(status, problem)| Message::ConfigReloaded("tools.json", status, problem)));
+                }
+                let agents_modified = modified_time(&agents_path);
+                if agents_modified.is_some() && agents_modified != self.agents_modified {
+                    self.agents_modified = agents_modified;
+                    reloads.push(Task::perform(reload_agents(agents_path, self.research_orchestrator.clone(), self.tool_executor.clone()),
+                        |(status, problem)| Message::ConfigReloaded("agents.json", status, problem)));
+                }
+                Task::batch(reloads)
+            }
+            Message::ConfigReloaded(file, status, problem) => {
+                tracing::info!("{}", status);
+                crate::progress::log(status);
+                match problem {
+                    Some(problem) => self.config_warning = Some(problem),
+                    // Fixed: drop the banner if it was about this file
+                    None if self.config_warning.as_deref().is_some_and(|w| w.starts_with(file)) => self.config_warning = None,
+                    None => {}
+                }
+                Task::none()
+            }
+            Message::ModelsChecked(result) => {
+                self.model_warning = match result {
+                    Ok(missing) if missing.is_empty() => None,
+                    Ok(missing) => Some(format!(
+                        "Missing Ollama models: {}. Pull them with `ollama pull <model>`.",
+                        missing.join(", ")
+                    )),
+                    Err(e) => Some(format!("Could not list Ollama models: {}", e)),
+                };
+                if let Some(warning) = &self.model_warning {
+                    tracing::warn!("{}", warning);
+                }
+                Task::none()
+            }
+            Message::PasteImage => {
+                if self.is_loading {
+                    return Task::none();
+                }
+                Task::perform(
+                    async {
+                        tokio::task::spawn_blocking(screenshot::clipboard_image)
+                            .await
+                            .map_err(|e| e.to_string())
+                            .and_then(|result| result.map_err(|e| e.to_string()))
+                    },
+                    |result| result,
+                )
+                .then(|result| match result {
+                    Ok(Some(path)) => Task::done(Message::ScreenshotCaptured(Ok(path))),
+                    // Text (or nothing) on the clipboard: behave like a normal paste
+                    Ok(None) => iced::clipboard::read().map(Message::PasteText),
+                    Err(e) => Task::done(Message::Error(format!("Could not read clipboard image: {}", e))),
+                })
+            }
+            Message::PasteText(content) => match content {
+                Some(content) => self.update(Message::InputAction(
+                    text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(content))),
+                )),
+                None => Task::none(),
+            },
+            Message::DismissModelWarning => {
+                self.model_warning = None;
+                Task::none()
+            }
+            Message::DismissConfigWarning => {
+                self.config_warning = None;
+                Task::none()
+            }
+            Message::CycleTheme => {
+                self.theme = next_theme(&self.theme);
+                tracing::debug!("Theme: {}", self.theme);
+                Task::none()
+            }
+            Message::OpenLink(url) => {
+                if is_openable_link(&url) {
+                    open_link(&url);
+                }
+                Task::none()
             }
             Message::Exit => {
                 // If a query is running, cancel it instead of exiting
                 if self.is_loading || self.current_query_cancel.is_some() {
                     return self.update(Message::CancelQuery);
                 }
+                self.save_window_geometry();
+                iced::exit()
+            }
+            Message::ZoomText(zoom_in) => {
+                let scale = step_text_scale(self.text_scale, zoom_in);
+                if scale != self.text_scale {
+                    self.text_scale = scale;
+                    if let Err(e) = config::Config::save_text_scale(&self.config_dir, scale) {
+                        tracing::warn!("Could not save text scale: {}", e);
+                    }
+                }
+                Task::none()
+            }
+            Message::WindowCloseRequested => {
+                self.save_window_geometry();
                 iced::exit()
             }
+            // Fullscreen geometry isn't worth restoring; keep the last windowed one
+            Message::WindowMoved(position) => {
+                if !self.is_fullscreen {
+                    self.window_geometry.x = Some(position.x);
+                    self.window_geometry.y = Some(position.y);
+                }
+                Task::none()
+            }
+            Message::WindowResized(size) => {
+                if !self.is_fullscreen {
+                    self.window_geometry.width = size.width;
+                    self.window_geometry.height = size.height;
+                }
+                Task::none()
+            }
+            Message::WindowFocusChanged(focused) => {
+                self.window_focused = focused;
+                Task::none()
+            }
             Message::ToggleFullscreen => {
                 // Toggle true fullscreen mode using iced window API
                 let new_mode = if self.is_fullscreen {
@@ -1090,9 +2794,14 @@ impl App {
                 match result {
                     Ok(path) => {
                         self.screenshot_path = Some(path.clone());
+                        self.last_query = Some(LastQuery::Image(path.clone()));
+                        self.query_model = self.vision_model.clone();
+                        self.query_research = false;
+                        ollama::reset_session_usage();
+                        self.query_started = Some(std::time::Instant::now());
                         self.is_loading = true;
                         self.response_text = "Extracting information from screenshot...".to_string();
-                        self.input_text = "Reading and analyzing screen content...".to_string();
+                        self.set_input("Reading and analyzing screen content...".to_string());
 
                         let client = self.ollama_client.clone();
                         let screenshot_path = path;
@@ -1167,18 +2876,46 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
             Subscription::none()
         };
 
-        let events = event::listen_with(|event, _status, _id| {
+        let events = event::listen_with(|event, status, _id| {
             match event {
                 IcedEvent::Keyboard(keyboard::Event::KeyPressed { key: Key::Named(keyboard::key::Named::Escape), .. }) => {
                     Some(Message::Exit)
                 }
+                IcedEvent::Window(window::Event::Moved(position)) => Some(Message::WindowMoved(position)),
+                IcedEvent::Window(window::Event::Resized(size)) => Some(Message::WindowResized(size)),
+                IcedEvent::Window(window::Event::CloseRequested) => Some(Message::WindowCloseRequested),
+                IcedEvent::Window(window::Event::Focused) => Some(Message::WindowFocusChanged(true)),
+                IcedEvent::Window(window::Event::Unfocused) => Some(Message::WindowFocusChanged(false)),
+                // Only the focused prompt editor captures bare arrow keys, so a captured
+                // Up/Down means the input has focus
+                IcedEvent::Keyboard(keyboard::Event::KeyPressed { key: Key::Named(named @ (keyboard::key::Named::ArrowUp | keyboard::key::Named::ArrowDown)), modifiers, .. })
+                    if modifiers.is_empty() && status == event::Status::Captured =>
+                {
+                    Some(Message::HistoryRecall(named == keyboard::key::Named::ArrowUp))
+                }
                 IcedEvent::Keyboard(keyboard::Event::KeyPressed { key: Key::Character(c), modifiers, .. }) => {
                     // macOS-style fullscreen shortcut: Cmd + Ctrl + F
                     // Use `logo()` to represent Command on macOS
                     if (c == "f" || c == "F") && modifiers.control() && modifiers.logo() {
                         Some(Message::ToggleFullscreen)
+                    } else if (c == "n" || c == "N") && (modifiers.logo() || modifiers.control()) && modifiers.shift() {
+                        Some(Message::NewConversation)
                     } else if (c == "n" || c == "N") && (modifiers.logo() || modifiers.control()) {
                         Some(Message::NewQuery)
+                    } else if (c == "t" || c == "T") && (modifiers.logo() || modifiers.control()) {
+                        Some(Message::CycleTheme)
+                    } else if (c == "=" || c == "+") && (modifiers.logo() || modifiers.control()) {
+                        Some(Message::ZoomText(Some(true)))
+                    } else if c == "-" && (modifiers.logo() || modifiers.control()) {
+                        Some(Message::ZoomText(Some(false)))
+                    } else if c == "0" && (modifiers.logo() || modifiers.control()) {
+                        Some(Message::ZoomText(None))
+                    } else if (c == "e" || c == "E") && (modifiers.logo() || modifiers.control()) {
+                        Some(Message::ExportHistory(if modifiers.shift() {
+                            history::ExportFormat::Json
+                        } else {
+                            history::ExportFormat::Markdown
+                        }))
                     } else {
                         None
                     }
@@ -1187,22 +2924,38 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
             }
         });
 
-        Subscription::batch([timer, events])
+        let progress = match &self.research_progress_rx {
+            Some(rx) => Subscription::run_with_id(
+                ("research-progress", self.research_run),
+                research_progress_stream(rx.clone()),
+            ),
+            None => Subscription::none(),
+        };
+
+        // tools.json / agents.json are picked up again when their modification time changes
+        let config_watch = time::every(CONFIG_POLL_INTERVAL).map(|_| Message::CheckConfigFiles);
+
+        Subscription::batch([timer, events, progress, config_watch])
     }
 
     fn view(&self) -> Element<'_, Message> {
-        let mut input = text_input("Type your message...", &self.input_text)
+        // Multi-line prompt: Enter submits, Shift+Enter inserts a newline
+        let input_empty = self.input_text.is_empty();
+        let mut editor = text_editor(&self.input_editor)
+            .placeholder("Type your message...")
             .padding(15)
             .size(18)
-            .id(self.input_id.clone());
+            .key_binding(move |key_press| input_key_binding(key_press, input_empty));
 
         // Only enable input when not loading
         if !self.is_loading {
-            input = input
-                .on_input(Message::InputChanged)
-                .on_submit(Message::Submit);
+            editor = editor.on_action(Message::InputAction);
         }
 
+        let input = container(editor)
+            .id(self.input_id.clone())
+            .max_height(240.0);
+
         // Research mode toggle button (only show if orchestrator is available)
         let research_toggle = if self.research_orchestrator.is_some() {
             let toggle_text = if self.research_mode {
@@ -1271,36 +3024,124 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
         };
 
         // Create input row with optional research toggle and action buttons
-        let input_row = if let Some(toggle) = research_toggle {
-            row![
-                container(input).width(Length::Fill),
-                enter_btn,
-                new_btn,
-                toggle
-            ]
-            .spacing(8)
-            .width(Length::Fill)
-        } else {
-            row![
-                container(input).width(Length::Fill),
-                enter_btn,
-                new_btn
-            ]
-            .spacing(8)
-            .width(Length::Fill)
-        };
+        let mut input_row = row![
+            container(input).width(Length::Fill),
+            enter_btn,
+            new_btn
+        ]
+        .spacing(8)
+        .width(Length::Fill);
+
+        // Conversation context only applies to chat queries
+        if !self.research_mode {
+            let conversation_text = if self.conversation_mode { "Chat: ON " } else { "Chat: OFF" };
+            let conversation_label = container(text(conversation_text).size(14))
+                .align_x(alignment::Horizontal::Center)
+                .align_y(alignment::Vertical::Center)
+                .width(Length::Fixed(90.0))
+                .height(Length::Fill);
+            let mut conversation_btn = button(conversation_label)
+                .padding([8, 12])
+                .height(Length::Fixed(INPUT_HEIGHT));
+            if !self.is_loading {
+                conversation_btn = conversation_btn.on_press(Message::ToggleConversationMode);
+            }
+            input_row = input_row.push(conversation_btn);
+
+            if self.conversation_mode && !self.conversation.is_empty() {
+                let reset_label = container(text(format!("New Conversation ({})", self.conversation.len() / 2)).size(14))
+                    .align_x(alignment::Horizontal::Center)
+                    .align_y(alignment::Vertical::Center)
+                    .height(Length::Fill);
+                let mut reset_btn = button(reset_label)
+                    .padding([8, 12])
+                    .height(Length::Fixed(INPUT_HEIGHT));
+                if !self.is_loading {
+                    reset_btn = reset_btn.on_press(Message::NewConversation);
+                }
+                input_row = input_row.push(reset_btn);
+            }
+        }
+
+        if let Some(toggle) = research_toggle {
+            let sessions_label = container(text("Sessions").size(16))
+                .align_x(alignment::Horizontal::Center)
+                .align_y(alignment::Vertical::Center)
+                .height(Length::Fill);
+            let mut sessions_btn = button(sessions_label)
+                .padding([8, 12])
+                .height(Length::Fixed(INPUT_HEIGHT));
+            if !self.is_loading {
+                sessions_btn = sessions_btn.on_press(Message::ShowSessions);
+            }
+            input_row = input_row.push(toggle);
+            // Depth preset only matters while research mode is on
+            if self.research_mode {
+                let depth_label = container(text(format!("Depth: {}", self.research_depth.label())).size(14))
+                    .align_x(alignment::Horizontal::Center)
+                    .align_y(alignment::Vertical::Center)
+                    .width(Length::Fixed(120.0))
+                    .height(Length::Fill);
+                let mut depth_btn = button(depth_label)
+                    .padding([8, 12])
+                    .height(Length::Fixed(INPUT_HEIGHT));
+                if !self.is_loading {
+                    depth_btn = depth_btn.on_press(Message::CycleResearchDepth);
+                }
+                input_row = input_row.push(depth_btn);
+            }
+            input_row = input_row.push(sessions_btn);
+        }
 
         let output: Element<Message> = if self.is_loading {
             // Show streaming text if available, otherwise show loading spinner
             if !self.streaming_text.is_empty() {
                 scrollable(
-                    container(render_markdown(self.streaming_text.clone()))
+                    container(self.streaming_markdown.render(&self.streaming_text, self.text_scale))
                         .padding(15)
                         .width(Length::Fill)
                 )
                 .direction(Direction::Vertical(Scrollbar::default()))
                 .height(Length::Fill)
                 .into()
+            } else if let Some(ref plan) = self.pending_plan {
+                // Interactive plan review: one `Worker: question` per line
+                let approved = parse_plan_edits(&self.plan_editor.text(), plan, &self.plan_workers);
+                let (start_label, problem) = match &approved {
+                    Ok(questions) => (format!("[Start Research ({})]", questions.len()), None),
+                    Err(e) => ("[Start Research]".to_string(), Some(e.clone())),
+                };
+                container(
+                    column![
+                        text("🔬 Review Research Plan").size(24),
+                        text("One sub-question per line as `Worker: question`. Edit, add or remove lines, then start.")
+                            .size(14)
+                            .color(Color::from_rgb(0.6, 0.6, 0.6)),
+                        text_editor(&self.plan_editor)
+                            .on_action(Message::PlanEditorAction)
+                            .padding(10)
+                            .size(15)
+                            .height(Length::Fill),
+                        text(problem.unwrap_or_default())
+                            .size(14)
+                            .color(Color::from_rgb(0.9, 0.6, 0.6)),
+                        row![
+                            Space::with_width(Length::Fill),
+                            button(text("[Cancel]").size(14))
+                                .on_press(Message::CancelQuery)
+                                .padding(8),
+                            button(text(start_label).size(14))
+                                .on_press_maybe(approved.ok().map(Message::ApprovePlan))
+                                .padding(8),
+                        ]
+                        .spacing(8),
+                    ]
+                    .spacing(12)
+                )
+                .padding(15)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .into()
             } else if let Some(ref progress_text) = self.research_progress {
                 // Show research progress with elapsed time
                 let elapsed = if let Some(start_time) = self.research_start_time {
@@ -1310,6 +3151,24 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                     "0s".to_string()
                 };
 
+                // Estimated completion with a naive linear ETA
+                let progress_view: Element<Message> = match self.research_fraction {
+                    Some(fraction) => {
+                        let eta = self.research_start_time
+                            .and_then(|start| format_eta(start.elapsed(), fraction))
+                            .map(|eta| format!(" · ~{} left", eta))
+                            .unwrap_or_default();
+                        column![
+                            progress_bar(0.0..=1.0, fraction).height(Length::Fixed(8.0)).width(Length::Fixed(320.0)),
+                            text(format!("{:.0}%{}", fraction * 100.0, eta)).size(13),
+                        ]
+                        .spacing(4)
+                        .align_x(alignment::Horizontal::Center)
+                        .into()
+                    }
+                    None => text("").into(),
+                };
+
                 // Fetch current sources list
                 let sources = crate::tools::get_current_sources();
                 let sources_view: Element<Message> = if sources.is_empty() {
@@ -1323,6 +3182,25 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                     col.spacing(4).into()
                 };
 
+                // What each running worker is writing right now
+                let worker_text_view: Element<Message> = if self.worker_text.is_empty() {
+                    text("").into()
+                } else {
+                    let mut col = column![text("Worker output:").size(14)];
+                    for (worker, output) in &self.worker_text {
+                        col = col.push(
+                            column![
+                                text(worker.clone())
+                                    .size(13)
+                                    .style(|_theme: &Theme| text_widget::Style { color: Some(Color::from_rgb(0.55, 0.85, 1.0)) }),
+                                text(last_lines(output, WORKER_TEXT_LINES)).size(12),
+                            ]
+                            .spacing(2)
+                        );
+                    }
+                    col.spacing(6).width(Length::Fixed(640.0)).into()
+                };
+
                 // Recent activity lines (verbose progress)
                 let recent = crate::progress::recent(8);
                 let recent_view: Element<Message> = if recent.is_empty() {
@@ -1353,6 +3231,11 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                         text("🔬 Research Mode").size(24),
                         text(progress_text).size(18),
                         text(format!("Elapsed: {}", elapsed)).size(14),
+                        progress_view,
+                        button(text("[Cancel Research]").size(14))
+                            .on_press(Message::CancelQuery)
+                            .padding(8),
+                        worker_text_view,
                         sources_view,
                         recent_view
                     ]
@@ -1419,6 +3302,47 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                 .align_y(alignment::Vertical::Center)
                 .into()
             }
+        } else if let Some(sessions) = &self.session_list {
+            let mut list = column![text("Research Sessions").size(24)].spacing(10);
+            if sessions.is_empty() {
+                list = list.push(text(format!("No saved sessions in {}", research::sessions_dir(&self.config_dir).display())).size(14));
+            }
+            for (path, session) in sessions {
+                let status = if session.is_complete() {
+                    "complete"
+                } else if session.can_resume() {
+                    "interrupted after workers"
+                } else {
+                    "incomplete"
+                };
+                let details = format!(
+                    "{} · {} · {} worker results",
+                    format_local_timestamp(session.started_at),
+                    status,
+                    session.worker_results.len()
+                );
+                let mut actions = row![
+                    column![
+                        text(session.query.clone()).size(15),
+                        text(details).size(12).color(Color::from_rgb(0.6, 0.6, 0.6)),
+                    ]
+                    .spacing(2)
+                    .width(Length::Fill),
+                    button(text("[Open]").size(14)).on_press(Message::OpenSession(path.clone())).padding(8),
+                ]
+                .spacing(8)
+                .align_y(alignment::Vertical::Center);
+                if session.can_resume() && self.research_orchestrator.is_some() {
+                    actions = actions.push(
+                        button(text("[Resume]").size(14)).on_press(Message::ResumeSession(path.clone())).padding(8)
+                    );
+                }
+                list = list.push(actions);
+            }
+            scrollable(container(list).padding(15).width(Length::Fill))
+                .direction(Direction::Vertical(Scrollbar::default()))
+                .height(Length::Fill)
+                .into()
         } else {
             if self.select_mode {
                 scrollable(
@@ -1447,8 +3371,36 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                 .align_y(alignment::Vertical::Center)
                 .into()
             } else {
+                let mut response_column = column![].spacing(10);
+                if self.show_reasoning && !self.reasoning_text.is_empty() {
+                    response_column = response_column.push(
+                        container(
+                            text(self.reasoning_text.clone())
+                                .size(13)
+                                .style(|_theme: &Theme| text_widget::Style { color: Some(Color::from_rgb(0.62, 0.66, 0.78)) })
+                        )
+                        .padding(10)
+                        .width(Length::Fill)
+                        .style(|_theme| container::Style {
+                            background: Some(Color::from_rgba(0.6, 0.7, 1.0, 0.05).into()),
+                            ..container::Style::default()
+                        })
+                    );
+                }
+                response_column = response_column.push(self.response_markdown.render(&self.response_text, self.text_scale));
+                if let Some(usage) = &self.token_usage {
+                    response_column = response_column.push(
+                        text(format_token_usage(usage, self.query_research)).size(12).color(Color::from_rgb(0.6, 0.6, 0.6))
+                    );
+                }
+                if let Some(details) = &self.history_details {
+                    response_column = response_column.push(
+                        text(details.clone()).size(12).color(Color::from_rgb(0.6, 0.6, 0.6))
+                    );
+                }
+
                 scrollable(
-                    container(render_markdown(self.response_text.clone()))
+                    container(response_column)
                         .padding(15)
                         .width(Length::Fill)
                 )
@@ -1458,22 +3410,91 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
             }
         };
 
-        let mut content_column = column![input_row, output]
-            .spacing(10)
+        let mut content_column = column![].spacing(10)
             // Equal left/right padding (horizontal=3), vertical=10
             .padding(Padding::from([10, 3]));
+        let warning_banner = |warning: &str, dismiss: Message| {
+            container(
+                row![
+                    text(warning.to_string())
+                        .size(14)
+                        .color(Color::from_rgb(1.0, 0.75, 0.4))
+                        .width(Length::Fill),
+                    button(text("×").size(14))
+                        .on_press(dismiss)
+                        .padding(6)
+                ]
+                .spacing(8)
+                .align_y(alignment::Vertical::Center)
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .style(|_theme| container::Style {
+                background: Some(Color::from_rgba(1.0, 0.6, 0.2, 0.12).into()),
+                ..container::Style::default()
+            })
+        };
+        if let Some(warning) = &self.model_warning {
+            content_column = content_column.push(warning_banner(warning, Message::DismissModelWarning));
+        }
+        if let Some(warning) = &self.config_warning {
+            content_column = content_column.push(warning_banner(warning, Message::DismissConfigWarning));
+        }
+        content_column = content_column.push(input_row).push(output);
 
         // Add action buttons at bottom right if we have output
         if !self.response_text.is_empty() && !self.is_loading {
-            let actions = row![
-                button(text(if self.select_mode { "[Done Selecting]" } else { "[Select Text]" }).size(14))
-                    .on_press(Message::ToggleSelectMode)
-                    .padding(8),
-                button(text("[Copy]").size(14))
-                    .on_press(Message::CopyOutput)
-                    .padding(8)
-            ]
-            .spacing(8);
+            let mut actions = row![].spacing(8).align_y(alignment::Vertical::Center);
+            if let Some(status) = &self.save_status {
+                actions = actions.push(text(status.clone()).size(12).color(Color::from_rgb(0.6, 0.6, 0.6)));
+            }
+            if !self.reasoning_text.is_empty() {
+                actions = actions.push(
+                    button(text(if self.show_reasoning { "[Hide Reasoning]" } else { "[Show Reasoning]" }).size(14))
+                        .on_press(Message::ToggleReasoning)
+                        .padding(8)
+                );
+            }
+            // Raw tool-call audit of the last research run, for debugging
+            if DEBUG_MODE.load(Ordering::Relaxed) && self.query_research && self.research_orchestrator.is_some() {
+                actions = actions
+                    .push(
+                        button(text("[Audit MD]").size(14))
+                            .on_press(Message::ExportToolAudit(history::ExportFormat::Markdown))
+                            .padding(8)
+                    )
+                    .push(
+                        button(text("[Audit JSON]").size(14))
+                            .on_press(Message::ExportToolAudit(history::ExportFormat::Json))
+                            .padding(8)
+                    );
+            }
+            actions = actions
+                .push(
+                    button(text(if self.select_mode { "[Done Selecting]" } else { "[Select Text]" }).size(14))
+                        .on_press(Message::ToggleSelectMode)
+                        .padding(8)
+                )
+                .push(
+                    button(text("[Regenerate]").size(14))
+                        .on_press(Message::Regenerate)
+                        .padding(8)
+                )
+                .push(
+                    button(text("[Copy]").size(14))
+                        .on_press(Message::CopyOutput)
+                        .padding(8)
+                )
+                .push(
+                    button(text("[Save]").size(14))
+                        .on_press(Message::SaveOutput)
+                        .padding(8)
+                )
+                .push(
+                    button(text("[Save HTML]").size(14))
+                        .on_press(Message::SaveOutputHtml)
+                        .padding(8)
+                );
 
             let actions_row = container(actions)
                 .width(Length::Fill)
@@ -1498,33 +3519,42 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
                     .align_y(alignment::Vertical::Center)
             );
 
-            // Add spacer so the list starts just below the input's bottom
-            items = items.push(Space::with_height(Length::Fixed(INPUT_HEIGHT / 2.0)));
+            // Search box sits where the spacer used to be, just below the input's bottom
+            items = items.push(Space::with_height(Length::Fixed(INPUT_HEIGHT / 4.0)));
+            items = items.push(
+                text_input("Search history...", &self.history_filter)
+                    .on_input(Message::HistorySearch)
+                    .size(12)
+                    .padding(6)
+            );
+            if self.history.is_empty() && !self.history_filter.trim().is_empty() {
+                items = items.push(text("No matches").size(12).color(Color::from_rgb(0.6, 0.6, 0.6)));
+            }
 
+            let now = unix_now();
             for (i, entry) in self.history.iter().enumerate() {
-                let title = {
-                    let s = &entry.prompt;
+                let truncate = |s: &str, max: usize| {
                     let mut it = s.chars();
-                    let taken: String = it.by_ref().take(16).collect();
-                    if it.next().is_some() { format!("{}...", taken) } else { s.clone() }
+                    let taken: String = it.by_ref().take(max).collect();
+                    if it.next().is_some() { format!("{}...", taken) } else { s.to_string() }
                 };
+                let title = truncate(&entry.prompt, 16);
+                let mut details = format_relative_time(entry.created_at, now);
+                if let Some(model) = &entry.model {
+                    details = format!("{} · {}", details, truncate(model, 14));
+                }
+                let label = || column![
+                    text(title.clone()).size(12).width(Length::Fill),
+                    text(details.clone()).size(10).color(Color::from_rgb(0.6, 0.6, 0.6)),
+                ]
+                .spacing(2);
                 let select_btn = if self.is_loading {
-                    button(
-                        text(title)
-                            .size(12)
-                            .width(Length::Fill)
-                    )
-                    .padding(6)
-                    .width(Length::Fill)
+                    button(label()).padding(6).width(Length::Fill)
                 } else {
-                    button(
-                        text(title)
-                            .size(12)
-                            .width(Length::Fill)
-                    )
-                    .on_press(Message::HistorySelect(i))
-                    .padding(6)
-                    .width(Length::Fill)
+                    button(label())
+                        .on_press(Message::HistorySelect(i))
+                        .padding(6)
+                        .width(Length::Fill)
                 };
 
                 let delete_btn = if self.is_loading {
@@ -1538,6 +3568,30 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
 
                 items = items.push(row![select_btn, delete_btn].spacing(4));
             }
+            if self.history_has_more {
+                items = items.push(
+                    button(text("Load more").size(12).width(Length::Fill).align_x(alignment::Horizontal::Center))
+                        .on_press(Message::HistoryLoadMore)
+                        .padding(6)
+                        .width(Length::Fill)
+                );
+            }
+            if !self.history.is_empty() {
+                let export_btn = |label: &'static str, format| {
+                    button(text(label).size(11).width(Length::Fill).align_x(alignment::Horizontal::Center))
+                        .on_press(Message::ExportHistory(format))
+                        .padding(4)
+                        .width(Length::Fill)
+                };
+                items = items.push(Space::with_height(Length::Fixed(6.0)));
+                items = items.push(
+                    row![
+                        export_btn("Export MD", history::ExportFormat::Markdown),
+                        export_btn("Export JSON", history::ExportFormat::Json),
+                    ]
+                    .spacing(4)
+                );
+            }
 
             scrollable(container(items).width(Length::Fixed(180.0)))
                 .width(Length::Fixed(200.0))
@@ -1559,6 +3613,553 @@ Remember: Only report what is objectively visible. Do not interpret, explain, or
     }
 
     fn theme(&self) -> Theme {
-        Theme::TokyoNight
+        self.theme.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_alert_due() {
+        let minute = Duration::from_secs(60);
+        assert!(completion_alert_due(Some(Duration::from_secs(90)), minute, false));
+        assert!(completion_alert_due(Some(minute), minute, false));
+        assert!(!completion_alert_due(Some(Duration::from_secs(90)), minute, true));
+        assert!(!completion_alert_due(Some(Duration::from_secs(5)), minute, false));
+        assert!(!completion_alert_due(None, minute, false));
+    }
+
+    /// Concatenate the text of every span in every block
+    fn plain_text(blocks: &[MarkdownBlock]) -> String {
+        blocks
+            .iter()
+            .flat_map(|block| match block {
+                MarkdownBlock::Text(spans)
+                | MarkdownBlock::Table(spans, _)
+                | MarkdownBlock::Code(spans, _)
+                | MarkdownBlock::Quote(_, spans) => spans.iter(),
+            })
+            .map(|s| s.text.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        assert_eq!(parse_theme("Dark"), Theme::Dark);
+        assert_eq!(parse_theme("CatppuccinMocha"), Theme::CatppuccinMocha);
+        assert_eq!(parse_theme("catppuccin mocha"), Theme::CatppuccinMocha);
+        assert_eq!(parse_theme("Nord"), Theme::Nord);
+        assert_eq!(parse_theme("no-such-theme"), Theme::TokyoNight);
+    }
+
+    #[test]
+    fn test_next_theme_wraps() {
+        let last = Theme::ALL.last().unwrap();
+        assert_eq!(next_theme(last), Theme::ALL[0]);
+        assert_eq!(next_theme(&Theme::ALL[0]), Theme::ALL[1]);
+    }
+
+    #[test]
+    fn test_screenshot_capture_mode_from_args() {
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(screenshot_capture_mode(&args(&["bob-bar"])).unwrap(), None);
+        assert_eq!(
+            screenshot_capture_mode(&args(&["bob-bar", "--screenshot"])).unwrap(),
+            Some(screenshot::CaptureMode::FullScreen)
+        );
+        assert_eq!(
+            screenshot_capture_mode(&args(&["bob-bar", "--screenshot", "--select-region"])).unwrap(),
+            Some(screenshot::CaptureMode::Select)
+        );
+        let region = screenshot::Region { x: 0, y: 0, width: 1280, height: 720 };
+        assert_eq!(
+            screenshot_capture_mode(&args(&["bob-bar", "--region", "0,0,1280,720"])).unwrap(),
+            Some(screenshot::CaptureMode::Region(region))
+        );
+        assert_eq!(
+            screenshot_capture_mode(&args(&["bob-bar", "--region=0,0,1280,720"])).unwrap(),
+            Some(screenshot::CaptureMode::Region(region))
+        );
+        assert!(screenshot_capture_mode(&args(&["bob-bar", "--region"])).is_err());
+    }
+
+    #[test]
+    fn test_config_dir_arg() {
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(config_dir_arg(&args(&["bob-bar", "--debug"])).unwrap(), None);
+        assert_eq!(
+            config_dir_arg(&args(&["bob-bar", "--config-dir", "/tmp/work-profile"])).unwrap(),
+            Some(std::path::PathBuf::from("/tmp/work-profile"))
+        );
+        assert_eq!(
+            config_dir_arg(&args(&["bob-bar", "--config-path=profiles/test"])).unwrap(),
+            Some(std::path::PathBuf::from("profiles/test"))
+        );
+        assert!(config_dir_arg(&args(&["bob-bar", "--config-dir"])).is_err());
+        assert!(config_dir_arg(&args(&["bob-bar", "--config-dir", "Cargo.toml"])).is_err());
+    }
+
+    #[test]
+    fn test_export_markdown_adds_title_and_timestamp() {
+        let doc = export_markdown("What is Rust?\nmore", "Rust is a language.\n\n", "2025-10-04 12:00 (UTC+00:00)");
+        assert_eq!(doc, "# What is Rust?\n\n*Exported 2025-10-04 12:00 (UTC+00:00)*\n\nRust is a language.\n");
+        assert!(export_markdown("  ", "body", "ts").starts_with("# bob-bar export\n"));
+    }
+
+    #[test]
+    fn test_parse_model_override() {
+        assert_eq!(parse_model_override("@llama3.1:70b explain X"), Some(("llama3.1:70b", "explain X")));
+        assert_eq!(parse_model_override("  @qwen3\n\nmulti\nline"), Some(("qwen3", "multi\nline")));
+        assert_eq!(parse_model_override("@mistral"), Some(("mistral", "")));
+        assert_eq!(parse_model_override("@ not a model"), None);
+        assert_eq!(parse_model_override("email me@example.com"), None);
+    }
+
+    #[test]
+    fn test_render_to_html() {
+        let markdown = export_markdown(
+            "Rust <vs> Go",
+            "| Lang | Speed |\n|---|---|\n| Rust | fast |\n\n## References\n\n1. <https://example.com>\n",
+            "2025-10-04 12:00 (UTC+00:00)",
+        );
+        let html = render_to_html(&markdown);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Rust &lt;vs&gt; Go</title>"));
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<td>Rust</td>"));
+        assert!(html.contains("<h2>References</h2>"));
+        assert!(html.contains("<a href=\"https://example.com\">https://example.com</a>"));
+        assert!(html.contains("border-collapse"));
+    }
+
+    #[test]
+    fn test_render_to_html_escapes_raw_html() {
+        let html = render_to_html("# Title\n\n<script>alert(1)</script>\n\nText with <b onclick=\"x()\">inline</b> tags\n");
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!html.contains("<b onclick"));
+        assert!(html.contains("&lt;b onclick="));
+    }
+
+    #[test]
+    fn test_append_worker_text() {
+        let mut buffers = Vec::new();
+        append_worker_text(&mut buffers, "Alpha".to_string(), "line one\n\nline");
+        append_worker_text(&mut buffers, "Beta".to_string(), "hello");
+        append_worker_text(&mut buffers, "Alpha".to_string(), " two\nline three\nline four");
+        assert_eq!(buffers.len(), 2);
+        assert_eq!(last_lines(&buffers[0].1, 3), "line two\nline three\nline four");
+        assert_eq!(last_lines(&buffers[1].1, 3), "hello");
+
+        // Only the tail is kept, cut on a character boundary
+        append_worker_text(&mut buffers, "Beta".to_string(), &"é".repeat(WORKER_TEXT_TAIL));
+        assert!(buffers[1].1.len() <= WORKER_TEXT_TAIL);
+        assert!(buffers[1].1.chars().all(|c| c == 'é'));
+    }
+
+    #[test]
+    fn test_default_export_name() {
+        assert_eq!(default_export_name("What's new in Rust 1.80?"), "what-s-new-in-rust-1-80.md");
+        assert_eq!(default_export_name("???"), "bob-bar-export.md");
+        assert!(default_export_name(&"word ".repeat(40)).len() <= 51);
+    }
+
+    #[test]
+    fn test_archive_response_writes_dated_file_with_frontmatter() {
+        let dir = std::env::temp_dir().join(format!("bob-bar-archive-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let now = unix_now();
+
+        let first = archive_response(&dir, "What is \"Rust\"?", "Rust is a language.\n", "llama3", true, now).unwrap();
+        let second = archive_response(&dir, "What is \"Rust\"?", "Again.", "llama3", false, now).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), second.parent());
+        assert!(first.file_name().unwrap().to_string_lossy().ends_with("-what-is-rust.md"));
+        assert!(second.file_name().unwrap().to_string_lossy().ends_with("-what-is-rust-2.md"));
+
+        let contents = std::fs::read_to_string(&first).unwrap();
+        assert!(contents.starts_with("---\nquery: \"What is \\\"Rust\\\"?\"\nmodel: \"llama3\"\nresearch: true\ntimestamp: "));
+        assert!(contents.ends_with("---\n\nRust is a language.\n"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_format_history_details() {
+        let mut entry = history::HistoryEntry {
+            id: 1,
+            prompt: "q".to_string(),
+            response: "a".to_string(),
+            created_at: 1_760_000_000,
+            model: Some("llama3".to_string()),
+            research_mode: Some(true),
+            duration_ms: Some(192_400),
+        };
+        let details = format_history_details(&entry);
+        assert!(details.starts_with("Research · llama3 · 2025-10-"), "{}", details);
+        assert!(details.ends_with(" · took 3m 12s"), "{}", details);
+
+        entry.model = None;
+        entry.research_mode = None;
+        entry.duration_ms = None;
+        assert_eq!(format_history_details(&entry), format_local_timestamp(entry.created_at));
+    }
+
+    #[test]
+    fn test_format_relative_time() {
+        let now = 1_760_000_000;
+        assert_eq!(format_relative_time(now - 5, now), "just now");
+        assert_eq!(format_relative_time(now - 5 * 60, now), "5m ago");
+        assert_eq!(format_relative_time(now - 2 * 3600 - 30, now), "2h ago");
+        assert_eq!(format_relative_time(now - 86400 - 60, now), "yesterday");
+        assert_eq!(format_relative_time(now - 4 * 86400, now), "4 days ago");
+        let old = format_relative_time(now - 30 * 86400, now);
+        assert_eq!(old.len(), 10);
+        assert!(old.starts_with("2025-09-"));
+    }
+
+    #[test]
+    fn test_format_token_usage() {
+        let usage = ollama::TokenUsage { prompt_tokens: 812, completion_tokens: 240, eval_duration_ns: 8_000_000_000, calls: 1 };
+        assert_eq!(format_token_usage(&usage, false), "Tokens: 812 prompt · 240 completion · 30.0 tok/s");
+        let session = ollama::TokenUsage { eval_duration_ns: 0, calls: 14, ..usage };
+        assert_eq!(format_token_usage(&session, true), "Session tokens (14 calls): 812 prompt · 240 completion");
+    }
+
+    #[test]
+    fn test_ordered_list_numbers_items() {
+        let blocks = markdown_blocks("1. first\n2. second\n3. third\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "1. first\n2. second\n3. third\n\n");
+    }
+
+    #[test]
+    fn test_ordered_list_respects_start() {
+        let blocks = markdown_blocks("4. four\n5. five\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "4. four\n5. five\n\n");
+    }
+
+    #[test]
+    fn test_nested_bullet_list_indents() {
+        let blocks = markdown_blocks("- a\n  - b\n    - c\n- d\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "• a\n  • b\n    • c\n• d\n\n");
+    }
+
+    #[test]
+    fn test_mixed_nested_ordered_list() {
+        let blocks = markdown_blocks("1. one\n   - bullet\n   - bullet\n2. two\n   1. sub one\n   2. sub two\n", config::TableOverflow::Truncate);
+        assert_eq!(
+            plain_text(&blocks),
+            "1. one\n  • bullet\n  • bullet\n2. two\n  1. sub one\n  2. sub two\n\n"
+        );
+    }
+
+    #[test]
+    fn test_strikethrough_is_parsed_and_styled() {
+        let blocks = markdown_blocks("keep ~~drop~~ keep\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "keep drop keep\n\n");
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let struck: Vec<_> = spans.iter().filter(|s| s.strikethrough).collect();
+        assert_eq!(struck.len(), 1);
+        assert_eq!(struck[0].text, "drop");
+        assert!(struck[0].color.is_some());
+    }
+
+    #[test]
+    fn test_strikethrough_inside_bold() {
+        let blocks = markdown_blocks("**bold ~~both~~ bold**\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "bold both bold\n\n");
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let texts: Vec<(String, bool)> = spans
+            .iter()
+            .filter(|s| !s.text.trim().is_empty())
+            .map(|s| (s.text.to_string(), s.strikethrough))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![("bold ".to_string(), false), ("both".to_string(), true), (" bold".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_emphasis_is_its_own_italic_span() {
+        let blocks = markdown_blocks("plain *slanted* plain\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let texts: Vec<String> = spans.iter().map(|s| s.text.to_string()).collect();
+        assert_eq!(texts, vec!["plain ", "slanted", " plain", "\n\n"]);
+        let italic = &spans[1];
+        assert_eq!(italic.font.map(|f| f.style), Some(font::Style::Italic));
+        assert!(spans[0].font.is_none());
+        assert!(spans[2].font.is_none());
+    }
+
+    #[test]
+    fn test_bold_italic_span() {
+        let blocks = markdown_blocks("***word***\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let word = spans.iter().find(|s| s.text == "word").expect("word span");
+        let f = word.font.expect("font set");
+        assert_eq!(f.style, font::Style::Italic);
+        assert_eq!(f.weight, font::Weight::Bold);
+    }
+
+    #[test]
+    fn test_blockquote_gets_own_block() {
+        let blocks = markdown_blocks("before\n\n> quoted\n\nafter\n", config::TableOverflow::Truncate);
+        assert_eq!(blocks.len(), 3);
+        assert!(matches!(&blocks[0], MarkdownBlock::Text(_)));
+        match &blocks[1] {
+            MarkdownBlock::Quote(depth, spans) => {
+                assert_eq!(*depth, 1);
+                let text: String = spans.iter().map(|s| s.text.to_string()).collect();
+                assert_eq!(text, "quoted");
+            }
+            _ => panic!("expected quote block"),
+        }
+        assert!(matches!(&blocks[2], MarkdownBlock::Text(_)));
+    }
+
+    #[test]
+    fn test_nested_blockquotes_increase_depth() {
+        let blocks = markdown_blocks("> outer\n>\n> > inner\n", config::TableOverflow::Truncate);
+        let depths: Vec<usize> = blocks
+            .iter()
+            .filter_map(|b| match b {
+                MarkdownBlock::Quote(depth, _) => Some(*depth),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(depths, vec![1, 2]);
+    }
+
+    fn link_of(s: &MarkdownSpan) -> Option<String> {
+        match &s.link {
+            Some(Message::OpenLink(url)) => Some(url.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_link_span_opens_url() {
+        let blocks = markdown_blocks("see [the docs](https://example.com/docs) now\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let link = spans.iter().find(|s| s.text == "the docs").expect("link span");
+        assert!(link.underline);
+        assert_eq!(link_of(link).as_deref(), Some("https://example.com/docs"));
+        assert!(spans.iter().filter(|s| s.text != "the docs").all(|s| s.link.is_none()));
+    }
+
+    #[test]
+    fn test_empty_link_text_falls_back_to_url() {
+        let blocks = markdown_blocks("[](https://example.com)\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        let link = spans.iter().find(|s| s.link.is_some()).expect("link span");
+        assert_eq!(link.text, "https://example.com");
+    }
+
+    #[test]
+    fn test_relative_and_anchor_links_are_not_clickable() {
+        let blocks = markdown_blocks("[top](#top) and [file](docs/readme.md)\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Text(spans) = &blocks[0] else { panic!("expected text block") };
+        assert!(spans.iter().all(|s| s.link.is_none()));
+        assert!(spans.iter().any(|s| s.text == "top" && s.underline));
+    }
+
+    #[test]
+    fn test_fenced_code_is_highlighted_by_language() {
+        let blocks = markdown_blocks("```rust\nlet x = 1; // one\n```\n\n```sql\nSELECT 1;\n```\n", config::TableOverflow::Truncate);
+        let MarkdownBlock::Code(spans, _) = &blocks[0] else { panic!("expected code block") };
+        let keyword = spans.iter().find(|s| s.text == "let").expect("keyword span");
+        let comment = spans.iter().find(|s| s.text == "// one").expect("comment span");
+        assert_ne!(keyword.color, comment.color);
+        assert_eq!(keyword.font, Some(Font::MONOSPACE));
+        let MarkdownBlock::Code(sql, _) = &blocks[1] else { panic!("expected code block") };
+        assert!(sql.iter().any(|s| s.text == "SELECT 1;\n"));
+        assert!(plain_text(&blocks).contains("let x = 1; // one\n"));
+    }
+
+    #[test]
+    fn test_code_blocks_are_separate_blocks_with_source() {
+        let blocks = markdown_blocks("Run this:\n\n```bash\necho \"hi\"\n```\n\nthen done\n\n```\nstill streaming", config::TableOverflow::Truncate);
+        assert_eq!(blocks.len(), 4);
+        assert!(matches!(&blocks[0], MarkdownBlock::Text(_)));
+        assert!(matches!(&blocks[1], MarkdownBlock::Code(_, code) if code == "echo \"hi\"\n"));
+        assert!(matches!(&blocks[2], MarkdownBlock::Text(_)));
+        assert!(matches!(&blocks[3], MarkdownBlock::Code(_, code) if code == "still streaming"));
+    }
+
+    #[test]
+    fn test_table_csv_quotes_special_cells() {
+        let rows = vec![
+            vec!["name".to_string(), "note".to_string()],
+            vec!["a, b".to_string(), "say \"hi\"".to_string()],
+            vec!["plain".to_string(), "two\nlines".to_string()],
+        ];
+        assert_eq!(table_csv(&rows), "name,note\n\"a, b\",\"say \"\"hi\"\"\"\nplain,\"two\nlines\"\n");
+    }
+
+    #[test]
+    fn test_markdown_cache_keeps_table_rows() {
+        let cache = MarkdownCache::default();
+        let _ = cache.render("| k | v |\n|---|---|\n| x | 1 |\n\ntext\n\n| only |\n|---|\n| y |\n", 1.0);
+        assert_eq!(cache.table_rows(0), Some(vec![vec!["k".to_string(), "v".to_string()], vec!["x".to_string(), "1".to_string()]]));
+        assert_eq!(cache.table_rows(1), Some(vec![vec!["only".to_string()], vec!["y".to_string()]]));
+        assert_eq!(cache.table_rows(2), None);
+    }
+
+    #[test]
+    fn test_wrap_cell() {
+        assert_eq!(wrap_cell("short", 10), vec!["short"]);
+        assert_eq!(wrap_cell("one two three", 7), vec!["one two", "three"]);
+        assert_eq!(wrap_cell("abcdefgh ij", 3), vec!["abc", "def", "gh", "ij"]);
+        assert_eq!(wrap_cell("", 5), vec![""]);
+    }
+
+    #[test]
+    fn test_fit_column_widths() {
+        // Fits already: untouched
+        assert_eq!(fit_column_widths(&[5, 40], 120), vec![5, 40]);
+        // Only the wide column shrinks, to exactly fill the target
+        assert_eq!(fit_column_widths(&[10, 200, 30], 120), vec![10, 70, 30]);
+        // Two wide columns share the space; the odd column goes to the first
+        assert_eq!(fit_column_widths(&[100, 100], 120), vec![57, 56]);
+        // Never below the minimum, even if the table still overflows
+        assert_eq!(fit_column_widths(&[4, 50, 50], 20), vec![4, TABLE_MIN_COL_WIDTH, TABLE_MIN_COL_WIDTH]);
+    }
+
+    #[test]
+    fn test_table_border_glyphs() {
+        let blocks = markdown_blocks("| a | bb |\n|---|---|\n| 1 | 2 |\n| 3 | 4 |\n", config::TableOverflow::Truncate);
+        let table: String = match &blocks[..] {
+            [MarkdownBlock::Table(spans, _)] => spans.iter().map(|s| s.text.to_string()).collect(),
+            _ => panic!("expected a single table block"),
+        };
+        assert_eq!(
+            table,
+            "┌───┬────┐\n\
+             │ a │ bb │\n\
+             ╞═══╪════╡\n\
+             │ 1 │ 2  │\n\
+             ├───┼────┤\n\
+             │ 3 │ 4  │\n\
+             └───┴────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_table_inline_code_cells_drop_backticks() {
+        let blocks = markdown_blocks("| flag | effect |\n|---|---|\n| `--debug` | logs `tool` calls |\n", config::TableOverflow::Truncate);
+        let (table, rows) = match &blocks[..] {
+            [MarkdownBlock::Table(spans, rows)] => (spans.iter().map(|s| s.text.to_string()).collect::<String>(), rows),
+            _ => panic!("expected a single table block"),
+        };
+        assert!(table.contains("│ --debug │ logs tool calls │"));
+        assert!(!table.contains('`'));
+        assert_eq!(rows[1], vec!["--debug".to_string(), "logs tool calls".to_string()]);
+    }
+
+    #[test]
+    fn test_table_wraps_long_cells_when_configured() {
+        let long = "word ".repeat(30);
+        let source = format!("| a | b |\n|---|---|\n| x | {} |\n", long.trim());
+        let table_lines = |overflow| -> Vec<String> {
+            plain_text(&markdown_blocks(&source, overflow)).lines().map(str::to_string).collect()
+        };
+
+        let truncated = table_lines(config::TableOverflow::Truncate);
+        assert!(truncated.iter().any(|l| l.contains('…')));
+
+        let wrapped = table_lines(config::TableOverflow::Wrap);
+        assert!(!wrapped.iter().any(|l| l.contains('…')));
+        // top, header, separator, 2 wrapped body lines, bottom
+        assert_eq!(wrapped.len(), 6);
+        assert!(wrapped[4].starts_with("│   │ word"));
+        // Every line of the bordered layout keeps the same width
+        let width = UnicodeWidthStr::width(wrapped[0].as_str());
+        assert!(wrapped.iter().all(|l| UnicodeWidthStr::width(l.as_str()) == width));
+    }
+
+    #[test]
+    fn test_text_scale_steps_and_scaled_spans() {
+        assert_eq!(step_text_scale(1.0, Some(true)), 1.1);
+        assert_eq!(step_text_scale(1.1, Some(false)), 1.0);
+        assert_eq!(step_text_scale(MAX_TEXT_SCALE, Some(true)), MAX_TEXT_SCALE);
+        assert_eq!(step_text_scale(MIN_TEXT_SCALE, Some(false)), MIN_TEXT_SCALE);
+        assert_eq!(step_text_scale(1.7, None), 1.0);
+
+        let scaled = scale_spans(&[styled_span("x".to_string(), false, false, false)], 1.5);
+        assert_eq!(scaled[0].size, Some(iced::Pixels(22.5)));
+    }
+
+    #[test]
+    fn test_markdown_cache_reparses_only_on_change() {
+        let cache = MarkdownCache::default();
+        let cached_hash = |cache: &MarkdownCache| cache.entry.borrow().as_ref().map(|(h, _)| *h);
+
+        let _ = cache.render("# Title", 1.0);
+        let first = cached_hash(&cache);
+        assert!(first.is_some());
+
+        let _ = cache.render("# Title", 1.0);
+        assert_eq!(cached_hash(&cache), first);
+
+        let _ = cache.render("# Title\n\nmore", 1.0);
+        assert_ne!(cached_hash(&cache), first);
+        let block_count = cache.entry.borrow().as_ref().map(|(_, b)| b.len());
+        assert_eq!(block_count, Some(1));
+    }
+
+    #[test]
+    fn test_list_after_paragraph_starts_on_new_line() {
+        let blocks = markdown_blocks("Intro\n\n- item\n", config::TableOverflow::Truncate);
+        assert_eq!(plain_text(&blocks), "Intro\n\n• item\n\n");
+    }
+
+    #[test]
+    fn test_step_history_cursor() {
+        assert_eq!(step_history_cursor(None, 0, true), None);
+        assert_eq!(step_history_cursor(None, 3, true), Some(0));
+        assert_eq!(step_history_cursor(Some(1), 3, true), Some(2));
+        assert_eq!(step_history_cursor(Some(2), 3, true), Some(2));
+        assert_eq!(step_history_cursor(Some(2), 3, false), Some(1));
+        assert_eq!(step_history_cursor(Some(0), 3, false), None);
+        assert_eq!(step_history_cursor(None, 3, false), None);
+    }
+
+    #[test]
+    fn test_parse_plan_edits() {
+        let original = vec![
+            research::SubQuestion { question: "What is A?".to_string(), assigned_worker: "worker_1".to_string() },
+            research::SubQuestion { question: "What is B?".to_string(), assigned_worker: "worker_2".to_string() },
+        ];
+        let text = format_plan_for_editing(&original);
+        assert_eq!(text, "worker_1: What is A?\nworker_2: What is B?");
+
+        let configured: Vec<String> = ["worker_1", "worker_2", "worker_3"].iter().map(|w| w.to_string()).collect();
+        let edited = "worker_2: What is A, really?\n\nNote: a new angle\nAnd another\nworker_3: What is C?";
+        let parsed = parse_plan_edits(edited, &original, &configured).unwrap();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].assigned_worker, "worker_2");
+        assert_eq!(parsed[0].question, "What is A, really?");
+        assert_eq!(parsed[1].question, "Note: a new angle");
+        assert_eq!(parsed[1].assigned_worker, "worker_1");
+        assert_eq!(parsed[2].assigned_worker, "worker_2");
+        // A configured worker that wasn't in the plan can still be named
+        assert_eq!(parsed[3].assigned_worker, "worker_3");
+        assert_eq!(parsed[3].question, "What is C?");
+
+        // An empty plan rotates over the configured workers
+        let parsed = parse_plan_edits("First\nSecond", &[], &configured).unwrap();
+        assert_eq!(parsed[1].assigned_worker, "worker_2");
+        // With no workers at all the plan can't be approved
+        assert!(parse_plan_edits("First", &[], &[]).is_err());
+    }
+
+    #[test]
+    fn test_format_eta() {
+        assert_eq!(format_eta(Duration::from_secs(10), 0.01), None);
+        assert_eq!(format_eta(Duration::from_secs(30), 0.5), Some("30s".to_string()));
+        assert_eq!(format_eta(Duration::from_secs(60), 0.25), Some("3m 0s".to_string()));
+        assert_eq!(format_eta(Duration::from_secs(60), 1.0), None);
     }
 }
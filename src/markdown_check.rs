@@ -0,0 +1,285 @@
+//! Deterministic checks for the research document before it reaches the critic.
+//!
+//! The document critic is an LLM and reliably misses mechanical breakage: a code fence
+//! that never closes swallows the rest of the document, a table row with the wrong number
+//! of cells renders misaligned or loses text, and `[text][ref]` / `(#anchor)` links point
+//! nowhere. These are found by parsing the markdown with pulldown-cmark, the same parser
+//! the UI renders with.
+
+use pulldown_cmark::{BrokenLink, CodeBlockKind, Event, LinkType, Options, Parser, Tag};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A problem found in the document, with the 1-based line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    UnclosedCodeFence { line: usize },
+    TableColumns { line: usize, expected: usize, found: usize },
+    EmptyHeading { line: usize },
+    UndefinedReference { line: usize, label: String },
+    MissingAnchor { line: usize, anchor: String },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::UnclosedCodeFence { line } => write!(f, "line {}: code fence is never closed", line),
+            Issue::TableColumns { line, expected, found } => {
+                write!(f, "line {}: table row has {} cells but the header has {}", line, found, expected)
+            }
+            Issue::EmptyHeading { line } => write!(f, "line {}: heading has no text", line),
+            Issue::UndefinedReference { line, label } => {
+                write!(f, "line {}: link reference [{}] is never defined", line, label)
+            }
+            Issue::MissingAnchor { line, anchor } => {
+                write!(f, "line {}: link to #{} matches no heading", line, anchor)
+            }
+        }
+    }
+}
+
+fn parser_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options
+}
+
+fn line_of(markdown: &str, offset: usize) -> usize {
+    markdown[..offset.min(markdown.len())].matches('\n').count() + 1
+}
+
+/// Every issue in `markdown`, in document order
+pub fn check(markdown: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut undefined = Vec::new();
+    let mut on_broken_link = |link: BrokenLink| {
+        // Shortcut links (`[1]`, `[Source: ...]`) are usually citations, not references
+        if matches!(link.link_type, LinkType::Reference | LinkType::Collapsed) {
+            undefined.push((link.span.start, link.reference.to_string()));
+        }
+        None
+    };
+    let parser = Parser::new_with_broken_link_callback(markdown, parser_options(), Some(&mut on_broken_link));
+
+    let mut heading: Option<(usize, String)> = None;
+    let mut heading_slugs: HashMap<String, usize> = HashMap::new();
+    let mut anchors = Vec::new();
+
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) if !fence_is_closed(&markdown[range.clone()]) => {
+                issues.push(Issue::UnclosedCodeFence { line: line_of(markdown, range.start) });
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                let first_line = line_of(markdown, range.start);
+                for (i, row) in markdown[range].lines().enumerate().skip(2) {
+                    let found = split_row(row).len();
+                    if !row.trim().is_empty() && found != alignments.len() {
+                        issues.push(Issue::TableColumns { line: first_line + i, expected: alignments.len(), found });
+                    }
+                }
+            }
+            Event::Start(Tag::Heading(..)) => heading = Some((range.start, String::new())),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, content)) = heading.as_mut() {
+                    content.push_str(&text);
+                }
+            }
+            Event::End(Tag::Heading(..)) => {
+                if let Some((start, content)) = heading.take() {
+                    if content.trim().is_empty() {
+                        issues.push(Issue::EmptyHeading { line: line_of(markdown, start) });
+                    } else {
+                        // GitHub suffixes repeated headings: #setup, #setup-1, ...
+                        let slug = heading_slug(&content);
+                        let seen = heading_slugs.get(&slug).copied().unwrap_or(0);
+                        if seen > 0 {
+                            heading_slugs.insert(format!("{}-{}", slug, seen), 1);
+                        }
+                        heading_slugs.insert(slug, seen + 1);
+                    }
+                }
+            }
+            Event::Start(Tag::Link(_, dest, _)) => {
+                if let Some(anchor) = dest.strip_prefix('#') {
+                    anchors.push((range.start, anchor.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (offset, anchor) in anchors {
+        if !heading_slugs.contains_key(&anchor.to_lowercase()) {
+            issues.push(Issue::MissingAnchor { line: line_of(markdown, offset), anchor });
+        }
+    }
+    for (offset, label) in undefined {
+        issues.push(Issue::UndefinedReference { line: line_of(markdown, offset), label });
+    }
+    issues.sort_by_key(|issue| match issue {
+        Issue::UnclosedCodeFence { line }
+        | Issue::TableColumns { line, .. }
+        | Issue::EmptyHeading { line }
+        | Issue::UndefinedReference { line, .. }
+        | Issue::MissingAnchor { line, .. } => *line,
+    });
+    issues
+}
+
+/// The issues as a list the document critic can act on
+pub fn critic_feedback(issues: &[Issue]) -> String {
+    let mut feedback = format!(
+        "Automated markdown checks found {} problem(s) in this document. Each one must be fixed before it can be approved:\n",
+        issues.len()
+    );
+    for issue in issues {
+        feedback.push_str(&format!("- {}\n", issue));
+    }
+    feedback
+}
+
+/// Pad table rows that are short of the header's column count and fold the surplus cells
+/// of long rows into their last column (pulldown-cmark would drop them). Returns the
+/// repaired document and how many rows were changed.
+pub fn repair_table_columns(markdown: &str) -> (String, usize) {
+    let mut fixes: HashMap<usize, usize> = HashMap::new();  // 0-based line -> expected cells
+    for (event, range) in Parser::new_ext(markdown, parser_options()).into_offset_iter() {
+        if let Event::Start(Tag::Table(alignments)) = event {
+            let first_line = line_of(markdown, range.start) - 1;
+            for (i, row) in markdown[range].lines().enumerate().skip(2) {
+                if !row.trim().is_empty() && split_row(row).len() != alignments.len() {
+                    fixes.insert(first_line + i, alignments.len());
+                }
+            }
+        }
+    }
+    if fixes.is_empty() {
+        return (markdown.to_string(), 0);
+    }
+
+    let mut repaired = String::with_capacity(markdown.len());
+    for (i, line) in markdown.split_inclusive('\n').enumerate() {
+        match fixes.get(&i) {
+            Some(&expected) => {
+                let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let mut cells = split_row(line);
+                if cells.len() > expected {
+                    let surplus = cells.split_off(expected.max(1) - 1);
+                    cells.push(surplus.join("; "));
+                }
+                cells.resize(expected, String::new());
+                repaired.push_str(&format!("{}| {} |{}", indent, cells.join(" | "), ending));
+            }
+            None => repaired.push_str(line),
+        }
+    }
+    (repaired, fixes.len())
+}
+
+/// A fenced block's source ends with a fence at least as long as the opening one
+fn fence_is_closed(block: &str) -> bool {
+    let mut lines = block.trim_end().lines().map(|line| line.trim_start_matches([' ', '\t', '>']));
+    let opening = lines.next().unwrap_or("");
+    let Some(fence_char) = opening.chars().next() else { return false };
+    let fence_len = opening.chars().take_while(|&c| c == fence_char).count();
+    match lines.next_back() {
+        Some(closing) => {
+            let closing = closing.trim();
+            closing.chars().all(|c| c == fence_char) && closing.chars().count() >= fence_len
+        }
+        None => false,
+    }
+}
+
+/// The cells of a table row, splitting on unescaped `|` (as GFM does, even inside code spans)
+fn split_row(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    for c in row.chars() {
+        if c == '|' && !escaped {
+            cells.push(std::mem::take(&mut cell));
+        } else {
+            cell.push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    // A trailing pipe closes the row rather than starting an empty cell
+    if !cell.trim().is_empty() || cells.is_empty() {
+        cells.push(cell);
+    }
+    cells.into_iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// GitHub-style anchor for a heading: lowercase, punctuation dropped, spaces to hyphens
+fn heading_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_document_has_no_issues() {
+        let doc = "# Report\n\nSee [the summary](#summary-of-results) and [docs][ref].\n\n\
+                   ## Summary of Results\n\n| A | B |\n|---|---|\n| 1 | `x\\|y` |\n\n\
+                   ```rust\nfn main() {}\n```\n\n[ref]: https://example.com\n";
+        assert_eq!(check(doc), vec![]);
+    }
+
+    #[test]
+    fn test_check_finds_issues() {
+        let doc = "# Report\n\n##\n\n| A | B |\n|---|---|\n| 1 |\n| 1 | 2 | 3 |\n\n\
+                   Jump to [details](#details) or [docs][missing], cited [1].\n\n```\nnever closed\n";
+        assert_eq!(check(doc), vec![
+            Issue::EmptyHeading { line: 3 },
+            Issue::TableColumns { line: 7, expected: 2, found: 1 },
+            Issue::TableColumns { line: 8, expected: 2, found: 3 },
+            Issue::MissingAnchor { line: 10, anchor: "details".to_string() },
+            Issue::UndefinedReference { line: 10, label: "missing".to_string() },
+            Issue::UnclosedCodeFence { line: 12 },
+        ]);
+    }
+
+    #[test]
+    fn test_repeated_heading_anchors() {
+        let doc = "## Setup\n\n## Setup\n\n[a](#setup) [b](#setup-1) [c](#setup-2)\n";
+        assert_eq!(check(doc), vec![Issue::MissingAnchor { line: 5, anchor: "setup-2".to_string() }]);
+    }
+
+    #[test]
+    fn test_repair_table_columns() {
+        let doc = "Intro\n\n| A | B | C |\n|---|---|---|\n| 1 |\n| 1 | 2 | 3 | 4 |\n| ok | ok | ok |\n";
+        let (repaired, fixed) = repair_table_columns(doc);
+        assert_eq!(fixed, 2);
+        assert_eq!(
+            repaired,
+            "Intro\n\n| A | B | C |\n|---|---|---|\n| 1 |  |  |\n| 1 | 2 | 3; 4 |\n| ok | ok | ok |\n"
+        );
+        assert!(check(&repaired).is_empty());
+        assert_eq!(repair_table_columns(&repaired), (repaired.clone(), 0));
+    }
+
+    #[test]
+    fn test_split_row() {
+        assert_eq!(split_row("| a | b |"), vec!["a", "b"]);
+        assert_eq!(split_row("a | b"), vec!["a", "b"]);
+        assert_eq!(split_row("| a \\| b | `c\\|d` |"), vec!["a \\| b", "`c\\|d`"]);
+        assert_eq!(split_row("| a |  |"), vec!["a", ""]);
+        assert_eq!(split_row("| `c|d` |"), vec!["`c", "d`"]);
+    }
+}
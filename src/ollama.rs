@@ -9,22 +9,124 @@ use std::pin::Pin;
 use std::future::Future;
 use anyhow::Result;
 use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
 
-#[allow(unused_macros)]
-macro_rules! debug_println {
-    ($($arg:tt)*) => {
-        if std::env::var("BOBBAR_DEBUG").is_ok() {
-            println!($($arg)*);
+/// Default guidance on laying out answers, sent with every query
+const STRUCTURED_DATA_INSTRUCTIONS: &str = "Present structured data using:
+- Clear section headings (## or ###)
+- Bullet points with bold labels (• **Label:** value)
+- Numbered lists for sequential information
+- Simple key-value format on separate lines
+- If you use a markdown table, include clear, specific column headers for each column (no generic names)";
+const MARKDOWN_INSTRUCTIONS: &str = "Format your response in clean markdown (use headers, lists, code blocks, etc. as appropriate). Be concise - keep responses to 1-3 sentences unless asked for more detail.";
+
+/// The longest prefix of `text` of at most `max_bytes` that doesn't split a character,
+/// for cutting model output down to a byte budget
+pub fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Whether an agent limited to `allowed` (`None` or empty = every tool) and barred from
+/// `denied` may call `tool_name`
+fn tool_permitted(allowed: Option<&[String]>, denied: &[String], tool_name: &str) -> bool {
+    if denied.iter().any(|t| t == tool_name) {
+        return false;
+    }
+    match allowed {
+        Some(allowed) if !allowed.is_empty() => allowed.iter().any(|t| t == tool_name),
+        _ => true,
+    }
+}
+
+/// Type of the configured tool called `tool_name`. Models sometimes give the wrong
+/// `tool_type` (e.g. "builtin" for an HTTP tool), so theirs is only used for unknown tools.
+fn resolve_tool_type(executor: &ToolExecutor, tool_name: &str, llm_type: &str) -> String {
+    match executor.get_tool_descriptions().into_iter().find(|t| t.name == tool_name) {
+        Some(tool_desc) => {
+            if tool_desc.tool_type != llm_type {
+                debug!("[Tool] Corrected type for '{}': {} -> {}", tool_name, llm_type, tool_desc.tool_type);
+            }
+            tool_desc.tool_type
+        }
+        None => {
+            debug!("[Tool] Warning: Tool '{}' not found in descriptions, using LLM-provided type '{}'", tool_name, llm_type);
+            llm_type.to_string()
+        }
+    }
+}
+
+/// Tool result returned in place of a call [tool_permitted] refused, so the model can
+/// pick a permitted tool or carry on without one
+fn blocked_tool_message(tool_name: &str, allowed: Option<&[String]>, denied: &[String]) -> String {
+    let permitted = match allowed {
+        Some(allowed) if !allowed.is_empty() => {
+            let usable: Vec<&str> = allowed.iter()
+                .filter(|t| !denied.contains(t))
+                .map(|t| t.as_str())
+                .collect();
+            format!("You may only use: {}.", usable.join(", "))
         }
+        _ => format!("Every tool except {} is available.", denied.join(", ")),
     };
+    format!("Tool '{}' is not permitted for this agent and was not run. {} Please continue without it.",
+        tool_name, permitted)
 }
 
-macro_rules! debug_eprintln {
-    ($($arg:tt)*) => {
-        if std::env::var("BOBBAR_DEBUG").is_ok() {
-            eprintln!($($arg)*);
+/// Identical calls in one iteration loop before the model is told to stop repeating
+/// itself and answer
+const REPEATED_TOOL_CALL_LIMIT: usize = 3;
+
+/// `tool_name` plus its parameters, identifying identical tool calls whatever order the
+/// parameters were given in
+fn tool_call_signature(tool_call: &Value) -> String {
+    let name = tool_call.get("tool_name").and_then(|v| v.as_str()).unwrap_or_default();
+    let params = match tool_call.get("parameters") {
+        Some(Value::Object(obj)) => {
+            let sorted: std::collections::BTreeMap<&String, &Value> = obj.iter().collect();
+            serde_json::to_string(&sorted).unwrap_or_default()
         }
+        Some(other) => other.to_string(),
+        None => String::new(),
     };
+    format!("{}:{}", name, params)
+}
+
+/// Tool calls that succeeded during one query, so repeats are answered with the earlier
+/// result instead of being executed again
+#[derive(Default)]
+struct ToolCallHistory {
+    calls: std::collections::HashMap<String, (usize, String)>,  // Signature -> (times requested, first result)
+}
+
+impl ToolCallHistory {
+    /// For a call that was already made: how often it has now been requested, and its result
+    fn repeat(&mut self, signature: &str) -> Option<(usize, String)> {
+        let (count, result) = self.calls.get_mut(signature)?;
+        *count += 1;
+        Some((*count, result.clone()))
+    }
+
+    fn record(&mut self, signature: String, result: String) {
+        self.calls.insert(signature, (1, result));
+    }
+}
+
+/// Tool result given in place of re-running a call made `count` times so far
+fn repeated_tool_call_message(tool_name: &str, count: usize, prior_result: &str) -> String {
+    format!(
+        "You already called '{}' with these exact parameters ({} times now), so it was not run again. \
+         The earlier result was:\n{}\n\nUse this result, or call a different tool or different parameters.",
+        tool_name, count, prior_result
+    )
 }
 
 // Helper function to extract JSON object from text
@@ -35,7 +137,7 @@ fn extract_json_object(text: &str) -> Option<String> {
         let mut in_string = false;
         let mut escape = false;
 
-        for (i, ch) in text[start..].chars().enumerate() {
+        for (i, ch) in text[start..].char_indices() {
             if escape {
                 escape = false;
                 continue;
@@ -67,6 +169,276 @@ fn extract_json_object(text: &str) -> Option<String> {
     None
 }
 
+/// Sampling/runtime parameters sent as Ollama's `options` object.
+/// Unset fields are omitted so the model's own defaults apply.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ModelOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+impl ModelOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == ModelOptions::default()
+    }
+
+    /// Fields set in `overrides` replace the corresponding fields in `self`
+    pub fn merged_with(&self, overrides: &ModelOptions) -> ModelOptions {
+        ModelOptions {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            top_k: overrides.top_k.or(self.top_k),
+            num_ctx: overrides.num_ctx.or(self.num_ctx),
+            seed: overrides.seed.or(self.seed),
+        }
+    }
+}
+
+/// Delay between retries of a failed Ollama request
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackoffStrategy {
+    /// Growing ladder: 2s, 5s, 10s, then +5s per attempt up to 40s
+    #[default]
+    Progressive,
+    /// Same delay (the base delay) before every retry
+    Fixed,
+    /// Base delay doubled on every attempt, capped at 60s
+    Exponential,
+    /// Retry immediately
+    None,
+}
+
+impl BackoffStrategy {
+    /// Delay before retrying after the given (1-based) failed attempt
+    pub fn delay(&self, attempt: usize, base_secs: u64) -> std::time::Duration {
+        let secs = match self {
+            BackoffStrategy::Progressive => match attempt {
+                1 => 2,
+                2 => 5,
+                3 => 10,
+                n => (5 * (n as u64 - 1)).min(40),
+            },
+            BackoffStrategy::Fixed => base_secs,
+            BackoffStrategy::Exponential => {
+                let exp = attempt.saturating_sub(1).min(16) as u32;
+                base_secs.saturating_mul(2u64.pow(exp)).min(60)
+            }
+            BackoffStrategy::None => 0,
+        };
+        std::time::Duration::from_secs(secs)
+    }
+}
+
+/// Token accounting reported by the server for one or more chat calls
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Time spent generating completions, in nanoseconds
+    pub eval_duration_ns: u64,
+    pub calls: u32,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.eval_duration_ns += other.eval_duration_ns;
+        self.calls += other.calls;
+    }
+
+    /// Completion tokens generated per second, if any generation time was recorded
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        (self.eval_duration_ns > 0 && self.completion_tokens > 0)
+            .then(|| self.completion_tokens as f64 / (self.eval_duration_ns as f64 / 1e9))
+    }
+}
+
+/// Usage summed over every chat call since the last reset, across all clients. Research
+/// runs create a client per agent, so this is what gives a session total.
+static SESSION_USAGE: Lazy<std::sync::Mutex<TokenUsage>> = Lazy::new(|| std::sync::Mutex::new(TokenUsage::default()));
+
+fn record_usage(usage: &TokenUsage) {
+    if let Ok(mut total) = SESSION_USAGE.lock() {
+        total.add(usage);
+    }
+}
+
+pub fn reset_session_usage() {
+    if let Ok(mut total) = SESSION_USAGE.lock() {
+        *total = TokenUsage::default();
+    }
+}
+
+pub fn session_usage() -> TokenUsage {
+    SESSION_USAGE.lock().map(|total| *total).unwrap_or_default()
+}
+
+/// Resolves once `cancel` fires; never without a token
+async fn cancelled(cancel: Option<&CancellationToken>) {
+    match cancel {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn cancelled_error() -> anyhow::Error {
+    anyhow::anyhow!("Query cancelled by user")
+}
+
+/// Chat API spoken by the server at `host`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiFlavor {
+    /// Ollama's native API: `/api/chat`, newline-delimited JSON when streaming
+    #[default]
+    Ollama,
+    /// OpenAI-compatible gateways (vLLM, LiteLLM, ...): `/v1/chat/completions` with
+    /// bearer-token auth, server-sent events when streaming
+    OpenAI,
+}
+
+/// Client errors that will fail identically on every attempt (bad request, unknown model, auth).
+/// Timeouts and rate limiting are still worth retrying.
+fn is_permanent_status(status: reqwest::StatusCode) -> bool {
+    status.is_client_error()
+        && status != reqwest::StatusCode::REQUEST_TIMEOUT
+        && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Rough allowance for the fixed instruction text wrapped around tool prompts
+const PROMPT_TEMPLATE_TOKENS: usize = 800;
+
+/// Rough token count: ~4 characters per token for English text and JSON
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// One earlier message of a conversation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatTurn {
+    pub role: String,  // "user" or "assistant"
+    pub content: String,
+}
+
+impl ChatTurn {
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatTurn { role: "user".to_string(), content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        ChatTurn { role: "assistant".to_string(), content: content.into() }
+    }
+}
+
+fn conversation_tokens(turns: &[ChatTurn]) -> usize {
+    turns.iter().map(|turn| estimate_tokens(&turn.content)).sum()
+}
+
+/// Tokens a conversation's history may take: a quarter of the context window
+pub fn conversation_budget(context_tokens: usize) -> usize {
+    context_tokens / 4
+}
+
+/// Drop the oldest turns until the rest fit in `budget_tokens`, so the conversation
+/// starts with a user message again
+pub fn trim_conversation(turns: &mut Vec<ChatTurn>, budget_tokens: usize) {
+    let mut start = 0;
+    while start < turns.len() && (conversation_tokens(&turns[start..]) > budget_tokens || turns[start].role != "user") {
+        start += 1;
+    }
+    turns.drain(..start);
+}
+
+/// Tokens kept free for the model's reply: a quarter of the window, at most 4096
+fn response_reserve_tokens(context_tokens: usize) -> usize {
+    (context_tokens / 4).min(4096)
+}
+
+/// Join tool result segments, keeping the most recent ones that fit in `budget` tokens.
+/// Older segments are dropped first; if even the newest doesn't fit, it's truncated.
+fn trim_to_token_budget(segments: &[String], budget: usize) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut used = 0;
+    for segment in segments.iter().rev() {
+        let tokens = estimate_tokens(segment) + 1;
+        if used + tokens > budget {
+            break;
+        }
+        used += tokens;
+        kept.push(segment);
+    }
+
+    let omitted = segments.len() - kept.len();
+    if omitted == 0 {
+        return segments.join("\n\n");
+    }
+
+    let truncated_newest;
+    if kept.is_empty() {
+        if let Some(newest) = segments.last() {
+            let max_chars = budget.saturating_mul(4);
+            truncated_newest = format!("{}\n[... truncated to fit context window]", newest.chars().take(max_chars).collect::<String>());
+            kept.push(&truncated_newest);
+        }
+    }
+
+    kept.reverse();
+    let dropped = segments.len() - kept.len();
+    let mut out = String::new();
+    if dropped > 0 {
+        out.push_str(&format!("[{} earlier tool result(s) omitted to fit context window]\n\n", dropped));
+    }
+    out.push_str(&kept.join("\n\n"));
+    out
+}
+
+/// Split a response into (answer, reasoning), pulling out `<think>...</think>` blocks that
+/// reasoning models emit inline. An unclosed `<think>` means the rest is still reasoning;
+/// a lone `</think>` means everything before it was reasoning.
+fn split_reasoning(text: &str) -> (String, String) {
+    const OPEN: &str = "<think>";
+    const CLOSE: &str = "</think>";
+
+    let mut answer = String::new();
+    let mut reasoning = String::new();
+    let mut rest = text;
+
+    // Some models omit the opening tag and start straight into their reasoning
+    if let Some(close) = rest.find(CLOSE) {
+        if !rest[..close].contains(OPEN) {
+            reasoning.push_str(rest[..close].trim());
+            rest = &rest[close + CLOSE.len()..];
+        }
+    }
+
+    while let Some(open) = rest.find(OPEN) {
+        answer.push_str(&rest[..open]);
+        let after_open = &rest[open + OPEN.len()..];
+        let (inner, remainder) = match after_open.find(CLOSE) {
+            Some(close) => (&after_open[..close], &after_open[close + CLOSE.len()..]),
+            None => (after_open, ""),
+        };
+        if !reasoning.is_empty() && !inner.trim().is_empty() {
+            reasoning.push_str("\n\n");
+        }
+        reasoning.push_str(inner.trim());
+        rest = remainder;
+    }
+    answer.push_str(rest);
+
+    (answer.trim_start().to_string(), reasoning)
+}
+
 #[derive(Debug, Serialize)]
 struct OllamaChatRequest {
     model: String,
@@ -74,8 +446,163 @@ struct OllamaChatRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<ModelOptions>,
+}
+
+/// Body for `/v1/chat/completions`. Images become `image_url` content parts and the
+/// sampling options that have an equivalent are lifted to the top level.
+#[derive(Debug, Serialize)]
+struct OpenAIChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<Value>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    /// Asks for a final chunk with token usage when streaming
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+}
+
+impl<'a> From<&'a OllamaChatRequest> for OpenAIChatRequest<'a> {
+    fn from(request: &'a OllamaChatRequest) -> Self {
+        let messages = request.messages.iter().map(|m| match &m.images {
+            Some(images) if !images.is_empty() => {
+                let mut parts = vec![serde_json::json!({ "type": "text", "text": m.content })];
+                parts.extend(images.iter().map(|image| serde_json::json!({
+                    "type": "image_url",
+                    "image_url": { "url": format!("data:image/png;base64,{}", image) },
+                })));
+                serde_json::json!({ "role": m.role, "content": parts })
+            }
+            _ => serde_json::json!({ "role": m.role, "content": m.content }),
+        }).collect();
+        // num_ctx is a server-side setting with no chat-completions equivalent
+        let options = request.options.clone().unwrap_or_default();
+        OpenAIChatRequest {
+            model: &request.model,
+            messages,
+            stream: request.stream,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            top_k: options.top_k,
+            seed: options.seed,
+            stream_options: request.stream.then(|| serde_json::json!({ "include_usage": true })),
+        }
+    }
+}
+
+/// Chat completion, or one streamed chunk of it (which carries `delta` instead of `message`)
+#[derive(Debug, Deserialize)]
+struct OpenAIChatResponse {
+    #[serde(default)]
+    choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OpenAIUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+impl OpenAIChatResponse {
+    /// Chat completions report no timing; the caller measures it
+    fn token_usage(&self) -> Option<TokenUsage> {
+        self.usage.as_ref().map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            eval_duration_ns: 0,
+            calls: 1,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIChoice {
+    #[serde(default)]
+    message: Option<OpenAIMessage>,
+    #[serde(default)]
+    delta: Option<OpenAIMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIModelsResponse {
+    data: Vec<OpenAIModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIModel {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingsResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbedding {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTagsResponse {
+    models: Vec<OllamaModelTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModelTag {
+    name: String,
+}
+
+/// Models in `required` that are not among the `available` (pulled) ones. A name
+/// without a tag refers to `:latest`, as it does for `ollama run`.
+pub fn missing_models(required: &[String], available: &[String]) -> Vec<String> {
+    let with_tag = |name: &str| if name.contains(':') { name.to_string() } else { format!("{}:latest", name) };
+    let available: std::collections::HashSet<String> = available.iter().map(|m| with_tag(m)).collect();
+    let mut missing: Vec<String> = Vec::new();
+    for model in required {
+        if !available.contains(&with_tag(model)) && !missing.contains(model) {
+            missing.push(model.clone());
+        }
+    }
+    missing
+}
+
+/// Maximum number of texts sent in a single `/api/embed` request
+const EMBED_BATCH_SIZE: usize = 32;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Message {
     role: String,
@@ -104,8 +631,46 @@ struct OllamaChatResponse {
     #[allow(dead_code)]
     created_at: String,
     message: ResponseMessage,
-    #[allow(dead_code)]
     done: bool,
+    // Token counts and timing (nanoseconds), sent with the final `done` chunk
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+    #[serde(default)]
+    eval_duration: Option<u64>,
+}
+
+impl OllamaChatResponse {
+    fn token_usage(&self) -> Option<TokenUsage> {
+        if !self.done || (self.prompt_eval_count.is_none() && self.eval_count.is_none()) {
+            return None;
+        }
+        Some(TokenUsage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or(0),
+            completion_tokens: self.eval_count.unwrap_or(0),
+            eval_duration_ns: self.eval_duration.unwrap_or(0),
+            calls: 1,
+        })
+    }
+}
+
+/// Text and accounting carried by a chat response, or by one streamed chunk of it
+#[derive(Debug, Default, PartialEq)]
+struct ChatReply {
+    content: String,
+    thinking: String,
+    usage: Option<TokenUsage>,
+}
+
+impl From<OllamaChatResponse> for ChatReply {
+    fn from(response: OllamaChatResponse) -> Self {
+        ChatReply {
+            usage: response.token_usage(),
+            content: response.message.content,
+            thinking: response.message.thinking.unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -116,7 +681,6 @@ struct ResponseMessage {
     #[allow(dead_code)]
     #[serde(default)]
     tool_calls: Vec<ToolCall>,
-    #[allow(dead_code)]
     #[serde(default)]
     thinking: Option<String>,
 }
@@ -127,10 +691,22 @@ pub struct OllamaClient {
     client: reqwest::Client,
     tool_executor: Option<Arc<Mutex<ToolExecutor>>>,
     available_tools: Option<Vec<String>>, // Filter to only these tools if specified
+    denied_tools: Vec<String>,  // Never offered or run, even if `available_tools` allows them
     max_tool_turns: usize,
     summarization_model: Option<String>,
     summarization_threshold: usize,
     is_research_mode: bool,  // Whether this client is used for research (higher thresholds)
+    options: ModelOptions,
+    max_attempts: usize,
+    backoff: BackoffStrategy,
+    retry_base_delay_secs: u64,
+    last_reasoning: String,  // Reasoning ("thinking") from the most recent query
+    conversation: Vec<ChatTurn>,  // Earlier turns sent ahead of each query (conversation mode)
+    context_window: usize,  // Model context size in tokens, used when num_ctx isn't set
+    api_flavor: ApiFlavor,
+    api_key: Option<String>,  // Sent as a bearer token when set
+    system_prompt: Option<String>,  // Persona/focus put before every query (`ollama.system_prompt`)
+    instructions: Option<String>,  // Replaces the default formatting instructions (`ollama.chat_instructions`)
 }
 
 impl OllamaClient {
@@ -149,10 +725,22 @@ impl OllamaClient {
             client,
             tool_executor: None,
             available_tools: None,
+            denied_tools: Vec::new(),
             max_tool_turns: 5,
             summarization_model: None,
             summarization_threshold: 5000,
             is_research_mode: false,
+            options: ModelOptions::default(),
+            max_attempts: 10,
+            backoff: BackoffStrategy::Progressive,
+            retry_base_delay_secs: 2,
+            last_reasoning: String::new(),
+            conversation: Vec::new(),
+            context_window: 128000,
+            api_flavor: ApiFlavor::Ollama,
+            api_key: None,
+            system_prompt: None,
+            instructions: None,
         }
     }
 
@@ -167,10 +755,22 @@ impl OllamaClient {
             client,
             tool_executor: None,
             available_tools: None,
+            denied_tools: Vec::new(),
             max_tool_turns: 5,
             summarization_model: None,
             summarization_threshold: 5000,
             is_research_mode: false,
+            options: ModelOptions::default(),
+            max_attempts: 10,
+            backoff: BackoffStrategy::Progressive,
+            retry_base_delay_secs: 2,
+            last_reasoning: String::new(),
+            conversation: Vec::new(),
+            context_window: 128000,
+            api_flavor: ApiFlavor::Ollama,
+            api_key: None,
+            system_prompt: None,
+            instructions: None,
         }
     }
 
@@ -186,62 +786,328 @@ impl OllamaClient {
         self.available_tools = Some(tools);
     }
 
+    pub fn set_denied_tools(&mut self, tools: Vec<String>) {
+        self.denied_tools = tools;
+    }
+
     pub fn set_summarization_config(&mut self, model: Option<String>, threshold: usize, is_research: bool) {
         self.summarization_model = model;
         self.summarization_threshold = threshold;
         self.is_research_mode = is_research;
     }
 
+    pub fn set_options(&mut self, options: ModelOptions) {
+        self.options = options;
+    }
+
+    /// Configure how many attempts a chat request gets and how long to wait between them
+    pub fn set_retry_policy(&mut self, max_attempts: usize, backoff: BackoffStrategy, base_delay_secs: u64) {
+        self.max_attempts = max_attempts.max(1);
+        self.backoff = backoff;
+        self.retry_base_delay_secs = base_delay_secs;
+    }
+
+    /// Persona or domain focus put before every query, and a replacement for the default
+    /// formatting instructions. Blank values are ignored.
+    pub fn set_chat_prompts(&mut self, system_prompt: Option<String>, instructions: Option<String>) {
+        self.system_prompt = system_prompt.filter(|p| !p.trim().is_empty());
+        self.instructions = instructions.filter(|p| !p.trim().is_empty());
+    }
+
+    /// Formatting instructions for a query; the tool-calling prompt has its own rules for
+    /// markdown, so it only gets the structured-data part of the default
+    fn chat_instructions(&self, with_tools: bool) -> String {
+        match &self.instructions {
+            Some(instructions) => instructions.clone(),
+            None if with_tools => STRUCTURED_DATA_INSTRUCTIONS.to_string(),
+            None => format!("{}\n\n{}", STRUCTURED_DATA_INSTRUCTIONS, MARKDOWN_INSTRUCTIONS),
+        }
+    }
+
+    /// Select the chat API the server speaks and the key to authenticate with
+    pub fn set_api(&mut self, flavor: ApiFlavor, api_key: Option<String>) {
+        self.api_flavor = flavor;
+        self.api_key = api_key.filter(|key| !key.trim().is_empty());
+    }
+
+    /// Endpoint URL for the configured flavor. OpenAI paths are relative to `/v1`,
+    /// which may already be part of the configured host.
+    fn api_url(&self, ollama_path: &str, openai_path: &str) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        match self.api_flavor {
+            ApiFlavor::Ollama => format!("{}{}", base, ollama_path),
+            ApiFlavor::OpenAI => format!("{}/v1{}", base.strip_suffix("/v1").unwrap_or(base), openai_path),
+        }
+    }
+
+    fn authorized(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    fn chat_request(&self, request: &OllamaChatRequest) -> reqwest::RequestBuilder {
+        let builder = self.client.post(self.api_url("/api/chat", "/chat/completions"));
+        let builder = match self.api_flavor {
+            ApiFlavor::Ollama => builder.json(request),
+            ApiFlavor::OpenAI => builder.json(&OpenAIChatRequest::from(request)),
+        };
+        self.authorized(builder)
+    }
+
+    /// A complete, non-streamed chat response body
+    fn parse_chat_reply(&self, body: &str) -> Result<ChatReply> {
+        match self.api_flavor {
+            ApiFlavor::Ollama => Ok(serde_json::from_str::<OllamaChatResponse>(body)?.into()),
+            ApiFlavor::OpenAI => {
+                let response: OpenAIChatResponse = serde_json::from_str(body)?;
+                let usage = response.token_usage();
+                let message = response.choices.into_iter().next().and_then(|c| c.message)
+                    .ok_or_else(|| anyhow::anyhow!("Chat completion response has no message"))?;
+                Ok(ChatReply {
+                    content: message.content.unwrap_or_default(),
+                    thinking: message.reasoning_content.unwrap_or_default(),
+                    usage,
+                })
+            }
+        }
+    }
+
+    /// What one line of a streamed response carries. Blank lines, SSE comments and
+    /// keep-alives and the final `data: [DONE]` carry nothing.
+    fn stream_delta(&self, line: &str) -> Option<ChatReply> {
+        let line = line.trim();
+        match self.api_flavor {
+            ApiFlavor::Ollama => Some(serde_json::from_str::<OllamaChatResponse>(line).ok()?.into()),
+            ApiFlavor::OpenAI => {
+                let data = line.strip_prefix("data:")?.trim();
+                if data == "[DONE]" {
+                    return None;
+                }
+                let response: OpenAIChatResponse = serde_json::from_str(data).ok()?;
+                let usage = response.token_usage();
+                // The usage chunk has no choices
+                let delta = response.choices.into_iter().next().and_then(|c| c.delta);
+                if delta.is_none() && usage.is_none() {
+                    return None;
+                }
+                let delta = delta.unwrap_or(OpenAIMessage { content: None, reasoning_content: None });
+                Some(ChatReply {
+                    content: delta.content.unwrap_or_default(),
+                    thinking: delta.reasoning_content.unwrap_or_default(),
+                    usage,
+                })
+            }
+        }
+    }
+
+    /// Add a call's usage to the session total, filling in measured time where the
+    /// server reported none
+    fn record_reply_usage(usage: Option<TokenUsage>, started: std::time::Instant) {
+        if let Some(mut usage) = usage {
+            if usage.eval_duration_ns == 0 {
+                usage.eval_duration_ns = started.elapsed().as_nanos() as u64;
+            }
+            record_usage(&usage);
+        }
+    }
+
+    /// POST a chat request, retrying transient failures according to the retry policy.
+    /// Permanent errors (e.g. 404 model not found, 400 bad request) fail immediately.
+    async fn send_chat_with_retry(&self, request: &OllamaChatRequest) -> Result<reqwest::Response> {
+        let max_attempts = self.max_attempts;
+        let mut last_error = None;
+
+        for attempt in 1..=max_attempts {
+            let req_response = self.chat_request(request).send().await;
+
+            match req_response {
+                Ok(resp) => {
+                    let status = resp.status();
+                    if status.is_success() {
+                        return Ok(resp);
+                    }
+
+                    let error_body = resp.text().await.unwrap_or_else(|_| "Could not read response body".to_string());
+                    if is_permanent_status(status) {
+                        let error_msg = format!("Ollama API error: {} (not retrying):\n{}", status, error_body);
+                        warn!("{}", error_msg);
+                        return Err(anyhow::anyhow!(error_msg));
+                    }
+
+                    let error_msg = format!("Ollama API error: {} (attempt {}/{}):\n{}", status, attempt, max_attempts, error_body);
+                    warn!("{}", error_msg);
+                    last_error = Some(error_msg);
+                },
+                Err(e) => {
+                    let error_msg = format!("Ollama request failed: {} (attempt {}/{})", e, attempt, max_attempts);
+                    warn!("{}", error_msg);
+                    last_error = Some(error_msg);
+                }
+            }
+
+            if attempt < max_attempts {
+                tokio::time::sleep(self.backoff.delay(attempt, self.retry_base_delay_secs)).await;
+            }
+        }
+
+        Err(anyhow::anyhow!("Ollama API failed after {} attempts. Last error: {}",
+            max_attempts,
+            last_error.unwrap_or_else(|| "Unknown error".to_string())))
+    }
+
+    /// Names of the models pulled on the Ollama server (`/api/tags`), or served by an
+    /// OpenAI-compatible one (`/v1/models`)
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self.authorized(self.client.get(self.api_url("/api/tags", "/models")))
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Ollama API error: {}", response.status()));
+        }
+        Ok(match self.api_flavor {
+            ApiFlavor::Ollama => response.json::<OllamaTagsResponse>().await?.models.into_iter().map(|m| m.name).collect(),
+            ApiFlavor::OpenAI => response.json::<OpenAIModelsResponse>().await?.data.into_iter().map(|m| m.id).collect(),
+        })
+    }
+
+    /// Embed `texts` with this client's model via `/api/embed` (`/v1/embeddings` for
+    /// OpenAI-compatible servers), sending up to `EMBED_BATCH_SIZE` texts per request.
+    /// Build the client with the embedding model.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(EMBED_BATCH_SIZE) {
+            // Both APIs accept the same `{model, input}` body
+            let response = self.authorized(self.client.post(self.api_url("/api/embed", "/embeddings")))
+                .json(&OllamaEmbedRequest { model: &self.model, input: batch })
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow::anyhow!("Embedding API error ({}): {}", status, body));
+            }
+
+            let parsed = match self.api_flavor {
+                ApiFlavor::Ollama => response.json::<OllamaEmbedResponse>().await?,
+                ApiFlavor::OpenAI => {
+                    let mut data = response.json::<OpenAIEmbeddingsResponse>().await?.data;
+                    data.sort_by_key(|e| e.index);
+                    OllamaEmbedResponse { embeddings: data.into_iter().map(|e| e.embedding).collect() }
+                }
+            };
+            if parsed.embeddings.len() != batch.len() {
+                return Err(anyhow::anyhow!(
+                    "Embedding API returned {} embeddings for {} inputs",
+                    parsed.embeddings.len(), batch.len()
+                ));
+            }
+            debug!("[Embed] {} text(s) embedded with {}", batch.len(), self.model);
+            embeddings.extend(parsed.embeddings);
+        }
+
+        Ok(embeddings)
+    }
+
+    /// Options for the request body; `None` when nothing is configured
+    fn request_options(&self) -> Option<ModelOptions> {
+        if self.options.is_empty() {
+            None
+        } else {
+            Some(self.options.clone())
+        }
+    }
+
+    pub fn set_context_window(&mut self, tokens: usize) {
+        self.context_window = tokens;
+    }
+
+    /// Token limit the assembled prompt must fit in: `num_ctx` if configured, else the context window
+    fn context_token_limit(&self) -> usize {
+        self.options.num_ctx.unwrap_or(self.context_window)
+    }
+
+    /// Earlier turns to send before the next queries, oldest first; empty for a standalone query
+    pub fn set_conversation(&mut self, turns: Vec<ChatTurn>) {
+        self.conversation = turns;
+    }
+
+    /// Reasoning emitted by a thinking model during the most recent query (empty if none)
+    pub fn last_reasoning(&self) -> &str {
+        &self.last_reasoning
+    }
+
     pub fn get_model(&self) -> &str {
         &self.model
     }
 
     #[allow(dead_code)]
     pub async fn query(&mut self, prompt: &str) -> Result<String> {
-        self.query_internal::<fn(String)>(prompt, true, None, None).await
+        self.query_internal::<fn(String)>(prompt, true, None, None, None).await
     }
 
     pub async fn query_streaming<F>(&mut self, prompt: &str, mut callback: F) -> Result<String>
     where
         F: FnMut(String) + Send,
     {
-        self.query_internal(prompt, true, None, Some(&mut callback)).await
+        self.query_internal(prompt, true, None, Some(&mut callback), None).await
+    }
+
+    /// Like `query_streaming`, but stops as soon as `cancel` fires: the response stream
+    /// is dropped (closing the connection) and a "cancelled" error is returned
+    pub async fn query_streaming_cancellable<F>(&mut self, prompt: &str, cancel: &CancellationToken, mut callback: F) -> Result<String>
+    where
+        F: FnMut(String) + Send,
+    {
+        self.query_internal(prompt, true, None, Some(&mut callback), Some(cancel)).await
     }
 
     pub async fn query_with_image(&mut self, prompt: &str, base64_image: &str) -> Result<String> {
-        self.query_internal::<fn(String)>(prompt, false, Some(base64_image.to_string()), None).await
+        self.query_internal::<fn(String)>(prompt, false, Some(base64_image.to_string()), None, None).await
     }
 
     #[allow(dead_code)]
     fn query_without_tools<'a>(&'a mut self, prompt: &'a str) -> Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>> {
-        Box::pin(self.query_internal::<fn(String)>(prompt, false, None, None))
+        Box::pin(self.query_internal::<fn(String)>(prompt, false, None, None, None))
     }
 
-    async fn query_internal<F>(&mut self, initial_prompt: &str, allow_tools: bool, image: Option<String>, callback: Option<&mut F>) -> Result<String>
+    async fn query_internal<F>(&mut self, initial_prompt: &str, allow_tools: bool, image: Option<String>, callback: Option<&mut F>, cancel: Option<&CancellationToken>) -> Result<String>
     where
         F: FnMut(String) + Send,
     {
         let max_iterations = self.max_tool_turns;
-        self.query_internal_with_iterations(initial_prompt, allow_tools, image, callback, max_iterations).await
+        self.query_internal_with_iterations(initial_prompt, allow_tools, image, callback, cancel, max_iterations).await
     }
 
-    async fn query_internal_with_iterations<F>(&mut self, initial_prompt: &str, allow_tools: bool, image: Option<String>, mut callback: Option<&mut F>, max_iterations: usize) -> Result<String>
+    async fn query_internal_with_iterations<F>(&mut self, initial_prompt: &str, allow_tools: bool, image: Option<String>, mut callback: Option<&mut F>, cancel: Option<&CancellationToken>, max_iterations: usize) -> Result<String>
     where
         F: FnMut(String) + Send,
     {
         let original_question = initial_prompt.to_string();
         let mut prompt_for_iteration = initial_prompt.to_string();
         let mut iteration = 0;
+        self.last_reasoning.clear();
         let mut use_image = image.clone(); // Clone for first iteration
         let mut tool_results_context = String::new();
+        let mut tool_result_segments: Vec<String> = Vec::new();
+        let mut call_history = ToolCallHistory::default();
 
         loop {
             iteration += 1;
 
+            // Tool calls run between iterations, so check before starting the next request
+            if cancel.is_some_and(|token| token.is_cancelled()) {
+                return Err(cancelled_error());
+            }
+
             if iteration > max_iterations {
                 // Return accumulated context instead of error message
                 if !tool_results_context.is_empty() {
-                    debug_eprintln!("[Tool] Maximum iteration limit ({}) reached. Returning accumulated context.", max_iterations);
+                    debug!("[Tool] Maximum iteration limit ({}) reached. Returning accumulated context.", max_iterations);
                     return Ok(format!(
                         "Based on the research gathered:\n\n{}\n\nNote: Reached maximum tool iteration limit. The above represents all gathered information.",
                         tool_results_context
@@ -256,21 +1122,37 @@ impl OllamaClient {
             let executor = self.tool_executor.as_ref().unwrap().lock().await;
             let mut tools = executor.get_tool_descriptions();
 
-            // Filter tools if available_tools is specified and non-empty
+            // Filter tools if available_tools is specified and non-empty, and drop denied ones
             // Empty list means no filtering (all tools visible)
-            if let Some(ref allowed) = self.available_tools {
-                if !allowed.is_empty() {
-                    tools.retain(|tool| allowed.contains(&tool.name));
-                }
-            }
+            tools.retain(|tool| tool_permitted(self.available_tools.as_deref(), &self.denied_tools, &tool.name));
 
             if !tools.is_empty() {
                 let tools_json = serde_json::to_string_pretty(&tools)?;
 
+                // Fit accumulated tool results into whatever the context window has left
+                let fixed_tokens = estimate_tokens(&tools_json)
+                    + estimate_tokens(&original_question)
+                    + estimate_tokens(&prompt_for_iteration)
+                    + conversation_tokens(&self.conversation)
+                    + PROMPT_TEMPLATE_TOKENS;
+                let context_tokens = self.context_token_limit();
+                let budget = context_tokens
+                    .saturating_sub(fixed_tokens)
+                    .saturating_sub(response_reserve_tokens(context_tokens));
+                let trimmed_context = trim_to_token_budget(&tool_result_segments, budget);
+                if trimmed_context.len() < tool_results_context.len() {
+                    debug!(
+                        "[Context] Trimmed tool results from ~{} to ~{} tokens to fit {}-token context",
+                        estimate_tokens(&tool_results_context),
+                        estimate_tokens(&trimmed_context),
+                        context_tokens
+                    );
+                }
+
                 // Build the full context with original question and any previous tool results
-                let full_context = if !tool_results_context.is_empty() {
+                let full_context = if !trimmed_context.is_empty() {
                     format!("Original user question: {}\n\n{}\n\nCurrent task: {}",
-                        original_question, tool_results_context, prompt_for_iteration)
+                        original_question, trimmed_context, prompt_for_iteration)
                 } else {
                     format!("User question: {}", prompt_for_iteration)
                 };
@@ -295,144 +1177,82 @@ Available tools:\n{}\n\n{}\n\nRemember:
 - If tools are needed, respond with ONLY the JSON (no markdown, no formatting)
 - If no tools are needed, format your response in clean markdown (use headers, lists, code blocks, etc. as appropriate)
 
-Present structured data using:
-- Clear section headings (## or ###)
-- Bullet points with bold labels (• **Label:** value)
-- Numbered lists for sequential information
-- Simple key-value format on separate lines
-- If you use a markdown table, include clear, specific column headers for each column (no generic names)", tools_json, full_context)
+{}", tools_json, full_context, self.chat_instructions(true))
             } else {
-                format!("Present structured data using:
-- Clear section headings (## or ###)
-- Bullet points with bold labels (• **Label:** value)
-- Numbered lists for sequential information
-- Simple key-value format on separate lines
-- If you use a markdown table, include clear, specific column headers for each column (no generic names)
-
-Format your response in clean markdown (use headers, lists, code blocks, etc. as appropriate). Be concise - keep responses to 1-3 sentences unless asked for more detail.\n\nUser: {}", prompt_for_iteration)
+                format!("{}\n\nUser: {}", self.chat_instructions(false), prompt_for_iteration)
             }
         } else {
-            format!("Present structured data using:
-- Clear section headings (## or ###)
-- Bullet points with bold labels (• **Label:** value)
-- Numbered lists for sequential information
-- Simple key-value format on separate lines
-- If you use a markdown table, include clear, specific column headers for each column (no generic names)
+            format!("{}\n\nUser: {}", self.chat_instructions(false), prompt_for_iteration)
+        };
 
-Format your response in clean markdown (use headers, lists, code blocks, etc. as appropriate). Be concise - keep responses to 1-3 sentences unless asked for more detail.\n\nUser: {}", prompt_for_iteration)
+        let enhanced_prompt = match &self.system_prompt {
+            Some(system_prompt) => format!("{}\n\n{}", system_prompt, enhanced_prompt),
+            None => enhanced_prompt,
         };
 
         let use_streaming = callback.is_some();
 
         let request = OllamaChatRequest {
             model: self.model.clone(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: enhanced_prompt,
-                tool_calls: None,
-                images: use_image.take().map(|img| vec![img]), // Take image on first use only
-            }],
+            messages: self.conversation.iter()
+                .map(|turn| Message { role: turn.role.clone(), content: turn.content.clone(), tool_calls: None, images: None })
+                .chain(std::iter::once(Message {
+                    role: "user".to_string(),
+                    content: enhanced_prompt,
+                    tool_calls: None,
+                    images: use_image.take().map(|img| vec![img]), // Take image on first use only
+                }))
+                .collect(),
             stream: use_streaming,
             tools: None,
+            options: self.request_options(),
         };
 
-        // Retry logic: try up to 10 times on non-2xx status codes
-        let mut last_error = None;
-        let mut response = None;
-
-        for attempt in 1..=10 {
-            let req_response = self.client
-                .post(format!("{}/api/chat", self.base_url))
-                .json(&request)
-                .send()
-                .await;
-
-            match req_response {
-                Ok(resp) => {
-                    let status = resp.status();
-                    if status.is_success() {
-                        response = Some(resp);
-                        break;
-                    } else {
-                        // For 400 errors, print full response body for debugging
-                        if status.as_u16() == 400 {
-                            let error_body = resp.text().await.unwrap_or_else(|_| "Could not read response body".to_string());
-                            let error_msg = format!("Ollama API 400 Bad Request (attempt {}/10):\n{}", attempt, error_body);
-                            eprintln!("{}", error_msg);
-                            last_error = Some(error_msg);
-                        } else {
-                            let error_msg = format!("Ollama API error: {} (attempt {}/10)", status, attempt);
-                            eprintln!("{}", error_msg);
-                            last_error = Some(error_msg);
-                        }
-
-                        // Wait before retrying (progressive backoff: 2s, 5s, 10s, 15s, 20s, 25s, 30s, 35s, 40s)
-                        if attempt < 10 {
-                            let delay_secs = match attempt {
-                                1 => 2,
-                                2 => 5,
-                                3 => 10,
-                                4 => 15,
-                                5 => 20,
-                                6 => 25,
-                                7 => 30,
-                                8 => 35,
-                                9 => 40,
-                                _ => 40,
-                            };
-                            tokio::time::sleep(tokio::time::Duration::from_secs(delay_secs)).await;
-                        }
-                    }
-                },
-                Err(e) => {
-                    let error_msg = format!("Ollama request failed: {} (attempt {}/10)", e, attempt);
-                    eprintln!("{}", error_msg);
-                    last_error = Some(error_msg);
-
-                    // Wait before retrying (progressive backoff: 2s, 5s, 10s, 15s, 20s, 25s, 30s, 35s, 40s)
-                    if attempt < 10 {
-                        let delay_secs = match attempt {
-                            1 => 2,
-                            2 => 5,
-                            3 => 10,
-                            4 => 15,
-                            5 => 20,
-                            6 => 25,
-                            7 => 30,
-                            8 => 35,
-                            9 => 40,
-                            _ => 40,
-                        };
-                        tokio::time::sleep(tokio::time::Duration::from_secs(delay_secs)).await;
-                    }
-                }
-            }
-        }
-
-        let response = response.ok_or_else(|| {
-            anyhow::anyhow!("Ollama API failed after 10 attempts. Last error: {}",
-                last_error.unwrap_or_else(|| "Unknown error".to_string()))
-        })?;
+        let started = std::time::Instant::now();
+        let response = tokio::select! {
+            response = self.send_chat_with_retry(&request) => response?,
+            _ = cancelled(cancel) => return Err(cancelled_error()),
+        };
 
         let mut response_text = String::new();
+        let mut thinking_text = String::new();
+        let mut usage = None;
 
         if use_streaming {
             let mut stream = response.bytes_stream();
-
-            while let Some(item) = stream.next().await {
-                let chunk = item?;
-                let chunk_str = String::from_utf8_lossy(&chunk);
-
-                for line in chunk_str.lines() {
-                    if line.trim().is_empty() {
-                        continue;
+            // Network chunks don't respect line (or UTF-8) boundaries, so only complete
+            // lines are parsed; the end of the stream terminates the last one
+            let mut pending: Vec<u8> = Vec::new();
+            let mut finished = false;
+
+            while !finished {
+                let next = tokio::select! {
+                    next = stream.next() => next,
+                    _ = cancelled(cancel) => {
+                        debug!("[Query] Cancelled mid-stream after {} chars", response_text.len());
+                        return Err(cancelled_error());
+                    }
+                };
+                match next {
+                    Some(item) => pending.extend_from_slice(&item?),
+                    None => {
+                        pending.push(b'\n');
+                        finished = true;
                     }
+                }
 
-                    if let Ok(chunk_response) = serde_json::from_str::<OllamaChatResponse>(line) {
-                        response_text.push_str(&chunk_response.message.content);
+                while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=newline).collect();
+                    if let Some(reply) = self.stream_delta(&String::from_utf8_lossy(&line)) {
+                        response_text.push_str(&reply.content);
+                        thinking_text.push_str(&reply.thinking);
+                        if reply.usage.is_some() {
+                            usage = reply.usage;
+                        }
 
                         if let Some(ref mut cb) = callback {
-                            cb(response_text.clone());
+                            // Only the visible answer is streamed; inline <think> blocks are held back
+                            cb(split_reasoning(&response_text).0);
                         }
                     }
                 }
@@ -441,8 +1261,23 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                 tokio::task::yield_now().await;
             }
         } else {
-            let ollama_response: OllamaChatResponse = response.json().await?;
-            response_text = ollama_response.message.content;
+            let reply = self.parse_chat_reply(&response.text().await?)?;
+            response_text = reply.content;
+            thinking_text = reply.thinking;
+            usage = reply.usage;
+        }
+        Self::record_reply_usage(usage, started);
+
+        // Keep reasoning out of the answer so tool-call detection only sees the answer
+        let (answer, inline_thinking) = split_reasoning(&response_text);
+        response_text = answer;
+        for reasoning in [thinking_text.trim(), inline_thinking.trim()] {
+            if !reasoning.is_empty() {
+                if !self.last_reasoning.is_empty() {
+                    self.last_reasoning.push_str("\n\n");
+                }
+                self.last_reasoning.push_str(reasoning);
+            }
         }
 
             // Check if response contains tool call(s) (only if tools are allowed)
@@ -498,32 +1333,25 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
 
                     let mut tools_executed = false;
                     let mut tool_results = Vec::new();
+                    let mut most_repeats = 0;  // Highest repeat count among this iteration's calls (0 = all new)
 
                     for attempt in &parse_attempts {
-                        // Try parsing as array of tool calls
-                        if let Ok(tool_calls_array) = serde_json::from_str::<Vec<Value>>(attempt) {
-                            for tool_call in tool_calls_array {
-                                if tool_call.get("tool_type").is_some() && tool_call.get("tool_name").is_some() {
-                                    let result = self.execute_tool_call_get_result(tool_call, executor.clone()).await?;
-                                    tool_results.push(result);
-                                    tools_executed = true;
-                                }
-                            }
-                            if tools_executed {
-                                break;
-                            }
-                        }
-
-                        // Try parsing as single tool call
-                        if !tools_executed {
-                            if let Ok(tool_call) = serde_json::from_str::<Value>(attempt) {
-                                if tool_call.get("tool_type").is_some() && tool_call.get("tool_name").is_some() {
-                                    let result = self.execute_tool_call_get_result(tool_call, executor.clone()).await?;
-                                    tool_results.push(result);
-                                    tools_executed = true;
-                                    break;
-                                }
+                        // Try parsing as array of tool calls, then as a single tool call
+                        let tool_calls: Vec<Value> = match serde_json::from_str::<Vec<Value>>(attempt) {
+                            Ok(array) => array,
+                            Err(_) => serde_json::from_str::<Value>(attempt).map(|call| vec![call]).unwrap_or_default(),
+                        };
+                        let tool_calls: Vec<Value> = tool_calls.into_iter()
+                            .filter(|call| call.get("tool_type").is_some() && call.get("tool_name").is_some())
+                            .collect();
+
+                        if !tool_calls.is_empty() {
+                            for (result, repeats) in self.run_tool_calls(tool_calls, executor.clone(), &mut call_history).await {
+                                tool_results.push(result);
+                                most_repeats = most_repeats.max(repeats);
                             }
+                            tools_executed = true;
+                            break;
                         }
                     }
 
@@ -532,25 +1360,28 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                         let combined_results = tool_results.join("\n\n---\n\n");
 
                         // Update context with these tool results
-                        if tool_results_context.is_empty() {
-                            tool_results_context = format!("Tool results from iteration {}:\n{}", iteration, combined_results);
-                        } else {
-                            tool_results_context.push_str(&format!("\n\nTool results from iteration {}:\n{}", iteration, combined_results));
-                        }
+                        tool_result_segments.push(format!("Tool results from iteration {}:\n{}", iteration, combined_results));
+                        tool_results_context = tool_result_segments.join("\n\n");
 
                         // Set next iteration prompt
-                        prompt_for_iteration = format!(
-                            "Based on the tool results above, either:\n1. Call more tools if additional information is needed to answer the original question\n2. Provide the final answer to the user in clean markdown format"
-                        );
+                        prompt_for_iteration = if most_repeats >= REPEATED_TOOL_CALL_LIMIT {
+                            warn!("[Tool] Model repeated an identical tool call {} times, asking for a final answer", most_repeats);
+                            "You keep repeating the same tool call and its result will not change. Do not call it again. \
+                             Provide the final answer to the user in clean markdown format using the tool results above".to_string()
+                        } else {
+                            format!(
+                                "Based on the tool results above, either:\n1. Call more tools if additional information is needed to answer the original question\n2. Provide the final answer to the user in clean markdown format"
+                            )
+                        };
 
                         // Print the full context that will be sent to the next iteration
-                        debug_eprintln!("\n=== Iteration {} Complete - Next Prompt ===", iteration);
-                        debug_eprintln!("Original question: {}", original_question);
+                        debug!("\n=== Iteration {} Complete - Next Prompt ===", iteration);
+                        debug!("Original question: {}", original_question);
                         if !tool_results_context.is_empty() {
-                            debug_eprintln!("\n{}", tool_results_context);
+                            debug!("\n{}", tool_results_context);
                         }
-                        debug_eprintln!("\nNext task: {}", prompt_for_iteration);
-                        debug_eprintln!("=========================================\n");
+                        debug!("\nNext task: {}", prompt_for_iteration);
+                        debug!("=========================================\n");
 
                         // Continue loop to reprocess with tool results
                         continue;
@@ -664,11 +1495,11 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
             return Ok(result.to_string());
         }
 
-        debug_eprintln!("[Tool] Result from '{}' is {} chars, summarizing (threshold: {})...", tool_name, result.len(), max_length);
+        debug!("[Tool] Result from '{}' is {} chars, summarizing (threshold: {})...", tool_name, result.len(), max_length);
 
         // Try structural summarization first for JSON
         if let Ok(json_value) = serde_json::from_str::<Value>(result) {
-            debug_eprintln!("[Tool] Attempting structural summarization for JSON...");
+            debug!("[Tool] Attempting structural summarization for JSON...");
 
             // Extract critical fields first
             let critical_fields = Self::extract_critical_fields(&json_value);
@@ -679,7 +1510,7 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                     .unwrap_or_else(|_| result.to_string());
 
                 if summarized_str.len() <= max_length * 2 {
-                    debug_eprintln!("[Tool] Structural summarization successful: {} -> {} chars", result.len(), summarized_str.len());
+                    debug!("[Tool] Structural summarization successful: {} -> {} chars", result.len(), summarized_str.len());
 
                     // Append critical fields as a note if any were extracted
                     if !critical_fields.is_empty() && summarized_str.len() < max_length {
@@ -700,7 +1531,7 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
         }
 
         // Fall back to LLM summarization
-        debug_eprintln!("[Tool] Using LLM summarization...");
+        debug!("[Tool] Using LLM summarization...");
 
         let prompt = format!(
             "Condense this tool result while keeping all important information:\n\n\
@@ -727,39 +1558,123 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
             }],
             stream: false,
             tools: None,
+            options: self.request_options(),
         };
 
-        let response = self.client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&request)
-            .send()
-            .await;
+        let started = std::time::Instant::now();
+        let response = self.chat_request(&request).send().await;
 
         match response {
             Ok(resp) if resp.status().is_success() => {
-                match resp.json::<OllamaChatResponse>().await {
-                    Ok(ollama_response) => {
-                        let summary = ollama_response.message.content;
-                        debug_eprintln!("[Tool] LLM summarized '{}' using {} from {} to {} chars",
+                let reply = match resp.text().await {
+                    Ok(body) => self.parse_chat_reply(&body),
+                    Err(e) => Err(e.into()),
+                };
+                match reply {
+                    Ok(ChatReply { content: summary, usage, .. }) => {
+                        Self::record_reply_usage(usage, started);
+                        debug!("[Tool] LLM summarized '{}' using {} from {} to {} chars",
                             tool_name, model_to_use, result.len(), summary.len());
                         Ok(summary)
                     },
                     Err(e) => {
-                        debug_eprintln!("[Tool] Failed to parse summarization response: {}", e);
-                        Ok(format!("{}...\n\n[Note: Content truncated due to length]", &result[..max_length]))
+                        debug!("[Tool] Failed to parse summarization response: {}", e);
+                        Ok(format!("{}...\n\n[Note: Content truncated due to length]", truncate_at_char_boundary(result, max_length)))
                     }
                 }
             },
             _ => {
-                debug_eprintln!("[Tool] Summarization request failed, using truncated version");
-                Ok(format!("{}...\n\n[Note: Content truncated due to length]", &result[..max_length]))
+                debug!("[Tool] Summarization request failed, using truncated version");
+                Ok(format!("{}...\n\n[Note: Content truncated due to length]", truncate_at_char_boundary(result, max_length)))
             }
         }
     }
 
-    async fn execute_tool_call_get_result(&mut self, tool_call: Value, executor: Arc<Mutex<crate::tools::ToolExecutor>>)
+    /// Run the tool calls the model asked for in one turn and return their results in the
+    /// same order, each with how many times it has now been requested when it repeated an
+    /// earlier call (0 when it ran). Built-in and HTTP calls run concurrently; MCP calls share
+    /// one connection per server, so they run one after another alongside them. Repeats of a
+    /// call that already succeeded this query are answered from `history` instead of running
+    /// again, and a failing call yields an error message in its slot rather than failing the
+    /// batch.
+    async fn run_tool_calls(&self, tool_calls: Vec<Value>, executor: Arc<Mutex<crate::tools::ToolExecutor>>, history: &mut ToolCallHistory)
+        -> Vec<(String, usize)> {
+        let signatures: Vec<String> = tool_calls.iter().map(tool_call_signature).collect();
+        let tool_name = |call: &Value| call.get("tool_name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+        // Answer repeats from history; run each distinct new call once
+        let mut results: Vec<Option<(String, usize)>> = vec![None; tool_calls.len()];
+        let mut to_run: Vec<usize> = Vec::new();
+        for (i, signature) in signatures.iter().enumerate() {
+            if signatures[..i].contains(signature) {
+                continue;  // Filled in from history once the first copy has run
+            }
+            if let Some((count, prior_result)) = history.repeat(signature) {
+                debug!("[Tool] Skipping repeated call to '{}' (requested {} times)", tool_name(&tool_calls[i]), count);
+                results[i] = Some((repeated_tool_call_message(&tool_name(&tool_calls[i]), count, &prior_result), count));
+            } else {
+                to_run.push(i);
+            }
+        }
+
+        if !to_run.is_empty() {
+            let executor = executor.lock().await;
+            let (sequential, concurrent): (Vec<usize>, Vec<usize>) = to_run.into_iter().partition(|&i| {
+                let call = &tool_calls[i];
+                let llm_type = call.get("tool_type").and_then(|v| v.as_str()).unwrap_or_default();
+                resolve_tool_type(&executor, &tool_name(call), llm_type) == "mcp"
+            });
+            if concurrent.len() > 1 {
+                debug!("[Tool] Running {} tool calls concurrently", concurrent.len());
+            }
+
+            let run_concurrent = futures_util::future::join_all(
+                concurrent.iter().map(|&i| self.execute_tool_call_with(&tool_calls[i], &executor))
+            );
+            let run_sequential = async {
+                let mut outcomes = Vec::with_capacity(sequential.len());
+                for &i in &sequential {
+                    outcomes.push(self.execute_tool_call_with(&tool_calls[i], &executor).await);
+                }
+                outcomes
+            };
+            let (concurrent_outcomes, sequential_outcomes) = futures_util::future::join(run_concurrent, run_sequential).await;
+            drop(executor);
+
+            let ran = concurrent.into_iter().zip(concurrent_outcomes)
+                .chain(sequential.into_iter().zip(sequential_outcomes));
+            for (i, outcome) in ran {
+                // Only successes are remembered, so a failed call may be retried later
+                let result = match outcome {
+                    Ok(result) => {
+                        history.record(signatures[i].clone(), result.clone());
+                        result
+                    }
+                    Err(e) => {
+                        warn!("[Tool] Call to '{}' failed: {}", tool_name(&tool_calls[i]), e);
+                        format!("Tool '{}' failed with error: {}", tool_name(&tool_calls[i]), e)
+                    }
+                };
+                results[i] = Some((result, 0));
+            }
+        }
+
+        // Copies of a call made earlier in this batch; a failed one just repeats its error
+        for i in 0..results.len() {
+            if results[i].is_none() {
+                results[i] = match history.repeat(&signatures[i]) {
+                    Some((count, prior_result)) => Some((repeated_tool_call_message(&tool_name(&tool_calls[i]), count, &prior_result), count)),
+                    None => signatures.iter().position(|s| *s == signatures[i]).and_then(|first| results[first].clone()),
+                };
+            }
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
+    /// Execute one tool call against an already locked executor and return a formatted result string
+    async fn execute_tool_call_with(&self, tool_call: &Value, executor: &crate::tools::ToolExecutor)
         -> Result<String> {
-        // Execute the tool and return a formatted result string
 
         let tool_type = tool_call.get("tool_type")
             .and_then(|v| v.as_str())
@@ -772,22 +1687,13 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
         let parameters = tool_call.get("parameters")
             .ok_or_else(|| anyhow::anyhow!("Missing parameters"))?;
 
-        // Always look up the tool by name to verify/correct the type
-        // LLM sometimes returns wrong tool_type (e.g., "builtin" for HTTP tools)
-        let actual_tool_type: String = {
-            let exec = executor.lock().await;
-            let tool_descriptions = exec.get_tool_descriptions();
+        // The model only sees permitted tools, but may still name others
+        if !tool_permitted(self.available_tools.as_deref(), &self.denied_tools, tool_name) {
+            warn!("[Tool] Blocked call to '{}': not in this agent's allowed tools", tool_name);
+            return Ok(blocked_tool_message(tool_name, self.available_tools.as_deref(), &self.denied_tools));
+        }
 
-            if let Some(tool_desc) = tool_descriptions.iter().find(|t| t.name == tool_name) {
-                if tool_desc.tool_type != tool_type {
-                    debug_eprintln!("[Tool] Corrected type for '{}': {} -> {}", tool_name, tool_type, tool_desc.tool_type);
-                }
-                tool_desc.tool_type.clone()
-            } else {
-                debug_eprintln!("[Tool] Warning: Tool '{}' not found in descriptions, using LLM-provided type '{}'", tool_name, tool_type);
-                tool_type.to_string()
-            }
-        };
+        let actual_tool_type = resolve_tool_type(executor, tool_name, tool_type);
 
         let raw_result = match actual_tool_type.as_str() {
             "builtin" => {
@@ -808,8 +1714,6 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                     std::collections::HashMap::new()
                 };
 
-                let executor = executor.lock().await;
-
                 // Format parameters for display
                 let params_summary: Vec<String> = params.iter()
                     .map(|(k, v)| format!("- **{}**: {}", k, v))
@@ -832,9 +1736,7 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                             tool_name, params_str, summarized_result
                         )
                     },
-                    Err(e) => {
-                        format!("Built-in tool '{}' failed with error: {}", tool_name, e)
-                    }
+                    Err(e) => return Err(e),
                 }
             },
             "http" => {
@@ -855,8 +1757,6 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                     std::collections::HashMap::new()
                 };
 
-                let executor = executor.lock().await;
-
                 // Format parameters for display
                 let params_summary: Vec<String> = params.iter()
                     .map(|(k, v)| format!("- **{}**: {}", k, v))
@@ -879,9 +1779,7 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                             tool_name, params_str, summarized_result
                         )
                     },
-                    Err(e) => {
-                        format!("Tool '{}' failed with error: {}", tool_name, e)
-                    }
+                    Err(e) => return Err(e),
                 }
             },
             "mcp" => {
@@ -891,19 +1789,15 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                     (parts[0].to_string(), parts[1])
                 } else {
                     // Fallback to first MCP server if not specified
-                    let first_server = {
-                        let exec = executor.lock().await;
-                        exec.config.tools.mcp.first()
-                            .map(|s| s.name.clone())
-                            .unwrap_or_else(|| "mcp".to_string())
-                    };
+                    let first_server = executor.config.tools.mcp.first()
+                        .map(|s| s.name.clone())
+                        .unwrap_or_else(|| "mcp".to_string());
                     (first_server, tool_name)
                 };
 
                 // Format parameters for display
                 let params_str = serde_json::to_string_pretty(&parameters)?;
 
-                let executor = executor.lock().await;
                 match executor.execute_mcp_tool(&server_name, actual_tool_name, parameters.clone()).await {
                     Ok(result) => {
                         let result_str = serde_json::to_string_pretty(&result)?;
@@ -916,9 +1810,7 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
                             tool_name, params_str, summarized_result
                         )
                     },
-                    Err(e) => {
-                        format!("MCP tool '{}' failed with error: {}", tool_name, e)
-                    }
+                    Err(e) => return Err(e),
                 }
             },
             _ => {
@@ -1076,3 +1968,479 @@ Format your response in clean markdown (use headers, lists, code blocks, etc. as
         self.base_url = url;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{spawn_test_http_server, TestResponse};
+
+    fn calculator_executor() -> Arc<Mutex<ToolExecutor>> {
+        let config = crate::tools::ToolsConfig {
+            tools: crate::tools::Tools {
+                builtin: vec!["calculate".to_string()],
+                max_result_bytes: 0,
+                ..Default::default()
+            },
+        };
+        Arc::new(Mutex::new(ToolExecutor::new(config, std::collections::HashMap::new())))
+    }
+
+    async fn run_one(client: &OllamaClient, call: Value, executor: Arc<Mutex<ToolExecutor>>, history: &mut ToolCallHistory) -> (String, usize) {
+        client.run_tool_calls(vec![call], executor, history).await.remove(0)
+    }
+
+    fn calculate_call() -> Value {
+        serde_json::json!({"tool_type": "builtin", "tool_name": "calculate", "parameters": {"expression": "6*7"}})
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_tool_call_is_not_executed() {
+        let mut client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        client.set_available_tools(vec!["web_search".to_string(), "memory_store".to_string()]);
+
+        let (result, _) = run_one(&client, calculate_call(), calculator_executor(), &mut ToolCallHistory::default()).await;
+        assert!(result.contains("not permitted"), "{}", result);
+        assert!(result.contains("web_search, memory_store"));
+        assert!(!result.contains("42"));
+    }
+
+    #[tokio::test]
+    async fn test_denied_tool_call_is_not_executed() {
+        let mut client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        client.set_denied_tools(vec!["calculate".to_string()]);
+
+        let (result, _) = run_one(&client, calculate_call(), calculator_executor(), &mut ToolCallHistory::default()).await;
+        assert!(result.contains("not permitted") && !result.contains("42"), "{}", result);
+    }
+
+    #[tokio::test]
+    async fn test_allowed_tool_call_runs() {
+        let mut client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        client.set_available_tools(vec!["calculate".to_string()]);
+
+        let (result, _) = run_one(&client, calculate_call(), calculator_executor(), &mut ToolCallHistory::default()).await;
+        assert!(result.contains("42"), "{}", result);
+    }
+
+    #[test]
+    fn test_tool_call_signature_ignores_param_order() {
+        let a: Value = serde_json::from_str(r#"{"tool_type": "builtin", "tool_name": "web_search", "parameters": {"query": "rust", "limit": 5}}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"tool_name": "web_search", "tool_type": "http", "parameters": {"limit": 5, "query": "rust"}}"#).unwrap();
+        let c: Value = serde_json::from_str(r#"{"tool_name": "web_search", "parameters": {"query": "go", "limit": 5}}"#).unwrap();
+        assert_eq!(tool_call_signature(&a), tool_call_signature(&b));
+        assert_ne!(tool_call_signature(&a), tool_call_signature(&c));
+    }
+
+    #[test]
+    fn test_tool_call_history_counts_repeats() {
+        let mut history = ToolCallHistory::default();
+        assert!(history.repeat("calc:1").is_none());
+        history.record("calc:1".to_string(), "42".to_string());
+        assert_eq!(history.repeat("calc:1"), Some((2, "42".to_string())));
+        assert_eq!(history.repeat("calc:1"), Some((3, "42".to_string())));
+        assert!(history.repeat("calc:2").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_tool_call_is_not_executed_again() {
+        let client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        let executor = calculator_executor();
+        let mut history = ToolCallHistory::default();
+
+        let (first, repeats) = run_one(&client, calculate_call(), executor.clone(), &mut history).await;
+        assert_eq!(repeats, 0);
+        assert!(first.contains("42"));
+
+        let (second, repeats) = run_one(&client, calculate_call(), executor, &mut history).await;
+        assert_eq!(repeats, 2);
+        assert!(second.contains("already called 'calculate'") && second.contains(&first), "{}", second);
+    }
+
+    #[tokio::test]
+    async fn test_failed_tool_call_is_retried() {
+        let client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        let executor = calculator_executor();
+        let mut history = ToolCallHistory::default();
+        let failing = serde_json::json!({"tool_type": "builtin", "tool_name": "calculate", "parameters": {"expression": "2*"}});
+
+        let (first, repeats) = run_one(&client, failing.clone(), executor.clone(), &mut history).await;
+        assert_eq!(repeats, 0);
+        assert!(first.contains("failed with error"), "{}", first);
+
+        let (second, repeats) = run_one(&client, failing, executor, &mut history).await;
+        assert_eq!(repeats, 0);
+        assert!(second.contains("failed with error") && !second.contains("already called"), "{}", second);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_batch_keeps_order_and_survives_failures() {
+        let client = OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string());
+        let call = |expression: &str| serde_json::json!({"tool_type": "builtin", "tool_name": "calculate", "parameters": {"expression": expression}});
+        let calls = vec![call("1+1"), call("2*"), call("3*3"), call("1+1")];
+
+        let results = client.run_tool_calls(calls, calculator_executor(), &mut ToolCallHistory::default()).await;
+        assert_eq!(results.len(), 4);
+        assert!(results[0].0.contains("returned") && results[0].0.contains('2'), "{}", results[0].0);
+        assert!(results[1].0.contains("failed with error"), "{}", results[1].0);
+        assert!(results[2].0.contains('9'), "{}", results[2].0);
+        // The same call twice in one batch runs once
+        assert_eq!(results[3].1, 2);
+        assert!(results[3].0.contains("already called"));
+    }
+
+    #[test]
+    fn test_tool_permitted() {
+        let allowed = vec!["web_search".to_string()];
+        assert!(tool_permitted(Some(&allowed), &[], "web_search"));
+        assert!(!tool_permitted(Some(&allowed), &[], "shell"));
+        assert!(tool_permitted(Some(&[]), &[], "shell"));
+        assert!(tool_permitted(None, &[], "shell"));
+        assert!(!tool_permitted(None, &["shell".to_string()], "shell"));
+        assert!(!tool_permitted(Some(&allowed), &["web_search".to_string()], "web_search"));
+    }
+
+    fn request_with(options: Option<ModelOptions>) -> Value {
+        let request = OllamaChatRequest {
+            model: "m".to_string(),
+            messages: Vec::new(),
+            stream: false,
+            tools: None,
+            options,
+        };
+        serde_json::to_value(&request).unwrap()
+    }
+
+    #[test]
+    fn test_trim_conversation_drops_oldest_exchanges() {
+        let mut turns = vec![
+            ChatTurn::user("a".repeat(40)),       // 10 tokens
+            ChatTurn::assistant("b".repeat(80)),  // 20 tokens
+            ChatTurn::user("c".repeat(40)),
+            ChatTurn::assistant("d".repeat(40)),
+        ];
+        trim_conversation(&mut turns, 100);
+        assert_eq!(turns.len(), 4);
+
+        // Over budget: the first exchange goes, and the rest still opens with the user
+        trim_conversation(&mut turns, 25);
+        assert_eq!(turns, vec![ChatTurn::user("c".repeat(40)), ChatTurn::assistant("d".repeat(40))]);
+
+        trim_conversation(&mut turns, 5);
+        assert!(turns.is_empty());
+    }
+
+    #[test]
+    fn test_chat_instructions_default_and_override() {
+        let mut client = OllamaClient::with_config("http://localhost:11434".to_string(), "m".to_string());
+        assert_eq!(client.chat_instructions(true), STRUCTURED_DATA_INSTRUCTIONS);
+        assert!(client.chat_instructions(false).ends_with(MARKDOWN_INSTRUCTIONS));
+
+        client.set_chat_prompts(Some("You are a pirate.".to_string()), Some("  ".to_string()));
+        assert_eq!(client.system_prompt.as_deref(), Some("You are a pirate."));
+        assert_eq!(client.chat_instructions(false), format!("{}\n\n{}", STRUCTURED_DATA_INSTRUCTIONS, MARKDOWN_INSTRUCTIONS));
+
+        client.set_chat_prompts(None, Some("Answer in German.".to_string()));
+        assert!(client.system_prompt.is_none());
+        assert_eq!(client.chat_instructions(true), "Answer in German.");
+        assert_eq!(client.chat_instructions(false), "Answer in German.");
+    }
+
+    #[test]
+    fn test_truncate_at_char_boundary() {
+        assert_eq!(truncate_at_char_boundary("hello", 10), "hello");
+        assert_eq!(truncate_at_char_boundary("hello", 3), "hel");
+        // "é" is two bytes; a cut through it backs off to the previous character
+        assert_eq!(truncate_at_char_boundary("caféine", 4), "caf");
+        assert_eq!(truncate_at_char_boundary("caféine", 5), "café");
+        assert_eq!(truncate_at_char_boundary("日本語", 1), "");
+    }
+
+    #[test]
+    fn test_extract_json_object_after_multibyte_text() {
+        let text = "Voilà la réponse : {\"tool\": \"météo\", \"args\": {\"ville\": \"Zürich\"}} fin";
+        assert_eq!(
+            extract_json_object(text).as_deref(),
+            Some("{\"tool\": \"météo\", \"args\": {\"ville\": \"Zürich\"}}")
+        );
+    }
+
+    #[test]
+    fn test_options_omitted_when_unset() {
+        let mut client = OllamaClient::with_config("http://localhost:11434".to_string(), "m".to_string());
+        assert!(client.request_options().is_none());
+        assert!(request_with(client.request_options()).get("options").is_none());
+
+        client.set_options(ModelOptions { temperature: Some(0.2), num_ctx: Some(4096), ..Default::default() });
+        let body = request_with(client.request_options());
+        assert_eq!(body["options"], serde_json::json!({ "temperature": 0.2f32, "num_ctx": 4096 }));
+    }
+
+    #[test]
+    fn test_progressive_backoff_matches_ladder() {
+        let ladder: Vec<u64> = (1..=10)
+            .map(|attempt| BackoffStrategy::Progressive.delay(attempt, 2).as_secs())
+            .collect();
+        assert_eq!(ladder, vec![2, 5, 10, 15, 20, 25, 30, 35, 40, 40]);
+    }
+
+    #[test]
+    fn test_fixed_exponential_and_no_backoff() {
+        assert_eq!(BackoffStrategy::Fixed.delay(7, 3).as_secs(), 3);
+        assert_eq!(BackoffStrategy::Exponential.delay(1, 1).as_secs(), 1);
+        assert_eq!(BackoffStrategy::Exponential.delay(4, 1).as_secs(), 8);
+        assert_eq!(BackoffStrategy::Exponential.delay(30, 1).as_secs(), 60);
+        assert_eq!(BackoffStrategy::None.delay(5, 2).as_secs(), 0);
+    }
+
+    #[test]
+    fn test_permanent_status_classification() {
+        use reqwest::StatusCode;
+        assert!(is_permanent_status(StatusCode::NOT_FOUND));
+        assert!(is_permanent_status(StatusCode::BAD_REQUEST));
+        assert!(!is_permanent_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_permanent_status(StatusCode::REQUEST_TIMEOUT));
+        assert!(!is_permanent_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_permanent_status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn test_split_reasoning_inline_think_block() {
+        let (answer, reasoning) = split_reasoning("<think>\nweigh options\n</think>\n\nThe answer is 4.");
+        assert_eq!(answer, "The answer is 4.");
+        assert_eq!(reasoning, "weigh options");
+    }
+
+    #[test]
+    fn test_split_reasoning_unclosed_and_missing_open() {
+        let (answer, reasoning) = split_reasoning("<think>still going");
+        assert_eq!(answer, "");
+        assert_eq!(reasoning, "still going");
+
+        let (answer, reasoning) = split_reasoning("quietly thinking</think>{\"tool_type\": \"http\"}");
+        assert_eq!(answer, "{\"tool_type\": \"http\"}");
+        assert_eq!(reasoning, "quietly thinking");
+    }
+
+    #[test]
+    fn test_split_reasoning_plain_answer() {
+        let (answer, reasoning) = split_reasoning("Just an answer");
+        assert_eq!(answer, "Just an answer");
+        assert!(reasoning.is_empty());
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_trim_keeps_everything_within_budget() {
+        let segments = vec!["a".repeat(40), "b".repeat(40)];
+        assert_eq!(trim_to_token_budget(&segments, 100), segments.join("\n\n"));
+    }
+
+    #[test]
+    fn test_trim_drops_oldest_segments_first() {
+        let segments = vec!["old".repeat(100), "mid".repeat(10), "new".repeat(10)];
+        let trimmed = trim_to_token_budget(&segments, 20);
+        assert!(trimmed.starts_with("[1 earlier tool result(s) omitted"));
+        assert!(trimmed.contains(&"mid".repeat(10)));
+        assert!(trimmed.ends_with(&"new".repeat(10)));
+        assert!(!trimmed.contains("oldold"));
+    }
+
+    #[test]
+    fn test_trim_truncates_oversized_newest_segment() {
+        let segments = vec!["x".repeat(10), "y".repeat(1000)];
+        let trimmed = trim_to_token_budget(&segments, 10);
+        assert!(trimmed.contains("[1 earlier tool result(s) omitted"));
+        assert!(trimmed.contains(&"y".repeat(40)));
+        assert!(!trimmed.contains(&"y".repeat(41)));
+        assert!(trimmed.ends_with("[... truncated to fit context window]"));
+    }
+
+    #[test]
+    fn test_context_limit_prefers_num_ctx() {
+        let mut client = OllamaClient::with_config("http://localhost:11434".to_string(), "m".to_string());
+        client.set_context_window(32000);
+        assert_eq!(client.context_token_limit(), 32000);
+        client.set_options(ModelOptions { num_ctx: Some(8192), ..Default::default() });
+        assert_eq!(client.context_token_limit(), 8192);
+    }
+
+    #[test]
+    fn test_missing_models_treats_untagged_as_latest() {
+        let available = vec!["llama3:latest".to_string(), "nomic-embed-text:v1.5".to_string()];
+        let required = vec![
+            "llama3".to_string(),
+            "llama3:latest".to_string(),
+            "nomic-embed-text".to_string(),
+            "llava".to_string(),
+            "llava".to_string(),
+        ];
+        assert_eq!(missing_models(&required, &available), vec!["nomic-embed-text".to_string(), "llava".to_string()]);
+        assert!(missing_models(&["nomic-embed-text:v1.5".to_string()], &available).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_embed_batches_inputs() {
+        let reply = |count: usize| TestResponse::json(&serde_json::json!({ "embeddings": vec![vec![0.5f32, 1.0]; count] }).to_string());
+        let server = spawn_test_http_server(vec![reply(EMBED_BATCH_SIZE), reply(3)]).await;
+
+        let client = OllamaClient::with_config(server.url(), "embedder".to_string());
+        let texts: Vec<String> = (0..EMBED_BATCH_SIZE + 3).map(|i| format!("text {}", i)).collect();
+        let embeddings = client.embed(&texts).await.unwrap();
+        assert_eq!(embeddings.len(), texts.len());
+        assert_eq!(embeddings[0], vec![0.5, 1.0]);
+        let batch_sizes: Vec<usize> = server.requests.lock().unwrap().iter()
+            .map(|r| serde_json::from_slice::<Value>(&r.body).unwrap()["input"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batch_sizes, vec![EMBED_BATCH_SIZE, 3]);
+    }
+
+    #[test]
+    fn test_openai_request_body() {
+        let request = OllamaChatRequest {
+            model: "gpt".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "what is this?".to_string(),
+                tool_calls: None,
+                images: Some(vec!["aGk=".to_string()]),
+            }],
+            stream: true,
+            tools: None,
+            options: Some(ModelOptions { temperature: Some(0.5), num_ctx: Some(4096), ..Default::default() }),
+        };
+        let body = serde_json::to_value(OpenAIChatRequest::from(&request)).unwrap();
+        assert_eq!(body, serde_json::json!({
+            "model": "gpt",
+            "stream": true,
+            "stream_options": { "include_usage": true },
+            "temperature": 0.5,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "what is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,aGk=" } },
+                ],
+            }],
+        }));
+    }
+
+    #[test]
+    fn test_api_urls_per_flavor() {
+        let mut client = OllamaClient::with_config("http://gateway:4000/".to_string(), "m".to_string());
+        assert_eq!(client.api_url("/api/chat", "/chat/completions"), "http://gateway:4000/api/chat");
+        client.set_api(ApiFlavor::OpenAI, Some("  ".to_string()));
+        assert_eq!(client.api_url("/api/chat", "/chat/completions"), "http://gateway:4000/v1/chat/completions");
+        assert!(client.api_key.is_none());
+        client.set_base_url("http://gateway:4000/v1".to_string());
+        assert_eq!(client.api_url("/api/tags", "/models"), "http://gateway:4000/v1/models");
+    }
+
+    #[test]
+    fn test_stream_delta_parses_sse_chunks() {
+        let mut client = OllamaClient::with_config("http://localhost".to_string(), "m".to_string());
+        client.set_api(ApiFlavor::OpenAI, None);
+        assert_eq!(
+            client.stream_delta(r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#),
+            Some(ChatReply { content: "Hel".to_string(), ..Default::default() })
+        );
+        assert_eq!(
+            client.stream_delta(r#"data:{"choices":[{"delta":{"reasoning_content":"hmm"}}]}"#),
+            Some(ChatReply { thinking: "hmm".to_string(), ..Default::default() })
+        );
+        assert_eq!(
+            client.stream_delta(r#"data: {"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3}}"#).unwrap().usage,
+            Some(TokenUsage { prompt_tokens: 12, completion_tokens: 3, eval_duration_ns: 0, calls: 1 })
+        );
+        assert_eq!(client.stream_delta(r#"data: {"choices":[]}"#), None);
+        assert_eq!(client.stream_delta("data: [DONE]"), None);
+        assert_eq!(client.stream_delta(": keep-alive"), None);
+        assert_eq!(client.stream_delta(""), None);
+        assert_eq!(
+            client.parse_chat_reply(r#"{"choices":[{"message":{"role":"assistant","content":"Hi"}}]}"#).unwrap(),
+            ChatReply { content: "Hi".to_string(), ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn test_ollama_final_chunk_carries_usage() {
+        let client = OllamaClient::with_config("http://localhost".to_string(), "m".to_string());
+        let partial = r#"{"model":"m","created_at":"t","message":{"role":"assistant","content":"Hi"},"done":false}"#;
+        assert_eq!(client.stream_delta(partial).unwrap().usage, None);
+
+        let last = r#"{"model":"m","created_at":"t","message":{"role":"assistant","content":""},"done":true,
+            "prompt_eval_count":20,"eval_count":50,"eval_duration":2000000000}"#.replace('\n', "");
+        let usage = client.stream_delta(&last).unwrap().usage.unwrap();
+        assert_eq!(usage, TokenUsage { prompt_tokens: 20, completion_tokens: 50, eval_duration_ns: 2_000_000_000, calls: 1 });
+        assert_eq!(usage.tokens_per_second(), Some(25.0));
+
+        let mut total = TokenUsage::default();
+        total.add(&usage);
+        total.add(&usage);
+        assert_eq!((total.prompt_tokens, total.calls), (40, 2));
+        assert_eq!(total.tokens_per_second(), Some(25.0));
+        assert_eq!(TokenUsage::default().tokens_per_second(), None);
+    }
+
+    #[tokio::test]
+    async fn test_openai_streaming_query() {
+        // The second event is split across writes to exercise line buffering
+        let server = spawn_test_http_server(vec![TestResponse::Parts(vec![
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n".to_string(),
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Hello\"}}]}\n\ndata: {\"choi".to_string(),
+            "ces\":[{\"delta\":{\"content\":\" world\"}}]}\n\n: ping\n\ndata: [DONE]\n\n".to_string(),
+        ])]).await;
+
+        let mut client = OllamaClient::with_config(server.url(), "gpt".to_string());
+        client.set_api(ApiFlavor::OpenAI, Some("secret".to_string()));
+        let mut streamed = Vec::new();
+        let answer = client.query_streaming("hi", |text| streamed.push(text)).await.unwrap();
+        assert_eq!(answer, "Hello world");
+        assert_eq!(streamed, vec!["Hello".to_string(), "Hello world".to_string()]);
+
+        let head = server.requests.lock().unwrap()[0].head.clone();
+        assert!(head.starts_with("POST /v1/chat/completions "));
+        assert!(head.to_ascii_lowercase().contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_query_stops_when_cancelled() {
+        // Keeps streaming until the client hangs up
+        let mut server = spawn_test_http_server(vec![TestResponse::Repeat {
+            head: "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n".to_string(),
+            chunk: format!("{}\n", r#"{"model":"m","created_at":"t","message":{"role":"assistant","content":"tick "},"done":false}"#),
+        }]).await;
+
+        let mut client = OllamaClient::with_config(server.url(), "m".to_string());
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        let mut chunks = 0;
+        let result = client.query_streaming_cancellable("hi", &cancel, |_| {
+            chunks += 1;
+            if chunks == 3 {
+                trigger.cancel();
+            }
+        }).await;
+
+        assert_eq!(result.unwrap_err().to_string(), "Query cancelled by user");
+        assert_eq!(chunks, 3);
+        tokio::time::timeout(std::time::Duration::from_secs(5), server.closed.recv()).await
+            .expect("connection should be closed after cancelling").unwrap();
+    }
+
+    #[test]
+    fn test_merged_options_prefer_overrides() {
+        let base = ModelOptions { temperature: Some(0.7), top_k: Some(40), ..Default::default() };
+        let overrides = ModelOptions { temperature: Some(0.1), seed: Some(7), ..Default::default() };
+        let merged = base.merged_with(&overrides);
+        assert_eq!(merged.temperature, Some(0.1));
+        assert_eq!(merged.top_k, Some(40));
+        assert_eq!(merged.seed, Some(7));
+        assert_eq!(merged.top_p, None);
+    }
+}
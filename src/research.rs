@@ -1,10 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::{Mutex, Semaphore, mpsc};
 use anyhow::Result;
 use crate::ollama::OllamaClient;
-use crate::tools::ToolExecutor;
+use crate::tools::{ToolExecutor, ToolsConfig};
 use std::collections::BTreeSet;
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone)]
 pub enum ResearchProgress {
@@ -14,11 +15,13 @@ pub enum ResearchProgress {
     PlanGenerated(usize), // number of sub-questions
     PlanCriticReviewing(usize, usize), // iteration, max
     PlanApproved,
+    PlanAwaitingApproval { plan: Vec<SubQuestion>, workers: Vec<String> }, // plan paused for the user to confirm or edit; workers are the configured names
     WorkersStarted(usize), // number of workers
     WorkerCompleted(String), // worker name
     #[allow(dead_code)]
     WorkerStarted { worker: String, question: String },
     WorkerStatus { worker: String, status: String },
+    WorkerText { worker: String, chunk: String }, // streamed piece of a worker's answer
     SupervisorAnalyzing,
     FollowUpQuestionsGenerated(usize), // number of follow-ups
     Combining,
@@ -30,6 +33,75 @@ pub enum ResearchProgress {
     DocumentReviewing,
     ExportingMemories,
     Completed,
+    Overall(f32), // estimated completion fraction of the whole run, 0.0..=1.0
+}
+
+/// Tasks spawned for the current run, aborted together when the run ends or is cancelled
+type TaskRegistry = Arc<std::sync::Mutex<Vec<tokio::task::AbortHandle>>>;
+
+fn track_task<T>(registry: &TaskRegistry, handle: &tokio::task::JoinHandle<T>) {
+    if let Ok(mut tasks) = registry.lock() {
+        tasks.push(handle.abort_handle());
+    }
+}
+
+/// Aborts every registered task on drop. Cancelling a run drops its future mid-await,
+/// and without this the detached worker tasks would keep generating in the background.
+struct AbortTasksOnDrop(TaskRegistry);
+
+impl Drop for AbortTasksOnDrop {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.0.lock() {
+            for task in tasks.drain(..) {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Rough completion estimate from phase events: planning takes the first 15%, workers
+/// up to 60%, debate/refinement up to 80% and document writing the rest
+#[derive(Debug, Default)]
+struct ProgressEstimate {
+    fraction: f32,
+    workers_total: usize,
+    workers_done: usize,
+}
+
+impl ProgressEstimate {
+    /// Fold in one event; returns the new overall fraction if it moved forward
+    fn observe(&mut self, event: &ResearchProgress) -> Option<f32> {
+        let step = |i: usize, max: usize| if max == 0 { 0.0 } else { i as f32 / max as f32 };
+        let estimate = match *event {
+            ResearchProgress::Started => {
+                *self = Self::default();
+                return Some(0.0);
+            }
+            ResearchProgress::PlanningIteration(i, max) => 0.15 * step(i.saturating_sub(1), max),
+            ResearchProgress::PlanApproved | ResearchProgress::PlanAwaitingApproval { .. } => 0.15,
+            ResearchProgress::WorkersStarted(n) => {
+                self.workers_total = n;
+                self.workers_done = 0;
+                0.15
+            }
+            ResearchProgress::WorkerCompleted(_) => {
+                self.workers_done = (self.workers_done + 1).min(self.workers_total);
+                0.15 + 0.45 * step(self.workers_done, self.workers_total)
+            }
+            ResearchProgress::Combining | ResearchProgress::Summarizing => 0.60,
+            ResearchProgress::Refining(i, max) => 0.60 + 0.20 * step(i, max),
+            ResearchProgress::WritingDocument(i, max) => 0.80 + 0.20 * step(i.saturating_sub(1), max),
+            ResearchProgress::ExportingMemories => 0.98,
+            ResearchProgress::Completed => 1.0,
+            _ => return None,
+        };
+        if estimate > self.fraction {
+            self.fraction = estimate.min(1.0);
+            Some(self.fraction)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -38,6 +110,128 @@ pub struct AgentsConfig {
     pub config: ResearchConfig,
 }
 
+impl AgentsConfig {
+    /// Cross-reference mistakes that would break a research run: workers the lead can't
+    /// tell apart or that its prompt routes to but don't exist, and missing debate roles
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let workers = &self.agents.workers;
+        if workers.is_empty() {
+            problems.push("\"workers\" is empty; research needs at least one worker".to_string());
+        }
+        for (i, worker) in workers.iter().enumerate() {
+            if worker.name.trim().is_empty() || worker.role.trim().is_empty() {
+                problems.push(format!("worker #{} needs a non-empty \"name\" and \"role\"", i + 1));
+            }
+            // The lead assigns sub-questions by role and results are matched back by name
+            if workers[..i].iter().any(|w| w.role == worker.role) {
+                problems.push(format!("more than one worker has role \"{}\"; sub-questions are assigned by role", worker.role));
+            }
+            if workers[..i].iter().any(|w| w.name == worker.name) {
+                problems.push(format!("more than one worker is named \"{}\"", worker.name));
+            }
+        }
+        for role in example_assignments(&self.agents.lead.system_prompt) {
+            if !workers.iter().any(|w| w.role == role) {
+                problems.push(format!(
+                    "the lead's system prompt assigns questions to worker \"{}\", but no worker has that role", role));
+            }
+        }
+        let debate_agents = &self.agents.debate_agents;
+        if !debate_agents.iter().any(|a| is_debate_judge(&a.role)) {
+            problems.push("\"debate_agents\" needs an agent whose role contains \"synthes\" or \"judge\" to decide the debate".to_string());
+        }
+        if !debate_agents.iter().any(|a| !is_debate_judge(&a.role)) {
+            problems.push("\"debate_agents\" needs at least one debater besides the synthesizer/judge".to_string());
+        }
+        if self.config.min_worker_count > self.config.max_worker_count {
+            problems.push(format!("min_worker_count ({}) is larger than max_worker_count ({})",
+                self.config.min_worker_count, self.config.max_worker_count));
+        }
+        problems
+    }
+
+    /// Oddities that don't stop a research run but probably aren't what was meant
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.agents.debate_agents.iter().filter(|a| is_debate_judge(&a.role)).count() > 1 {
+            warnings.push("\"debate_agents\" has more than one synthesizer/judge; only the first decides the debate".to_string());
+        }
+        warnings
+    }
+
+    /// Entries in the agents' `available_tools` that `tools` doesn't define. Those agents
+    /// still run, just without the missing tools.
+    pub fn unknown_tools(&self, tools: &ToolsConfig) -> Vec<String> {
+        let mut unknown = Vec::new();
+        for agent in self.all_agents() {
+            for tool in agent.available_tools.iter().filter(|t| !tools.provides(t)) {
+                unknown.push(format!("agent \"{}\" lists tool \"{}\", which tools.json does not define", agent.name, tool));
+            }
+        }
+        unknown
+    }
+
+    fn all_agents(&self) -> impl Iterator<Item = &AgentRole> {
+        let agents = &self.agents;
+        [&agents.lead, &agents.refiner, &agents.writer, &agents.document_critic, &agents.plan_critic]
+            .into_iter()
+            .chain(agents.summarizer.iter())
+            .chain(agents.workers.iter())
+            .chain(agents.debate_agents.iter())
+    }
+
+}
+
+/// Debate agent that hears the whole debate and gives the verdict, rather than arguing
+fn is_debate_judge(role: &str) -> bool {
+    let role = role.to_lowercase();
+    role.contains("synthes") || role.contains("judge")
+}
+
+/// The first synthesizer/judge and, in config order, every other debate agent
+fn split_debate_agents(agents: &[AgentRole]) -> Result<(&AgentRole, Vec<&AgentRole>)> {
+    let judge = agents.iter()
+        .find(|a| is_debate_judge(&a.role))
+        .ok_or_else(|| anyhow::anyhow!("No synthesizer or judge in debate_agents"))?;
+    let debaters: Vec<&AgentRole> = agents.iter().filter(|a| !is_debate_judge(&a.role)).collect();
+    if debaters.is_empty() {
+        anyhow::bail!("No debaters in debate_agents besides {}", judge.name);
+    }
+    Ok((judge, debaters))
+}
+
+/// A debater's turn: the output under review and, after the opening turn, the transcript so far
+fn debate_turn_prompt(debater: &AgentRole, output: &str, transcript: &str) -> String {
+    if transcript.trim().is_empty() {
+        format!("{}\n\nResearch Output:\n{}\n\nPresent your position:", debater.system_prompt, output)
+    } else {
+        format!(
+            "{}\n\nResearch Output:\n{}\n\nDebate History:\n{}\n\nRespond to the latest arguments:",
+            debater.system_prompt, output, transcript.trim()
+        )
+    }
+}
+
+/// Worker roles used in the example sub-question assignments (`"worker": "role"`) of a
+/// lead prompt; the lead copies these, so each must name a real worker role
+fn example_assignments(prompt: &str) -> Vec<String> {
+    let mut roles: Vec<String> = Vec::new();
+    let mut rest = prompt;
+    while let Some(pos) = rest.find("\"worker\"") {
+        rest = &rest[pos + "\"worker\"".len()..];
+        let value = rest.trim_start().strip_prefix(':').map(str::trim_start).and_then(|v| v.strip_prefix('"'));
+        if let Some(role) = value.and_then(|v| v.split_once('"')).map(|(role, _)| role) {
+            // Placeholders such as "..." or "<role>" aren't real assignments
+            let is_name = !role.is_empty() && role.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+            if is_name && !roles.iter().any(|r| r == role) {
+                roles.push(role.to_string());
+            }
+        }
+    }
+    roles
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Agents {
     pub lead: AgentRole,
@@ -47,6 +241,8 @@ pub struct Agents {
     pub writer: AgentRole,
     pub document_critic: AgentRole,
     pub plan_critic: AgentRole,
+    #[serde(default)]
+    pub summarizer: Option<AgentRole>,  // Condenses oversized worker results; built-in prompt if absent
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -56,6 +252,8 @@ pub struct AgentRole {
     pub description: String,
     pub system_prompt: String,
     pub available_tools: Vec<String>,
+    #[serde(default)]
+    pub denied_tools: Vec<String>,  // Refused at execution time even when `available_tools` is empty
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -73,23 +271,195 @@ impl Default for ResearchConfig {
     }
 }
 
+/// How thorough a research run is. Deep runs the full pipeline with the configured
+/// limits; the lighter presets cap them to trade thoroughness for speed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResearchDepth {
+    Quick,
+    Standard,
+    #[default]
+    Deep,
+}
+
+impl ResearchDepth {
+    pub fn label(self) -> &'static str {
+        match self {
+            ResearchDepth::Quick => "Quick",
+            ResearchDepth::Standard => "Standard",
+            ResearchDepth::Deep => "Deep",
+        }
+    }
+
+    /// The next preset, wrapping around, for a cycling UI toggle
+    pub fn next(self) -> Self {
+        match self {
+            ResearchDepth::Quick => ResearchDepth::Standard,
+            ResearchDepth::Standard => ResearchDepth::Deep,
+            ResearchDepth::Deep => ResearchDepth::Quick,
+        }
+    }
+
+    /// `configured` limits capped for this preset. Quick skips the debate entirely
+    /// and writes a single draft; Standard keeps one round of each.
+    fn limits(self, configured: DepthLimits) -> DepthLimits {
+        let capped = |max_workers: usize, refinement: usize, debate: usize, document: usize| {
+            let max_workers = configured.max_workers.min(max_workers);
+            DepthLimits {
+                min_workers: configured.min_workers.min(max_workers),
+                max_workers,
+                refinement_iterations: configured.refinement_iterations.min(refinement),
+                debate_rounds: configured.debate_rounds.min(debate),
+                document_iterations: configured.document_iterations.min(document),
+            }
+        };
+        match self {
+            ResearchDepth::Quick => capped(3, 0, 0, 1),
+            ResearchDepth::Standard => capped(6, 1, 1, 2),
+            ResearchDepth::Deep => configured,
+        }
+    }
+}
+
+/// The per-run limits a [ResearchDepth] adjusts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DepthLimits {
+    min_workers: usize,
+    max_workers: usize,
+    refinement_iterations: usize,  // Debate + refine cycles; 0 skips the debate
+    debate_rounds: usize,
+    document_iterations: usize,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SubQuestion {
     pub question: String,
     pub assigned_worker: String,
 }
 
-#[derive(Debug, Clone)]
+/// Counters collected during a research run, used for the "Run Details" header
+#[derive(Debug, Clone, Default)]
+pub struct RunStats {
+    pub initial_workers: usize,
+    pub follow_up_workers: usize,
+    pub gap_workers: usize,
+    pub debate_rounds: usize,
+    pub source_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkerResult {
     pub question: String,
     pub answer: String,
     pub worker_name: String,
 }
 
+/// Inputs of [ResearchOrchestrator::execute_worker], which runs as its own task
+#[derive(Clone)]
+struct WorkerContext {
+    tool_executor: Option<Arc<Mutex<ToolExecutor>>>,
+    research_model: String,
+    max_tool_turns: usize,
+    progress_tx: Option<mpsc::UnboundedSender<ResearchProgress>>,
+    shared_memory: Option<Arc<crate::shared_memory::SharedMemory>>,
+    ollama_config: crate::config::OllamaConfig,  // Also supplies the API delay and summarization threshold
+}
+
+/// Inputs of [ResearchOrchestrator::supervisor_loop], which runs as its own task
+struct SupervisorContext {
+    shared_memory: Arc<crate::shared_memory::SharedMemory>,
+    ollama_config: crate::config::OllamaConfig,
+    research_model: String,
+    max_tool_turns: usize,
+    query: String,
+    query_id: Option<String>,
+    gap_worker_tx: mpsc::Sender<Vec<SubQuestion>>,  // Requests gap-filling workers, at most once
+    max_worker_count: usize,
+    initial_worker_count: usize,
+    gap_midpoint: usize,  // Completed workers before gaps are looked for
+    workers: Vec<AgentRole>,
+}
+
+/// Artifacts of one research run, checkpointed to disk after each stage so the run can
+/// be reopened later or resumed once its workers have finished
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResearchSession {
+    pub query: String,
+    pub query_id: String,
+    pub started_at: i64,  // Unix seconds
+    pub plan: String,
+    pub sub_questions: Vec<SubQuestion>,
+    pub worker_results: Vec<WorkerResult>,
+    pub debate_transcript: Vec<String>,
+    pub refined_output: Option<String>,
+    pub final_document: Option<String>,
+}
+
+impl ResearchSession {
+    pub fn is_complete(&self) -> bool {
+        self.final_document.is_some()
+    }
+
+    /// Workers finished but the document was never written
+    pub fn can_resume(&self) -> bool {
+        !self.is_complete() && !self.worker_results.is_empty()
+    }
+
+    /// Render every saved artifact as one markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Research Session: {}\n\n", self.query);
+        if !self.plan.trim().is_empty() {
+            out.push_str(&format!("## Plan\n\n{}\n\n", self.plan.trim()));
+        }
+        if !self.sub_questions.is_empty() {
+            out.push_str("## Sub-questions\n\n");
+            for (i, q) in self.sub_questions.iter().enumerate() {
+                out.push_str(&format!("{}. [{}] {}\n", i + 1, q.assigned_worker, q.question));
+            }
+            out.push('\n');
+        }
+        if !self.worker_results.is_empty() {
+            out.push_str("## Worker Results\n\n");
+            for result in &self.worker_results {
+                out.push_str(&format!("### {}: {}\n\n{}\n\n", result.worker_name, result.question, result.answer.trim()));
+            }
+        }
+        for (i, transcript) in self.debate_transcript.iter().enumerate() {
+            out.push_str(&format!("## Debate {}\n\n{}\n\n", i + 1, transcript.trim()));
+        }
+        match &self.final_document {
+            Some(document) => out.push_str(&format!("## Final Document\n\n{}\n", document.trim())),
+            None => out.push_str("_This session did not finish; no final document was written._\n"),
+        }
+        out
+    }
+}
+
+/// Directory research sessions are checkpointed to, inside config directory `config_dir`
+pub fn sessions_dir(config_dir: &std::path::Path) -> std::path::PathBuf {
+    config_dir.join("sessions")
+}
+
+/// Saved sessions, newest first. Unreadable files are skipped.
+pub fn list_sessions(config_dir: &std::path::Path) -> Vec<(std::path::PathBuf, ResearchSession)> {
+    let Ok(entries) = std::fs::read_dir(sessions_dir(config_dir)) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| ResearchOrchestrator::load_session(&path).ok().map(|s| (path, s)))
+        .collect();
+    sessions.sort_by_key(|(_, session)| std::cmp::Reverse(session.started_at));
+    sessions
+}
+
 pub struct ResearchOrchestrator {
     config: AgentsConfig,
     ollama_config: crate::config::OllamaConfig,
-    base_client: Arc<Mutex<OllamaClient>>,
+    config_dir: std::path::PathBuf,  // Holds the memory database, model cache and sessions
+    #[allow(dead_code)]
+    base_client: Arc<Mutex<OllamaClient>>,  // Workers and the supervisor build their own clients
     tool_executor: Option<Arc<Mutex<ToolExecutor>>>,
     shared_memory: Option<Arc<crate::shared_memory::SharedMemory>>,
     progress_tx: Option<mpsc::UnboundedSender<ResearchProgress>>,
@@ -98,33 +468,49 @@ pub struct ResearchOrchestrator {
     max_tool_turns: usize,
     query_id: Option<String>,  // Current research query ID for tracking history
     export_memories: bool,  // Whether to export memory summary to output
+    keep_debate_transcript: bool,  // Whether to append the round-by-round debate to output
+    include_run_details: bool,  // Whether to prepend a run summary header to output
+    semantic_source_dedup: bool,  // Whether to merge near-duplicate references by embedding similarity
+    verify_sources: bool,  // Whether to HEAD-check cited URLs and flag dead ones
+    interactive_plan: bool,  // Whether to pause for the user to approve/edit the plan
+    plan_only: bool,  // Whether to stop after planning and return the plan as the response
+    depth: ResearchDepth,  // Preset capping worker counts and iterations for the next run
+    plan_approval_rx: Option<mpsc::UnboundedReceiver<Vec<SubQuestion>>>,
+    source_similarity_threshold: f32,  // Cosine similarity above which two references are merged
+    max_concurrent_workers: usize,  // Cap on workers generating at once (0 = unlimited)
+    worker_timeout_secs: u64,  // Give up on a single worker after this long (0 = no limit)
+    early_refinement_after: usize,  // Completed workers before follow-up questions are generated (0 = never)
+    gap_detection_at_fraction: f32,  // Share of initial workers done before the supervisor looks for gaps
+    repair_document_tables: bool,  // Whether to pad/fold table rows to the header's column count
+    run_stats: Arc<std::sync::Mutex<RunStats>>,  // Counters for the current run
+    session: std::sync::Mutex<ResearchSession>,  // Artifacts of the current run, checkpointed to disk
+    estimate: std::sync::Mutex<ProgressEstimate>,  // Completion fraction reported as `Overall`
+    worker_tasks: TaskRegistry,  // Spawned worker/supervisor tasks of the current run
 }
 
 impl ResearchOrchestrator {
-    pub fn new(config: AgentsConfig, ollama_config: crate::config::OllamaConfig, base_client: Arc<Mutex<OllamaClient>>, context_window: usize, research_model: String, max_tool_turns: usize) -> Self {
+    pub fn new(config: AgentsConfig, ollama_config: crate::config::OllamaConfig, config_dir: &std::path::Path, base_client: Arc<Mutex<OllamaClient>>, context_window: usize, research_model: String, max_tool_turns: usize) -> Self {
         // Initialize shared memory with embedding configuration
-        let shared_memory = match crate::shared_memory::SharedMemory::new(
-            ollama_config.host.clone(),
-            ollama_config.embedding_model.clone(),
-            ollama_config.embedding_dimensions,
-        ) {
+        let shared_memory = match crate::embeddings::Embedder::from_config(&ollama_config, config_dir)
+            .and_then(|embedder| crate::shared_memory::SharedMemory::new(&config_dir.join("communication.sqlite"), embedder, ollama_config.embedding_dimensions))
+        {
             Ok(mem) => {
-                eprintln!("✓ Shared memory initialized successfully");
+                info!("✓ Shared memory initialized successfully");
                 Some(Arc::new(mem))
             }
             Err(e) => {
-                eprintln!("⚠ Warning: Could not initialize shared memory: {}", e);
-                eprintln!("  Research will continue without memory features");
+                warn!("Could not initialize shared memory, research will continue without memory features: {}", e);
                 None
             }
         };
 
-        eprintln!("[Research] Initializing with summarization_threshold_research = {} chars",
-                  ollama_config.summarization_threshold_research);
+        debug!("Initializing with summarization_threshold_research = {} chars",
+               ollama_config.summarization_threshold_research);
 
         Self {
             config,
             ollama_config,
+            config_dir: config_dir.to_path_buf(),
             base_client,
             tool_executor: None,
             shared_memory,
@@ -134,13 +520,66 @@ impl ResearchOrchestrator {
             max_tool_turns,
             query_id: None,
             export_memories: false,  // Default, will be overridden by config
+            keep_debate_transcript: false,
+            include_run_details: true,
+            semantic_source_dedup: false,
+            verify_sources: false,
+            interactive_plan: false,
+            plan_only: false,
+            depth: ResearchDepth::Deep,
+            plan_approval_rx: None,
+            source_similarity_threshold: 0.92,
+            max_concurrent_workers: 4,
+            worker_timeout_secs: 600,
+            early_refinement_after: 2,
+            gap_detection_at_fraction: 0.5,
+            repair_document_tables: true,
+            run_stats: Arc::new(std::sync::Mutex::new(RunStats::default())),
+            session: std::sync::Mutex::new(ResearchSession::default()),
+            estimate: std::sync::Mutex::new(ProgressEstimate::default()),
+            worker_tasks: Arc::new(std::sync::Mutex::new(Vec::new())),
         }
     }
 
-    pub fn from_file(path: &std::path::Path, ollama_config: crate::config::OllamaConfig, base_client: Arc<Mutex<OllamaClient>>, context_window: usize, research_model: String, max_tool_turns: usize) -> Result<Self> {
+    /// Orchestrator for agents.json in config directory `config_dir`
+    pub fn from_file(config_dir: &std::path::Path, ollama_config: crate::config::OllamaConfig, base_client: Arc<Mutex<OllamaClient>>, context_window: usize, research_model: String, max_tool_turns: usize) -> Result<Self> {
+        let config = Self::load_agents_config(&config_dir.join("agents.json"))?;
+        Ok(Self::new(config, ollama_config, config_dir, base_client, context_window, research_model, max_tool_turns))
+    }
+
+    /// Read agents.json, describing a syntax error by position and otherwise every
+    /// problem found by [AgentsConfig::problems] at once
+    pub fn load_agents_config(path: &std::path::Path) -> Result<AgentsConfig> {
         let config_str = std::fs::read_to_string(path)?;
-        let config: AgentsConfig = serde_json::from_str(&config_str)?;
-        Ok(Self::new(config, ollama_config, base_client, context_window, research_model, max_tool_turns))
+        let config: AgentsConfig = serde_json::from_str(&config_str)
+            .map_err(|e| crate::config::json_error("agents.json", &e))?;
+        crate::config::ensure_valid("agents.json", config.problems())?;
+        for warning in config.warnings() {
+            warn!("agents.json: {}", warning);
+        }
+        Ok(config)
+    }
+
+    pub fn agents_config(&self) -> &AgentsConfig {
+        &self.config
+    }
+
+    /// Every tool call recorded for the latest research run, grouped by agent with
+    /// timestamps and full parameters. Works whether or not `export_memories` is on.
+    pub async fn export_tool_audit(&self, format: crate::history::ExportFormat) -> Result<String> {
+        let shared_memory = self.shared_memory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Shared memory is unavailable, so no tool calls were recorded"))?;
+        let query_id = self.query_id.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("No research run to audit yet"))?;
+        let calls = shared_memory.get_tool_calls(Some(query_id)).await?;
+        render_tool_audit(query_id, &calls, format)
+    }
+
+    /// Swap in a re-read agents.json, keeping the worker counts set from config.toml
+    pub fn replace_agents_config(&mut self, mut config: AgentsConfig) {
+        config.config.min_worker_count = self.config.config.min_worker_count;
+        config.config.max_worker_count = self.config.config.max_worker_count;
+        self.config = config;
     }
 
     /// Override config values from global config.toml
@@ -150,6 +589,108 @@ impl ResearchOrchestrator {
         self.config.config.max_worker_count = toml_config.max_worker_count;
         // Override export_memories setting
         self.export_memories = toml_config.export_memories;
+        self.keep_debate_transcript = toml_config.keep_debate_transcript;
+        self.include_run_details = toml_config.include_run_details;
+        self.semantic_source_dedup = toml_config.semantic_source_dedup;
+        self.verify_sources = toml_config.verify_sources;
+        self.interactive_plan = toml_config.interactive_plan;
+        self.plan_only = toml_config.plan_only;
+        self.source_similarity_threshold = toml_config.source_similarity_threshold;
+        self.max_concurrent_workers = toml_config.max_concurrent_workers;
+        self.worker_timeout_secs = toml_config.worker_timeout_secs;
+        self.early_refinement_after = toml_config.early_refinement_after;
+        self.gap_detection_at_fraction = toml_config.gap_detection_at_fraction;
+        self.repair_document_tables = toml_config.repair_document_tables;
+        if let Some(ref shared_memory) = self.shared_memory {
+            shared_memory.set_dedup_threshold(toml_config.memory_dedup_threshold);
+        }
+    }
+
+    /// Dry-run mode: research stops after planning and returns the plan
+    pub fn set_plan_only(&mut self, plan_only: bool) {
+        self.plan_only = plan_only;
+    }
+
+    /// Depth preset for the following runs
+    pub fn set_depth(&mut self, depth: ResearchDepth) {
+        self.depth = depth;
+    }
+
+    /// Worker counts and iteration limits for this run, after the depth preset
+    fn limits(&self) -> DepthLimits {
+        self.depth.limits(DepthLimits {
+            min_workers: self.config.config.min_worker_count,
+            max_workers: self.config.config.max_worker_count,
+            refinement_iterations: self.ollama_config.max_refinement_iterations,
+            debate_rounds: self.ollama_config.max_debate_rounds,
+            document_iterations: self.ollama_config.max_document_iterations,
+        })
+    }
+
+    /// What a spawned worker task needs from the orchestrator
+    fn worker_context(&self) -> WorkerContext {
+        WorkerContext {
+            tool_executor: self.tool_executor.clone(),
+            research_model: self.research_model.clone(),
+            max_tool_turns: self.max_tool_turns,
+            progress_tx: self.progress_tx.clone(),
+            shared_memory: self.shared_memory.clone(),
+            ollama_config: self.ollama_config.clone(),
+        }
+    }
+
+    /// Everything the supervisor task needs for a run of `initial_worker_count` workers on
+    /// `query`; None without shared memory, since the supervisor works from it
+    fn supervisor_context(&self, query: &str, gap_worker_tx: mpsc::Sender<Vec<SubQuestion>>, initial_worker_count: usize) -> Option<SupervisorContext> {
+        Some(SupervisorContext {
+            shared_memory: self.shared_memory.clone()?,
+            ollama_config: self.ollama_config.clone(),
+            research_model: self.research_model.clone(),
+            max_tool_turns: self.max_tool_turns,
+            query: query.to_string(),
+            query_id: self.query_id.clone(),
+            gap_worker_tx,
+            max_worker_count: self.limits().max_workers,
+            initial_worker_count,
+            gap_midpoint: gap_detection_midpoint(initial_worker_count, self.gap_detection_at_fraction),
+            workers: self.config.agents.workers.clone(),
+        })
+    }
+
+    /// Fresh semaphore bounding how many workers of a run generate at once; excess
+    /// workers wait for a permit instead of all hitting Ollama together
+    fn worker_semaphore(&self) -> Arc<Semaphore> {
+        let permits = match self.max_concurrent_workers {
+            0 => Semaphore::MAX_PERMITS,
+            n => n,
+        };
+        Arc::new(Semaphore::new(permits))
+    }
+
+    /// Run one worker future, turning a timeout into an error so result collection never
+    /// waits on a stalled agent. The timeout is reported as a `WorkerStatus` update.
+    async fn with_worker_timeout(
+        worker_name: &str,
+        timeout_secs: u64,
+        progress_tx: Option<mpsc::UnboundedSender<ResearchProgress>>,
+        work: impl std::future::Future<Output = Result<String>>,
+    ) -> Result<String> {
+        if timeout_secs == 0 {
+            return work.await;
+        }
+        match tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), work).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Worker {} timed out after {}s", worker_name, timeout_secs);
+                if let Some(tx) = progress_tx {
+                    let _ = tx.send(ResearchProgress::WorkerStatus {
+                        worker: worker_name.to_string(),
+                        status: format!("timed out after {}s", timeout_secs),
+                    });
+                }
+                Err(anyhow::anyhow!("worker timed out after {}s", timeout_secs))
+            }
+        }
     }
 
     pub fn set_tool_executor(&mut self, executor: Arc<Mutex<ToolExecutor>>) {
@@ -166,9 +707,103 @@ impl ResearchOrchestrator {
         self.progress_tx = Some(tx);
     }
 
+    /// Write the current run's artifacts to `path` as JSON
+    pub fn save_session(&self, path: &std::path::Path) -> Result<()> {
+        let session = self.session.lock().map(|s| s.clone()).unwrap_or_default();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&session)?)?;
+        Ok(())
+    }
+
+    pub fn load_session(path: &std::path::Path) -> Result<ResearchSession> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Apply a change to the current session and checkpoint it under `sessions_dir()`
+    fn update_session(&self, change: impl FnOnce(&mut ResearchSession)) {
+        let query_id = match self.session.lock() {
+            Ok(mut session) => {
+                change(&mut session);
+                session.query_id.clone()
+            }
+            Err(_) => return,
+        };
+        if query_id.is_empty() {
+            return;
+        }
+        let path = sessions_dir(&self.config_dir).join(format!("{}.json", query_id));
+        if let Err(e) = self.save_session(&path) {
+            warn!("Failed to save session: {}", e);
+        }
+    }
+
+    /// Continue a saved session whose workers completed, running only the remaining
+    /// refinement and document-writing stages. Finished sessions return their document.
+    #[tracing::instrument(name = "research", skip_all, fields(query_id = %session.query_id))]
+    pub async fn resume_session(&mut self, session: ResearchSession) -> Result<String> {
+        if let Some(document) = session.final_document.clone() {
+            return Ok(document);
+        }
+        if session.worker_results.is_empty() {
+            return Err(anyhow::anyhow!("Session has no completed workers to resume from"));
+        }
+
+        info!("Resuming session {} ({} worker results)", session.query_id, session.worker_results.len());
+        let _abort_tasks = AbortTasksOnDrop(self.worker_tasks.clone());
+        self.query_id = Some(session.query_id.clone());
+        self.send_progress(ResearchProgress::Started);
+        let run_started = std::time::Instant::now();
+        if let Ok(mut stats) = self.run_stats.lock() {
+            *stats = RunStats::default();
+        }
+
+        let query = session.query.clone();
+        let worker_results = session.worker_results.clone();
+        let refined_output = session.refined_output.clone();
+        if let Ok(mut current) = self.session.lock() {
+            *current = session;
+        }
+        self.finish_research(&query, &worker_results, refined_output, run_started).await
+    }
+
+    /// Channel on which the UI sends back the approved (possibly edited) plan
+    pub fn set_plan_approval_channel(&mut self, rx: mpsc::UnboundedReceiver<Vec<SubQuestion>>) {
+        self.plan_approval_rx = Some(rx);
+    }
+
+    /// In interactive mode, surface the plan and wait for the user's version of it.
+    /// The original plan is kept if there's no approval channel or it closes.
+    async fn await_plan_approval(&mut self, sub_questions: Vec<SubQuestion>) -> Vec<SubQuestion> {
+        if !self.interactive_plan || self.plan_approval_rx.is_none() {
+            return sub_questions;
+        }
+        self.send_progress(ResearchProgress::PlanAwaitingApproval {
+            plan: sub_questions.clone(),
+            workers: self.config.agents.workers.iter().map(|w| w.name.clone()).collect(),
+        });
+        info!("Waiting for plan approval...");
+        let approved = match self.plan_approval_rx.as_mut() {
+            Some(rx) => rx.recv().await,
+            None => None,
+        };
+        match approved {
+            Some(questions) => {
+                info!("Plan approved with {} sub-questions", questions.len());
+                questions
+            }
+            None => sub_questions,
+        }
+    }
+
     fn send_progress(&self, progress: ResearchProgress) {
         if let Some(tx) = &self.progress_tx {
             let _ = tx.send(progress.clone());
+            if let Some(fraction) = self.estimate.lock().ok().and_then(|mut e| e.observe(&progress)) {
+                let _ = tx.send(ResearchProgress::Overall(fraction));
+            }
         }
         // Also log a human-readable line for the UI verbose log
         use crate::progress::{log_with, Kind};
@@ -179,6 +814,7 @@ impl ResearchOrchestrator {
             ResearchProgress::PlanGenerated(n) => (format!("Generated plan with {} sub-questions", n), Kind::Info),
             ResearchProgress::PlanCriticReviewing(i, max) => (format!("Plan critic reviewing (iteration {}/{})", i, max), Kind::Debate),
             ResearchProgress::PlanApproved => ("Plan approved, starting research".to_string(), Kind::Info),
+            ResearchProgress::PlanAwaitingApproval { ref plan, .. } => (format!("Waiting for approval of {} sub-questions", plan.len()), Kind::Info),
             ResearchProgress::WorkersStarted(n) => (format!("Dispatching {} workers", n), Kind::Worker),
             ResearchProgress::WorkerCompleted(name) => (format!("✓ Worker completed: {}", name), Kind::Worker),
             ResearchProgress::WorkerStarted { worker, question } => (format!("→ {} researching: {}", worker, question), Kind::Worker),
@@ -204,6 +840,7 @@ impl ResearchOrchestrator {
             ResearchProgress::DocumentReviewing => ("Document critic reviewing".to_string(), Kind::DocumentCritic),
             ResearchProgress::ExportingMemories => ("Exporting research memories".to_string(), Kind::Info),
             ResearchProgress::Completed => ("Research complete".to_string(), Kind::Info),
+            ResearchProgress::WorkerText { .. } | ResearchProgress::Overall(_) => return,
         };
         log_with(kind, line);
     }
@@ -218,6 +855,7 @@ impl ResearchOrchestrator {
     }
 
     /// Main entry point for research mode
+    #[tracing::instrument(name = "research", skip_all, fields(query_id = tracing::field::Empty))]
     pub async fn research(&mut self, query: &str) -> Result<String> {
         // Generate unique query ID for this research session using timestamp + random
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -228,6 +866,7 @@ impl ResearchOrchestrator {
         let random: u32 = (timestamp % 10000) as u32; // Simple pseudo-random from timestamp
         let query_id = format!("query_{}_{}", timestamp, random);
         self.query_id = Some(query_id.clone());
+        tracing::Span::current().record("query_id", query_id.as_str());
 
         // Set query_id on tool executor for memory tools
         if let Some(ref executor) = self.tool_executor {
@@ -236,26 +875,50 @@ impl ResearchOrchestrator {
             }
         }
 
-        eprintln!("[Research] Starting query: {} (ID: {})", query, query_id);
+        info!("Starting query: {} (ID: {})", query, query_id);
+        let _abort_tasks = AbortTasksOnDrop(self.worker_tasks.clone());
         self.send_progress(ResearchProgress::Started);
+        let run_started = std::time::Instant::now();
+        if let Ok(mut stats) = self.run_stats.lock() {
+            *stats = RunStats::default();
+        }
+        if let Ok(mut session) = self.session.lock() {
+            *session = ResearchSession {
+                query: query.to_string(),
+                query_id: query_id.clone(),
+                started_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0),
+                ..Default::default()
+            };
+        }
 
         // Clear previous memories from database to start fresh
         if let Some(ref shared_memory) = self.shared_memory {
-            eprintln!("[Research] Clearing previous memories from database...");
+            info!("Clearing previous memories from database...");
             if let Err(e) = shared_memory.clear().await {
-                eprintln!("[Research] Warning: Failed to clear memories: {}", e);
+                warn!("Failed to clear memories: {}", e);
             } else {
-                eprintln!("[Research] ✓ Memories cleared, starting fresh research session");
+                info!("✓ Memories cleared, starting fresh research session");
             }
         }
 
         // Step 1: Decompose query into sub-questions and create plan
         self.send_progress(ResearchProgress::Decomposing);
         let (sub_questions, plan) = self.decompose_query_and_plan(query).await?;
+        let sub_questions = self.await_plan_approval(sub_questions).await;
 
         if sub_questions.is_empty() {
             return Ok("Unable to decompose query into sub-questions.".to_string());
         }
+        self.update_session(|session| {
+            session.plan = plan.clone();
+            session.sub_questions = sub_questions.clone();
+        });
+
+        if self.plan_only {
+            info!("Plan-only mode, skipping workers");
+            self.send_progress(ResearchProgress::Completed);
+            return Ok(format_plan_only(query, &sub_questions, &plan));
+        }
 
         // Store the initial plan in shared memory
         if let Some(ref shared_memory) = self.shared_memory {
@@ -282,21 +945,41 @@ impl ResearchOrchestrator {
                 "lead_researcher".to_string(),
                 Some(metadata)
             ).await {
-                Ok(_) => eprintln!("[Research] Plan stored in shared memory"),
-                Err(e) => eprintln!("[Research] Warning: Failed to store plan: {}", e),
+                Ok(_) => info!("Plan stored in shared memory"),
+                Err(e) => warn!("Failed to store plan: {}", e),
             }
         }
 
         // Step 2: Execute workers with iterative refinement and supervisor monitoring
         self.send_progress(ResearchProgress::WorkersStarted(sub_questions.len()));
         let worker_results = self.execute_workers_with_refinement(&sub_questions, query).await?;
+        self.update_session(|session| session.worker_results = worker_results.clone());
 
-        // Step 3: Combine results (with summarization if needed)
-        self.send_progress(ResearchProgress::Combining);
-        let combined_output = self.combine_results(query, &worker_results).await?;
+        self.finish_research(query, &worker_results, None, run_started).await
+    }
 
-        // Step 4: Refinement loop with critic
-        let refined_output = self.refinement_loop(&combined_output).await?;
+    /// Steps after the workers: combine, refine and write the document. A saved
+    /// `refined_output` (when resuming) skips straight to document writing.
+    async fn finish_research(
+        &self,
+        query: &str,
+        worker_results: &[WorkerResult],
+        refined_output: Option<String>,
+        run_started: std::time::Instant,
+    ) -> Result<String> {
+        let refined_output = match refined_output {
+            Some(refined) => refined,
+            None => {
+                // Step 3: Combine results (with summarization if needed)
+                self.send_progress(ResearchProgress::Combining);
+                let combined_output = self.combine_results(query, worker_results).await?;
+
+                // Step 4: Refinement loop with critic
+                let refined = self.refinement_loop(&combined_output).await?;
+                self.update_session(|session| session.refined_output = Some(refined.clone()));
+                refined
+            }
+        };
 
         // Step 5: Document writing loop with document critic
         let mut final_document = self.document_writing_loop(query, &refined_output).await?;
@@ -343,7 +1026,7 @@ impl ResearchOrchestrator {
                                                 let val_str = match v {
                                                     serde_json::Value::String(s) => {
                                                         if s.len() > 50 {
-                                                            format!("{}...", &s[..50])
+                                                            format!("{}...", crate::ollama::truncate_at_char_boundary(s, 50))
                                                         } else {
                                                             s.clone()
                                                         }
@@ -409,21 +1092,68 @@ impl ResearchOrchestrator {
                 );
 
                 final_document.push_str(&memory_summary);
-                eprintln!("[Research] Memory summary appended to output");
+                info!("Memory summary appended to output");
             }
 
             // Clear memories for this query
             match shared_memory.clear().await {
-                Ok(_) => eprintln!("[Research] Shared memory cleared for next query"),
-                Err(e) => eprintln!("[Research] Warning: Failed to clear memory: {}", e),
+                Ok(_) => info!("Shared memory cleared for next query"),
+                Err(e) => warn!("Failed to clear memory: {}", e),
             }
         }
 
+        if self.keep_debate_transcript {
+            let transcripts = self.session.lock().map(|s| s.debate_transcript.clone()).unwrap_or_default();
+            if !transcripts.is_empty() {
+                final_document.push_str(&format_debate_appendix(&transcripts));
+                info!("Debate transcript appended to output");
+            }
+        }
+
+        if self.include_run_details {
+            let stats = self.run_stats.lock().map(|s| s.clone()).unwrap_or_default();
+            let header = Self::format_run_details(query, &self.research_model, &stats, run_started.elapsed());
+            final_document = format!("{}{}", header, final_document);
+        }
+
+        self.update_session(|session| session.final_document = Some(final_document.clone()));
         self.send_progress(ResearchProgress::Completed);
         Ok(final_document)
     }
 
+    /// Render the "Run Details" provenance header prepended to research documents
+    fn format_run_details(query: &str, model: &str, stats: &RunStats, elapsed: std::time::Duration) -> String {
+        let secs = elapsed.as_secs();
+        let duration = if secs >= 60 {
+            format!("{}m {}s", secs / 60, secs % 60)
+        } else {
+            format!("{}s", secs)
+        };
+        let total_workers = stats.initial_workers + stats.follow_up_workers + stats.gap_workers;
+
+        format!(
+            "## Run Details\n\n\
+            - **Query:** {}\n\
+            - **Model:** {}\n\
+            - **Workers:** {} (initial: {}, follow-up: {}, gap-filling: {})\n\
+            - **Debate rounds:** {}\n\
+            - **Duration:** {}\n\
+            - **Sources:** {}\n\n\
+            ---\n\n",
+            query.split_whitespace().collect::<Vec<_>>().join(" "),
+            model,
+            total_workers,
+            stats.initial_workers,
+            stats.follow_up_workers,
+            stats.gap_workers,
+            stats.debate_rounds,
+            duration,
+            stats.source_count
+        )
+    }
+
     /// Decompose query into sub-questions and create research plan using lead agent
+    #[tracing::instrument(name = "plan", skip_all)]
     async fn decompose_query_and_plan(&self, query: &str) -> Result<(Vec<SubQuestion>, String)> {
         let max_iterations = self.ollama_config.max_plan_iterations;
         let mut current_plan = String::new();
@@ -431,7 +1161,7 @@ impl ResearchOrchestrator {
 
         for iteration in 0..max_iterations {
             self.send_progress(ResearchProgress::PlanningIteration(iteration + 1, max_iterations));
-            eprintln!("[Research] Planning iteration {}/{}", iteration + 1, max_iterations);
+            info!("Planning iteration {}/{}", iteration + 1, max_iterations);
 
             // Generate or refine the plan
             let (plan_response, questions_json) = if iteration == 0 {
@@ -452,23 +1182,25 @@ impl ResearchOrchestrator {
 
             // Get plan critic feedback
             self.send_progress(ResearchProgress::PlanCriticReviewing(iteration + 1, max_iterations));
-            eprintln!("[Research] Reviewing plan with plan critic...");
+            info!("Reviewing plan with plan critic...");
             let criticism = self.review_plan(query, &current_plan, &questions_json).await?;
 
             // Check if approved
-            if criticism.trim().to_uppercase().starts_with("APPROVED") {
+            let approved = parse_verdict(&criticism)
+                .unwrap_or_else(|| criticism.trim().to_uppercase().starts_with("APPROVED"));
+            if approved {
                 self.send_progress(ResearchProgress::PlanApproved);
-                eprintln!("[Research] Plan approved after {} iteration(s)", iteration + 1);
+                info!("Plan approved after {} iteration(s)", iteration + 1);
                 break;
             }
 
             // If not approved and not last iteration, we'll refine in next iteration
-            eprintln!("[Research] Plan iteration {}: Feedback received, will revise", iteration + 1);
+            info!("Plan iteration {}: Feedback received, will revise", iteration + 1);
 
             // On last iteration, use what we have
             if iteration == max_iterations - 1 {
                 self.send_progress(ResearchProgress::PlanApproved);
-                eprintln!("[Research] Max plan iterations reached. Using current plan.");
+                info!("Max plan iterations reached. Using current plan.");
             }
         }
 
@@ -478,6 +1210,7 @@ impl ResearchOrchestrator {
 
     /// Generate initial research plan
     async fn generate_initial_plan(&self, query: &str) -> Result<(String, String)> {
+        let limits = self.limits();
         let prompt = format!(
             "{}\n\n**WORKER COUNT GUIDANCE**: Based on query complexity, create between {} and {} sub-questions. \
             Simple queries should use fewer workers (closer to {}), while complex multi-faceted queries should \
@@ -486,10 +1219,10 @@ impl ResearchOrchestrator {
             1. JSON array of sub-questions (as before)\n\
             2. After the JSON, provide a brief research strategy/plan explaining the approach and what to focus on.",
             self.config.agents.lead.system_prompt,
-            self.config.config.min_worker_count,
-            self.config.config.max_worker_count,
-            self.config.config.min_worker_count,
-            self.config.config.max_worker_count,
+            limits.min_workers,
+            limits.max_workers,
+            limits.min_workers,
+            limits.max_workers,
             query
         );
 
@@ -498,6 +1231,7 @@ impl ResearchOrchestrator {
 
         let mut lead_client = OllamaClient::with_config(base_url, self.research_model.clone());
         lead_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut lead_client, &self.ollama_config);
 
         let response = lead_client.query_streaming(&prompt, |_| {}).await?;
 
@@ -509,6 +1243,7 @@ impl ResearchOrchestrator {
 
     /// Refine plan based on critic feedback
     async fn refine_plan(&self, query: &str, previous_plan: &str, _previous_json: &str) -> Result<(String, String)> {
+        let limits = self.limits();
         let prompt = format!(
             "{}\n\n**WORKER COUNT GUIDANCE**: Based on query complexity, create between {} and {} sub-questions.\n\n\
             Original Query: {}\n\n\
@@ -517,8 +1252,8 @@ impl ResearchOrchestrator {
             1. JSON array of revised sub-questions\n\
             2. After the JSON, provide updated research strategy",
             self.config.agents.lead.system_prompt,
-            self.config.config.min_worker_count,
-            self.config.config.max_worker_count,
+            limits.min_workers,
+            limits.max_workers,
             query,
             previous_plan
         );
@@ -528,6 +1263,7 @@ impl ResearchOrchestrator {
 
         let mut lead_client = OllamaClient::with_config(base_url, self.research_model.clone());
         lead_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut lead_client, &self.ollama_config);
 
         let response = lead_client.query_streaming(&prompt, |_| {}).await?;
 
@@ -544,11 +1280,12 @@ impl ResearchOrchestrator {
         let prompt = format!(
             "{}\n\nOriginal Query: {}\n\n\
             Research Plan:\n{}\n\n\
-            Questions (JSON):\n{}",
+            Questions (JSON):\n{}\n\n{}",
             self.config.agents.plan_critic.system_prompt,
             query,
             plan,
-            questions_json
+            questions_json,
+            VERDICT_INSTRUCTIONS
         );
 
         let base_url = std::env::var("OLLAMA_HOST")
@@ -556,6 +1293,7 @@ impl ResearchOrchestrator {
 
         let mut critic_client = OllamaClient::with_config(base_url, self.research_model.clone());
         critic_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut critic_client, &self.ollama_config);
         let review = critic_client.query_streaming(&prompt, |_| {}).await?;
 
         Ok(review)
@@ -579,13 +1317,11 @@ impl ResearchOrchestrator {
         };
 
         // Log the planner's decisions in debug mode
-        if std::env::var("BOBBAR_DEBUG").is_ok() {
-            eprintln!("\n[Research Planner] Decomposed query into {} sub-questions:", assignments.len());
-            for (i, assignment) in assignments.iter().enumerate() {
-                eprintln!("  {}. [{}] {}", i + 1, assignment.worker, assignment.question);
-            }
-            eprintln!("\n[Research Strategy] {}\n", plan);
+        debug!("Planner decomposed query into {} sub-questions", assignments.len());
+        for (i, assignment) in assignments.iter().enumerate() {
+            debug!("  {}. [{}] {}", i + 1, assignment.worker, assignment.question);
         }
+        debug!("Research strategy: {}", plan);
 
         // Map worker role to actual worker name
         let mut sub_questions = Vec::new();
@@ -617,7 +1353,8 @@ impl ResearchOrchestrator {
         early_results: &[WorkerResult],
         research_model: &str,
         max_tool_turns: usize,
-        _ollama_config: &crate::config::OllamaConfig,
+        ollama_config: &crate::config::OllamaConfig,
+        workers: &[AgentRole],
     ) -> Result<Vec<SubQuestion>> {
         if early_results.is_empty() {
             return Ok(Vec::new());
@@ -644,11 +1381,13 @@ impl ResearchOrchestrator {
             3. Explore promising areas more deeply\n\
             4. Investigate new angles that emerged from findings\n\n\
             CRITICAL: Return ONLY valid JSON array. No markdown, no text, no code blocks.\n\
-            Format: [{{\"question\": \"...\", \"worker\": \"...\"}}]\n\n\
-            Available workers: web_researcher, technical_analyst, data_specialist, comparative_analyst, news_researcher",
+            Format: [{{\"question\": \"...\", \"worker\": \"{}\"}}]\n\n\
+            Available workers:\n{}",
             original_query,
             early_results.len(),
-            early_findings
+            early_findings,
+            worker_name_choices(workers),
+            describe_workers(workers)
         );
 
         let base_url = std::env::var("OLLAMA_HOST")
@@ -656,6 +1395,7 @@ impl ResearchOrchestrator {
 
         let mut refinement_client = OllamaClient::with_config(base_url, research_model.to_string());
         refinement_client.set_max_tool_turns(max_tool_turns);
+        configure_research_client(&mut refinement_client, ollama_config);
 
         let response = refinement_client.query_streaming(&prompt, |_| {}).await?;
 
@@ -686,12 +1426,13 @@ impl ResearchOrchestrator {
             })
             .collect();
 
-        eprintln!("[Research] Generated {} follow-up questions based on early results", follow_ups.len());
+        info!("Generated {} follow-up questions based on early results", follow_ups.len());
 
         Ok(follow_ups)
     }
 
     /// Execute workers with iterative refinement: start initial workers, then add follow-ups based on early results
+    #[tracing::instrument(name = "workers", skip_all)]
     async fn execute_workers_with_refinement(&self, initial_questions: &[SubQuestion], query: &str) -> Result<Vec<WorkerResult>> {
         if initial_questions.is_empty() {
             return Ok(Vec::new());
@@ -701,38 +1442,18 @@ impl ResearchOrchestrator {
         let (gap_tx, mut gap_rx) = mpsc::channel::<Vec<SubQuestion>>(1);
 
         // Spawn supervisor task
-        let shared_memory = self.shared_memory.clone();
-        let ollama_config = self.ollama_config.clone();
-        let research_model = self.research_model.clone();
-        let max_tool_turns = self.max_tool_turns;
-        let query_owned = query.to_string();
-        let query_id = self.query_id.clone();
-        let min_worker_count = self.config.config.min_worker_count;
-        let max_worker_count = self.config.config.max_worker_count;
-        let initial_worker_count = initial_questions.len();
-
-        let supervisor_handle = if shared_memory.is_some() {
-            Some(tokio::spawn(async move {
-                Self::supervisor_loop(
-                    shared_memory.unwrap(),
-                    ollama_config,
-                    research_model,
-                    max_tool_turns,
-                    query_owned,
-                    query_id,
-                    gap_tx,
-                    min_worker_count,
-                    max_worker_count,
-                    initial_worker_count
-                ).await
-            }))
-        } else {
-            None
-        };
+        let supervisor_handle = self.supervisor_context(query, gap_tx, initial_questions.len())
+            .map(|context| tokio::spawn(Self::supervisor_loop(context)));
+        if let Some(ref handle) = supervisor_handle {
+            track_task(&self.worker_tasks, handle);
+        }
 
         // Set up channel for worker results (with extra capacity for follow-ups)
         let (tx, mut rx) = mpsc::channel(initial_questions.len() + 10);
 
+        // Initial, gap-filling and follow-up workers all share one concurrency limit
+        let semaphore = self.worker_semaphore();
+
         // Launch initial workers
         let mut handles = Vec::new();
         for sub_q in initial_questions {
@@ -744,31 +1465,20 @@ impl ResearchOrchestrator {
                 .cloned();
 
             if worker.is_none() {
-                eprintln!("[Research] Warning: No worker found for {}", sub_q.assigned_worker);
+                warn!("No worker found for {}", sub_q.assigned_worker);
                 continue;
             }
 
-            let base_client = self.base_client.clone();
-            let tool_executor = self.tool_executor.clone();
-            let research_model = self.research_model.clone();
-            let max_tool_turns = self.max_tool_turns;
-            let progress_tx = self.progress_tx.clone();
-            let shared_memory = self.shared_memory.clone();
-
-            let api_delay_ms = self.ollama_config.api_delay_ms;
-            let summarization_threshold_research = self.ollama_config.summarization_threshold_research;
+            let context = self.worker_context();
+            let semaphore = semaphore.clone();
+            let worker_timeout_secs = self.worker_timeout_secs;
             let handle = tokio::spawn(async move {
-                let result = Self::execute_worker(
-                    worker.unwrap(),
-                    &sub_q.question,
-                    base_client,
-                    tool_executor,
-                    research_model,
-                    max_tool_turns,
-                    progress_tx,
-                    shared_memory,
-                    api_delay_ms,
-                    summarization_threshold_research,
+                let _permit = semaphore.acquire_owned().await;
+                let result = Self::with_worker_timeout(
+                    &sub_q.assigned_worker,
+                    worker_timeout_secs,
+                    context.progress_tx.clone(),
+                    Self::execute_worker(worker.unwrap(), &sub_q.question, context),
                 )
                 .await;
 
@@ -781,23 +1491,28 @@ impl ResearchOrchestrator {
                 let _ = tx.send(worker_result).await;
             });
 
+            track_task(&self.worker_tasks, &handle);
             handles.push(handle);
         }
 
+        if let Ok(mut stats) = self.run_stats.lock() {
+            stats.initial_workers = handles.len();
+        }
+
         // Don't drop tx yet - we may spawn gap-filling workers
         // Keep track of active workers and gap-filling state
         let mut active_workers = initial_questions.len();
         let mut gap_workers_spawned = false;
         let total_initial_workers = initial_questions.len();
 
-        // Collect results, triggering refinement after first 2-3 completions
+        // Collect results, triggering refinement after the first `early_refinement_after` completions
         let mut all_results = Vec::new();
         let mut early_results_for_refinement = Vec::new();
         let mut refinement_triggered = false;
-        let early_threshold = 2.min(initial_questions.len());
+        let early_threshold = self.early_refinement_after.min(initial_questions.len());
 
-        // Calculate midpoint for gap detection (halfway through initial workers)
-        let midpoint_threshold = (total_initial_workers + 1) / 2;
+        // Point at which the supervisor checks for gaps (halfway through initial workers by default)
+        let midpoint_threshold = gap_detection_midpoint(total_initial_workers, self.gap_detection_at_fraction);
 
         loop {
             tokio::select! {
@@ -805,7 +1520,7 @@ impl ResearchOrchestrator {
                 Some(gap_questions) = gap_rx.recv() => {
                     if !gap_workers_spawned {
                         gap_workers_spawned = true;
-                        eprintln!("[Research] Supervisor requested {} gap-filling workers", gap_questions.len());
+                        info!("Supervisor requested {} gap-filling workers", gap_questions.len());
 
                         for sub_q in gap_questions {
                             let tx = tx.clone();
@@ -815,32 +1530,24 @@ impl ResearchOrchestrator {
                                 .cloned();
 
                             if worker.is_none() {
-                                eprintln!("[Research] Warning: No worker found for {}", sub_q.assigned_worker);
+                                warn!("No worker found for {}", sub_q.assigned_worker);
                                 continue;
                             }
 
                             active_workers += 1;
-                            let base_client = self.base_client.clone();
-                            let tool_executor = self.tool_executor.clone();
-                            let research_model = self.research_model.clone();
-                            let max_tool_turns = self.max_tool_turns;
-                            let progress_tx = self.progress_tx.clone();
-                            let shared_memory = self.shared_memory.clone();
-
-            let api_delay_ms = self.ollama_config.api_delay_ms;
-            let summarization_threshold_research = self.ollama_config.summarization_threshold_research;
+                            if let Ok(mut stats) = self.run_stats.lock() {
+                                stats.gap_workers += 1;
+                            }
+                            let context = self.worker_context();
+                            let semaphore = semaphore.clone();
+                            let worker_timeout_secs = self.worker_timeout_secs;
                             let handle = tokio::spawn(async move {
-                                let result = Self::execute_worker(
-                                    worker.unwrap(),
-                                    &sub_q.question,
-                                    base_client,
-                                    tool_executor,
-                                    research_model,
-                                    max_tool_turns,
-                                    progress_tx,
-                                    shared_memory,
-                                    api_delay_ms,
-                                    summarization_threshold_research,
+                                let _permit = semaphore.acquire_owned().await;
+                                let result = Self::with_worker_timeout(
+                                    &sub_q.assigned_worker,
+                                    worker_timeout_secs,
+                                    context.progress_tx.clone(),
+                                    Self::execute_worker(worker.unwrap(), &sub_q.question, context),
                                 )
                                 .await;
 
@@ -853,6 +1560,7 @@ impl ResearchOrchestrator {
                                 let _ = tx.send(worker_result).await;
                             });
 
+                            track_task(&self.worker_tasks, &handle);
                             handles.push(handle);
                         }
                     }
@@ -893,29 +1601,30 @@ impl ResearchOrchestrator {
                     let query_clone = query.to_string();
                     let early_clone = early_results_for_refinement.clone();
                     let config = self.config.clone();
-                    let base_client = self.base_client.clone();
-                    let tool_executor = self.tool_executor.clone();
                     let research_model = self.research_model.clone();
                     let max_tool_turns = self.max_tool_turns;
                     let progress_tx = self.progress_tx.clone();
                     let ollama_config = self.ollama_config.clone();
-                    let shared_memory = self.shared_memory.clone();
-                    let api_delay_ms_clone = self.ollama_config.api_delay_ms;
-                    let summarization_threshold_research = self.ollama_config.summarization_threshold_research;
+                    let worker_context = self.worker_context();
+                    let run_stats = self.run_stats.clone();
+                    let semaphore = semaphore.clone();
+                    let worker_timeout_secs = self.worker_timeout_secs;
+                    let worker_tasks = self.worker_tasks.clone();
 
-                    tokio::spawn(async move {
+                    let follow_up_task = tokio::spawn(async move {
                         // Generate follow-up questions
                         let follow_ups = Self::generate_follow_up_questions_static(
                             &query_clone,
                             &early_clone,
                             &research_model,
                             max_tool_turns,
-                            &ollama_config
+                            &ollama_config,
+                            &config.agents.workers
                         ).await;
 
                         if let Ok(follow_ups) = follow_ups {
                             if !follow_ups.is_empty() {
-                                eprintln!("[Research] Launching {} follow-up workers...", follow_ups.len());
+                                info!("Launching {} follow-up workers...", follow_ups.len());
                                 // Send progress update (need to access progress_tx from outer scope)
                                 if let Some(ref ptx) = progress_tx {
                                     let _ = ptx.send(ResearchProgress::FollowUpQuestionsGenerated(follow_ups.len()));
@@ -928,33 +1637,29 @@ impl ResearchOrchestrator {
                                         .cloned();
 
                                     if let Some(worker) = worker {
-                                        let base_client = base_client.clone();
-                                        let tool_executor = tool_executor.clone();
-                                        let research_model = research_model.clone();
-                                        let progress_tx = progress_tx.clone();
-                                        let shared_memory = shared_memory.clone();
-                                        let api_delay_ms = api_delay_ms_clone;
-                                        let summarization_threshold_research = summarization_threshold_research;
-
-                                        tokio::spawn(async move {
-                                            let _ = Self::execute_worker(
-                                                worker,
-                                                &follow_up.question,
-                                                base_client,
-                                                tool_executor,
-                                                research_model,
-                                                max_tool_turns,
-                                                progress_tx,
-                                                shared_memory,
-                                                api_delay_ms,
-                                                summarization_threshold_research,
+                                        if let Ok(mut stats) = run_stats.lock() {
+                                            stats.follow_up_workers += 1;
+                                        }
+                                        let context = worker_context.clone();
+                                        let semaphore = semaphore.clone();
+
+                                        let handle = tokio::spawn(async move {
+                                            let _permit = semaphore.acquire_owned().await;
+                                            let worker_name = worker.name.clone();
+                                            let _ = Self::with_worker_timeout(
+                                                &worker_name,
+                                                worker_timeout_secs,
+                                                context.progress_tx.clone(),
+                                                Self::execute_worker(worker, &follow_up.question, context),
                                             ).await;
                                         });
+                                        track_task(&worker_tasks, &handle);
                                     }
                                 }
                             }
                         }
                     });
+                    track_task(&self.worker_tasks, &follow_up_task);
                 }
             }
 
@@ -989,34 +1694,11 @@ impl ResearchOrchestrator {
         let (gap_tx, _gap_rx) = mpsc::channel::<Vec<SubQuestion>>(1);
 
         // Spawn supervisor task
-        let shared_memory = self.shared_memory.clone();
-        let ollama_config = self.ollama_config.clone();
-        let research_model = self.research_model.clone();
-        let max_tool_turns = self.max_tool_turns;
-        let query_owned = query.to_string();
-        let query_id = self.query_id.clone();
-        let min_worker_count = self.config.config.min_worker_count;
-        let max_worker_count = self.config.config.max_worker_count;
-        let initial_worker_count = sub_questions.len();
-
-        let supervisor_handle = if shared_memory.is_some() {
-            Some(tokio::spawn(async move {
-                Self::supervisor_loop(
-                    shared_memory.unwrap(),
-                    ollama_config,
-                    research_model,
-                    max_tool_turns,
-                    query_owned,
-                    query_id,
-                    gap_tx,
-                    min_worker_count,
-                    max_worker_count,
-                    initial_worker_count
-                ).await
-            }))
-        } else {
-            None
-        };
+        let supervisor_handle = self.supervisor_context(query, gap_tx, sub_questions.len())
+            .map(|context| tokio::spawn(Self::supervisor_loop(context)));
+        if let Some(ref handle) = supervisor_handle {
+            track_task(&self.worker_tasks, handle);
+        }
 
         // Execute workers as normal
         let results = self.execute_workers(sub_questions).await?;
@@ -1031,18 +1713,21 @@ impl ResearchOrchestrator {
 
     /// Supervisor loop - monitors memory and provides guidance
     /// Can spawn 1-3 gap-filling workers once during research if gaps detected
-    async fn supervisor_loop(
-        shared_memory: Arc<crate::shared_memory::SharedMemory>,
-        ollama_config: crate::config::OllamaConfig,
-        research_model: String,
-        max_tool_turns: usize,
-        query: String,
-        query_id: Option<String>,
-        gap_worker_tx: mpsc::Sender<Vec<SubQuestion>>,
-        _min_worker_count: usize,
-        max_worker_count: usize,
-        initial_worker_count: usize,
-    ) {
+    #[tracing::instrument(name = "supervisor", skip_all)]
+    async fn supervisor_loop(context: SupervisorContext) {
+        let SupervisorContext {
+            shared_memory,
+            ollama_config,
+            research_model,
+            max_tool_turns,
+            query,
+            query_id,
+            gap_worker_tx,
+            max_worker_count,
+            initial_worker_count,
+            gap_midpoint,
+            workers,
+        } = context;
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(15));
         let mut iteration = 0;
         let mut gap_workers_requested = false;
@@ -1066,10 +1751,10 @@ impl ResearchOrchestrator {
                     .unwrap_or(0);
                 let midpoint = latest_progress.metadata.get("midpoint_threshold")
                     .and_then(|s| s.parse::<usize>().ok())
-                    .unwrap_or((initial_worker_count + 1) / 2);
+                    .unwrap_or(gap_midpoint);
                 (completed, midpoint)
             } else {
-                (0, (initial_worker_count + 1) / 2)
+                (0, gap_midpoint)
             };
 
             // Reverse to get newest first
@@ -1082,14 +1767,14 @@ impl ResearchOrchestrator {
 
             // Skip first iteration if nothing to review yet
             if discoveries.is_empty() && insights.is_empty() && iteration == 1 {
-                eprintln!("[Supervisor] Iteration {}: No discoveries/insights yet, skipping", iteration);
+                info!("Iteration {}: No discoveries/insights yet, skipping", iteration);
                 continue;
             }
 
             // After first iteration, always provide feedback even if workers haven't produced much
             // This helps guide workers who may be stuck or off-track
 
-            eprintln!("[Supervisor] Iteration {}: Reviewing {} discoveries, {} insights, {} deadends",
+            info!("Iteration {}: Reviewing {} discoveries, {} insights, {} deadends",
                 iteration, discoveries.len(), insights.len(), deadends.len());
 
             // Get the plan
@@ -1132,10 +1817,11 @@ impl ResearchOrchestrator {
 
             let mut supervisor_client = OllamaClient::with_config(base_url, research_model.clone());
             supervisor_client.set_max_tool_turns(max_tool_turns);
+            configure_research_client(&mut supervisor_client, &ollama_config);
 
             match supervisor_client.query_streaming(&analysis_prompt, |_| {}).await {
                 Ok(analysis) => {
-                    eprintln!("[Supervisor] Analysis: {}", analysis.chars().take(150).collect::<String>());
+                    info!("Analysis: {}", analysis.chars().take(150).collect::<String>());
 
                     // Store feedback in memory
                     let mut metadata = std::collections::HashMap::new();
@@ -1153,12 +1839,12 @@ impl ResearchOrchestrator {
                         "supervisor".to_string(),
                         Some(metadata)
                     ).await {
-                        eprintln!("[Supervisor] Failed to update feedback: {}", e);
+                        warn!("Failed to update feedback: {}", e);
                     }
 
                     // GAP DETECTION: When halfway through workers complete, check for gaps and spawn workers once
                     if !gap_workers_requested && completed_count >= midpoint_threshold && completed_count > 0 && initial_worker_count < max_worker_count {
-                        eprintln!("[Supervisor] Midpoint reached ({}/{} workers completed), checking for research gaps...", completed_count, initial_worker_count);
+                        info!("Midpoint reached ({}/{} workers completed), checking for research gaps...", completed_count, initial_worker_count);
                         gap_workers_requested = true;
 
                         let gap_detection_prompt = format!(
@@ -1177,13 +1863,8 @@ impl ResearchOrchestrator {
                             Respond with EITHER:\n\
                             1. 'NO_GAPS' if research coverage is adequate\n\
                             2. A JSON array of gap-filling questions:\n\
-                            [{{\"question\": \"...\", \"worker\": \"web_researcher|technical_analyst|data_specialist|comparative_analyst|news_researcher\"}}]\n\n\
-                            Available workers:\n\
-                            - web_researcher: General web research, organizational info, established facts\n\
-                            - technical_analyst: Technical specifications, APIs, implementation details\n\
-                            - data_specialist: Quantitative metrics, statistics, numerical data\n\
-                            - comparative_analyst: Side-by-side comparisons, trade-off analysis\n\
-                            - news_researcher: Recent developments, breaking news, updates\n\n\
+                            [{{\"question\": \"...\", \"worker\": \"{}\"}}]\n\n\
+                            Available workers:\n{}\n\n\
                             CRITICAL: Return ONLY 'NO_GAPS' or valid JSON array. No markdown, no explanation.",
                             query,
                             plan_content,
@@ -1193,7 +1874,9 @@ impl ResearchOrchestrator {
                             if insights_summary.is_empty() { "(none yet)" } else { &insights_summary },
                             initial_worker_count,
                             max_worker_count,
-                            (max_worker_count - initial_worker_count).min(3)
+                            (max_worker_count - initial_worker_count).min(3),
+                            worker_name_choices(&workers),
+                            describe_workers(&workers)
                         );
 
                         match supervisor_client.query_streaming(&gap_detection_prompt, |_| {}).await {
@@ -1219,23 +1902,23 @@ impl ResearchOrchestrator {
                                                 .collect();
 
                                             if !gap_questions.is_empty() {
-                                                eprintln!("[Supervisor] Detected research gaps, spawning {} additional workers", gap_questions.len());
+                                                info!("Detected research gaps, spawning {} additional workers", gap_questions.len());
                                                 let _ = gap_worker_tx.send(gap_questions).await;
                                             }
                                         }
                                     }
                                 } else {
-                                    eprintln!("[Supervisor] No significant research gaps detected");
+                                    info!("No significant research gaps detected");
                                 }
                             },
                             Err(e) => {
-                                eprintln!("[Supervisor] Error in gap detection: {}", e);
+                                warn!("Error in gap detection: {}", e);
                             }
                         }
                     }
                 },
                 Err(e) => {
-                    eprintln!("[Supervisor] Error analyzing memories: {}", e);
+                    warn!("Error analyzing memories: {}", e);
                 }
             }
         }
@@ -1246,6 +1929,7 @@ impl ResearchOrchestrator {
     async fn execute_workers(&self, sub_questions: &[SubQuestion]) -> Result<Vec<WorkerResult>> {
         let (tx, mut rx) = mpsc::channel(sub_questions.len());
         let mut handles = Vec::new();
+        let semaphore = self.worker_semaphore();
 
         for sub_q in sub_questions {
             let tx = tx.clone();
@@ -1256,33 +1940,18 @@ impl ResearchOrchestrator {
                 .cloned()
                 .ok_or_else(|| anyhow::anyhow!("Worker not found: {}", sub_q.assigned_worker))?;
 
-            let base_client = self.base_client.clone();
-            let tool_executor = self.tool_executor.clone();
+            let context = self.worker_context();
             let progress_tx = self.progress_tx.clone();
-            let research_model = self.research_model.clone();
-            let max_tool_turns = self.max_tool_turns;
-            let shared_memory = self.shared_memory.clone();
 
             // Emit start event for this worker
             if let Some(p) = &progress_tx {
                 let _ = p.send(ResearchProgress::WorkerStarted { worker: worker.name.clone(), question: sub_q.question.clone() });
             }
 
-            let api_delay_ms = self.ollama_config.api_delay_ms;
-            let summarization_threshold_research = self.ollama_config.summarization_threshold_research;
+            let semaphore = semaphore.clone();
             let handle = tokio::spawn(async move {
-                let result = Self::execute_worker(
-                    worker,
-                    &sub_q.question,
-                    base_client,
-                    tool_executor,
-                    research_model,
-                    max_tool_turns,
-                    progress_tx.clone(),
-                    shared_memory,
-                    api_delay_ms,
-                    summarization_threshold_research,
-                ).await;
+                let _permit = semaphore.acquire_owned().await;
+                let result = Self::execute_worker(worker, &sub_q.question, context).await;
 
                 let worker_result = match result {
                     Ok(answer) => WorkerResult {
@@ -1305,6 +1974,7 @@ impl ResearchOrchestrator {
                 let _ = tx.send(worker_result).await;
             });
 
+            track_task(&self.worker_tasks, &handle);
             handles.push(handle);
         }
 
@@ -1372,11 +2042,12 @@ impl ResearchOrchestrator {
             }
         }
 
-        // 3. Semantic search for relevant discoveries (top 5)
+        // 3. Semantic search for relevant discoveries (top 5 above the relevance floor)
         let mut search_params = std::collections::HashMap::new();
         search_params.insert("query".to_string(), question.to_string());
         search_params.insert("type".to_string(), "discovery".to_string());
         search_params.insert("limit".to_string(), "5".to_string());
+        search_params.insert("min_score".to_string(), CONTEXT_MIN_SCORE.to_string());
 
         if let Ok(search_result) = executor_lock.execute_builtin_tool("memory_search", search_params).await {
             if let Some(results_arr) = search_result.get("results").and_then(|v| v.as_array()) {
@@ -1385,7 +2056,8 @@ impl ResearchOrchestrator {
                         .filter_map(|r| {
                             let content = r.get("content")?.as_str()?;
                             let created_by = r.get("created_by")?.as_str()?;
-                            Some(format!("  • [{}]: {}", created_by, content))
+                            let score = r.get("score").and_then(|s| s.as_f64()).unwrap_or(0.0);
+                            Some(format!("  • [{}] (relevance {:.2}): {}", created_by, score, content))
                         })
                         .collect();
                     if !discovery_items.is_empty() {
@@ -1433,29 +2105,22 @@ impl ResearchOrchestrator {
     }
 
     /// Execute a single worker agent
-    async fn execute_worker(
-        worker: AgentRole,
-        question: &str,
-        _base_client: Arc<Mutex<OllamaClient>>,
-        tool_executor: Option<Arc<Mutex<ToolExecutor>>>,
-        research_model: String,
-        max_tool_turns: usize,
-        progress_tx: Option<mpsc::UnboundedSender<ResearchProgress>>,
-        shared_memory: Option<Arc<crate::shared_memory::SharedMemory>>,
-        api_delay_ms: u64,
-        summarization_threshold_research: usize,
-    ) -> Result<String> {
+    #[tracing::instrument(name = "worker", skip_all, fields(worker = %worker.name))]
+    async fn execute_worker(worker: AgentRole, question: &str, context: WorkerContext) -> Result<String> {
+        let WorkerContext { tool_executor, research_model, max_tool_turns, progress_tx, shared_memory, ollama_config } = context;
+
         // Add small delay to avoid rate limiting
-        tokio::time::sleep(tokio::time::Duration::from_millis(api_delay_ms)).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(ollama_config.api_delay_ms)).await;
 
         let base_url = std::env::var("OLLAMA_HOST")
             .unwrap_or_else(|_| "http://localhost:11434".to_string());
 
         let mut worker_client = OllamaClient::with_config(base_url, research_model.clone());
         worker_client.set_max_tool_turns(max_tool_turns);
+        configure_research_client(&mut worker_client, &ollama_config);
 
         // Configure research mode summarization with threshold from config
-        worker_client.set_summarization_config(None, summarization_threshold_research, true);
+        worker_client.set_summarization_config(None, ollama_config.summarization_threshold_research, true);
 
         // Set tool executor and available tools if available
         if let Some(ref executor) = tool_executor {
@@ -1466,6 +2131,7 @@ impl ResearchOrchestrator {
         }
         let available = worker.available_tools.clone();
         worker_client.set_available_tools(available.clone());
+        worker_client.set_denied_tools(worker.denied_tools.clone());
 
         // Emit a brief status about planned tool usage
         if let Some(p) = &progress_tx {
@@ -1551,8 +2217,14 @@ impl ResearchOrchestrator {
             });
         }
 
-        // query_streaming handles tool iterations internally with its own context
-        let answer = worker_client.query_streaming(&prompt, |_| {}).await?;
+        // query_streaming handles tool iterations internally with its own context;
+        // the answer is forwarded as it's generated so the UI can show it live
+        let worker_name = worker.name.clone();
+        let answer = worker_client.query_streaming(&prompt, move |chunk| {
+            if let Some(ref p) = progress_tx {
+                let _ = p.send(ResearchProgress::WorkerText { worker: worker_name.clone(), chunk });
+            }
+        }).await?;
 
         Ok(answer)
     }
@@ -1563,18 +2235,29 @@ impl ResearchOrchestrator {
         // This is much higher than regular chat to preserve detailed research findings
         let max_chars = self.ollama_config.summarization_threshold_research;
 
-        eprintln!("[Research] Summarization threshold: {} chars, Worker result: {} chars",
+        debug!("Summarization threshold: {} chars, Worker result: {} chars",
                   max_chars, result.answer.len());
 
         // If result is within limit, return as-is
         if result.answer.len() <= max_chars {
-            eprintln!("[Research] Worker result within threshold, keeping full content");
+            debug!("Worker result within threshold, keeping full content");
             return Ok(result.answer.clone());
         }
 
-        eprintln!("[Research] Worker result exceeds threshold ({} > {} chars), summarizing...",
+        info!("Worker result exceeds threshold ({} > {} chars), summarizing...",
                   result.answer.len(), max_chars);
 
+        let instructions = self.config.agents.summarizer.as_ref()
+            .map(|summarizer| summarizer.system_prompt.as_str())
+            .unwrap_or(DEFAULT_SUMMARIZER_PROMPT);
+        let prompt = format!("{}\n\nResearch findings:\n{}", instructions, result.answer);
+
+        Ok(self.summarize_with_model(&prompt, &result.answer, max_chars).await)
+    }
+
+    /// Run `prompt`, which asks for a condensed version of `text`, on the research model.
+    /// If the model fails, `text` cut to `max_len` bytes is returned instead.
+    async fn summarize_with_model(&self, prompt: &str, text: &str, max_len: usize) -> String {
         // Add delay to avoid rate limiting
         tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
 
@@ -1583,33 +2266,24 @@ impl ResearchOrchestrator {
 
         let mut summarizer_client = OllamaClient::with_config(base_url, self.research_model.clone());
         summarizer_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut summarizer_client, &self.ollama_config);
 
-        let prompt = format!(
-            "Condense these research findings while preserving all key information:\n\n\
-            - Keep ALL facts, data points, and citations [Source: name]\n\
-            - Preserve technical details and specifications\n\
-            - Maintain examples and context\n\
-            - Remove only redundant explanations and filler words\n\
-            - Keep the depth and completeness of information\n\n\
-            Research findings:\n{}",
-            result.answer
-        );
-
-        match summarizer_client.query_streaming(&prompt, |_| {}).await {
+        match summarizer_client.query_streaming(prompt, |_| {}).await {
             Ok(summary) => {
-                eprintln!("[Research] Summarized from {} to {} characters", result.answer.len(), summary.len());
-                Ok(summary)
+                info!("Summarized from {} to {} characters", text.len(), summary.len());
+                summary
             },
             Err(e) => {
-                eprintln!("[Research] Summarization failed: {}, using truncated version", e);
+                warn!("Summarization failed: {}, using truncated version", e);
                 // Fallback to truncation if summarization fails
-                let truncate_len = max_chars.min(result.answer.len());
-                Ok(format!("{}...\n\n[Note: Content truncated due to length]", &result.answer[..truncate_len]))
+                let truncated = crate::ollama::truncate_at_char_boundary(text, max_len);
+                format!("{}...\n\n[Note: Content truncated due to length]", truncated)
             }
         }
     }
 
     /// Combine worker results into a cohesive output
+    #[tracing::instrument(name = "combine", skip_all)]
     async fn combine_results(&self, original_query: &str, results: &[WorkerResult]) -> Result<String> {
         // Emit a status about combination stage for verbosity
         self.send_progress(ResearchProgress::WorkerStatus {
@@ -1618,6 +2292,9 @@ impl ResearchOrchestrator {
         });
         let mut output = format!("# Research Results for: {}\n\n", original_query);
         let num_workers = results.len();
+        // Sources across every worker, in canonical form, so each is listed once
+        let mut all_sources: BTreeSet<String> = BTreeSet::new();
+        let mut citation_count = 0;
 
         for (idx, result) in results.iter().enumerate() {
             // Show progress for summarization if needed
@@ -1625,8 +2302,12 @@ impl ResearchOrchestrator {
                 self.send_progress(ResearchProgress::Summarizing);
             }
 
-            // Summarize if needed based on available context per worker
-            let answer = self.summarize_worker_result(result, num_workers).await?;
+            // Summarize if needed based on available context per worker, then cite every
+            // URL in canonical form so workers citing the same page agree
+            let answer = canonicalize_urls_in(&self.summarize_worker_result(result, num_workers).await?);
+            let sources = self.extract_sources(&answer);
+            citation_count += sources.len();
+            all_sources.extend(sources);
 
             output.push_str(&format!(
                 "## {}\n**Question:** {}\n\n{}\n\n",
@@ -1636,31 +2317,27 @@ impl ResearchOrchestrator {
             ));
         }
 
+        if !all_sources.is_empty() {
+            info!("Workers cited {} unique sources ({} citations across workers)", all_sources.len(), citation_count);
+            output.push_str("## Sources Cited by Workers\n\n");
+            for source in &all_sources {
+                output.push_str(&format!("- {}\n", source));
+            }
+            output.push('\n');
+        }
+
         Ok(output)
     }
 
     /// Extract sources from text and add sources section
-    fn add_sources_section(&self, text: &str) -> String {
+    async fn add_sources_section(&self, text: &str) -> String {
         let sources = self.extract_sources(text);
 
         if sources.is_empty() {
-            eprintln!("[Research] No sources found in document");
+            info!("No sources found in document");
             return text.to_string();
         }
 
-        eprintln!("[Research] Found {} unique sources", sources.len());
-
-        // Create sources section
-        let mut output = text.to_string();
-
-        // Ensure there's separation from main content
-        if !output.ends_with("\n\n") {
-            output.push_str("\n\n");
-        }
-
-        output.push_str("---\n\n");
-        output.push_str("## References\n\n");
-
         // Separate URLs from other sources for better organization
         let mut urls = Vec::new();
         let mut other_sources = Vec::new();
@@ -1673,13 +2350,46 @@ impl ResearchOrchestrator {
             }
         }
 
+        // Optionally check which cited URLs actually resolve
+        let unreachable = if self.verify_sources && !urls.is_empty() {
+            find_unreachable_urls(&urls).await
+        } else {
+            std::collections::HashSet::new()
+        };
+
+        // Merge the same non-URL source cited with slightly different wording
+        if self.semantic_source_dedup && other_sources.len() > 1 {
+            other_sources = self.dedup_similar_sources(other_sources).await;
+        }
+
+        let source_count = urls.len() + other_sources.len();
+        info!("Found {} unique sources", source_count);
+        if let Ok(mut stats) = self.run_stats.lock() {
+            stats.source_count = source_count;
+        }
+
+        // Create sources section
+        let mut output = text.to_string();
+
+        // Ensure there's separation from main content
+        if !output.ends_with("\n\n") {
+            output.push_str("\n\n");
+        }
+
+        output.push_str("---\n\n");
+        output.push_str("## References\n\n");
+
         // List URLs first (primary sources for verification)
         if !urls.is_empty() {
             output.push_str("### Web Sources\n\n");
             output.push_str("The following websites and online resources were consulted:\n\n");
             for (i, url) in urls.iter().enumerate() {
                 // Format as clickable markdown links
-                output.push_str(&format!("{}. <{}>\n", i + 1, url));
+                if unreachable.contains(*url) {
+                    output.push_str(&format!("{}. <{}> (unreachable)\n", i + 1, url));
+                } else {
+                    output.push_str(&format!("{}. <{}>\n", i + 1, url));
+                }
             }
         }
 
@@ -1699,6 +2409,37 @@ impl ResearchOrchestrator {
         output
     }
 
+    /// Collapse sources whose embeddings are near-identical, keeping the first of each cluster.
+    /// Falls back to the original list if embeddings can't be generated.
+    async fn dedup_similar_sources<'a>(&self, sources: Vec<&'a str>) -> Vec<&'a str> {
+        let texts: Vec<String> = sources.iter().map(|s| s.to_string()).collect();
+        let embeddings = match crate::embeddings::Embedder::from_config(&self.ollama_config, &self.config_dir) {
+            Ok(embedder) => embedder.embed(&texts).await,
+            Err(e) => Err(e),
+        };
+        let embeddings = match embeddings {
+            Ok(embeddings) => embeddings,
+            Err(e) => {
+                info!("Skipping semantic source dedup: {}", e);
+                return sources;
+            }
+        };
+
+        let mut kept: Vec<usize> = Vec::new();
+        for (i, embedding) in embeddings.iter().enumerate() {
+            let duplicate = kept.iter().any(|&k| {
+                crate::embeddings::cosine_similarity(&embeddings[k], embedding) >= self.source_similarity_threshold
+            });
+            if duplicate {
+                info!("Merged near-duplicate source: {}", sources[i]);
+            } else {
+                kept.push(i);
+            }
+        }
+
+        kept.into_iter().map(|i| sources[i]).collect()
+    }
+
     /// Extract unique sources from text with various citation formats
     fn extract_sources(&self, text: &str) -> BTreeSet<String> {
         let mut sources = BTreeSet::new();
@@ -1709,7 +2450,7 @@ impl ResearchOrchestrator {
                 if let Some(source) = cap.get(1) {
                     let cleaned = source.as_str().trim().to_string();
                     if !cleaned.is_empty() {
-                        sources.insert(cleaned);
+                        sources.insert(normalize_source(&cleaned));
                     }
                 }
             }
@@ -1721,7 +2462,7 @@ impl ResearchOrchestrator {
                 if let Some(source) = cap.get(1) {
                     let cleaned = source.as_str().trim().to_string();
                     if !cleaned.is_empty() {
-                        sources.insert(cleaned);
+                        sources.insert(normalize_source(&cleaned));
                     }
                 }
             }
@@ -1734,7 +2475,7 @@ impl ResearchOrchestrator {
                 // Only include if not already captured in a [Source: ] tag
                 if !text.contains(&format!("[Source: {}]", url)) &&
                    !text.contains(&format!("(Source: {})", url)) {
-                    sources.insert(url.trim_end_matches(|c| c == '.' || c == ',' || c == ';').to_string());
+                    sources.insert(normalize_source(url.trim_end_matches(|c| c == '.' || c == ',' || c == ';')));
                 }
             }
         }
@@ -1744,27 +2485,31 @@ impl ResearchOrchestrator {
 
     /// Legacy method - kept for compatibility
     /// Refinement loop with multi-agent debate
+    #[tracing::instrument(name = "refine", skip_all)]
     async fn refinement_loop(&self, initial_output: &str) -> Result<String> {
         let mut current_output = initial_output.to_string();
-        let max_iterations = self.ollama_config.max_refinement_iterations;
+        let max_iterations = self.limits().refinement_iterations;
 
         for iteration in 0..max_iterations {
             // Multi-agent debate
             self.send_progress(ResearchProgress::CriticReviewing);
             self.send_progress(ResearchProgress::WorkerStatus {
                 worker: "Debate".to_string(),
-                status: "Launching debate between Advocate, Skeptic, and Synthesizer".to_string(),
+                status: format!("Launching debate between {}", self.config.agents.debate_agents.iter()
+                    .map(|a| a.name.as_str()).collect::<Vec<_>>().join(", ")),
             });
             let debate_result = self.conduct_debate(&current_output).await?;
 
             // Check if approved
-            if debate_result.trim().to_uppercase().contains("APPROVED") {
-                eprintln!("[Research] Output approved by debate after {} iteration(s)", iteration + 1);
+            let approved = parse_verdict(&debate_result)
+                .unwrap_or_else(|| debate_result.trim().to_uppercase().contains("APPROVED"));
+            if approved {
+                info!("Output approved by debate after {} iteration(s)", iteration + 1);
                 break;
             }
 
             // Refine based on debate conclusions
-            eprintln!("[Research] Iteration {}: Refining based on debate", iteration + 1);
+            info!("Iteration {}: Refining based on debate", iteration + 1);
             self.send_progress(ResearchProgress::Refining(iteration + 1, max_iterations));
             self.send_progress(ResearchProgress::WorkerStatus {
                 worker: "Refiner".to_string(),
@@ -1774,7 +2519,7 @@ impl ResearchOrchestrator {
 
             // If this was the last iteration, use the refined output anyway
             if iteration == max_iterations - 1 {
-                eprintln!("[Research] Max iterations reached. Using last refined output.");
+                info!("Max iterations reached. Using last refined output.");
             }
         }
 
@@ -1782,157 +2527,97 @@ impl ResearchOrchestrator {
     }
 
     /// Conduct multi-agent debate to evaluate research output
+    #[tracing::instrument(name = "debate", skip_all)]
     async fn conduct_debate(&self, output: &str) -> Result<String> {
-        eprintln!("[Research] Starting multi-agent debate...");
+        info!("Starting multi-agent debate...");
         self.send_progress(ResearchProgress::WorkerStatus {
             worker: "Debate".to_string(),
             status: "Starting debate session".to_string(),
         });
 
-        // Get advocate, skeptic, and synthesizer from debate_agents
-        let advocate = self.config.agents.debate_agents.iter()
-            .find(|a| a.role == "advocate")
-            .ok_or_else(|| anyhow::anyhow!("Advocate agent not found"))?;
-
-        let skeptic = self.config.agents.debate_agents.iter()
-            .find(|a| a.role == "skeptic")
-            .ok_or_else(|| anyhow::anyhow!("Skeptic agent not found"))?;
-
-        let synthesizer = self.config.agents.debate_agents.iter()
-            .find(|a| a.role == "synthesizer")
-            .ok_or_else(|| anyhow::anyhow!("Synthesizer agent not found"))?;
+        // The synthesizer/judge decides; every other debate agent takes a turn each round
+        let (synthesizer, debaters) = split_debate_agents(&self.config.agents.debate_agents)?;
 
         let base_url = std::env::var("OLLAMA_HOST")
             .unwrap_or_else(|_| "http://localhost:11434".to_string());
 
-        let max_rounds = self.ollama_config.max_debate_rounds;
-        let mut debate_history = String::new();
-        let mut last_advocate_arg;
-        let mut last_skeptic_arg = String::new();
+        let max_rounds = self.limits().debate_rounds;
+        let mut debate_rounds: Vec<String> = Vec::new();  // One transcript entry per round
 
         // Conduct multiple rounds of debate
         for round in 1..=max_rounds {
             self.send_progress(ResearchProgress::DebateRound(round, max_rounds));
-            eprintln!("[Research] Debate round {}/{}", round, max_rounds);
-            self.send_progress(ResearchProgress::WorkerStatus {
-                worker: "Debate".to_string(),
-                status: format!("Advocate presenting arguments (round {}/{})", round, max_rounds),
-            });
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
-
-            // Advocate's turn
-            let advocate_prompt = if round == 1 {
-                // First round: defend the research
-                format!(
-                    "{}\n\nResearch Output to Defend:\n{}",
-                    advocate.system_prompt,
-                    output
-                )
-            } else {
-                // Subsequent rounds: respond to skeptic's critique
-                format!(
-                    "{}\n\nResearch Output:\n{}\n\nDebate History:\n{}\n\nSkeptic's Last Critique:\n{}\n\nProvide your rebuttal:",
-                    advocate.system_prompt,
-                    output,
-                    debate_history,
-                    last_skeptic_arg
-                )
-            };
+            if let Ok(mut stats) = self.run_stats.lock() {
+                stats.debate_rounds += 1;
+            }
+            info!("Debate round {}/{}", round, max_rounds);
 
-            let mut advocate_client = OllamaClient::with_config(base_url.clone(), self.research_model.clone());
-            advocate_client.set_max_tool_turns(self.max_tool_turns);
-            if let Some(executor) = &self.tool_executor {
-                advocate_client.set_tool_executor(executor.clone());
-            }
-            advocate_client.set_available_tools(advocate.available_tools.clone());
-
-            last_advocate_arg = advocate_client.query_streaming(&advocate_prompt, |_| {}).await?;
-            eprintln!("[Research] Advocate round {}: presented argument", round);
-            // Log a shortened advocate argument for UI verbosity
-            crate::progress::log_with(
-                crate::progress::Kind::Debate,
-                format!(
-                    "Advocate (round {}/{}): {}",
-                    round,
-                    max_rounds,
-                    Self::summarize_arg(&last_advocate_arg, 140)
-                ),
-            );
+            for (turn, debater) in debaters.iter().enumerate() {
+                self.send_progress(ResearchProgress::WorkerStatus {
+                    worker: "Debate".to_string(),
+                    status: format!("{} arguing (round {}/{})", debater.name, round, max_rounds),
+                });
 
-            // Add to history
-            debate_history.push_str(&format!("\n--- Round {} ---\n", round));
-            debate_history.push_str(&format!("**Advocate:**\n{}\n\n", last_advocate_arg));
+                tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
+                // Each debater sees the output plus every turn so far, this round included
+                let prompt = debate_turn_prompt(debater, output, &debate_rounds.concat());
 
-            // Skeptic's turn
-            self.send_progress(ResearchProgress::WorkerStatus {
-                worker: "Debate".to_string(),
-                status: format!("Skeptic challenging (round {}/{})", round, max_rounds),
-            });
-            let skeptic_prompt = if round == 1 {
-                // First round: critique the research and advocate's defense
-                format!(
-                    "{}\n\nResearch Output:\n{}\n\nAdvocate's Defense:\n{}\n\nPresent your critique:",
-                    skeptic.system_prompt,
-                    output,
-                    last_advocate_arg
-                )
-            } else {
-                // Subsequent rounds: respond to advocate's rebuttal
-                format!(
-                    "{}\n\nResearch Output:\n{}\n\nDebate History:\n{}\n\nAdvocate's Last Rebuttal:\n{}\n\nProvide your response:",
-                    skeptic.system_prompt,
-                    output,
-                    debate_history,
-                    last_advocate_arg
-                )
-            };
-
-            let mut skeptic_client = OllamaClient::with_config(base_url.clone(), self.research_model.clone());
-            skeptic_client.set_max_tool_turns(self.max_tool_turns);
-            if let Some(executor) = &self.tool_executor {
-                skeptic_client.set_tool_executor(executor.clone());
-            }
-            skeptic_client.set_available_tools(skeptic.available_tools.clone());
-
-            last_skeptic_arg = skeptic_client.query_streaming(&skeptic_prompt, |_| {}).await?;
-            eprintln!("[Research] Skeptic round {}: presented critique", round);
-            // Log a shortened skeptic rebuttal
-            crate::progress::log_with(
-                crate::progress::Kind::Debate,
-                format!(
-                    "Skeptic (round {}/{}): {}",
-                    round,
-                    max_rounds,
-                    Self::summarize_arg(&last_skeptic_arg, 140)
-                ),
-            );
+                let mut debater_client = OllamaClient::with_config(base_url.clone(), self.research_model.clone());
+                debater_client.set_max_tool_turns(self.max_tool_turns);
+                configure_research_client(&mut debater_client, &self.ollama_config);
+                if let Some(executor) = &self.tool_executor {
+                    debater_client.set_tool_executor(executor.clone());
+                }
+                debater_client.set_available_tools(debater.available_tools.clone());
+                debater_client.set_denied_tools(debater.denied_tools.clone());
+
+                let argument = debater_client.query_streaming(&prompt, |_| {}).await?;
+                info!("{} round {}: presented argument", debater.name, round);
+                // Log a shortened argument for UI verbosity
+                crate::progress::log_with(
+                    crate::progress::Kind::Debate,
+                    format!(
+                        "{} (round {}/{}): {}",
+                        debater.name,
+                        round,
+                        max_rounds,
+                        Self::summarize_arg(&argument, 140)
+                    ),
+                );
 
-            // Add to history
-            debate_history.push_str(&format!("**Skeptic:**\n{}\n\n", last_skeptic_arg));
+                // Add to history
+                let entry = format!("**{}:**\n{}\n\n", debater.name, argument);
+                match debate_rounds.last_mut() {
+                    Some(current) if turn > 0 => current.push_str(&entry),
+                    _ => debate_rounds.push(format!("\n--- Round {} ---\n{}", round, entry)),
+                }
+            }
         }
 
         tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
 
-        // Synthesizer makes final decision after all rounds
+        // Synthesizer makes final decision after all rounds, from a transcript that fits its context
+        let synthesizer_transcript = self.condense_debate_transcript(&debate_rounds).await;
         let synthesizer_prompt = format!(
-            "{}\n\nResearch Output:\n{}\n\nComplete Debate Transcript:\n{}\n\nProvide your balanced assessment:",
+            "{}\n\nResearch Output:\n{}\n\nComplete Debate Transcript:\n{}\n\n{}\n\nProvide your balanced assessment:",
             synthesizer.system_prompt,
             output,
-            debate_history
+            synthesizer_transcript,
+            VERDICT_INSTRUCTIONS
         );
 
         let mut synthesizer_client = OllamaClient::with_config(base_url, self.research_model.clone());
         synthesizer_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut synthesizer_client, &self.ollama_config);
         if let Some(executor) = &self.tool_executor {
             synthesizer_client.set_tool_executor(executor.clone());
         }
         synthesizer_client.set_available_tools(synthesizer.available_tools.clone());
+        synthesizer_client.set_denied_tools(synthesizer.denied_tools.clone());
 
         let final_decision = synthesizer_client.query_streaming(&synthesizer_prompt, |_| {}).await?;
-        eprintln!("[Research] Synthesizer reached decision after {} debate rounds", max_rounds);
+        info!("{} reached decision after {} debate rounds", synthesizer.name, max_rounds);
         // Log a shortened synthesizer decision
         crate::progress::log_with(
             crate::progress::Kind::Debate,
@@ -1945,14 +2630,48 @@ impl ResearchOrchestrator {
             worker: "Debate".to_string(),
             status: "Synthesizer compiling final decision".to_string(),
         });
-        
+        self.update_session(|session| {
+            session.debate_transcript.push(format!("{}\n**{}:**\n{}", debate_rounds.concat(), synthesizer.name, final_decision));
+        });
+
         Ok(final_decision)
     }
 
+    /// Debate transcript for the synthesizer. Once it outgrows the research summarization
+    /// threshold, rounds before the latest are condensed and the latest round is kept in full.
+    async fn condense_debate_transcript(&self, rounds: &[String]) -> String {
+        let max_chars = self.ollama_config.summarization_threshold_research;
+        let Some((earlier, latest)) = debate_rounds_to_condense(rounds, max_chars) else {
+            return rounds.concat();
+        };
+
+        info!("Debate transcript exceeds threshold ({} chars), condensing {} earlier rounds...",
+                  earlier.len() + latest.len(), rounds.len() - 1);
+        self.send_progress(ResearchProgress::WorkerStatus {
+            worker: "Debate".to_string(),
+            status: format!("Condensing {} earlier debate rounds", rounds.len() - 1),
+        });
+
+        let prompt = format!(
+            "Condense these earlier rounds of a research quality debate:\n\n\
+            - Keep every distinct argument, critique and rebuttal, and who made it\n\
+            - Keep which points were conceded and which remain disputed\n\
+            - Keep cited facts and sources [Source: name]\n\
+            - Drop repetition and restated arguments\n\n\
+            Debate rounds:\n{}",
+            earlier
+        );
+
+        let condensed = self.summarize_with_model(&prompt, &earlier, max_chars.saturating_sub(latest.len())).await;
+
+        format!("\n--- Earlier rounds (condensed) ---\n{}\n{}", condensed.trim(), latest)
+    }
+
     /// Document writing loop with document critic
+    #[tracing::instrument(name = "write", skip_all)]
     async fn document_writing_loop(&self, original_query: &str, research_content: &str) -> Result<String> {
         let mut current_document = String::new();
-        let max_iterations = self.ollama_config.max_document_iterations;
+        let max_iterations = self.limits().document_iterations;
 
         for iteration in 0..max_iterations {
             // Write or rewrite the document
@@ -1970,7 +2689,11 @@ impl ResearchOrchestrator {
                 self.write_document(original_query, research_content, Some(&current_document)).await?
             };
 
-            current_document = document;
+            current_document = self.repair_document(document);
+            let issues = crate::markdown_check::check(&current_document);
+            for issue in &issues {
+                debug!("Document check: {}", issue);
+            }
 
             // Get document critic feedback
             self.send_progress(ResearchProgress::DocumentReviewing);
@@ -1978,25 +2701,33 @@ impl ResearchOrchestrator {
                 worker: "DocumentCritic".to_string(),
                 status: "Reviewing draft for clarity, correctness, and structure".to_string(),
             });
-            let criticism = self.review_document(original_query, &current_document).await?;
+            let criticism = self.review_document(original_query, &current_document, &issues).await?;
 
             // Check if approved
-            if criticism.trim().to_uppercase() == "APPROVED" {
-                eprintln!("[Research] Document approved after {} iteration(s)", iteration + 1);
+            let approved = parse_verdict(&criticism)
+                .unwrap_or_else(|| criticism.trim().to_uppercase() == "APPROVED");
+            if approved {
+                info!("Document approved after {} iteration(s)", iteration + 1);
                 break;
             }
 
             // If not approved and not last iteration, we'll rewrite in next iteration
-            eprintln!("[Research] Document iteration {}: Feedback received, will revise", iteration + 1);
+            info!("Document iteration {}: Feedback received, will revise", iteration + 1);
 
             // On last iteration, use what we have
             if iteration == max_iterations - 1 {
-                eprintln!("[Research] Max document iterations reached. Using current version.");
+                info!("Max document iterations reached. Using current version.");
             }
         }
 
+        let remaining = crate::markdown_check::check(&current_document);
+        if !remaining.is_empty() {
+            warn!("Final document still has {} markdown problem(s):\n{}", remaining.len(),
+                remaining.iter().map(|issue| format!("  {}", issue)).collect::<Vec<_>>().join("\n"));
+        }
+
         // Add sources section to the document
-        let final_document = self.add_sources_section(&current_document);
+        let final_document = self.add_sources_section(&current_document).await;
         self.send_progress(ResearchProgress::WorkerStatus {
             worker: "Writer".to_string(),
             status: "Finalizing document and references".to_string(),
@@ -2037,22 +2768,42 @@ impl ResearchOrchestrator {
 
         let mut writer_client = OllamaClient::with_config(base_url, self.research_model.clone());
         writer_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut writer_client, &self.ollama_config);
         let document = writer_client.query_streaming(&prompt, |_| {}).await?;
 
         Ok(document)
     }
 
-    /// Review document with document critic
-    async fn review_document(&self, original_query: &str, document: &str) -> Result<String> {
+    /// Fix table rows with the wrong number of cells, if enabled
+    fn repair_document(&self, document: String) -> String {
+        if !self.repair_document_tables {
+            return document;
+        }
+        let (repaired, fixed_rows) = crate::markdown_check::repair_table_columns(&document);
+        if fixed_rows > 0 {
+            debug!("Repaired the column count of {} table row(s)", fixed_rows);
+        }
+        repaired
+    }
+
+    /// Review document with document critic, passing along any deterministic markdown issues
+    async fn review_document(&self, original_query: &str, document: &str, issues: &[crate::markdown_check::Issue]) -> Result<String> {
         // Add delay to avoid rate limiting
         tokio::time::sleep(tokio::time::Duration::from_millis(self.ollama_config.api_delay_ms)).await;
 
+        let checks = if issues.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", crate::markdown_check::critic_feedback(issues))
+        };
         let prompt = format!(
             "{}\n\nOriginal Query: {}\n\n\
-            Document to Review:\n{}",
+            Document to Review:\n{}\n\n{}{}",
             self.config.agents.document_critic.system_prompt,
             original_query,
-            document
+            document,
+            checks,
+            VERDICT_INSTRUCTIONS
         );
 
         let base_url = std::env::var("OLLAMA_HOST")
@@ -2060,6 +2811,7 @@ impl ResearchOrchestrator {
 
         let mut critic_client = OllamaClient::with_config(base_url, self.research_model.clone());
         critic_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut critic_client, &self.ollama_config);
         let review = critic_client.query_streaming(&prompt, |_| {}).await?;
 
         Ok(review)
@@ -2082,12 +2834,14 @@ impl ResearchOrchestrator {
 
         let mut refiner_client = OllamaClient::with_config(base_url, self.research_model.clone());
         refiner_client.set_max_tool_turns(self.max_tool_turns);
+        configure_research_client(&mut refiner_client, &self.ollama_config);
 
         // Refiner can use tools
         if let Some(executor) = &self.tool_executor {
             refiner_client.set_tool_executor(executor.clone());
         }
         refiner_client.set_available_tools(self.config.agents.refiner.available_tools.clone());
+        refiner_client.set_denied_tools(self.config.agents.refiner.denied_tools.clone());
 
         let refined = refiner_client.query_streaming(&prompt, |_| {}).await?;
         Ok(refined)
@@ -2116,3 +2870,621 @@ impl ResearchOrchestrator {
         Err(anyhow::anyhow!("No JSON array found in response"))
     }
 }
+
+/// Apply the configured sampling options and retry policy to a freshly created research agent client
+fn configure_research_client(client: &mut OllamaClient, config: &crate::config::OllamaConfig) {
+    client.set_options(config.research_model_options());
+    client.set_context_window(config.context_window);
+    client.set_retry_policy(config.max_attempts, config.retry_backoff, config.retry_base_delay_secs);
+    client.set_api(config.api_flavor, config.api_key.clone());
+}
+
+/// Query parameters that only carry tracking/attribution data and never change the page
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "ref", "ref_src", "igshid", "_ga"];
+
+/// `a|b|c` list of worker names for the `"worker"` field of a JSON prompt template
+fn worker_name_choices(workers: &[AgentRole]) -> String {
+    workers.iter().map(|w| w.name.as_str()).collect::<Vec<_>>().join("|")
+}
+
+/// One `- name: description` line per configured worker
+fn describe_workers(workers: &[AgentRole]) -> String {
+    workers
+        .iter()
+        .map(|w| format!("- {}: {}", w.name, w.description))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Used by `summarize_worker_result` when agents.json has no `summarizer`
+const DEFAULT_SUMMARIZER_PROMPT: &str = "Condense these research findings while preserving all key information:\n\n\
+    - Keep ALL facts, data points, and citations [Source: name]\n\
+    - Preserve technical details and specifications\n\
+    - Maintain examples and context\n\
+    - Remove only redundant explanations and filler words\n\
+    - Keep the depth and completeness of information";
+
+/// Appended to critic/synthesizer prompts so approval is read from one structured line
+const VERDICT_INSTRUCTIONS: &str = "End your response with a final line that is exactly `VERDICT: APPROVED` \
+if no further changes are needed, or `VERDICT: REVISE` if improvements are required.";
+
+/// Read the last `VERDICT:` line of a critic response. Anything other than a bare
+/// APPROVED counts as a revise; `None` means no verdict line was given.
+fn parse_verdict(response: &str) -> Option<bool> {
+    let line = response
+        .lines()
+        .rev()
+        .map(|l| l.trim().trim_matches(|c: char| matches!(c, '*' | '_' | '`' | '#' | '>' | '"')).trim())
+        .find(|l| l.get(..7).is_some_and(|p| p.eq_ignore_ascii_case("VERDICT")))?;
+    let value = line["VERDICT".len()..].trim_start_matches(|c: char| c == ':' || c == '*' || c.is_whitespace());
+    let value = value
+        .trim_end_matches(|c: char| !c.is_alphanumeric())
+        .to_uppercase();
+    Some(value == "APPROVED")
+}
+
+/// Cosine similarity below which discoveries are left out of a worker's pre-task context
+const CONTEXT_MIN_SCORE: f32 = 0.5;
+
+const SOURCE_CHECK_TIMEOUT_SECS: u64 = 8;
+const SOURCE_CHECK_CONCURRENCY: usize = 8;
+
+/// Statuses that mean the cited page doesn't exist. Auth walls, rate limits and
+/// transient server errors still count as reachable.
+fn is_dead_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 404 | 410)
+}
+
+/// HEAD each URL (falling back to GET when HEAD isn't allowed) and return the ones that
+/// fail to connect or report a missing page.
+async fn find_unreachable_urls(urls: &[&str]) -> std::collections::HashSet<String> {
+    let client = match reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(SOURCE_CHECK_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            info!("Skipping source verification: {}", e);
+            return std::collections::HashSet::new();
+        }
+    };
+
+    let semaphore = Arc::new(Semaphore::new(SOURCE_CHECK_CONCURRENCY));
+    let mut checks = tokio::task::JoinSet::new();
+    for url in urls {
+        let url = url.to_string();
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        checks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let status = match client.head(&url).send().await {
+                Ok(resp) if matches!(resp.status().as_u16(), 405 | 501) => {
+                    client.get(&url).send().await.map(|r| r.status())
+                }
+                other => other.map(|r| r.status()),
+            };
+            let alive = matches!(status, Ok(s) if !is_dead_status(s));
+            (!alive).then_some(url)
+        });
+    }
+
+    let mut dead = std::collections::HashSet::new();
+    while let Some(result) = checks.join_next().await {
+        if let Ok(Some(url)) = result {
+            dead.insert(url);
+        }
+    }
+
+    info!("Verified {} cited URLs, {} unreachable", urls.len(), dead.len());
+    dead
+}
+
+/// Normalize a cited source so trivially different spellings of the same URL dedup together
+fn normalize_source(source: &str) -> String {
+    let source = source.trim();
+    if source.starts_with("http://") || source.starts_with("https://") {
+        canonicalize_url(source)
+    } else {
+        source.to_string()
+    }
+}
+
+/// Rewrite every http(s) URL in `text` to its [canonicalize_url] form, leaving any
+/// sentence punctuation that followed it in place
+fn canonicalize_urls_in(text: &str) -> String {
+    static URL: once_cell::sync::Lazy<regex::Regex> =
+        once_cell::sync::Lazy::new(|| regex::Regex::new(r"https?://[^\s\)\]>]+").unwrap());
+    URL.replace_all(text, |caps: &regex::Captures| {
+        let found = &caps[0];
+        let url = found.trim_end_matches(['.', ',', ';']);
+        format!("{}{}", canonicalize_url(url), &found[url.len()..])
+    })
+    .into_owned()
+}
+
+/// Canonicalize a URL: strip `www.`, fragments, tracking query params and trailing slashes
+fn canonicalize_url(raw: &str) -> String {
+    let mut url = match reqwest::Url::parse(raw) {
+        Ok(url) => url,
+        Err(_) => return raw.trim_end_matches('/').to_string(),
+    };
+
+    url.set_fragment(None);
+
+    if let Some(host) = url.host_str().and_then(|h| h.strip_prefix("www.")).map(|h| h.to_string()) {
+        let _ = url.set_host(Some(&host));
+    }
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+        })
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&path);
+
+    let canonical = url.to_string();
+    if url.query().is_none() {
+        canonical.trim_end_matches('/').to_string()
+    } else {
+        canonical
+    }
+}
+
+/// Collapsible appendix with every debate of the run, one section per refinement iteration.
+/// `<details>` folds it away in exported markdown; the in-app renderer skips the HTML tags.
+fn format_debate_appendix(transcripts: &[String]) -> String {
+    let mut appendix = String::from("\n\n---\n\n<details>\n<summary>Debate Transcript</summary>\n\n## Debate Transcript\n\n");
+    for (i, transcript) in transcripts.iter().enumerate() {
+        appendix.push_str(&format!("### Iteration {}\n\n{}\n\n", i + 1, transcript.trim()));
+    }
+    appendix.push_str("</details>\n");
+    appendix
+}
+
+/// Split an oversized debate transcript into the earlier rounds to condense and the latest
+/// round to keep verbatim. `None` when it fits in `max_chars` or there's only one round.
+fn debate_rounds_to_condense(rounds: &[String], max_chars: usize) -> Option<(String, &str)> {
+    let (latest, earlier) = rounds.split_last()?;
+    if earlier.is_empty() || rounds.iter().map(String::len).sum::<usize>() <= max_chars {
+        return None;
+    }
+    Some((earlier.concat(), latest.as_str()))
+}
+
+/// Number of completed initial workers at which gap detection starts: `fraction` of
+/// `total`, rounded up, so the default 0.5 gives the old `(total + 1) / 2`
+fn gap_detection_midpoint(total: usize, fraction: f32) -> usize {
+    ((total as f32 * fraction.clamp(0.0, 1.0)).ceil() as usize).min(total)
+}
+
+/// Response for a plan-only run: the sub-questions with their assigned workers, then the strategy
+fn format_plan_only(query: &str, sub_questions: &[SubQuestion], plan: &str) -> String {
+    let mut doc = format!("# Research plan\n\n*Plan only, no workers were run for:* {}\n\n## Sub-questions\n\n", query.trim());
+    for (i, sq) in sub_questions.iter().enumerate() {
+        doc.push_str(&format!("{}. **{}** {}\n", i + 1, sq.assigned_worker, sq.question));
+    }
+    if !plan.trim().is_empty() {
+        doc.push_str(&format!("\n## Strategy\n\n{}\n", plan.trim()));
+    }
+    doc
+}
+
+/// Tool calls grouped by agent (alphabetically), each agent's calls in the order they were made
+fn render_tool_audit(query_id: &str, calls: &[crate::shared_memory::ToolCall], format: crate::history::ExportFormat) -> Result<String> {
+    let mut by_agent: std::collections::BTreeMap<&str, Vec<&crate::shared_memory::ToolCall>> = std::collections::BTreeMap::new();
+    for call in calls {
+        by_agent.entry(call.agent_name.as_str()).or_default().push(call);
+    }
+    let unix_secs = |call: &crate::shared_memory::ToolCall| {
+        call.created_at.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+    };
+    // Parameters are stored as JSON text; keep anything unparseable verbatim
+    let parameters = |call: &crate::shared_memory::ToolCall| {
+        serde_json::from_str::<serde_json::Value>(&call.parameters)
+            .unwrap_or_else(|_| serde_json::Value::String(call.parameters.clone()))
+    };
+
+    match format {
+        crate::history::ExportFormat::Json => {
+            let agents: Vec<serde_json::Value> = by_agent.iter()
+                .map(|(agent, calls)| serde_json::json!({
+                    "agent": agent,
+                    "calls": calls.iter().map(|call| serde_json::json!({
+                        "timestamp": crate::history::utc_timestamp(unix_secs(call)),
+                        "tool_type": call.tool_type,
+                        "tool_name": call.tool_name,
+                        "parameters": parameters(call),
+                    })).collect::<Vec<_>>(),
+                }))
+                .collect();
+            Ok(serde_json::to_string_pretty(&serde_json::json!({
+                "query_id": query_id,
+                "total_calls": calls.len(),
+                "agents": agents,
+            }))?)
+        }
+        crate::history::ExportFormat::Markdown => {
+            let mut doc = format!("# Tool call audit\n\n*Query `{}` · {} call(s)*\n", query_id, calls.len());
+            if calls.is_empty() {
+                doc.push_str("\nNo tools were used during this research.\n");
+            }
+            for (agent, calls) in &by_agent {
+                doc.push_str(&format!("\n## {} ({})\n", agent, calls.len()));
+                for call in calls {
+                    let params = serde_json::to_string_pretty(&parameters(call)).unwrap_or_default();
+                    doc.push_str(&format!(
+                        "\n- {} · `[{}] {}`\n\n  ```json\n  {}\n  ```\n",
+                        crate::history::utc_timestamp(unix_secs(call)), call.tool_type, call.tool_name,
+                        params.replace('\n', "\n  ")
+                    ));
+                }
+            }
+            Ok(doc)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_abort_tasks_on_drop() {
+        let registry: TaskRegistry = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handle = tokio::spawn(tokio::time::sleep(std::time::Duration::from_secs(60)));
+        track_task(&registry, &handle);
+        drop(AbortTasksOnDrop(registry.clone()));
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert!(registry.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_progress_estimate_is_monotonic() {
+        let mut estimate = ProgressEstimate::default();
+        assert_eq!(estimate.observe(&ResearchProgress::Started), Some(0.0));
+        assert_eq!(estimate.observe(&ResearchProgress::PlanApproved), Some(0.15));
+        assert_eq!(estimate.observe(&ResearchProgress::WorkersStarted(3)), None);
+        let after_one = estimate.observe(&ResearchProgress::WorkerCompleted("w1".to_string())).unwrap();
+        assert!((after_one - 0.30).abs() < 1e-6);
+        // Going back a phase never lowers the estimate
+        assert_eq!(estimate.observe(&ResearchProgress::PlanningIteration(1, 3)), None);
+        assert_eq!(estimate.observe(&ResearchProgress::WorkerStatus { worker: "w1".to_string(), status: "x".to_string() }), None);
+        assert_eq!(estimate.observe(&ResearchProgress::Completed), Some(1.0));
+    }
+
+    #[test]
+    fn test_session_round_trip_and_resume_state() {
+        let mut session = ResearchSession {
+            query: "rust async runtimes".to_string(),
+            query_id: "query_1_1".to_string(),
+            plan: "Compare tokio and async-std".to_string(),
+            sub_questions: vec![SubQuestion { question: "What is tokio?".to_string(), assigned_worker: "web_researcher".to_string() }],
+            ..Default::default()
+        };
+        assert!(!session.can_resume());
+
+        session.worker_results.push(WorkerResult {
+            question: "What is tokio?".to_string(),
+            answer: "An async runtime".to_string(),
+            worker_name: "web_researcher".to_string(),
+        });
+        assert!(session.can_resume());
+
+        let path = std::env::temp_dir().join(format!("bob-bar-session-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string(&session).unwrap()).unwrap();
+        let loaded = ResearchOrchestrator::load_session(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.worker_results.len(), 1);
+        assert!(loaded.to_markdown().contains("### web_researcher: What is tokio?"));
+
+        session.final_document = Some("Done".to_string());
+        assert!(session.is_complete() && !session.can_resume());
+    }
+
+    #[test]
+    fn test_worker_prompt_lists() {
+        let role = |name: &str, description: &str| AgentRole {
+            name: name.to_string(),
+            role: "worker".to_string(),
+            description: description.to_string(),
+            system_prompt: String::new(),
+            available_tools: Vec::new(),
+            denied_tools: Vec::new(),
+        };
+        let workers = vec![role("historian", "Archival research"), role("economist", "Market data")];
+        assert_eq!(worker_name_choices(&workers), "historian|economist");
+        assert_eq!(describe_workers(&workers), "- historian: Archival research\n- economist: Market data");
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        assert_eq!(parse_verdict("Looks good.\n\nVERDICT: APPROVED"), Some(true));
+        assert_eq!(parse_verdict("Fine overall\n**VERDICT: APPROVED**\n"), Some(true));
+        assert_eq!(parse_verdict("verdict: approved."), Some(true));
+        assert_eq!(parse_verdict("I would not say this is APPROVED.\nVERDICT: REVISE"), Some(false));
+        assert_eq!(parse_verdict("VERDICT: NOT APPROVED"), Some(false));
+        assert_eq!(parse_verdict("VERDICT: APPROVED\nOn reflection, no.\nVERDICT: REVISE"), Some(false));
+        assert_eq!(parse_verdict("APPROVED\n\nThe research meets quality standards."), None);
+    }
+
+    #[test]
+    fn test_is_dead_status() {
+        assert!(is_dead_status(reqwest::StatusCode::NOT_FOUND));
+        assert!(is_dead_status(reqwest::StatusCode::GONE));
+        assert!(!is_dead_status(reqwest::StatusCode::OK));
+        assert!(!is_dead_status(reqwest::StatusCode::FORBIDDEN));
+        assert!(!is_dead_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[tokio::test]
+    async fn test_worker_timeout_reports_status() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result = ResearchOrchestrator::with_worker_timeout("W1", 1, Some(tx), async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok("late".to_string())
+        })
+        .await;
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+        assert!(matches!(rx.try_recv(), Ok(ResearchProgress::WorkerStatus { worker, .. }) if worker == "W1"));
+
+        let ok = ResearchOrchestrator::with_worker_timeout("W2", 0, None, async { Ok("done".to_string()) }).await;
+        assert_eq!(ok.unwrap(), "done");
+    }
+
+    #[test]
+    fn test_format_run_details() {
+        let stats = RunStats {
+            initial_workers: 4,
+            follow_up_workers: 2,
+            gap_workers: 1,
+            debate_rounds: 3,
+            source_count: 12,
+        };
+        let header = ResearchOrchestrator::format_run_details(
+            "what is\nrust?",
+            "llama3",
+            &stats,
+            std::time::Duration::from_secs(125),
+        );
+
+        assert!(header.starts_with("## Run Details\n"));
+        assert!(header.contains("- **Query:** what is rust?\n"));
+        assert!(header.contains("- **Model:** llama3\n"));
+        assert!(header.contains("- **Workers:** 7 (initial: 4, follow-up: 2, gap-filling: 1)\n"));
+        assert!(header.contains("- **Debate rounds:** 3\n"));
+        assert!(header.contains("- **Duration:** 2m 5s\n"));
+        assert!(header.contains("- **Sources:** 12\n"));
+    }
+
+    #[test]
+    fn test_canonicalize_url() {
+        assert_eq!(canonicalize_url("https://www.example.com/page/"), "https://example.com/page");
+        assert_eq!(canonicalize_url("https://example.com/page#section"), "https://example.com/page");
+        assert_eq!(canonicalize_url("https://example.com/"), "https://example.com");
+        assert_eq!(
+            canonicalize_url("https://example.com/a?utm_source=x&id=7&fbclid=abc"),
+            "https://example.com/a?id=7"
+        );
+        assert_eq!(canonicalize_url("https://Example.COM/Path"), "https://example.com/Path");
+    }
+
+    #[test]
+    fn test_canonicalize_url_equivalent_forms() {
+        let forms = [
+            "https://example.com/guide",
+            "https://example.com/guide/",
+            "https://www.example.com/guide",
+            "https://example.com/guide#install",
+            "https://example.com/guide/?utm_source=newsletter&utm_medium=email",
+            "https://EXAMPLE.com/guide?fbclid=123#top",
+        ];
+        for form in forms {
+            assert_eq!(canonicalize_url(form), "https://example.com/guide", "{}", form);
+        }
+        // Meaningful query parameters and path case still distinguish pages
+        assert_ne!(canonicalize_url("https://example.com/guide?page=2"), canonicalize_url("https://example.com/guide"));
+        assert_ne!(canonicalize_url("https://example.com/Guide"), canonicalize_url("https://example.com/guide"));
+    }
+
+    #[test]
+    fn test_canonicalize_urls_in_text() {
+        let text = "See https://www.example.com/a/?utm_source=x. Also [Source: https://example.com/a#intro], \
+                    (Source: https://example.com/b/) and <https://example.com/c/>";
+        assert_eq!(
+            canonicalize_urls_in(text),
+            "See https://example.com/a. Also [Source: https://example.com/a], \
+                    (Source: https://example.com/b) and <https://example.com/c>"
+        );
+        assert_eq!(canonicalize_urls_in("no links here"), "no links here");
+    }
+
+    #[test]
+    fn test_normalize_source_leaves_names_alone() {
+        assert_eq!(normalize_source("  Rust Book, 2024 "), "Rust Book, 2024");
+        assert_eq!(normalize_source("http://www.example.com/"), "http://example.com");
+    }
+
+    fn example_agents() -> AgentsConfig {
+        serde_json::from_str(include_str!("../agents.example.json")).unwrap()
+    }
+
+    #[test]
+    fn test_example_configs_validate() {
+        let tools: ToolsConfig = serde_json::from_str(include_str!("../tools.example.json")).unwrap();
+        assert_eq!(tools.problems(), Vec::<String>::new());
+        let agents = example_agents();
+        assert_eq!(agents.problems(), Vec::<String>::new());
+        assert_eq!(agents.warnings(), Vec::<String>::new());
+        assert_eq!(agents.unknown_tools(&tools), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_agents_problems_report_cross_references() {
+        let mut agents = example_agents();
+        agents.agents.workers.retain(|w| w.role != "technical_analyst");
+        let duplicate = agents.agents.workers[0].clone();
+        agents.agents.workers.push(duplicate);
+        agents.agents.debate_agents.retain(|a| a.role != "synthesizer");
+        agents.config.min_worker_count = 20;
+
+        let problems = agents.problems();
+        assert!(problems.iter().any(|p| p.contains("assigns questions to worker \"technical_analyst\"")), "{:?}", problems);
+        assert!(problems.iter().any(|p| p.contains("more than one worker has role")));
+        assert!(problems.iter().any(|p| p.contains("needs an agent whose role contains \"synthes\" or \"judge\"")));
+        assert!(problems.iter().any(|p| p.contains("min_worker_count (20)")));
+
+        let no_tools = ToolsConfig { tools: serde_json::from_value(serde_json::json!({"http": [], "mcp": []})).unwrap() };
+        let unknown = agents.unknown_tools(&no_tools);
+        assert!(unknown.iter().any(|p| p.contains("lists tool \"web_search\"")));
+    }
+
+    #[test]
+    fn test_second_debate_judge_is_only_a_warning() {
+        let mut agents = example_agents();
+        let mut judge = agents.agents.debate_agents.iter().find(|a| is_debate_judge(&a.role)).unwrap().clone();
+        judge.name = "Second Judge".to_string();
+        agents.agents.debate_agents.push(judge);
+
+        assert_eq!(agents.problems(), Vec::<String>::new());
+        assert!(agents.warnings().iter().any(|w| w.contains("more than one synthesizer/judge")));
+    }
+
+    #[test]
+    fn test_depth_limits() {
+        let configured = DepthLimits { min_workers: 3, max_workers: 10, refinement_iterations: 3, debate_rounds: 2, document_iterations: 3 };
+        assert_eq!(ResearchDepth::Deep.limits(configured), configured);
+        assert_eq!(
+            ResearchDepth::Quick.limits(configured),
+            DepthLimits { min_workers: 3, max_workers: 3, refinement_iterations: 0, debate_rounds: 0, document_iterations: 1 }
+        );
+        assert_eq!(
+            ResearchDepth::Standard.limits(configured),
+            DepthLimits { min_workers: 3, max_workers: 6, refinement_iterations: 1, debate_rounds: 1, document_iterations: 2 }
+        );
+        // Presets never raise a limit the user configured lower
+        let small = DepthLimits { min_workers: 1, max_workers: 2, refinement_iterations: 0, debate_rounds: 1, document_iterations: 1 };
+        assert_eq!(ResearchDepth::Standard.limits(small), small);
+        assert_eq!(ResearchDepth::Deep.next(), ResearchDepth::Quick);
+        assert_eq!(serde_json::from_str::<ResearchDepth>("\"standard\"").unwrap(), ResearchDepth::Standard);
+    }
+
+    #[test]
+    fn test_format_debate_appendix() {
+        let appendix = format_debate_appendix(&["\n--- Round 1 ---\n**Skeptic:**\nNo sources.\n".to_string(), "second".to_string()]);
+        assert!(appendix.starts_with("\n\n---\n\n<details>\n<summary>Debate Transcript</summary>\n\n"));
+        assert!(appendix.contains("### Iteration 1\n\n--- Round 1 ---\n**Skeptic:**\nNo sources.\n\n### Iteration 2\n\nsecond\n\n"));
+        assert!(appendix.ends_with("</details>\n"));
+    }
+
+    #[test]
+    fn test_split_debate_agents() {
+        let mut agents = example_agents().agents.debate_agents;
+        let mut expert = agents[0].clone();
+        expert.name = "Domain Expert".to_string();
+        expert.role = "domain_expert".to_string();
+        agents.insert(0, expert);
+        let mut judge = agents[0].clone();
+        judge.role = "Final_Judge".to_string();
+        agents.push(judge);
+
+        let (decider, debaters) = split_debate_agents(&agents).unwrap();
+        assert_eq!(decider.role, "synthesizer");
+        assert_eq!(debaters.iter().map(|a| a.role.as_str()).collect::<Vec<_>>(), vec!["domain_expert", "advocate", "skeptic"]);
+
+        agents.retain(|a| !is_debate_judge(&a.role));
+        assert!(split_debate_agents(&agents).is_err());
+        let judges_only: Vec<AgentRole> = example_agents().agents.debate_agents.into_iter().filter(|a| a.role == "synthesizer").collect();
+        assert!(split_debate_agents(&judges_only).is_err());
+    }
+
+    #[test]
+    fn test_debate_turn_prompt() {
+        let debater = example_agents().agents.debate_agents.remove(0);
+        let opening = debate_turn_prompt(&debater, "the output", "");
+        assert!(opening.ends_with("Research Output:\nthe output\n\nPresent your position:"));
+        let reply = debate_turn_prompt(&debater, "the output", "\n--- Round 1 ---\n**Skeptic:**\nNo sources.\n\n");
+        assert!(reply.contains("Debate History:\n--- Round 1 ---\n**Skeptic:**\nNo sources.\n\nRespond to the latest arguments:"));
+    }
+
+    #[test]
+    fn test_debate_rounds_to_condense() {
+        let rounds = vec!["round one ".repeat(10), "round two ".repeat(10), "latest".to_string()];
+        assert!(debate_rounds_to_condense(&rounds, 1000).is_none());
+        assert!(debate_rounds_to_condense(&rounds[2..], 1).is_none());
+        assert!(debate_rounds_to_condense(&[], 1).is_none());
+
+        let (earlier, latest) = debate_rounds_to_condense(&rounds, 100).unwrap();
+        assert_eq!(earlier, format!("{}{}", rounds[0], rounds[1]));
+        assert_eq!(latest, "latest");
+    }
+
+    #[test]
+    fn test_gap_detection_midpoint() {
+        for total in 0..12 {
+            assert_eq!(gap_detection_midpoint(total, 0.5), (total + 1) / 2);
+        }
+        assert_eq!(gap_detection_midpoint(10, 0.25), 3);
+        assert_eq!(gap_detection_midpoint(10, 1.0), 10);
+        assert_eq!(gap_detection_midpoint(10, 2.0), 10);
+        assert_eq!(gap_detection_midpoint(10, 0.0), 0);
+    }
+
+    #[test]
+    fn test_format_plan_only() {
+        let sub_questions = vec![
+            SubQuestion { question: "What is Rust?".to_string(), assigned_worker: "web_researcher".to_string() },
+            SubQuestion { question: "How fast is it?".to_string(), assigned_worker: "data_specialist".to_string() },
+        ];
+        let doc = format_plan_only(" rust overview ", &sub_questions, "Start broad, then benchmark.\n");
+        assert!(doc.starts_with("# Research plan\n"));
+        assert!(doc.contains("for:* rust overview\n"));
+        assert!(doc.contains("1. **web_researcher** What is Rust?\n2. **data_specialist** How fast is it?\n"));
+        assert!(doc.ends_with("## Strategy\n\nStart broad, then benchmark.\n"));
+        assert!(!format_plan_only("q", &sub_questions, "  ").contains("## Strategy"));
+    }
+
+    #[test]
+    fn test_render_tool_audit_groups_by_agent() {
+        let call = |agent: &str, tool: &str, parameters: &str, secs: u64| crate::shared_memory::ToolCall {
+            agent_name: agent.to_string(),
+            tool_type: "http".to_string(),
+            tool_name: tool.to_string(),
+            parameters: parameters.to_string(),
+            created_at: std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs),
+        };
+        let calls = vec![
+            call("web_researcher", "web_search", r#"{"query":"rust"}"#, 60),
+            call("data_specialist", "fetch", "not json", 120),
+            call("web_researcher", "fetch", r#"{"url":"https://example.com"}"#, 180),
+        ];
+
+        let json: serde_json::Value = serde_json::from_str(&render_tool_audit("q1", &calls, crate::history::ExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["total_calls"], 3);
+        assert_eq!(json["agents"][0]["agent"], "data_specialist");
+        assert_eq!(json["agents"][0]["calls"][0]["parameters"], "not json");
+        assert_eq!(json["agents"][1]["calls"][0]["parameters"]["query"], "rust");
+        assert_eq!(json["agents"][1]["calls"][1]["timestamp"], "1970-01-01T00:03:00Z");
+
+        let markdown = render_tool_audit("q1", &calls, crate::history::ExportFormat::Markdown).unwrap();
+        assert!(markdown.contains("## web_researcher (2)"));
+        assert!(markdown.contains("1970-01-01T00:01:00Z · `[http] web_search`"));
+        assert!(render_tool_audit("q1", &[], crate::history::ExportFormat::Markdown).unwrap().contains("No tools were used"));
+    }
+
+    #[test]
+    fn test_example_assignments() {
+        let prompt = r#"Output: [{"question": "a", "worker": "web_researcher"}, {"question":"b","worker" : "data_specialist"}, {"worker": "web_researcher"}, {"worker": "..."}] and "worker" alone"#;
+        assert_eq!(example_assignments(prompt), vec!["web_researcher", "data_specialist"]);
+    }
+}
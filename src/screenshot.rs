@@ -3,18 +3,156 @@ use std::path::PathBuf;
 use std::process::Command;
 use image::GenericImageView;
 
-pub fn capture_screenshot() -> Result<PathBuf> {
-    eprintln!("[Screenshot] Starting screenshot capture...");
+/// A screen rectangle in pixels, as given to `--region x,y,w,h`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
 
-    // Generate temp file path
-    let temp_path = std::env::temp_dir().join(format!("bob-bar-screenshot-{}.png",
+impl Region {
+    /// Parse `x,y,w,h` (whitespace around the numbers is allowed)
+    pub fn parse(spec: &str) -> Result<Self> {
+        let parts: Vec<&str> = spec.split(',').map(str::trim).collect();
+        if parts.len() != 4 {
+            return Err(anyhow::anyhow!("Region must be x,y,w,h (got \"{}\")", spec));
+        }
+        let region = Region {
+            x: parts[0].parse().context("Invalid region x")?,
+            y: parts[1].parse().context("Invalid region y")?,
+            width: parts[2].parse().context("Invalid region width")?,
+            height: parts[3].parse().context("Invalid region height")?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(anyhow::anyhow!("Region width and height must be greater than zero"));
+        }
+        Ok(region)
+    }
+
+    /// Geometry in the `x,y wxh` form used by grim and slurp
+    fn geometry(&self) -> String {
+        format!("{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// What `--screenshot` should capture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureMode {
+    FullScreen,
+    /// Let the user drag a rectangle on screen
+    Select,
+    Region(Region),
+}
+
+/// Capture according to `mode`; every mode produces a PNG usable by `encode_image_base64`
+pub fn capture(mode: CaptureMode) -> Result<PathBuf> {
+    match mode {
+        CaptureMode::FullScreen => capture_screenshot(),
+        CaptureMode::Select => capture_selection(),
+        CaptureMode::Region(region) => capture_region(region.x, region.y, region.width, region.height),
+    }
+}
+
+fn temp_screenshot_path() -> PathBuf {
+    std::env::temp_dir().join(format!("bob-bar-screenshot-{}.png",
         std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs()
-    ));
+    ))
+}
+
+/// Run a capture command and report whether it produced `path`
+fn run_capture_tool(program: &str, args: &[&str], path: &PathBuf) -> bool {
+    match Command::new(program).args(args).arg(path).output() {
+        Ok(output) if output.status.success() && path.exists() => {
+            tracing::debug!("Screenshot captured with {}", program);
+            true
+        }
+        Ok(output) => {
+            tracing::warn!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr));
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Capture a `width`x`height` rectangle whose top-left corner is at (`x`, `y`).
+/// Uses grim's geometry option on Wayland, otherwise crops a full-screen capture.
+pub fn capture_region(x: i32, y: i32, width: u32, height: u32) -> Result<PathBuf> {
+    let region = Region { x, y, width, height };
+    tracing::debug!("Capturing region {}", region.geometry());
+
+    let temp_path = temp_screenshot_path();
+    if run_capture_tool("grim", &["-g", &region.geometry()], &temp_path) {
+        return Ok(temp_path);
+    }
+
+    let full_path = capture_screenshot()?;
+    crop_to_region(&full_path, region)?;
+    Ok(full_path)
+}
+
+/// Crop the image at `path` in place to `region`, clamped to the image bounds
+fn crop_to_region(path: &PathBuf, region: Region) -> Result<()> {
+    let img = image::open(path).context("Failed to open screenshot")?;
+    let (img_width, img_height) = img.dimensions();
+    let left = region.x.max(0) as u32;
+    let top = region.y.max(0) as u32;
+    // The part of the region above or left of the screen is cut off, not shifted in
+    let visible_width = region.width.saturating_sub(region.x.min(0).unsigned_abs());
+    let visible_height = region.height.saturating_sub(region.y.min(0).unsigned_abs());
+    if left >= img_width || top >= img_height || visible_width == 0 || visible_height == 0 {
+        return Err(anyhow::anyhow!(
+            "Region {} lies outside the {}x{} screen", region.geometry(), img_width, img_height
+        ));
+    }
+    let width = visible_width.min(img_width - left);
+    let height = visible_height.min(img_height - top);
+    img.crop_imm(left, top, width, height)
+        .save(path)
+        .context("Failed to save cropped screenshot")?;
+    Ok(())
+}
+
+/// Let the user drag a rectangle: slurp + grim on Wayland, `scrot -s` on X11,
+/// or `gnome-screenshot -a`
+pub fn capture_selection() -> Result<PathBuf> {
+    tracing::debug!("Waiting for region selection...");
+    let temp_path = temp_screenshot_path();
+
+    if let Ok(output) = Command::new("slurp").output() {
+        if output.status.success() {
+            let geometry = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if run_capture_tool("grim", &["-g", &geometry], &temp_path) {
+                return Ok(temp_path);
+            }
+        } else {
+            // slurp exits non-zero when the selection is cancelled with Escape
+            return Err(anyhow::anyhow!("Region selection cancelled"));
+        }
+    }
+
+    if run_capture_tool("scrot", &["-s", "-f"], &temp_path)
+        || run_capture_tool("gnome-screenshot", &["-a", "-f"], &temp_path)
+    {
+        return Ok(temp_path);
+    }
+
+    Err(anyhow::anyhow!(
+        "Failed to capture a selected region. Please install one of: slurp + grim (Wayland), scrot (X11), or gnome-screenshot"
+    ))
+}
+
+pub fn capture_screenshot() -> Result<PathBuf> {
+    tracing::debug!("Starting screenshot capture...");
+
+    // Generate temp file path
+    let temp_path = temp_screenshot_path();
 
-    eprintln!("[Screenshot] Output path: {}", temp_path.display());
+    tracing::debug!("Output path: {}", temp_path.display());
 
     // Try Wayland first (grim)
     let wayland_result = Command::new("grim")
@@ -23,28 +161,28 @@ pub fn capture_screenshot() -> Result<PathBuf> {
 
     if let Ok(output) = wayland_result {
         if output.status.success() && temp_path.exists() {
-            eprintln!("[Screenshot] Screenshot captured with grim (Wayland)");
+            tracing::debug!("Screenshot captured with grim (Wayland)");
             return Ok(temp_path);
         }
-        eprintln!("[Screenshot] grim failed: {}", String::from_utf8_lossy(&output.stderr));
+        tracing::debug!("grim failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
     // Try X11 fallback (scrot)
-    eprintln!("[Screenshot] Trying X11 fallback (scrot)...");
+    tracing::debug!("Trying X11 fallback (scrot)...");
     let x11_result = Command::new("scrot")
         .arg(&temp_path)
         .output();
 
     if let Ok(output) = x11_result {
         if output.status.success() && temp_path.exists() {
-            eprintln!("[Screenshot] Screenshot captured with scrot (X11)");
+            tracing::debug!("Screenshot captured with scrot (X11)");
             return Ok(temp_path);
         }
-        eprintln!("[Screenshot] scrot failed: {}", String::from_utf8_lossy(&output.stderr));
+        tracing::debug!("scrot failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
     // Try gnome-screenshot as last resort
-    eprintln!("[Screenshot] Trying gnome-screenshot fallback...");
+    tracing::debug!("Trying gnome-screenshot fallback...");
     let gnome_result = Command::new("gnome-screenshot")
         .arg("-f")
         .arg(&temp_path)
@@ -52,10 +190,10 @@ pub fn capture_screenshot() -> Result<PathBuf> {
 
     if let Ok(output) = gnome_result {
         if output.status.success() && temp_path.exists() {
-            eprintln!("[Screenshot] Screenshot captured with gnome-screenshot");
+            tracing::debug!("Screenshot captured with gnome-screenshot");
             return Ok(temp_path);
         }
-        eprintln!("[Screenshot] gnome-screenshot failed: {}", String::from_utf8_lossy(&output.stderr));
+        tracing::warn!("gnome-screenshot failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
     Err(anyhow::anyhow!(
@@ -63,6 +201,29 @@ pub fn capture_screenshot() -> Result<PathBuf> {
     ))
 }
 
+/// Save an image from the clipboard to a temp PNG. `Ok(None)` when the clipboard holds
+/// no image (e.g. it contains text). Blocks on the system clipboard, so call it from a
+/// blocking task.
+pub fn clipboard_image() -> Result<Option<PathBuf>> {
+    let mut clipboard = arboard::Clipboard::new().context("Clipboard is not available")?;
+    let data = match clipboard.get_image() {
+        Ok(data) => data,
+        Err(arboard::Error::ContentNotAvailable) => {
+            tracing::debug!("No image on the clipboard");
+            return Ok(None);
+        }
+        Err(e) => return Err(e).context("Failed to read clipboard image"),
+    };
+
+    let img = image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .context("Clipboard image could not be decoded")?;
+    let temp_path = temp_screenshot_path();
+    img.save_with_format(&temp_path, image::ImageFormat::Png)
+        .context("Failed to save clipboard image")?;
+    tracing::debug!("Clipboard image saved to {}", temp_path.display());
+    Ok(Some(temp_path))
+}
+
 pub fn encode_image_base64(path: &PathBuf) -> Result<String> {
     let mut img = image::open(path)
         .context("Failed to open image")?;
@@ -72,7 +233,7 @@ pub fn encode_image_base64(path: &PathBuf) -> Result<String> {
     const MAX_HEIGHT: u32 = 1120;
 
     let (width, height) = img.dimensions();
-    eprintln!("[Screenshot] Original dimensions: {}x{}", width, height);
+    tracing::debug!("Original dimensions: {}x{}", width, height);
 
     // Check if resizing is needed
     if width > MAX_WIDTH || height > MAX_HEIGHT {
@@ -84,18 +245,52 @@ pub fn encode_image_base64(path: &PathBuf) -> Result<String> {
         let new_width = (width as f32 * scale) as u32;
         let new_height = (height as f32 * scale) as u32;
 
-        eprintln!("[Screenshot] Resizing to: {}x{} (scale: {:.2})", new_width, new_height, scale);
+        tracing::debug!("Resizing to: {}x{} (scale: {:.2})", new_width, new_height, scale);
 
         img = img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
     } else {
-        eprintln!("[Screenshot] No resizing needed");
+        tracing::debug!("No resizing needed");
     }
 
     let mut buffer = Vec::new();
     img.write_to(&mut std::io::Cursor::new(&mut buffer), image::ImageFormat::Png)
         .context("Failed to encode image")?;
 
-    eprintln!("[Screenshot] Encoded image size: {} bytes", buffer.len());
+    tracing::debug!("Encoded image size: {} bytes", buffer.len());
 
     Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, buffer))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_region() {
+        let region = Region::parse("10, -20,640,480").unwrap();
+        assert_eq!(region, Region { x: 10, y: -20, width: 640, height: 480 });
+        assert_eq!(region.geometry(), "10,-20 640x480");
+        assert!(Region::parse("10,20,640").is_err());
+        assert!(Region::parse("10,20,0,480").is_err());
+        assert!(Region::parse("a,b,c,d").is_err());
+    }
+
+    #[test]
+    fn test_crop_to_region_clamps_to_image() {
+        let path = std::env::temp_dir().join(format!("bob-bar-crop-test-{}.png", std::process::id()));
+        image::RgbaImage::new(100, 50).save(&path).unwrap();
+
+        crop_to_region(&path, Region { x: 80, y: 10, width: 40, height: 20 }).unwrap();
+        assert_eq!(image::open(&path).unwrap().dimensions(), (20, 20));
+
+        // Negative offsets lose the off-screen part of the region
+        crop_to_region(&path, Region { x: -5, y: -15, width: 10, height: 30 }).unwrap();
+        assert_eq!(image::open(&path).unwrap().dimensions(), (5, 15));
+
+        let outside = crop_to_region(&path, Region { x: 500, y: 0, width: 10, height: 10 });
+        assert!(outside.is_err());
+        let above = crop_to_region(&path, Region { x: 0, y: -20, width: 5, height: 10 });
+        assert!(above.is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -5,10 +5,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
 
-use crate::embeddings;
+use crate::embeddings::{self, Embedder};
 
-#[allow(dead_code)]
 const SIMILARITY_THRESHOLD: f32 = 0.90; // 90% similarity = duplicate
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +23,16 @@ pub struct Memory {
     pub embedding: Option<Vec<f32>>,
 }
 
+/// A memory waiting to be stored by [`SharedMemory::store_memories`]
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct NewMemory {
+    pub memory_type: MemoryType,
+    pub content: String,
+    pub created_by: String,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MemoryType {
     Discovery,
@@ -62,19 +72,125 @@ impl MemoryType {
 }
 
 pub struct SharedMemory {
-    ollama_host: String,
-    embedding_model: String,
-    #[allow(dead_code)]
+    embedder: Embedder,  // Backend selected by `embedding_backend`
     embedding_dimensions: usize,
     db: Arc<Mutex<Connection>>,
+    dedup_threshold: std::sync::Mutex<f32>,  // Cosine similarity at which a new memory counts as a duplicate (<= 0 disables)
+}
+
+/// Default similarity above which a new memory is treated as a restatement of an existing one
+pub const DEFAULT_DEDUP_THRESHOLD: f32 = 0.95;
+
+/// Number of nearest neighbours checked for near-duplicates on store
+const DEDUP_CANDIDATES: i64 = 20;
+
+/// Nearest neighbours fetched per requested search result before cosine reranking
+const SEARCH_OVERSAMPLE: usize = 3;
+
+/// Rank `(item, embedding)` candidates by cosine similarity to `query`, dropping those
+/// below `min_score` and keeping the best `top_k`
+fn rerank<T>(query: &[f32], candidates: Vec<(T, Vec<f32>)>, min_score: f32, top_k: usize) -> Vec<(T, f32)> {
+    let mut scored: Vec<(T, f32)> = candidates
+        .into_iter()
+        .map(|(item, embedding)| {
+            let score = embeddings::cosine_similarity(query, &embedding);
+            (item, score)
+        })
+        .filter(|(_, score)| *score >= min_score)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(top_k);
+    scored
+}
+
+/// Width `n` of the `embedding FLOAT[n]` column in a vec_memories table definition
+fn declared_dimensions(table_sql: &str) -> Option<usize> {
+    let start = table_sql.find("FLOAT[")? + "FLOAT[".len();
+    let end = start + table_sql[start..].find(']')?;
+    table_sql[start..end].trim().parse().ok()
+}
+
+/// Most memories sqlite-vec returns from one KNN query
+const MAX_KNN_NEIGHBOURS: usize = 4096;
+
+/// The `top_k` memories most similar to `query_embedding` (see [rerank]), optionally only
+/// of `memory_type` and/or written by `created_by`. The filters can only be applied to the
+/// KNN candidates, so when they exclude most of them the neighbour count is widened until
+/// enough matches are found or every memory has been considered.
+fn nearest_memories(
+    db: &Connection,
+    query_embedding: &[f32],
+    memory_type: Option<MemoryType>,
+    created_by: Option<&str>,
+    top_k: usize,
+    min_score: f32,
+) -> Result<Vec<(Memory, f32)>> {
+    let query_blob = encode_embedding(query_embedding);
+    let wanted = top_k * SEARCH_OVERSAMPLE;
+    let total = db.query_row("SELECT COUNT(*) FROM vec_memories", [], |row| row.get::<_, i64>(0))? as usize;
+    let filtered = memory_type.is_some() || created_by.is_some();
+
+    let sql = "SELECT m.memory_type, m.content, m.metadata, m.created_by, m.created_at, v.embedding
+         FROM memories m
+         JOIN (
+             SELECT memory_id, embedding, distance
+             FROM vec_memories
+             WHERE embedding MATCH ?1
+             ORDER BY distance
+             LIMIT ?2
+         ) v ON m.id = v.memory_id
+         WHERE (?3 IS NULL OR m.created_by = ?3)
+           AND (?4 IS NULL OR m.memory_type = ?4)
+         ORDER BY v.distance";
+    let mut stmt = db.prepare(sql)?;
+
+    let mut neighbours = wanted.min(MAX_KNN_NEIGHBOURS);
+    loop {
+        let candidates = stmt
+            .query_map(params![&query_blob, neighbours as i64, created_by, memory_type.as_ref().map(MemoryType::as_str)], |row| {
+                let metadata_json: String = row.get(2)?;
+                let metadata: HashMap<String, String> =
+                    serde_json::from_str(&metadata_json).unwrap_or_default();
+
+                let created_at_secs: i64 = row.get(4)?;
+                let created_at = UNIX_EPOCH + std::time::Duration::from_secs(created_at_secs as u64);
+
+                let memory = Memory {
+                    memory_type: MemoryType::from_str(&row.get::<_, String>(0)?)
+                        .unwrap_or(MemoryType::Context),
+                    content: row.get(1)?,
+                    metadata,
+                    created_by: row.get(3)?,
+                    created_at,
+                    embedding: None, // Don't return embedding to save memory
+                };
+                Ok((memory, decode_embedding(&row.get::<_, Vec<u8>>(5)?)))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !filtered || candidates.len() >= wanted || neighbours >= total || neighbours >= MAX_KNN_NEIGHBOURS {
+            return Ok(rerank(query_embedding, candidates, min_score, top_k));
+        }
+        neighbours = (neighbours * 4).min(total).min(MAX_KNN_NEIGHBOURS);
+    }
+}
+
+/// Little-endian f32 blob, the layout vec0 columns store
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn decode_embedding(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
 }
 
 impl SharedMemory {
-    pub fn new(
-        ollama_host: String,
-        embedding_model: String,
-        embedding_dimensions: usize,
-    ) -> Result<Self> {
+    /// Open the persistent memory database at `db_path`. `embedder` computes the vectors,
+    /// which are expected to have `embedding_dimensions` entries. Fails if an existing
+    /// vector table was created with a different width.
+    pub fn new(db_path: &std::path::Path, embedder: Embedder, embedding_dimensions: usize) -> Result<Self> {
         // Register sqlite-vec as an auto-loading extension
         // This needs to be done once, but it's safe to call multiple times
         unsafe {
@@ -83,16 +199,13 @@ impl SharedMemory {
             )));
         }
 
-        // Get persistent database path
-        let db_path = crate::config::Config::get_config_dir().join("communication.sqlite");
-
         // Ensure config directory exists
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
         // Open persistent SQLite database (vec extension will auto-load)
-        let db = Connection::open(&db_path)?;
+        let db = Connection::open(db_path)?;
 
         // Verify vec0 extension is loaded by trying to create a test query
         let vec_test = db.query_row(
@@ -112,22 +225,21 @@ impl SharedMemory {
 
                 match has_vec0 {
                     Ok(1) => {
-                        eprintln!("[SharedMemory] ✓ vec0 extension loaded (found in {} total modules)", count);
+                        debug!("✓ vec0 extension loaded (found in {} total modules)", count);
                     }
                     Ok(0) => {
-                        eprintln!("[SharedMemory] ✗ WARNING: vec0 extension NOT found among {} modules", count);
-                        eprintln!("[SharedMemory] Vector search will NOT work!");
+                        warn!("vec0 extension NOT found among {} modules, vector search will NOT work", count);
                     }
                     Ok(n) => {
-                        eprintln!("[SharedMemory] Unexpected: found {} vec0 modules", n);
+                        warn!("Unexpected: found {} vec0 modules", n);
                     }
                     Err(e) => {
-                        eprintln!("[SharedMemory] ✗ WARNING: Could not check for vec0: {}", e);
+                        warn!("Could not check for vec0: {}", e);
                     }
                 }
             }
             Err(e) => {
-                eprintln!("[SharedMemory] ✗ WARNING: Could not query modules: {}", e);
+                warn!("Could not query modules: {}", e);
             }
         }
 
@@ -169,7 +281,7 @@ impl SharedMemory {
 
             // If it contains "TEXT PRIMARY KEY", we need to recreate with INTEGER
             if table_sql.contains("TEXT PRIMARY KEY") {
-                eprintln!("[SharedMemory] Detected old vec_memories schema (TEXT). Recreating with INTEGER...");
+                info!("Detected old vec_memories schema (TEXT). Recreating with INTEGER...");
                 db.execute("DROP TABLE vec_memories", [])?;
                 db.execute(
                     &format!(
@@ -181,7 +293,15 @@ impl SharedMemory {
                     ),
                     [],
                 )?;
-                eprintln!("[SharedMemory] ✓ vec_memories recreated with INTEGER PRIMARY KEY");
+                info!("✓ vec_memories recreated with INTEGER PRIMARY KEY");
+            } else if let Some(existing) = declared_dimensions(&table_sql) {
+                if existing != embedding_dimensions {
+                    return Err(anyhow::anyhow!(
+                        "Memory database stores {}-dimensional embeddings but embedding_dimensions is {}; \
+                         change it back or delete {} to start over",
+                        existing, embedding_dimensions, db_path.display()
+                    ));
+                }
             }
         } else {
             // Table doesn't exist, create it
@@ -218,14 +338,83 @@ impl SharedMemory {
         )?;
 
         Ok(Self {
-            ollama_host,
-            embedding_model,
+            embedder,
             embedding_dimensions,
             db: Arc::new(Mutex::new(db)),
+            dedup_threshold: std::sync::Mutex::new(DEFAULT_DEDUP_THRESHOLD),
         })
     }
 
-    /// Store a new memory with automatic embedding generation
+    /// Generate the embedding for a single text
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&[text.to_string()]).await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Embedding API returned no embedding"))
+    }
+
+    /// Embed `texts`, rejecting vectors that don't fit the vec table
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let embeddings = self.embedder.embed(texts).await?;
+        if let Some(wrong) = embeddings.iter().find(|e| e.len() != self.embedding_dimensions) {
+            embeddings::check_dimensions("embedding backend", wrong.len(), self.embedding_dimensions)?;
+        }
+        Ok(embeddings)
+    }
+
+    /// Set the similarity threshold used to skip near-duplicate memories (<= 0 disables)
+    pub fn set_dedup_threshold(&self, threshold: f32) {
+        if let Ok(mut t) = self.dedup_threshold.lock() {
+            *t = threshold;
+        }
+    }
+
+    /// Find an existing memory of the same type (and research query) whose embedding is at
+    /// least `dedup_threshold` similar to `embedding`
+    async fn find_near_duplicate(
+        &self,
+        memory_type: &MemoryType,
+        query_id: Option<&str>,
+        embedding: &[f32],
+    ) -> Result<Option<(i64, f32)>> {
+        let threshold = self.dedup_threshold.lock().map(|t| *t).unwrap_or(DEFAULT_DEDUP_THRESHOLD);
+        if threshold <= 0.0 {
+            return Ok(None);
+        }
+
+        let query_blob = encode_embedding(embedding);
+
+        let db = self.db.lock().await;
+        let mut stmt = db.prepare(
+            "SELECT m.id, v.embedding
+             FROM memories m
+             JOIN (
+                 SELECT memory_id, embedding, distance
+                 FROM vec_memories
+                 WHERE embedding MATCH ?1
+                 ORDER BY distance
+                 LIMIT ?2
+             ) v ON m.id = v.memory_id
+             WHERE m.memory_type = ?3
+               AND json_extract(m.metadata, '$.query_id') IS ?4
+             ORDER BY v.distance"
+        )?;
+        let candidates = stmt
+            .query_map(
+                params![&query_blob, DEDUP_CANDIDATES, memory_type.as_str(), query_id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(candidates
+            .into_iter()
+            .map(|(id, blob)| (id, embeddings::cosine_similarity(embedding, &decode_embedding(&blob))))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1)))
+    }
+
+    /// Store a new memory with automatic embedding generation.
+    /// If a near-identical memory of the same type already exists, nothing is stored and
+    /// the existing id is returned.
     pub async fn store_memory(
         &self,
         memory_type: MemoryType,
@@ -234,12 +423,53 @@ impl SharedMemory {
         metadata: Option<HashMap<String, String>>,
     ) -> Result<i64> {
         // Generate embedding
-        let embedding = embeddings::generate_embedding(
-            &self.ollama_host,
-            &self.embedding_model,
-            &content,
-        )
-        .await?;
+        let embedding = self.embed_one(&content).await?;
+        self.store_with_embedding(memory_type, content, created_by, metadata, embedding).await
+    }
+
+    /// Store several memories, generating all their embeddings in one batched request.
+    /// If the batch fails, each memory is embedded and stored on its own instead.
+    #[allow(dead_code)]
+    pub async fn store_memories(&self, memories: Vec<NewMemory>) -> Result<Vec<i64>> {
+        let texts: Vec<String> = memories.iter().map(|m| m.content.clone()).collect();
+        let mut ids = Vec::with_capacity(memories.len());
+
+        match self.embed(&texts).await {
+            Ok(embeddings) => {
+                for (memory, embedding) in memories.into_iter().zip(embeddings) {
+                    ids.push(self.store_with_embedding(
+                        memory.memory_type, memory.content, memory.created_by, memory.metadata, embedding,
+                    ).await?);
+                }
+            }
+            Err(e) => {
+                warn!("Batch embedding of {} memories failed, storing one by one: {}", texts.len(), e);
+                for memory in memories {
+                    ids.push(self.store_memory(
+                        memory.memory_type, memory.content, memory.created_by, memory.metadata,
+                    ).await?);
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Insert a memory whose embedding has already been generated, skipping near-duplicates
+    async fn store_with_embedding(
+        &self,
+        memory_type: MemoryType,
+        content: String,
+        created_by: String,
+        metadata: Option<HashMap<String, String>>,
+        embedding: Vec<f32>,
+    ) -> Result<i64> {
+        let query_id = metadata.as_ref().and_then(|m| m.get("query_id")).map(|s| s.as_str());
+        if let Some((existing_id, similarity)) = self.find_near_duplicate(&memory_type, query_id, &embedding).await? {
+            debug!("Skipping {} from {}: {:.3} similar to existing memory {}",
+                memory_type.as_str(), created_by, similarity, existing_id);
+            return Ok(existing_id);
+        }
 
         let metadata_json = serde_json::to_string(&metadata.unwrap_or_default())?;
         let created_at = SystemTime::now()
@@ -266,10 +496,7 @@ impl SharedMemory {
         let id = db.last_insert_rowid();
 
         // Store embedding as blob
-        let embedding_blob: Vec<u8> = embedding
-            .iter()
-            .flat_map(|f| f.to_le_bytes())
-            .collect();
+        let embedding_blob = encode_embedding(&embedding);
 
         db.execute(
             "INSERT INTO vec_memories (memory_id, embedding) VALUES (?1, ?2)",
@@ -313,16 +540,16 @@ impl SharedMemory {
             ).ok();
 
             if result.is_some() {
-                eprintln!("[Memory] Found existing {} from {} for query {}, will update",
+                debug!("Found existing {} from {} for query {}, will update",
                     memory_type.as_str(), created_by, qid);
             } else {
-                eprintln!("[Memory] No existing {} from {} for query {}, will create new",
+                debug!("No existing {} from {} for query {}, will create new",
                     memory_type.as_str(), created_by, qid);
             }
 
             result
         } else {
-            eprintln!("[Memory] No query_id in metadata, will create new {} from {}",
+            debug!("No query_id in metadata, will create new {} from {}",
                 memory_type.as_str(), created_by);
             None
         };
@@ -331,12 +558,7 @@ impl SharedMemory {
 
         if let Some(id) = existing_id {
             // Update existing memory
-            let embedding = embeddings::generate_embedding(
-                &self.ollama_host,
-                &self.embedding_model,
-                &content,
-            )
-            .await?;
+            let embedding = self.embed_one(&content).await?;
 
             let metadata_json = serde_json::to_string(&metadata.unwrap_or_default())?;
             let created_at = SystemTime::now()
@@ -354,10 +576,7 @@ impl SharedMemory {
             )?;
 
             // Update embedding
-            let embedding_blob: Vec<u8> = embedding
-                .iter()
-                .flat_map(|f| f.to_le_bytes())
-                .collect();
+            let embedding_blob = encode_embedding(&embedding);
 
             db.execute(
                 "UPDATE vec_memories
@@ -375,89 +594,32 @@ impl SharedMemory {
         }
     }
 
-    /// Search for similar memories by content
+    /// Search for similar memories by content, returning each with its cosine similarity
+    /// to `query` (best first). Memories scoring below `min_score` are left out, as are
+    /// those not written by `created_by` when it is given.
     pub async fn search_similar(
         &self,
         query: &str,
         memory_type: Option<MemoryType>,
+        created_by: Option<&str>,
         top_k: usize,
-    ) -> Result<Vec<Memory>> {
+        min_score: f32,
+    ) -> Result<Vec<(Memory, f32)>> {
         // Generate query embedding
-        let query_embedding = embeddings::generate_embedding(
-            &self.ollama_host,
-            &self.embedding_model,
-            query,
-        )
-        .await?;
-
-        let query_blob: Vec<u8> = query_embedding
-            .iter()
-            .flat_map(|f| f.to_le_bytes())
-            .collect();
+        let query_embedding = self.embed_one(query).await?;
 
         let db = self.db.lock().await;
-
-        // Vector search query
-        let type_filter = if let Some(ref mt) = memory_type {
-            format!("AND m.memory_type = '{}'", mt.as_str())
-        } else {
-            String::new()
-        };
-
-        let sql = format!(
-            "SELECT m.memory_type, m.content, m.metadata, m.created_by, m.created_at
-             FROM memories m
-             JOIN (
-                 SELECT memory_id, distance
-                 FROM vec_memories
-                 WHERE embedding MATCH ?1
-                 ORDER BY distance
-                 LIMIT ?2
-             ) v ON m.id = v.memory_id
-             WHERE 1=1 {}
-             ORDER BY v.distance",
-            type_filter
-        );
-
-        let mut stmt = db.prepare(&sql)?;
-        let memories = stmt
-            .query_map(params![&query_blob, top_k as i64], |row| {
-                let metadata_json: String = row.get(2)?;
-                let metadata: HashMap<String, String> =
-                    serde_json::from_str(&metadata_json).unwrap_or_default();
-
-                let created_at_secs: i64 = row.get(4)?;
-                let created_at = UNIX_EPOCH + std::time::Duration::from_secs(created_at_secs as u64);
-
-                Ok(Memory {
-                    memory_type: MemoryType::from_str(&row.get::<_, String>(0)?)
-                        .unwrap_or(MemoryType::Context),
-                    content: row.get(1)?,
-                    metadata,
-                    created_by: row.get(3)?,
-                    created_at,
-                    embedding: None, // Don't return embedding to save memory
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(memories)
+        nearest_memories(&db, &query_embedding, memory_type, created_by, top_k, min_score)
     }
 
     /// Check if a query has been executed before (deduplication)
     #[allow(dead_code)]
     pub async fn check_duplicate_query(&self, query: &str) -> Result<Option<Memory>> {
         let similar = self
-            .search_similar(query, Some(MemoryType::QueryResult), 1)
+            .search_similar(query, Some(MemoryType::QueryResult), None, 1, SIMILARITY_THRESHOLD)
             .await?;
 
-        if let Some(memory) = similar.first() {
-            // Note: We'd need to store similarity scores to check threshold
-            // For now, just return if we found something
-            return Ok(Some(memory.clone()));
-        }
-
-        Ok(None)
+        Ok(similar.into_iter().next().map(|(memory, _)| memory))
     }
 
     /// Get all memories by type
@@ -497,44 +659,41 @@ impl SharedMemory {
         .unwrap_or_default()
     }
 
-    // Unused - commented out after removing id field
-    // /// Get all memories by agent
-    // pub async fn get_by_agent(&self, agent_name: &str) -> Vec<Memory> {
-    //     let db = match self.db.lock().await {
-    //         db => db,
-    //     };
+    /// Get all memories written by `agent`, optionally only those of one type, oldest first
+    pub async fn get_by_agent(&self, agent: &str, memory_type: Option<MemoryType>) -> Vec<Memory> {
+        let db = self.db.lock().await;
 
-    //     let sql = "SELECT id, memory_type, content, metadata, created_by, created_at
-    //                FROM memories WHERE created_by = ?1";
+        let sql = "SELECT memory_type, content, metadata, created_by, created_at
+                   FROM memories WHERE created_by = ?1 AND (?2 IS NULL OR memory_type = ?2)
+                   ORDER BY created_at";
 
-    //     let mut stmt = match db.prepare(sql) {
-    //         Ok(s) => s,
-    //         Err(_) => return Vec::new(),
-    //     };
+        let mut stmt = match db.prepare(sql) {
+            Ok(s) => s,
+            Err(_) => return Vec::new(),
+        };
 
-    //     stmt.query_map(params![agent_name], |row| {
-    //         let metadata_json: String = row.get(3)?;
-    //         let metadata: HashMap<String, String> =
-    //             serde_json::from_str(&metadata_json).unwrap_or_default();
+        stmt.query_map(params![agent, memory_type.as_ref().map(|t| t.as_str())], |row| {
+            let metadata_json: String = row.get(2)?;
+            let metadata: HashMap<String, String> =
+                serde_json::from_str(&metadata_json).unwrap_or_default();
 
-    //         let created_at_secs: i64 = row.get(5)?;
-    //         let created_at = UNIX_EPOCH + std::time::Duration::from_secs(created_at_secs as u64);
+            let created_at_secs: i64 = row.get(4)?;
+            let created_at = UNIX_EPOCH + std::time::Duration::from_secs(created_at_secs as u64);
 
-    //         Ok(Memory {
-    //             id: row.get(0)?,
-    //             memory_type: MemoryType::from_str(&row.get::<_, String>(1)?)
-    //                 .unwrap_or(MemoryType::Context),
-    //             content: row.get(2)?,
-    //             metadata,
-    //             created_by: row.get(4)?,
-    //             created_at,
-    //             embedding: None,
-    //         })
-    //     })
-    //     .ok()
-    //     .map(|rows| rows.filter_map(|r| r.ok()).collect())
-    //     .unwrap_or_default()
-    // }
+            Ok(Memory {
+                memory_type: MemoryType::from_str(&row.get::<_, String>(0)?)
+                    .unwrap_or(MemoryType::Context),
+                content: row.get(1)?,
+                metadata,
+                created_by: row.get(3)?,
+                created_at,
+                embedding: None,
+            })
+        })
+        .ok()
+        .map(|rows| rows.filter_map(|r| r.ok()).collect())
+        .unwrap_or_default()
+    }
 
     // Unused - commented out after removing id field
     // /// Get a specific memory by ID
@@ -757,3 +916,97 @@ impl std::fmt::Display for MemoryStats {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_blob_round_trip() {
+        let embedding = vec![0.25f32, -1.5, 3.0];
+        let blob = encode_embedding(&embedding);
+        assert_eq!(blob.len(), embedding.len() * 4);
+        assert_eq!(&blob[..4], &0.25f32.to_le_bytes());
+        assert_eq!(decode_embedding(&blob), embedding);
+        assert!(encode_embedding(&[]).is_empty());
+        assert!(decode_embedding(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_rerank_orders_and_filters_by_cosine() {
+        let query = vec![1.0, 0.0];
+        let candidates = vec![
+            ("far", vec![0.0, 1.0]),
+            ("close", vec![0.9, 0.1]),
+            ("exact", vec![2.0, 0.0]),
+        ];
+        let ranked = rerank(&query, candidates.clone(), 0.5, 5);
+        let names: Vec<_> = ranked.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["exact", "close"]);
+        assert!((ranked[0].1 - 1.0).abs() < 0.001);
+
+        assert_eq!(rerank(&query, candidates, -1.0, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_declared_dimensions() {
+        let sql = "CREATE VIRTUAL TABLE vec_memories USING vec0(
+                        memory_id INTEGER PRIMARY KEY,
+                        embedding FLOAT[384]
+                    )";
+        assert_eq!(declared_dimensions(sql), Some(384));
+        assert_eq!(declared_dimensions("CREATE TABLE t (x TEXT)"), None);
+    }
+
+    /// A fresh 2-dimensional memory database with near-duplicate merging off; the embedder
+    /// is never reached because tests store and search with explicit vectors
+    fn test_memory(name: &str) -> (SharedMemory, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("bob-bar-memory-{}-{}.sqlite", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let embedder = Embedder::Ollama(crate::ollama::OllamaClient::with_config("http://localhost:1".to_string(), "m".to_string()));
+        let memory = SharedMemory::new(&path, embedder, 2).unwrap();
+        memory.set_dedup_threshold(0.0);
+        (memory, path)
+    }
+
+    async fn store(memory: &SharedMemory, memory_type: MemoryType, agent: &str, content: &str, embedding: Vec<f32>) {
+        memory.store_with_embedding(memory_type, content.to_string(), agent.to_string(), None, embedding).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_by_agent() {
+        let (memory, path) = test_memory("by-agent");
+        store(&memory, MemoryType::Discovery, "alpha", "first", vec![1.0, 0.0]).await;
+        store(&memory, MemoryType::Insight, "alpha", "second", vec![0.0, 1.0]).await;
+        store(&memory, MemoryType::Discovery, "beta", "other", vec![1.0, 1.0]).await;
+
+        let mut all: Vec<String> = memory.get_by_agent("alpha", None).await.into_iter().map(|m| m.content).collect();
+        all.sort();
+        assert_eq!(all, vec!["first", "second"]);
+        let insights = memory.get_by_agent("alpha", Some(MemoryType::Insight)).await;
+        assert_eq!(insights.len(), 1);
+        assert_eq!(insights[0].content, "second");
+        assert!(memory.get_by_agent("gamma", None).await.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_agent_filter_looks_past_other_agents_neighbours() {
+        let (memory, path) = test_memory("agent-filter");
+        // Every nearest neighbour of the query belongs to "busy"
+        for i in 0..8 {
+            store(&memory, MemoryType::Discovery, "busy", &format!("busy {}", i), vec![1.0, 0.01 * i as f32]).await;
+        }
+        store(&memory, MemoryType::Discovery, "quiet", "quiet note", vec![1.0, 0.5]).await;
+
+        let db = memory.db.lock().await;
+        let found = nearest_memories(&db, &[1.0, 0.0], None, Some("quiet"), 1, 0.0).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.content, "quiet note");
+
+        let typed = nearest_memories(&db, &[1.0, 0.0], Some(MemoryType::Insight), Some("busy"), 1, 0.0).unwrap();
+        assert!(typed.is_empty());
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,142 @@
+//! Scripted HTTP server for tests that need a real socket (Ollama client, HTTP tools).
+//!
+//! Each accepted connection gets the next response from the script; once the script
+//! runs out, the last response is repeated. Every request is recorded so tests can
+//! assert on what the client sent.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// What the server does with one connection
+#[derive(Clone)]
+pub enum TestResponse {
+    /// Raw response text written in pieces, pausing between them to exercise line buffering
+    Parts(Vec<String>),
+    /// Head, then `chunk` repeated until the client hangs up
+    Repeat { head: String, chunk: String },
+    /// Read the request and never answer
+    Silent,
+}
+
+impl TestResponse {
+    /// A complete raw response written in one go
+    pub fn raw(response: &str) -> Self {
+        TestResponse::Parts(vec![response.to_string()])
+    }
+
+    /// `200 OK` with a JSON body
+    pub fn json(body: &str) -> Self {
+        TestResponse::raw(&format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(), body
+        ))
+    }
+}
+
+/// A request as the server received it
+#[derive(Clone, Debug)]
+pub struct TestRequest {
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub requests: Arc<Mutex<Vec<TestRequest>>>,
+    /// Receives once for every connection the server has finished with
+    pub closed: mpsc::UnboundedReceiver<()>,
+}
+
+impl TestServer {
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+pub async fn spawn_test_http_server(responses: Vec<TestResponse>) -> TestServer {
+    assert!(!responses.is_empty(), "test server needs at least one response");
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let (closed_tx, closed) = mpsc::unbounded_channel();
+
+    let server_requests = requests.clone();
+    tokio::spawn(async move {
+        let mut next = 0;
+        while let Ok((socket, _)) = listener.accept().await {
+            let response = responses[next.min(responses.len() - 1)].clone();
+            next += 1;
+            let requests = server_requests.clone();
+            let closed_tx = closed_tx.clone();
+            tokio::spawn(async move {
+                serve(socket, response, requests).await;
+                let _ = closed_tx.send(());
+            });
+        }
+    });
+
+    TestServer { addr, requests, closed }
+}
+
+async fn serve(mut socket: TcpStream, response: TestResponse, requests: Arc<Mutex<Vec<TestRequest>>>) {
+    let Some(request) = read_request(&mut socket).await else { return };
+    requests.lock().unwrap().push(request);
+
+    match response {
+        TestResponse::Parts(parts) => {
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                if socket.write_all(part.as_bytes()).await.is_err() || socket.flush().await.is_err() {
+                    return;
+                }
+            }
+        }
+        TestResponse::Repeat { head, chunk } => {
+            if socket.write_all(head.as_bytes()).await.is_err() {
+                return;
+            }
+            while socket.write_all(chunk.as_bytes()).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        TestResponse::Silent => {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+    }
+}
+
+/// Read the headers, then exactly Content-Length bytes of body
+async fn read_request(socket: &mut TcpStream) -> Option<TestRequest> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some(split) = text.find("\r\n\r\n") {
+            let length: usize = text[..split].lines()
+                .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                .unwrap_or(0);
+            if request.len() >= split + 4 + length {
+                return Some(TestRequest {
+                    head: text[..split].to_string(),
+                    body: request[split + 4..split + 4 + length].to_vec(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,406 @@
+//! Minimal timezone support for the `current_date` tool.
+//!
+//! Resolves IANA names through the system zoneinfo database (TZif files), POSIX TZ
+//! strings like `EST5EDT,M3.2.0,M11.1.0`, and fixed offsets like `+05:30` or `UTC-8`,
+//! without pulling in a full date/time crate.
+
+use anyhow::{anyhow, Result};
+
+/// A timezone resolved to its UTC offset at a particular instant
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedOffset {
+    pub name: String,
+    pub offset_secs: i64,
+}
+
+impl ResolvedOffset {
+    fn utc() -> Self {
+        Self { name: "UTC".to_string(), offset_secs: 0 }
+    }
+
+    /// Offset formatted as `+HH:MM` / `-HH:MM`
+    pub fn offset_string(&self) -> String {
+        let sign = if self.offset_secs < 0 { '-' } else { '+' };
+        let abs = self.offset_secs.abs();
+        format!("{}{:02}:{:02}", sign, abs / 3600, (abs % 3600) / 60)
+    }
+}
+
+/// Resolve `spec` (IANA name, POSIX TZ string or fixed offset) at `timestamp`.
+/// With no spec the system local timezone is used, falling back to UTC.
+pub fn resolve(spec: Option<&str>, timestamp: i64) -> Result<ResolvedOffset> {
+    match spec.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(spec) => resolve_spec(spec, timestamp),
+        None => Ok(local(timestamp).unwrap_or_else(ResolvedOffset::utc)),
+    }
+}
+
+fn local(timestamp: i64) -> Option<ResolvedOffset> {
+    if let Ok(tz) = std::env::var("TZ") {
+        let tz = tz.trim_start_matches(':');
+        if !tz.is_empty() {
+            if let Ok(resolved) = resolve_spec(tz, timestamp) {
+                return Some(resolved);
+            }
+        }
+    }
+
+    let data = std::fs::read("/etc/localtime").ok()?;
+    let offset_secs = tzif_offset(&data, timestamp)?;
+    let name = std::fs::read_to_string("/etc/timezone")
+        .ok()
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| {
+            // /etc/localtime is usually a symlink into the zoneinfo tree
+            std::fs::read_link("/etc/localtime").ok().and_then(|target| {
+                let target = target.to_string_lossy().into_owned();
+                target.split("zoneinfo/").nth(1).map(str::to_string)
+            })
+        })
+        .unwrap_or_else(|| "local".to_string());
+    Some(ResolvedOffset { name, offset_secs })
+}
+
+fn resolve_spec(spec: &str, timestamp: i64) -> Result<ResolvedOffset> {
+    if let Some(offset_secs) = parse_fixed_offset(spec) {
+        return Ok(ResolvedOffset { name: spec.to_string(), offset_secs });
+    }
+
+    if is_zone_name(spec) {
+        let dir = std::env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string());
+        if let Ok(data) = std::fs::read(std::path::Path::new(&dir).join(spec)) {
+            let offset_secs = tzif_offset(&data, timestamp)
+                .ok_or_else(|| anyhow!("Invalid zoneinfo data for timezone '{}'", spec))?;
+            return Ok(ResolvedOffset { name: spec.to_string(), offset_secs });
+        }
+    }
+
+    if let Some(rule) = PosixTz::parse(spec) {
+        return Ok(ResolvedOffset { name: spec.to_string(), offset_secs: rule.offset_at(timestamp) });
+    }
+
+    Err(anyhow!("Unknown timezone '{}'. Use an IANA name like 'America/New_York' or an offset like '+05:30'", spec))
+}
+
+/// IANA names are relative paths of letters, digits, `_`, `-`, `+` and `/`
+fn is_zone_name(spec: &str) -> bool {
+    !spec.starts_with('/')
+        && !spec.split('/').any(|part| part.is_empty() || part == "." || part == "..")
+        && spec.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '/'))
+}
+
+/// Parse `Z`, `UTC`, `GMT`, `+05:30`, `-0800`, `+9`, `UTC+2`, `GMT-03:00`
+fn parse_fixed_offset(spec: &str) -> Option<i64> {
+    let upper = spec.to_ascii_uppercase();
+    let rest = upper.strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))
+        .unwrap_or(&upper);
+    if upper == "Z" || rest.is_empty() {
+        return Some(0);
+    }
+
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i64 = hours.parse().ok()?;
+    let minutes: i64 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Offset at `timestamp` from a TZif (v1-v4) zoneinfo file
+fn tzif_offset(data: &[u8], timestamp: i64) -> Option<i64> {
+    if data.len() < 44 || &data[..4] != b"TZif" {
+        return None;
+    }
+    let version = data[4];
+    let counts = |at: usize| -> Option<[usize; 6]> {
+        let mut out = [0usize; 6];
+        for (i, slot) in out.iter_mut().enumerate() {
+            let start = at + 20 + i * 4;
+            *slot = u32::from_be_bytes(data.get(start..start + 4)?.try_into().ok()?) as usize;
+        }
+        Some(out)
+    };
+
+    let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts(0)?;
+    let v1_len = timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt;
+
+    // v2+ files repeat the data with 64-bit transition times, followed by a POSIX TZ footer
+    let (header, time_size) = if version >= b'2' { (44 + v1_len, 8) } else { (0, 4) };
+    let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts(header)?;
+    if typecnt == 0 {
+        return None;
+    }
+    let times_at = header + 44;
+    let indices_at = times_at + timecnt * time_size;
+    let types_at = indices_at + timecnt;
+    let block_end = types_at + typecnt * 6 + charcnt + leapcnt * (time_size + 4) + isstdcnt + isutcnt;
+
+    let transition = |i: usize| -> Option<i64> {
+        let start = times_at + i * time_size;
+        let bytes = data.get(start..start + time_size)?;
+        Some(if time_size == 8 {
+            i64::from_be_bytes(bytes.try_into().ok()?)
+        } else {
+            i32::from_be_bytes(bytes.try_into().ok()?) as i64
+        })
+    };
+    let utoff = |type_index: usize| -> Option<i64> {
+        let start = types_at + type_index * 6;
+        Some(i32::from_be_bytes(data.get(start..start + 4)?.try_into().ok()?) as i64)
+    };
+
+    let mut last = None;
+    for i in 0..timecnt {
+        if transition(i)? > timestamp {
+            break;
+        }
+        last = Some(i);
+    }
+
+    let footer = if version >= b'2' {
+        data.get(block_end..)
+            .and_then(|rest| std::str::from_utf8(rest).ok())
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .and_then(|rest| rest.split('\n').next())
+            .and_then(PosixTz::parse)
+    } else {
+        None
+    };
+
+    match last {
+        // Past the final transition, the footer rule describes future DST changes
+        Some(i) if i + 1 == timecnt && footer.is_some() => footer.map(|rule| rule.offset_at(timestamp)),
+        Some(i) => utoff(*data.get(indices_at + i)? as usize),
+        None if timecnt == 0 && footer.is_some() => footer.map(|rule| rule.offset_at(timestamp)),
+        None => utoff(0),
+    }
+}
+
+/// A POSIX TZ rule such as `EST5EDT,M3.2.0,M11.1.0` (only the `Mm.w.d` date form)
+#[derive(Debug, Clone, PartialEq)]
+struct PosixTz {
+    std_offset: i64,
+    dst: Option<(i64, DstRule, DstRule)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DstRule {
+    month: u32,
+    week: u32,
+    weekday: u32,
+    time_secs: i64,
+}
+
+impl PosixTz {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut rest = spec;
+        Self::take_name(&mut rest)?;
+        // POSIX offsets are west-positive; flip to the usual east-positive convention
+        let std_offset = -Self::take_offset(&mut rest)?;
+        if rest.is_empty() {
+            return Some(Self { std_offset, dst: None });
+        }
+
+        Self::take_name(&mut rest)?;
+        let dst_offset = if rest.starts_with(',') || rest.is_empty() {
+            std_offset + 3600
+        } else {
+            -Self::take_offset(&mut rest)?
+        };
+        let rules = rest.strip_prefix(',')?;
+        let (start, end) = rules.split_once(',')?;
+        Some(Self { std_offset, dst: Some((dst_offset, DstRule::parse(start)?, DstRule::parse(end)?)) })
+    }
+
+    fn take_name(rest: &mut &str) -> Option<()> {
+        if let Some(quoted) = rest.strip_prefix('<') {
+            let end = quoted.find('>')?;
+            *rest = &quoted[end + 1..];
+        } else {
+            let len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+            if len < 3 {
+                return None;
+            }
+            *rest = &rest[len..];
+        }
+        Some(())
+    }
+
+    fn take_offset(rest: &mut &str) -> Option<i64> {
+        let len = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, ':' | '+' | '-'))).unwrap_or(rest.len());
+        let value = parse_clock(&rest[..len])?;
+        *rest = &rest[len..];
+        Some(value)
+    }
+
+    fn offset_at(&self, timestamp: i64) -> i64 {
+        let Some((dst_offset, start, end)) = self.dst else {
+            return self.std_offset;
+        };
+        let (year, _, _) = civil_from_days((timestamp + self.std_offset).div_euclid(86400));
+        // Start is given in local standard time, end in local daylight time
+        let dst_start = start.local_secs(year) - self.std_offset;
+        let dst_end = end.local_secs(year) - dst_offset;
+        let in_dst = if dst_start < dst_end {
+            timestamp >= dst_start && timestamp < dst_end
+        } else {
+            !(timestamp >= dst_end && timestamp < dst_start)
+        };
+        if in_dst { dst_offset } else { self.std_offset }
+    }
+}
+
+impl DstRule {
+    fn parse(spec: &str) -> Option<Self> {
+        let (date, time) = match spec.split_once('/') {
+            Some((date, time)) => (date, parse_clock(time)?),
+            None => (spec, 2 * 3600),
+        };
+        let mut parts = date.strip_prefix('M')?.split('.');
+        let month: u32 = parts.next()?.parse().ok()?;
+        let week: u32 = parts.next()?.parse().ok()?;
+        let weekday: u32 = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        Some(Self { month, week, weekday, time_secs: time })
+    }
+
+    /// Seconds since the epoch (in local wall time) when this rule fires in `year`
+    fn local_secs(&self, year: i64) -> i64 {
+        let first = days_from_civil(year, self.month, 1);
+        let first_weekday = (first + 4).rem_euclid(7) as u32;  // 1970-01-01 was a Thursday
+        let mut day = 1 + (self.weekday + 7 - first_weekday) % 7 + (self.week - 1) * 7;
+        while day > days_in_month(year, self.month) {
+            day -= 7;
+        }
+        (first + day as i64 - 1) * 86400 + self.time_secs
+    }
+}
+
+/// Parse `[+-]hh[:mm[:ss]]` into seconds
+fn parse_clock(text: &str) -> Option<i64> {
+    let (sign, text) = match text.as_bytes().first()? {
+        b'-' => (-1, &text[1..]),
+        b'+' => (1, &text[1..]),
+        _ => (1, text),
+    };
+    let mut total = 0;
+    for (i, part) in text.split(':').enumerate() {
+        if i > 2 || part.is_empty() {
+            return None;
+        }
+        let value: i64 = part.parse().ok()?;
+        total += value * [3600, 60, 1][i];
+    }
+    Some(sign * total)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+/// (algorithm from https://howardhinnant.github.io/date_algorithms.html)
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) for a count of days since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468; // Days from 0000-03-01 to 1970-01-01
+    let era = z.div_euclid(146097);
+    let doe = (z - era * 146097) as u32; // Day of era
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // Year of era
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // Day of year
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JULY_2024: i64 = 1719835200; // 2024-07-01T12:00:00Z
+    const JAN_2024: i64 = 1705320000; // 2024-01-15T12:00:00Z
+
+    #[test]
+    fn test_civil_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(JULY_2024 / 86400), (2024, 7, 1));
+        assert_eq!(days_from_civil(2024, 2, 29), 19782);
+        assert_eq!(civil_from_days(days_from_civil(1969, 12, 31)), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_fixed_offsets() {
+        assert_eq!(parse_fixed_offset("Z"), Some(0));
+        assert_eq!(parse_fixed_offset("UTC"), Some(0));
+        assert_eq!(parse_fixed_offset("+05:30"), Some(19800));
+        assert_eq!(parse_fixed_offset("-0800"), Some(-28800));
+        assert_eq!(parse_fixed_offset("UTC+2"), Some(7200));
+        assert_eq!(parse_fixed_offset("gmt-03:00"), Some(-10800));
+        assert_eq!(parse_fixed_offset("Europe/Paris"), None);
+        assert_eq!(parse_fixed_offset("+25"), None);
+
+        let resolved = resolve(Some("-03:30"), JULY_2024).unwrap();
+        assert_eq!(resolved.offset_string(), "-03:30");
+    }
+
+    #[test]
+    fn test_posix_rules() {
+        let new_york = PosixTz::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(new_york.offset_at(JULY_2024), -4 * 3600);
+        assert_eq!(new_york.offset_at(JAN_2024), -5 * 3600);
+        // DST began 2024-03-10 at 02:00 EST = 07:00 UTC
+        assert_eq!(new_york.offset_at(1710054000), -4 * 3600);
+        assert_eq!(new_york.offset_at(1710054000 - 1), -5 * 3600);
+
+        let sydney = PosixTz::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(JAN_2024), 11 * 3600);
+        assert_eq!(sydney.offset_at(JULY_2024), 10 * 3600);
+
+        assert_eq!(PosixTz::parse("<+0530>-5:30").unwrap().offset_at(JULY_2024), 19800);
+    }
+
+    #[test]
+    fn test_zoneinfo_lookup() {
+        if !std::path::Path::new("/usr/share/zoneinfo/America/New_York").exists() {
+            return;
+        }
+        assert_eq!(resolve(Some("America/New_York"), JULY_2024).unwrap().offset_secs, -4 * 3600);
+        assert_eq!(resolve(Some("America/New_York"), JAN_2024).unwrap().offset_secs, -5 * 3600);
+        assert_eq!(resolve(Some("Asia/Kolkata"), JULY_2024).unwrap().offset_secs, 19800);
+        assert!(resolve(Some("Not/AZone"), JULY_2024).is_err());
+        assert!(resolve(Some("../../etc/passwd"), JULY_2024).is_err());
+    }
+}
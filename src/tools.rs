@@ -3,34 +3,105 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::process::Stdio;
 use tokio::process::Command;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use toml;
 use std::time::{Instant, Duration};
 use std::sync::Mutex as StdMutex;
 use once_cell::sync::Lazy;
 use std::collections::BTreeSet as StdBTreeSet;
 use tokio::sync::Mutex as TokioMutex;
+use tracing::{debug, warn};
 
-macro_rules! debug_println {
-    ($($arg:tt)*) => {
-        if std::env::var("BOBBAR_DEBUG").is_ok() {
-            println!($($arg)*);
-        }
-    };
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolsConfig {
+    pub tools: Tools,
 }
 
-#[allow(unused_macros)]
-macro_rules! debug_eprintln {
-    ($($arg:tt)*) => {
-        if std::env::var("BOBBAR_DEBUG").is_ok() {
-            eprintln!($($arg)*);
+/// Built-in tools that can be enabled under `builtin` in tools.json
+const BUILTIN_TOOLS: &[&str] = &[
+    "pdf_extract", "memory_store", "memory_search", "memory_get_discoveries", "memory_get_deadends",
+    "memory_get_insights", "memory_get_feedback", "memory_get_plan", "memory_get_by_agent", "memory_stats",
+    "web_search", "fetch_url", "current_date", "read_file", "calculate",
+];
+
+impl ToolsConfig {
+    /// Read tools.json, describing a syntax error by position and otherwise every
+    /// problem found by [ToolsConfig::problems] at once
+    pub fn load(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+        let config_str = std::fs::read_to_string(path)?;
+        let config: ToolsConfig = serde_json::from_str(&config_str)
+            .map_err(|e| crate::config::json_error("tools.json", &e))?;
+        crate::config::ensure_valid("tools.json", config.problems())?;
+        Ok(config)
+    }
+
+    /// Mistakes that parse fine but would break tools at runtime
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut names: Vec<&str> = Vec::new();
+        for tool in &self.tools.http {
+            if tool.name.trim().is_empty() {
+                problems.push("an HTTP tool has an empty \"name\"".to_string());
+            } else if names.contains(&tool.name.as_str()) {
+                problems.push(format!("tool \"{}\" is defined more than once", tool.name));
+            }
+            names.push(&tool.name);
+            if tool.endpoint.trim().is_empty() {
+                problems.push(format!("HTTP tool \"{}\" has an empty \"endpoint\"", tool.name));
+            }
+            match tool.body_format.as_str() {
+                "json" | "form" => {}
+                "raw" if tool.body_template.is_none() => problems.push(format!(
+                    "HTTP tool \"{}\" uses body_format \"raw\" but has no \"body_template\"", tool.name)),
+                "raw" => {}
+                other => problems.push(format!(
+                    "HTTP tool \"{}\" has unknown body_format \"{}\" (expected json, form or raw)", tool.name, other)),
+            }
+            if tool.max_calls_per_window == Some(0) {
+                problems.push(format!("HTTP tool \"{}\" has max_calls_per_window 0 (omit it to use the default delays)", tool.name));
+            }
+            if tool.rate_limit_window_secs == 0 {
+                problems.push(format!("HTTP tool \"{}\" has rate_limit_window_secs 0", tool.name));
+            }
+        }
+        for name in &self.tools.builtin {
+            if !BUILTIN_TOOLS.contains(&name.as_str()) {
+                problems.push(format!("unknown built-in tool \"{}\" (available: {})", name, BUILTIN_TOOLS.join(", ")));
+            } else if names.contains(&name.as_str()) {
+                problems.push(format!("tool \"{}\" is defined more than once", name));
+            } else if name == "read_file" && self.tools.allowed_read_dir.is_none() {
+                problems.push("built-in tool \"read_file\" needs \"allowed_read_dir\" set under \"tools\"".to_string());
+            }
+            names.push(name);
         }
-    };
-}
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct ToolsConfig {
-    pub tools: Tools,
+        let mut servers: Vec<&str> = Vec::new();
+        for server in &self.tools.mcp {
+            if servers.contains(&server.name.as_str()) {
+                problems.push(format!("MCP server \"{}\" is defined more than once", server.name));
+            }
+            servers.push(&server.name);
+            match server.transport.as_str() {
+                "stdio" if server.command.trim().is_empty() => problems.push(format!(
+                    "MCP server \"{}\" uses the stdio transport but has no \"command\"", server.name)),
+                "sse" | "http" if server.url.as_deref().is_none_or(|u| u.trim().is_empty()) => problems.push(format!(
+                    "MCP server \"{}\" uses the {} transport but has no \"url\"", server.name, server.transport)),
+                "stdio" | "sse" | "http" => {}
+                other => problems.push(format!(
+                    "MCP server \"{}\" has unknown transport \"{}\" (expected stdio, sse or http)", server.name, other)),
+            }
+        }
+        problems
+    }
+
+    /// Whether an agent's `available_tools` entry names a configured tool. MCP tools are
+    /// `server:tool` and only discovered once connected, so just the server is checked.
+    pub fn provides(&self, name: &str) -> bool {
+        if let Some((server, _)) = name.split_once(':') {
+            return self.tools.mcp.iter().any(|s| s.name == server);
+        }
+        self.tools.http.iter().any(|t| t.name == name) || self.tools.builtin.iter().any(|b| b == name)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -39,6 +110,368 @@ pub struct Tools {
     pub mcp: Vec<McpServer>,
     #[serde(default)]
     pub builtin: Vec<String>, // List of built-in tools to enable
+    #[serde(default)]
+    pub web_search: WebSearchConfig,  // Backend for the built-in web_search tool
+    #[serde(default)]
+    pub local_pdf_dir: Option<String>,  // If set, pdf_extract only reads local files inside this directory
+    #[serde(default)]
+    pub allowed_read_dir: Option<String>,  // Directory read_file may read from; read_file is refused without it
+    #[serde(default = "default_max_result_bytes")]
+    pub max_result_bytes: usize,  // Cap on HTTP, MCP and pdf_extract results without their own limit (0 = no limit)
+}
+
+fn default_max_result_bytes() -> usize {
+    100_000
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Tools {
+            http: Vec::new(),
+            mcp: Vec::new(),
+            builtin: Vec::new(),
+            web_search: WebSearchConfig::default(),
+            local_pdf_dir: None,
+            allowed_read_dir: None,
+            max_result_bytes: default_max_result_bytes(),
+        }
+    }
+}
+
+/// `text` cut to at most `max_bytes` (0 = no limit), ending with a marker saying how much
+/// was dropped
+fn cap_text(text: String, max_bytes: usize) -> String {
+    if max_bytes == 0 || text.len() <= max_bytes {
+        return text;
+    }
+    let kept = crate::ollama::truncate_at_char_boundary(&text, max_bytes);
+    format!("{}\n[truncated {} bytes]", kept, text.len() - kept.len())
+}
+
+/// A tool result cut down to about `max_bytes` (0 = no limit) before it reaches the model.
+/// Plain text and `{"text": ...}` results keep their shape; other JSON is replaced by its
+/// truncated serialization.
+fn cap_result(result: Value, max_bytes: usize) -> Value {
+    if max_bytes == 0 {
+        return result;
+    }
+    match result {
+        Value::String(text) => Value::String(cap_text(text, max_bytes)),
+        Value::Object(mut obj) if obj.get("text").is_some_and(Value::is_string) => {
+            if let Some(Value::String(text)) = obj.remove("text") {
+                obj.insert("text".to_string(), Value::String(cap_text(text, max_bytes)));
+            }
+            Value::Object(obj)
+        }
+        other => {
+            let serialized = serde_json::to_string(&other).unwrap_or_default();
+            if serialized.len() <= max_bytes {
+                other
+            } else {
+                Value::String(cap_text(serialized, max_bytes))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebSearchConfig {
+    #[serde(default = "default_search_backend")]
+    pub backend: String,  // "duckduckgo" (default) or "searxng"
+    #[serde(default)]
+    pub searxng_url: Option<String>,  // Base URL of a SearXNG instance with the JSON format enabled
+    #[serde(default = "default_search_max_results")]
+    pub max_results: usize,
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: "duckduckgo".to_string(),
+            searxng_url: None,
+            max_results: 8,
+        }
+    }
+}
+
+fn default_search_backend() -> String {
+    "duckduckgo".to_string()
+}
+
+fn default_search_max_results() -> usize {
+    8
+}
+
+const DUCKDUCKGO_HTML_URL: &str = "https://html.duckduckgo.com/html/";
+
+static DDG_RESULT_LINK: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"(?s)<a[^>]*class="result__a"[^>]*>.*?</a>"#).unwrap()
+});
+static DDG_SNIPPET: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"(?s)class="result__snippet"[^>]*>(.*?)</(?:a|div|td)>"#).unwrap()
+});
+static HTML_HREF: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"href="([^"]*)""#).unwrap()
+});
+static HTML_TAG: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"<[^>]+>").unwrap()
+});
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Strip tags and decode the handful of entities DuckDuckGo emits
+fn html_to_text(html: &str) -> String {
+    let text = decode_html_entities(&HTML_TAG.replace_all(html, ""));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Resolve a `file://` URL or bare path for pdf_extract, refusing anything that escapes
+/// `allowed_dir` once symlinks and `..` components are resolved
+fn resolve_local_pdf_path(source: &str, allowed_dir: Option<&str>) -> Result<std::path::PathBuf, anyhow::Error> {
+    let path = if source.starts_with("file://") {
+        reqwest::Url::parse(source)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid file URL: {}", source))?
+    } else {
+        std::path::PathBuf::from(source)
+    };
+
+    let path = std::fs::canonicalize(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot read PDF at {}: {}", path.display(), e))?;
+
+    if let Some(dir) = allowed_dir {
+        let root = std::fs::canonicalize(dir)
+            .map_err(|e| anyhow::anyhow!("Configured local_pdf_dir {} is not accessible: {}", dir, e))?;
+        if !path.starts_with(&root) {
+            return Err(anyhow::anyhow!("{} is outside the allowed PDF directory {}", path.display(), root.display()));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Resolve a path for read_file. Relative paths are taken from `allowed_dir`, and anything
+/// that resolves outside it (via `..` or symlinks) is refused.
+fn resolve_read_path(requested: &str, allowed_dir: Option<&str>) -> Result<std::path::PathBuf, anyhow::Error> {
+    let dir = allowed_dir
+        .ok_or_else(|| anyhow::anyhow!("read_file is disabled: set allowed_read_dir under tools in tools.json"))?;
+    let root = std::fs::canonicalize(dir)
+        .map_err(|e| anyhow::anyhow!("Configured allowed_read_dir {} is not accessible: {}", dir, e))?;
+
+    let path = root.join(requested.trim());
+    let path = std::fs::canonicalize(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    if !path.starts_with(&root) {
+        return Err(anyhow::anyhow!("{} is outside the allowed directory {}", path.display(), root.display()));
+    }
+    Ok(path)
+}
+
+/// Text of a file read by read_file, cut to at most `max_bytes` on a character boundary.
+/// Returns whether it was truncated; fails for files that aren't UTF-8 text.
+fn decode_text_file(mut bytes: Vec<u8>, max_bytes: usize) -> Result<(String, bool), anyhow::Error> {
+    let truncated = bytes.len() > max_bytes;
+    bytes.truncate(max_bytes);
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok((text, truncated)),
+        // The cut landed inside a multi-byte character: drop the partial character
+        Err(e) if truncated && e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            Ok((String::from_utf8(bytes)?, true))
+        }
+        Err(_) => Err(anyhow::anyhow!("File is not UTF-8 text")),
+    }
+}
+
+struct PdfExtraction {
+    text: String,
+    page_count: u32,
+    start_page: u32,
+    end_page: u32,
+}
+
+/// Clamp a requested 1-based page range to the document, defaulting to every page
+fn resolve_page_range(start: Option<u32>, end: Option<u32>, page_count: u32) -> Result<(u32, u32), anyhow::Error> {
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(page_count).min(page_count);
+    if start > page_count {
+        return Err(anyhow::anyhow!("start_page {} is past the end of the document ({} pages)", start, page_count));
+    }
+    if start > end {
+        return Err(anyhow::anyhow!("start_page {} is after end_page {}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Extract text from a PDF, either whole or only pages `start..=end`
+fn extract_pdf_text(bytes: &[u8], start: Option<u32>, end: Option<u32>) -> Result<PdfExtraction, anyhow::Error> {
+    let mut doc = pdf_extract::Document::load_mem(bytes)?;
+    if doc.is_encrypted() {
+        doc.decrypt("")?;
+    }
+    let page_count = doc.get_pages().len() as u32;
+
+    if start.is_none() && end.is_none() {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::new(&mut text))?;
+        return Ok(PdfExtraction { text, page_count, start_page: 1, end_page: page_count });
+    }
+
+    let (start_page, end_page) = resolve_page_range(start, end, page_count)?;
+    let mut text = String::new();
+    for page in start_page..=end_page {
+        let mut output = pdf_extract::PlainTextOutput::new(&mut text);
+        pdf_extract::output_doc_page(&doc, &mut output, page)?;
+    }
+
+    Ok(PdfExtraction { text, page_count, start_page, end_page })
+}
+
+/// Maximum bytes downloaded by fetch_url before the body is cut off
+const FETCH_URL_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// Default cap on the readable text fetch_url returns to the model
+const FETCH_URL_MAX_CHARS: usize = 20_000;
+
+/// Largest slice of a file read_file returns; longer files are cut off and flagged
+const READ_FILE_MAX_BYTES: usize = 256 * 1024;
+
+static HTML_BOILERPLATE: Lazy<Vec<regex::Regex>> = Lazy::new(|| {
+    ["script", "style", "noscript", "svg", "nav", "header", "footer", "aside", "form", "iframe"]
+        .iter()
+        .map(|tag| regex::Regex::new(&format!(r"(?is)<{0}\b[^>]*>.*?</{0}>", tag)).unwrap())
+        .collect()
+});
+static HTML_COMMENT: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"(?s)<!--.*?-->").unwrap());
+static HTML_TITLE: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+static HTML_OG_TITLE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"(?is)<meta[^>]*property="og:title"[^>]*content="([^"]*)""#).unwrap()
+});
+static HTML_MAIN_CONTENT: Lazy<Vec<regex::Regex>> = Lazy::new(|| {
+    ["article", "main", "body"]
+        .iter()
+        .map(|tag| regex::Regex::new(&format!(r"(?is)<{0}\b[^>]*>(.*)</{0}>", tag)).unwrap())
+        .collect()
+});
+static HTML_LINE_BREAK: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"(?i)<br\s*/?>").unwrap());
+static HTML_LIST_ITEM: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"(?i)<li\b[^>]*>").unwrap());
+static HTML_BLOCK_END: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"(?i)</(p|div|h[1-6]|li|tr|section|article|blockquote|pre|table|ul|ol|dd|dt)>").unwrap()
+});
+
+/// Readability-style extraction: drop page chrome, keep the article/main body as plain
+/// paragraphs. Returns (title, text).
+fn extract_readable_text(html: &str) -> (String, String) {
+    let title = HTML_OG_TITLE.captures(html)
+        .or_else(|| HTML_TITLE.captures(html))
+        .map(|caps| html_to_text(&caps[1]))
+        .unwrap_or_default();
+
+    let mut cleaned = HTML_COMMENT.replace_all(html, "").into_owned();
+    for re in HTML_BOILERPLATE.iter() {
+        cleaned = re.replace_all(&cleaned, "").into_owned();
+    }
+
+    let content = HTML_MAIN_CONTENT.iter()
+        .find_map(|re| re.captures(&cleaned).map(|caps| caps[1].to_string()))
+        .unwrap_or(cleaned);
+
+    let content = HTML_LINE_BREAK.replace_all(&content, "\n");
+    let content = HTML_LIST_ITEM.replace_all(&content, "\n- ");
+    let content = HTML_BLOCK_END.replace_all(&content, "\n\n");
+    let content = decode_html_entities(&HTML_TAG.replace_all(&content, ""));
+
+    // Collapse runs of whitespace inside lines and blank lines between paragraphs
+    let mut text = String::new();
+    let mut blank_pending = false;
+    for line in content.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank_pending = !text.is_empty();
+            continue;
+        }
+        if blank_pending {
+            text.push_str("\n\n");
+        } else if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&line);
+        blank_pending = false;
+    }
+
+    (title, text)
+}
+
+/// DuckDuckGo wraps result links in a redirect (`//duckduckgo.com/l/?uddg=<target>`)
+fn resolve_duckduckgo_link(href: &str) -> String {
+    let href = href.replace("&amp;", "&");
+    let absolute = if href.starts_with("//") { format!("https:{}", href) } else { href.clone() };
+    if let Ok(url) = reqwest::Url::parse(&absolute) {
+        if url.path().starts_with("/l/") {
+            if let Some((_, target)) = url.query_pairs().find(|(k, _)| k == "uddg") {
+                return target.into_owned();
+            }
+        }
+    }
+    absolute
+}
+
+fn parse_duckduckgo_html(html: &str, max_results: usize) -> Vec<Value> {
+    let links: Vec<regex::Match> = DDG_RESULT_LINK.find_iter(html).collect();
+    let mut results = Vec::new();
+
+    for (i, link) in links.iter().enumerate() {
+        let href = match HTML_HREF.captures(link.as_str()) {
+            Some(caps) => caps[1].to_string(),
+            None => continue,
+        };
+        // Sponsored results go through an ad redirect
+        if href.contains("duckduckgo.com/y.js") {
+            continue;
+        }
+
+        // The snippet sits between this result's link and the next one
+        let segment_end = links.get(i + 1).map(|next| next.start()).unwrap_or(html.len());
+        let snippet = DDG_SNIPPET.captures(&html[link.end()..segment_end])
+            .map(|caps| html_to_text(&caps[1]))
+            .unwrap_or_default();
+
+        results.push(json!({
+            "title": html_to_text(link.as_str()),
+            "url": resolve_duckduckgo_link(&href),
+            "snippet": snippet
+        }));
+        if results.len() >= max_results {
+            break;
+        }
+    }
+
+    results
+}
+
+fn parse_searxng_results(response: &Value, max_results: usize) -> Vec<Value> {
+    response.get("results")
+        .and_then(|r| r.as_array())
+        .map(|items| items.iter()
+            .take(max_results)
+            .map(|item| json!({
+                "title": item.get("title").and_then(|v| v.as_str()).unwrap_or(""),
+                "url": item.get("url").and_then(|v| v.as_str()).unwrap_or(""),
+                "snippet": item.get("content").and_then(|v| v.as_str()).unwrap_or("")
+            }))
+            .collect())
+        .unwrap_or_default()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -54,39 +487,367 @@ pub struct HttpTool {
     pub headers: HashMap<String, String>,
     pub response_format: String,
     #[serde(default)]
-    pub response_path: Option<String>,  // Optional JSON path to extract from response (e.g., "data.results[0].value")
+    pub response_path: Option<String>,  // Optional JSON path to extract from response (e.g., "data.results[0].value" or "results[*].title")
     #[serde(default = "default_expected_status")]
-    pub expected_status: Vec<String>,  // Expected successful status codes (default: ["2xx"]) - supports wildcards like "2xx", "3xx" or specific codes like "200"
+    pub expected_status: Vec<String>,  // Expected successful status codes (default: ["2xx", "3xx"]) - supports wildcards like "2xx", ranges like "200-299" or specific codes like "200"
     #[serde(default)]
     pub acceptable_status: Vec<String>,  // Acceptable status codes to ignore result (empty response) - supports wildcards
     #[serde(default)]
     pub error_status: Vec<String>,  // Status codes that should throw detailed errors (if empty, all non-expected are errors) - supports wildcards
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,  // Per-request timeout covering connect, send and body read (default: 30)
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,  // How long identical calls reuse a cached response (0 = no caching, default: 0)
+    #[serde(default)]
+    pub retry_count: u32,  // Extra attempts after a retryable status (default: 0 = never retry)
+    #[serde(default = "default_retry_on_status")]
+    pub retry_on_status: Vec<String>,  // Status codes that trigger a retry (default: ["429", "502", "503", "504"]) - supports wildcards and ranges
+    #[serde(default = "default_body_format")]
+    pub body_format: String,  // How params are sent for body methods: "json", "form" (x-www-form-urlencoded) or "raw" (default: "json")
+    #[serde(default)]
+    pub body_template: Option<String>,  // Body for "raw" format with {param} placeholders (e.g. "<query>{q}</query>")
+    #[serde(default)]
+    pub max_calls_per_window: Option<u32>,  // Call budget per rate-limit window; calls beyond it are spaced out to fit (default: none = progressive delay ladder)
+    #[serde(default = "default_rate_limit_window_secs")]
+    pub rate_limit_window_secs: u64,  // Window the call budget refills over, or the delay ladder resets after (default: 60)
+    #[serde(default)]
+    pub rate_limit_group: Option<String>,  // Tools in the same group share one rate-limit budget (default: the endpoint's hostname)
+    #[serde(default)]
+    pub max_result_bytes: Option<usize>,  // Results longer than this are truncated (default: tools.max_result_bytes, 0 = no limit)
+}
+
+impl HttpTool {
+    /// Key this tool's calls are counted under for rate limiting
+    fn rate_limit_key(&self) -> String {
+        match self.rate_limit_group.as_deref().map(str::trim) {
+            Some(group) if !group.is_empty() => group.to_string(),
+            _ => {
+                let host = host_from_url(&self.endpoint);
+                if host.is_empty() { self.name.clone() } else { host }
+            }
+        }
+    }
 }
 
 fn default_expected_status() -> Vec<String> {
     vec!["2xx".to_string(), "3xx".to_string()]
 }
 
-/// Check if a status code matches a pattern (supports wildcards like "2xx" or specific codes like "200")
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    0
+}
+
+fn default_rate_limit_window_secs() -> u64 {
+    60
+}
+
+fn default_body_format() -> String {
+    "json".to_string()
+}
+
+fn default_retry_on_status() -> Vec<String> {
+    ["429", "502", "503", "504"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Longest we will wait between retries, whatever the server asks for.
+const MAX_RETRY_DELAY_SECS: u64 = 60;
+
+/// Delay before retry number `attempt` (0-based). A `Retry-After` header given
+/// in seconds wins; otherwise back off exponentially from one second.
+fn retry_delay(attempt: u32, retry_after: Option<&str>) -> Duration {
+    let secs = retry_after
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| 1u64 << attempt.min(6));
+    Duration::from_secs(secs.min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Maximum number of HTTP tool responses kept in the response cache
+const RESPONSE_CACHE_CAPACITY: usize = 256;
+
+struct CachedResponse {
+    value: Value,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+/// Small LRU cache of successful HTTP tool responses, keyed by tool name + sorted params
+#[derive(Default)]
+struct ResponseCache {
+    entries: HashMap<String, CachedResponse>,
+    clock: u64,
+}
+
+impl ResponseCache {
+    fn key(tool_name: &str, params: &HashMap<String, String>) -> String {
+        let sorted: std::collections::BTreeMap<&String, &String> = params.iter().collect();
+        format!("{}|{}", tool_name, serde_json::to_string(&sorted).unwrap_or_default())
+    }
+
+    fn get(&mut self, key: &str, ttl: Duration) -> Option<Value> {
+        self.clock += 1;
+        let clock = self.clock;
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.stored_at.elapsed() < ttl => {
+                entry.last_used = clock;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: String, value: Value) {
+        self.clock += 1;
+        if self.entries.len() >= RESPONSE_CACHE_CAPACITY && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CachedResponse { value, stored_at: Instant::now(), last_used: self.clock });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Check if a status code matches a pattern: a specific code ("200"), a class wildcard
+/// ("2xx", case-insensitive) or an inclusive range ("500-599")
 fn status_matches(status_code: u16, pattern: &str) -> bool {
+    let pattern = pattern.trim();
+
     // Check for exact match first
     if let Ok(exact) = pattern.parse::<u16>() {
         return status_code == exact;
     }
 
+    // Inclusive ranges like "500-599"
+    if let Some((low, high)) = pattern.split_once('-') {
+        return match (low.trim().parse::<u16>(), high.trim().parse::<u16>()) {
+            (Ok(low), Ok(high)) => (low..=high).contains(&status_code),
+            _ => false,
+        };
+    }
+
     // Check for wildcard patterns like "2xx", "3xx", etc.
-    if pattern.ends_with("xx") && pattern.len() == 3 {
-        if let Some(prefix) = pattern.chars().next() {
-            if let Some(digit) = prefix.to_digit(10) {
-                let status_hundreds = status_code / 100;
-                return status_hundreds == digit as u16;
-            }
+    let lower = pattern.to_ascii_lowercase();
+    if lower.len() == 3 && lower.ends_with("xx") {
+        if let Some(digit) = lower.chars().next().and_then(|c| c.to_digit(10)) {
+            return status_code / 100 == digit as u16;
         }
     }
 
     false
 }
 
+/// How an HTTP tool response should be handled based on its status code
+#[derive(Debug, PartialEq)]
+enum StatusDisposition {
+    Success,
+    Ignore,
+    Error,
+}
+
+/// Precedence: an explicit `error_status` match always wins, then `acceptable_status`,
+/// then `expected_status`. Anything else is an error unless `error_status` is set, in
+/// which case only the listed codes are errors.
+fn classify_status(status_code: u16, tool: &HttpTool) -> StatusDisposition {
+    if status_in_list(status_code, &tool.error_status) {
+        StatusDisposition::Error
+    } else if status_in_list(status_code, &tool.acceptable_status) {
+        StatusDisposition::Ignore
+    } else if status_in_list(status_code, &tool.expected_status) || !tool.error_status.is_empty() {
+        StatusDisposition::Success
+    } else {
+        StatusDisposition::Error
+    }
+}
+
+/// Methods whose parameters are sent in the query string rather than a request body
+fn sends_params_as_query(method: &reqwest::Method) -> bool {
+    matches!(
+        *method,
+        reqwest::Method::GET | reqwest::Method::HEAD | reqwest::Method::DELETE | reqwest::Method::OPTIONS
+    )
+}
+
+/// Render a parameter value the way it appears in a URL, form field or raw body
+fn param_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => serde_json::to_string(value).unwrap_or_default()
+    }
+}
+
+/// Fill `{param}` placeholders in a raw body template in one pass. Declared parameters that
+/// were not supplied are replaced with an empty string; other braces are left alone.
+/// Substituted values are not scanned again, so a value containing `{name}` stays as is.
+fn render_body_template(template: &str, tool: &HttpTool, params: &HashMap<String, Value>) -> String {
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        body.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let name = after.find('}').map(|close| &after[..close]);
+        let value = name.and_then(|name| match params.get(name) {
+            Some(value) => Some(param_to_string(value)),
+            None => tool.parameters.contains_key(name).then(String::new),
+        });
+        match (name, value) {
+            (Some(name), Some(value)) => {
+                body.push_str(&value);
+                rest = &after[name.len() + 1..];
+            }
+            _ => {
+                body.push('{');
+                rest = after;
+            }
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+/// Attach tool parameters to a request: query string for GET/HEAD/DELETE/OPTIONS,
+/// otherwise a body in the tool's `body_format` (JSON, form-urlencoded or a raw template)
+fn attach_params(
+    request_builder: reqwest::RequestBuilder,
+    method: &reqwest::Method,
+    tool: &HttpTool,
+    params: &HashMap<String, Value>,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    let string_params = || -> HashMap<String, String> {
+        params.iter().map(|(k, v)| (k.clone(), param_to_string(v))).collect()
+    };
+
+    if sends_params_as_query(method) {
+        let query_params = string_params();
+        debug!("[HTTP] Adding query parameters: {:?}", query_params);
+        return Ok(request_builder.query(&query_params));
+    }
+
+    match tool.body_format.trim().to_ascii_lowercase().as_str() {
+        "json" => {
+            debug!("[HTTP] Sending JSON body: {:?}", params);
+            Ok(request_builder.json(params))
+        }
+        "form" => {
+            let form_params = string_params();
+            debug!("[HTTP] Sending form body: {:?}", form_params);
+            Ok(request_builder.form(&form_params))
+        }
+        "raw" => {
+            let template = tool.body_template.as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Tool '{}' uses body_format \"raw\" but has no body_template", tool.name))?;
+            let body = render_body_template(template, tool, params);
+            debug!("[HTTP] Sending raw body: {}", body);
+            // Headers from the tool definition decide the type; plain text otherwise
+            let has_content_type = tool.headers.keys().any(|k| k.eq_ignore_ascii_case("content-type"));
+            let request_builder = if has_content_type {
+                request_builder
+            } else {
+                request_builder.header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            };
+            Ok(request_builder.body(body))
+        }
+        other => Err(anyhow::anyhow!(
+            "Unsupported body_format '{}' for tool '{}' (expected json, form or raw)",
+            other, tool.name
+        )),
+    }
+}
+
+/// Extract the value at `path` from a JSON response. Segments are dotted field names,
+/// each optionally followed by bracket selectors: `[2]` picks one element, `[*]` maps over
+/// every element and `[1:3]` (either bound optional) over a slice. Once a wildcard or slice
+/// is applied the result is an array of everything the rest of the path matches, flattened
+/// across nested wildcards, and elements the rest of the path doesn't match are skipped.
+fn extract_json_path(json: &Value, path: &str) -> Result<Value, anyhow::Error> {
+    debug!("[JSON] Extracting path: {} from response", path);
+
+    let mut current = vec![json.clone()];
+    // Whether a wildcard or slice has turned `current` into a list of matches
+    let mut multi = false;
+
+    for part in path.split('.') {
+        let (field_name, mut selectors) = part.split_at(part.find('[').unwrap_or(part.len()));
+
+        if !field_name.is_empty() {
+            current = if multi {
+                current.iter().filter_map(|v| v.get(field_name).cloned()).collect()
+            } else {
+                let value = current[0].get(field_name)
+                    .ok_or_else(|| anyhow::anyhow!("Field '{}' not found in JSON", field_name))?;
+                vec![value.clone()]
+            };
+        }
+
+        while !selectors.is_empty() {
+            let close = selectors.find(']')
+                .filter(|_| selectors.starts_with('['))
+                .ok_or_else(|| anyhow::anyhow!("Malformed array selector in '{}'", part))?;
+            let selector = selectors[1..close].trim();
+            selectors = &selectors[close + 1..];
+
+            let elements = |value: &Value| -> Result<Vec<Value>, anyhow::Error> {
+                match value.as_array() {
+                    Some(items) => Ok(items.clone()),
+                    None if multi => Ok(Vec::new()),
+                    None => Err(anyhow::anyhow!("Expected array at '{}'", part)),
+                }
+            };
+
+            if selector == "*" {
+                current = current.iter().map(elements).collect::<Result<Vec<_>, _>>()?.concat();
+                multi = true;
+            } else if let Some((from, to)) = selector.split_once(':') {
+                let bound = |b: &str| -> Result<Option<usize>, anyhow::Error> {
+                    let b = b.trim();
+                    if b.is_empty() {
+                        return Ok(None);
+                    }
+                    b.parse::<usize>().map(Some).map_err(|_| anyhow::anyhow!("Invalid slice bound: {}", b))
+                };
+                let (from, to) = (bound(from)?, bound(to)?);
+                let mut sliced = Vec::new();
+                for value in &current {
+                    let items = elements(value)?;
+                    let end = to.unwrap_or(items.len()).min(items.len());
+                    let begin = from.unwrap_or(0).min(end);
+                    sliced.extend_from_slice(&items[begin..end]);
+                }
+                current = sliced;
+                multi = true;
+            } else if let Ok(index) = selector.parse::<usize>() {
+                current = if multi {
+                    current.iter().filter_map(|v| v.as_array()?.get(index).cloned()).collect()
+                } else {
+                    let value = elements(&current[0])?
+                        .get(index)
+                        .cloned()
+                        .ok_or_else(|| anyhow::anyhow!("Index {} out of bounds", index))?;
+                    vec![value]
+                };
+            } else {
+                return Err(anyhow::anyhow!("Invalid array index: {}", selector));
+            }
+        }
+    }
+
+    debug!("[JSON] Successfully extracted value from path: {}", path);
+    Ok(if multi { Value::Array(current) } else { current.swap_remove(0) })
+}
+
 /// Check if status code matches any pattern in the list
 fn status_in_list(status_code: u16, patterns: &[String]) -> bool {
     patterns.iter().any(|pattern| status_matches(status_code, pattern))
@@ -102,22 +863,132 @@ pub struct ParameterDef {
     pub default: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct McpServer {
     pub name: String,
-    pub transport: String,
+    pub transport: String,  // "stdio", "sse" or "http"
+    #[serde(default)]
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
     pub description: String,
+    #[serde(default)]
     pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub url: Option<String>,  // Endpoint for "sse" and "http" transports
+    #[serde(default)]
+    pub max_result_bytes: Option<usize>,  // Cap on this server's tool results (default: tools.max_result_bytes, 0 = no limit)
 }
 
 #[derive(Debug)]
-pub struct McpConnection {
-    #[allow(dead_code)]
-    process: tokio::process::Child,
-    stdin: tokio::process::ChildStdin,
-    stdout: BufReader<tokio::process::ChildStdout>,
+pub enum McpConnection {
+    /// Local process speaking newline-delimited JSON-RPC over stdin/stdout
+    Stdio {
+        #[allow(dead_code)]
+        process: tokio::process::Child,
+        stdin: tokio::process::ChildStdin,
+        stdout: BufReader<tokio::process::ChildStdout>,
+    },
+    /// Server-sent event stream for reads, JSON-RPC POSTed to the announced endpoint for writes
+    Sse {
+        post_url: String,
+        events: tokio::sync::mpsc::UnboundedReceiver<Value>,
+    },
+    /// Streamable HTTP: each POST answers with JSON or a short SSE stream
+    Http {
+        url: String,
+        session_id: Option<String>,
+        pending: std::collections::VecDeque<Value>,
+    },
+}
+
+/// How long to wait for an SSE server to announce its message endpoint
+const MCP_SSE_ENDPOINT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq)]
+struct SseEvent {
+    event: String,
+    data: String,
+}
+
+/// Incremental parser for `text/event-stream` bodies that may arrive in arbitrary chunks
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    event: String,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    let event = if self.event.is_empty() { "message".to_string() } else { self.event.clone() };
+                    events.push(SseEvent { event, data: self.data.join("\n") });
+                }
+                self.event.clear();
+                self.data.clear();
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;  // Comment / keep-alive
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => self.event = value.to_string(),
+                "data" => self.data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// MCP servers to disconnect and to (re)connect when the configuration changes from `old`
+/// to `new`, matched by name: removed and edited servers are stopped, added and edited
+/// ones started
+fn mcp_server_changes(old: &[McpServer], new: &[McpServer]) -> (Vec<String>, Vec<McpServer>) {
+    let stop = old.iter()
+        .filter(|server| !new.contains(server))
+        .map(|server| server.name.clone())
+        .collect();
+    let start = new.iter()
+        .filter(|server| !old.contains(server))
+        .cloned()
+        .collect();
+    (stop, start)
+}
+
+/// Extract JSON-RPC messages from a streamable HTTP response body
+fn parse_mcp_http_body(content_type: &str, body: &str) -> Vec<Value> {
+    if content_type.starts_with("text/event-stream") {
+        let mut parser = SseParser::default();
+        let mut events = parser.feed(body.as_bytes());
+        events.extend(parser.feed(b"\n\n"));
+        events.into_iter()
+            .filter(|e| e.event == "message")
+            .filter_map(|e| serde_json::from_str(&e.data).ok())
+            .collect()
+    } else if body.trim().is_empty() {
+        Vec::new()
+    } else {
+        match serde_json::from_str::<Value>(body) {
+            Ok(Value::Array(batch)) => batch,
+            Ok(message) => vec![message],
+            Err(_) => Vec::new(),
+        }
+    }
 }
 
 // Track tool usage for rate limiting
@@ -125,6 +996,53 @@ pub struct McpConnection {
 struct ToolUsage {
     last_call: Instant,
     call_count: usize,
+    tokens: f64,  // Remaining call budget for tools with max_calls_per_window; negative when calls are queued
+}
+
+impl ToolUsage {
+    fn new(now: Instant, budget: Option<u32>) -> Self {
+        ToolUsage { last_call: now, call_count: 0, tokens: budget.unwrap_or(0) as f64 }
+    }
+
+    /// Progressive delay: 0ms, 100ms, 250ms, 500ms, 1000ms, then cap at 1500ms, for calls
+    /// less than `window` apart. Less aggressive to support research mode with many
+    /// parallel workers.
+    fn ladder_delay(&mut self, now: Instant, window: Duration) -> u64 {
+        let elapsed = now.duration_since(self.last_call);
+        self.last_call = now;
+        if elapsed > window || self.call_count == 0 {
+            // Reset counter if outside window
+            self.call_count = 1;
+            return 0; // No delay for first call in window
+        }
+
+        self.call_count += 1;
+        match self.call_count {
+            1 => 0,
+            2 => 100,
+            3 => 250,
+            4 => 500,
+            5 => 1000,
+            _ => 1500, // Cap at 1.5 seconds
+        }
+    }
+
+    /// Token bucket holding up to `max_calls`, refilled evenly over `window`. A call that
+    /// finds the bucket empty still takes a token (going negative) and waits until that
+    /// token would have refilled, so concurrent callers queue behind each other instead of
+    /// all waking at once.
+    fn budget_delay(&mut self, now: Instant, max_calls: u32, window: Duration) -> u64 {
+        let per_sec = max_calls as f64 / window.as_secs_f64().max(1.0);
+        let refill = now.saturating_duration_since(self.last_call).as_secs_f64() * per_sec;
+        self.tokens = (self.tokens + refill).min(max_calls as f64) - 1.0;
+        self.last_call = now;
+        self.call_count += 1;
+        if self.tokens >= 0.0 {
+            0
+        } else {
+            (-self.tokens / per_sec * 1000.0).ceil() as u64
+        }
+    }
 }
 
 pub struct ToolExecutor {
@@ -133,10 +1051,12 @@ pub struct ToolExecutor {
     mcp_connections: TokioMutex<HashMap<String, McpConnection>>,  // Tokio mutex for async-safe access
     mcp_tools: StdMutex<HashMap<String, Vec<McpTool>>>,  // Store discovered MCP tools per server
     api_keys: HashMap<String, String>,
+    env_file_keys: HashMap<String, String>,  // From api_keys.toml's `env_file`; below api_keys and the environment
     tool_usage: StdMutex<HashMap<String, ToolUsage>>,  // Track usage per tool (with interior mutability)
     shared_memory: Option<std::sync::Arc<crate::shared_memory::SharedMemory>>,  // Optional shared memory for research mode
     query_id: StdMutex<Option<String>>,  // Current research query ID for tracking history
     agent_name: StdMutex<Option<String>>,  // Current agent name for tool call tracking
+    response_cache: StdMutex<ResponseCache>,  // Cached HTTP tool responses for the current session
 }
 
 // Track current HTTP sources for UI verbosity
@@ -185,10 +1105,12 @@ impl ToolExecutor {
             mcp_connections: TokioMutex::new(HashMap::new()),
             mcp_tools: StdMutex::new(HashMap::new()),
             api_keys,
+            env_file_keys: HashMap::new(),
             tool_usage: StdMutex::new(HashMap::new()),
             shared_memory: None,
             query_id: StdMutex::new(None),
             agent_name: StdMutex::new(None),
+            response_cache: StdMutex::new(ResponseCache::default()),
         };
 
         // Register built-in tools
@@ -205,6 +1127,10 @@ impl ToolExecutor {
         if let Ok(mut id) = self.query_id.lock() {
             *id = Some(query_id);
         }
+        // A new research session starts with fresh responses
+        if let Ok(mut cache) = self.response_cache.lock() {
+            cache.clear();
+        }
     }
 
     pub fn set_agent_name(&mut self, agent_name: String) {
@@ -217,6 +1143,35 @@ impl ToolExecutor {
         self.query_id.lock().ok().and_then(|id| id.clone())
     }
 
+    /// Build the error for a timed-out HTTP tool, recording the endpoint as a deadend
+    /// in shared memory so other research workers don't retry it
+    async fn timeout_error(&self, tool: &HttpTool, endpoint: &str) -> anyhow::Error {
+        let host = host_from_url(endpoint);
+        let message = format!(
+            "HTTP tool '{}' timed out after {}s waiting for {}. Treat this source as a dead end and try another.",
+            tool.name, tool.timeout_secs, host
+        );
+        warn!("{}", message);
+        crate::progress::log_with(crate::progress::Kind::Http, format!("HTTP {} timed out [tool: {}]", host, tool.name));
+
+        if let Some(ref memory) = self.shared_memory {
+            let agent_name = self.agent_name.lock().ok().and_then(|n| n.clone()).unwrap_or_else(|| "unknown".to_string());
+            let mut metadata = HashMap::new();
+            metadata.insert("tool".to_string(), tool.name.clone());
+            metadata.insert("endpoint".to_string(), endpoint.to_string());
+            if let Err(e) = memory.store_memory(
+                crate::shared_memory::MemoryType::Deadend,
+                format!("{} timed out after {}s via tool '{}'", endpoint, tool.timeout_secs, tool.name),
+                agent_name,
+                Some(metadata),
+            ).await {
+                debug!("[HTTP] Could not record timeout deadend: {}", e);
+            }
+        }
+
+        anyhow::anyhow!(message)
+    }
+
     /// Record a tool call to shared memory (non-blocking)
     async fn record_tool_call(&self, tool_type: &str, tool_name: &str, parameters: &str) {
         if let Some(ref memory) = self.shared_memory {
@@ -237,7 +1192,7 @@ impl ToolExecutor {
     fn register_builtin_tools(&mut self) {
         // Check which built-in tools are enabled
         for tool_name in &self.config.tools.builtin {
-            debug_println!("[BuiltIn] Registering built-in tool: {}", tool_name);
+            debug!("[BuiltIn] Registering built-in tool: {}", tool_name);
         }
     }
 
@@ -246,66 +1201,125 @@ impl ToolExecutor {
         self.config.tools.builtin.contains(&tool_name.to_string())
     }
 
-    pub fn from_file(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+    /// Executor for tools.json in config directory `config_dir`, with API keys from the
+    /// api_keys.toml beside it
+    pub fn from_file(config_dir: &std::path::Path) -> Result<Self, anyhow::Error> {
         // Load tools config
-        let config_str = std::fs::read_to_string(path)?;
-        let config: ToolsConfig = serde_json::from_str(&config_str)?;
+        let config = ToolsConfig::load(&config_dir.join("tools.json"))?;
 
-        // Load API keys from config directory
-        let config_dir = crate::config::Config::get_config_dir();
         let api_keys_path = config_dir.join("api_keys.toml");
 
         let api_keys = load_api_keys(&api_keys_path).unwrap_or_else(|e| {
-            debug_eprintln!("Warning: Failed to load api_keys.toml: {}", e);
-            HashMap::new()
+            debug!("Warning: Failed to load api_keys.toml: {}", e);
+            ApiKeysConfig::default()
         });
+        let env_file_keys = match &api_keys.env_file {
+            Some(env_file) => {
+                let env_path = config_dir.join(env_file);
+                std::fs::read_to_string(&env_path)
+                    .map(|contents| parse_env_file(&contents))
+                    .unwrap_or_else(|e| {
+                        warn!("Could not read env_file {}: {}", env_path.display(), e);
+                        HashMap::new()
+                    })
+            }
+            None => HashMap::new(),
+        };
+
+        let mut executor = Self::new(config, api_keys.keys);
+        executor.env_file_keys = env_file_keys;
+        Ok(executor)
+    }
+
+    /// Value for a `${NAME}` placeholder: api_keys.toml, then the environment, then the
+    /// `env_file`. `${cmd:...}` runs the command instead. None leaves the placeholder as is.
+    async fn resolve_secret(&self, name: &str) -> Option<String> {
+        if let Some(command) = name.strip_prefix("cmd:") {
+            return match run_secret_command(command.trim()).await {
+                Ok(secret) => Some(secret),
+                Err(e) => {
+                    warn!("Could not resolve a ${{cmd:...}} secret: {}", e);
+                    None
+                }
+            };
+        }
+        self.api_keys.get(name).cloned()
+            .or_else(|| std::env::var(name).ok())
+            .or_else(|| self.env_file_keys.get(name).cloned())
+    }
 
-        Ok(Self::new(config, api_keys))
+    /// Replace every `${NAME}` / `${cmd:...}` in `template`, leaving unresolved ones in place
+    async fn substitute_secrets(&self, template: &str) -> String {
+        let mut value = template.to_string();
+        let mut from = 0;
+        while let Some(start) = value[from..].find("${").map(|i| from + i) {
+            let Some(end) = value[start..].find('}').map(|i| start + i) else { break };
+            match self.resolve_secret(&value[start + 2..end]).await {
+                Some(replacement) => {
+                    value.replace_range(start..=end, &replacement);
+                    from = start + replacement.len();
+                }
+                None => from = end + 1,
+            }
+        }
+        value
+    }
+
+    /// Swap in a re-read tools.json. HTTP tools and built-ins take effect immediately;
+    /// only MCP servers that were added, removed or edited are disconnected/reconnected,
+    /// so unchanged servers keep their running process. Returns the restarted servers.
+    pub async fn apply_config(&mut self, config: ToolsConfig) -> Vec<String> {
+        let (stop, start) = mcp_server_changes(&self.config.tools.mcp, &config.tools.mcp);
+
+        for name in &stop {
+            debug!("[MCP] Disconnecting from server: {}", name);
+            self.mcp_tools.lock().unwrap().remove(name);
+            let connection = self.mcp_connections.lock().await.remove(name);
+            if let Some(McpConnection::Stdio { mut process, .. }) = connection {
+                let _ = process.kill().await;
+            }
+        }
+
+        self.config = config;
+        self.register_builtin_tools();
+        // Cached responses may come from tools whose definition just changed
+        self.response_cache.lock().unwrap().clear();
+
+        let mut restarted = Vec::new();
+        for server in start {
+            debug!("[MCP] Connecting to server: {}", server.name);
+            match self.connect_mcp_server(server.clone()).await {
+                Ok(_) => debug!("[MCP] ✓ Successfully connected to: {}", server.name),
+                Err(e) => debug!("[MCP] ✗ Failed to connect to {}: {}", server.name, e),
+            }
+            restarted.push(server.name);
+        }
+        restarted
     }
 
     /// Calculate delay based on recent tool usage
-    /// Returns delay in milliseconds based on call frequency
+    /// Returns delay in milliseconds based on call frequency and the tool's rate-limit policy
     fn calculate_rate_limit_delay(&self, tool_name: &str) -> u64 {
         let now = Instant::now();
-        const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60); // 1 minute window
+        let policy = self.config.tools.http.iter().find(|t| t.name == tool_name);
+        // HTTP tools share a budget with others on the same host (or group)
+        let key = policy.map_or_else(|| tool_name.to_string(), HttpTool::rate_limit_key);
+        let budget = policy.and_then(|t| t.max_calls_per_window).filter(|&n| n > 0);
+        let window = Duration::from_secs(policy.map_or_else(default_rate_limit_window_secs, |t| t.rate_limit_window_secs));
 
         let mut usage_map = self.tool_usage.lock().unwrap();
 
         // Get or create usage entry
-        let usage = usage_map.entry(tool_name.to_string())
-            .or_insert(ToolUsage {
-                last_call: now,
-                call_count: 0,
-            });
+        let usage = usage_map.entry(key.clone())
+            .or_insert_with(|| ToolUsage::new(now, budget));
 
-        // Check if we're still in the same time window
-        let elapsed = now.duration_since(usage.last_call);
-
-        if elapsed > RATE_LIMIT_WINDOW {
-            // Reset counter if outside window
-            usage.call_count = 1;
-            usage.last_call = now;
-            debug_println!("[RateLimit] {} - First call in new window", tool_name);
-            return 0; // No delay for first call in window
-        }
-
-        // Within window - increment counter and calculate delay
-        usage.call_count += 1;
-        usage.last_call = now;
-
-        // Progressive delay: 0ms, 100ms, 250ms, 500ms, 1000ms, then cap at 1500ms
-        // Less aggressive to support research mode with many parallel workers
-        let delay_ms = match usage.call_count {
-            1 => 0,
-            2 => 100,
-            3 => 250,
-            4 => 500,
-            5 => 1000,
-            _ => 1500, // Cap at 1.5 seconds
+        let delay_ms = match budget {
+            Some(max_calls) => usage.budget_delay(now, max_calls, window),
+            None => usage.ladder_delay(now, window),
         };
 
-        debug_println!("[RateLimit] {} - Call #{} in window, delay: {}ms",
-                      tool_name, usage.call_count, delay_ms);
+        debug!("[RateLimit] {} ({}) - Call #{} in window, delay: {}ms",
+                      tool_name, key, usage.call_count, delay_ms);
 
         delay_ms
     }
@@ -314,7 +1328,7 @@ impl ToolExecutor {
     async fn apply_rate_limit(&self, tool_name: &str) {
         let delay_ms = self.calculate_rate_limit_delay(tool_name);
         if delay_ms > 0 {
-            debug_println!("[RateLimit] Waiting {}ms before calling {}", delay_ms, tool_name);
+            debug!("[RateLimit] Waiting {}ms before calling {}", delay_ms, tool_name);
             tokio::time::sleep(Duration::from_millis(delay_ms)).await;
         }
     }
@@ -322,27 +1336,44 @@ impl ToolExecutor {
     pub async fn initialize_mcp_servers(&self) -> Result<(), anyhow::Error> {
         let servers = self.config.tools.mcp.clone();
         if servers.is_empty() {
-            debug_println!("[MCP] No MCP servers configured");
+            debug!("[MCP] No MCP servers configured");
             return Ok(());
         }
 
-        debug_println!("[MCP] Initializing {} MCP servers...", servers.len());
+        debug!("[MCP] Initializing {} MCP servers...", servers.len());
         for server in servers {
-            debug_println!("[MCP] Connecting to server: {}", server.name);
+            debug!("[MCP] Connecting to server: {}", server.name);
             match self.connect_mcp_server(server.clone()).await {
-                Ok(_) => debug_println!("[MCP] ✓ Successfully connected to: {}", server.name),
-                Err(e) => debug_eprintln!("[MCP] ✗ Failed to connect to {}: {}", server.name, e),
+                Ok(_) => debug!("[MCP] ✓ Successfully connected to: {}", server.name),
+                Err(e) => debug!("[MCP] ✗ Failed to connect to {}: {}", server.name, e),
             }
         }
         Ok(())
     }
 
     async fn connect_mcp_server(&self, server: McpServer) -> Result<(), anyhow::Error> {
-        if server.transport != "stdio" {
-            return Err(anyhow::anyhow!("Unsupported transport: {}", server.transport));
-        }
+        let connection = match server.transport.as_str() {
+            "stdio" => self.spawn_stdio_connection(&server)?,
+            "sse" => self.open_sse_connection(&server).await?,
+            "http" => {
+                let url = server.url.clone()
+                    .ok_or_else(|| anyhow::anyhow!("MCP server {} uses http transport but has no url", server.name))?;
+                debug!("[MCP] Using streamable HTTP endpoint for {}: {}", server.name, url);
+                McpConnection::Http { url, session_id: None, pending: std::collections::VecDeque::new() }
+            }
+            other => return Err(anyhow::anyhow!("Unsupported transport: {}", other)),
+        };
+
+        self.mcp_connections.lock().await.insert(server.name.clone(), connection);
+
+        // Send initialization message
+        self.initialize_mcp_connection(&server.name).await?;
+
+        Ok(())
+    }
 
-        debug_println!("[MCP] Starting process: {} {:?}", server.command, server.args);
+    fn spawn_stdio_connection(&self, server: &McpServer) -> Result<McpConnection, anyhow::Error> {
+        debug!("[MCP] Starting process: {} {:?}", server.command, server.args);
         let mut cmd = Command::new(&server.command);
         cmd.args(&server.args)
             .stdin(Stdio::piped())
@@ -350,12 +1381,12 @@ impl ToolExecutor {
             .stderr(Stdio::piped());  // Capture stderr for debugging
 
         for (key, value) in &server.env {
-            debug_println!("[MCP] Setting env var: {}=***", key);
+            debug!("[MCP] Setting env var: {}=***", key);
             cmd.env(key, value);
         }
 
         let mut process = cmd.spawn()?;
-        debug_println!("[MCP] Process spawned for: {}", server.name);
+        debug!("[MCP] Process spawned for: {}", server.name);
         let stdin = process.stdin.take().ok_or_else(|| anyhow::anyhow!("Failed to get stdin"))?;
         let stdout = process.stdout.take().ok_or_else(|| anyhow::anyhow!("Failed to get stdout"))?;
         let stderr = process.stderr.take().ok_or_else(|| anyhow::anyhow!("Failed to get stderr"))?;
@@ -369,24 +1400,70 @@ impl ToolExecutor {
             while let Ok(bytes) = stderr_reader.read_line(&mut line).await {
                 if bytes == 0 { break; }
                 if !line.trim().is_empty() {
-                    debug_eprintln!("[MCP] {} stderr: {}", server_name_clone, line.trim());
+                    debug!("[MCP] {} stderr: {}", server_name_clone, line.trim());
                 }
                 line.clear();
             }
         });
 
-        let connection = McpConnection {
+        Ok(McpConnection::Stdio {
             process,
             stdin,
             stdout,
-        };
+        })
+    }
 
-        self.mcp_connections.lock().await.insert(server.name.clone(), connection);
+    async fn open_sse_connection(&self, server: &McpServer) -> Result<McpConnection, anyhow::Error> {
+        let url = server.url.clone()
+            .ok_or_else(|| anyhow::anyhow!("MCP server {} uses sse transport but has no url", server.name))?;
+        debug!("[MCP] Opening SSE stream for {}: {}", server.name, url);
 
-        // Send initialization message
-        self.initialize_mcp_connection(&server.name).await?;
+        let mut response = self.http_client.get(&url)
+            .header("Accept", "text/event-stream")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("MCP server {} SSE stream returned {}", server.name, response.status()));
+        }
 
-        Ok(())
+        // The first "endpoint" event tells us where to POST messages; everything else is JSON-RPC
+        let (endpoint_tx, endpoint_rx) = tokio::sync::oneshot::channel::<String>();
+        let (events_tx, events_rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+        let server_name = server.name.clone();
+        tokio::spawn(async move {
+            let mut parser = SseParser::default();
+            let mut endpoint_tx = Some(endpoint_tx);
+            while let Ok(Some(chunk)) = response.chunk().await {
+                for event in parser.feed(&chunk) {
+                    match event.event.as_str() {
+                        "endpoint" => {
+                            if let Some(tx) = endpoint_tx.take() {
+                                let _ = tx.send(event.data);
+                            }
+                        }
+                        "message" => match serde_json::from_str::<Value>(&event.data) {
+                            Ok(message) => {
+                                if events_tx.send(message).is_err() {
+                                    return;
+                                }
+                            }
+                            Err(e) => debug!("[MCP] {} sent non-JSON SSE message: {}", server_name, e),
+                        },
+                        _ => {}
+                    }
+                }
+            }
+            debug!("[MCP] SSE stream closed for: {}", server_name);
+        });
+
+        let endpoint = tokio::time::timeout(Duration::from_secs(MCP_SSE_ENDPOINT_TIMEOUT_SECS), endpoint_rx)
+            .await
+            .map_err(|_| anyhow::anyhow!("MCP server {} did not announce an endpoint", server.name))?
+            .map_err(|_| anyhow::anyhow!("MCP server {} closed the SSE stream before announcing an endpoint", server.name))?;
+        let post_url = reqwest::Url::parse(&url)?.join(endpoint.trim())?.to_string();
+        debug!("[MCP] {} message endpoint: {}", server.name, post_url);
+
+        Ok(McpConnection::Sse { post_url, events: events_rx })
     }
 
     async fn initialize_mcp_connection(&self, server_name: &str) -> Result<(), anyhow::Error> {
@@ -407,7 +1484,7 @@ impl ToolExecutor {
         self.send_mcp_message(server_name, &init_message).await?;
         // Read and process the response
         let init_response = self.read_mcp_response(server_name).await?;
-        debug_println!("[MCP] Initialize response: {:?}", init_response);
+        debug!("[MCP] Initialize response: {:?}", init_response);
 
         // Now request the list of tools
         let list_tools_message = json!({
@@ -417,7 +1494,7 @@ impl ToolExecutor {
             "id": 2
         });
 
-        debug_println!("[MCP] Requesting tool list from {}", server_name);
+        debug!("[MCP] Requesting tool list from {}", server_name);
         self.send_mcp_message(server_name, &list_tools_message).await?;
         let tools_response = self.read_mcp_response(server_name).await?;
 
@@ -425,13 +1502,13 @@ impl ToolExecutor {
         if let Some(result) = tools_response.get("result") {
             if let Some(tools_array) = result.get("tools") {
                 if let Ok(tools) = serde_json::from_value::<Vec<McpTool>>(tools_array.clone()) {
-                    debug_println!("[MCP] {} tools discovered from {}:", tools.len(), server_name);
+                    debug!("[MCP] {} tools discovered from {}:", tools.len(), server_name);
                     for tool in &tools {
-                        debug_println!("[MCP]   • {}: {}", tool.name, tool.description.as_ref().unwrap_or(&"No description".to_string()));
+                        debug!("[MCP]   • {}: {}", tool.name, tool.description.as_ref().unwrap_or(&"No description".to_string()));
                     }
                     self.mcp_tools.lock().unwrap().insert(server_name.to_string(), tools);
                 } else {
-                    debug_println!("[MCP] Failed to parse tools from response");
+                    debug!("[MCP] Failed to parse tools from response");
                 }
             }
         }
@@ -440,34 +1517,91 @@ impl ToolExecutor {
     }
 
     async fn send_mcp_message(&self, server_name: &str, message: &Value) -> Result<(), anyhow::Error> {
-        debug_println!("[MCP] Sending message to {}: {}", server_name, message);
+        debug!("[MCP] Sending message to {}: {}", server_name, message);
 
         let mut connections = self.mcp_connections.lock().await;
         let connection = connections.get_mut(server_name)
             .ok_or_else(|| anyhow::anyhow!("MCP server {} not connected", server_name))?;
 
-        let msg_str = message.to_string();
-        connection.stdin.write_all(msg_str.as_bytes()).await?;
-        connection.stdin.write_all(b"\n").await?;
-        connection.stdin.flush().await?;
-        debug_println!("[MCP] Message sent to: {}", server_name);
+        match connection {
+            McpConnection::Stdio { stdin, .. } => {
+                let msg_str = message.to_string();
+                stdin.write_all(msg_str.as_bytes()).await?;
+                stdin.write_all(b"\n").await?;
+                stdin.flush().await?;
+            }
+            McpConnection::Sse { post_url, .. } => {
+                // The reply arrives on the SSE stream, not in the POST response
+                let response = self.http_client.post(post_url.as_str()).json(message).send().await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("MCP server {} rejected message: {}", server_name, response.status()));
+                }
+            }
+            McpConnection::Http { url, session_id, pending } => {
+                let mut request = self.http_client.post(url.as_str())
+                    .header("Accept", "application/json, text/event-stream")
+                    .json(message);
+                if let Some(id) = session_id.as_ref() {
+                    request = request.header("Mcp-Session-Id", id.as_str());
+                }
+                let response = request.send().await?;
+                let status = response.status();
+                if let Some(id) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+                    *session_id = Some(id.to_string());
+                }
+                let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("")
+                    .to_string();
+                let body = response.text().await?;
+                if !status.is_success() {
+                    return Err(anyhow::anyhow!("MCP server {} returned {}: {}", server_name, status, body));
+                }
+                pending.extend(parse_mcp_http_body(&content_type, &body));
+            }
+        }
+        debug!("[MCP] Message sent to: {}", server_name);
 
         Ok(())
     }
 
     async fn read_mcp_response(&self, server_name: &str) -> Result<Value, anyhow::Error> {
-        debug_println!("[MCP] Reading response from: {}", server_name);
+        debug!("[MCP] Reading response from: {}", server_name);
 
         let mut connections = self.mcp_connections.lock().await;
         let connection = connections.get_mut(server_name)
             .ok_or_else(|| anyhow::anyhow!("MCP server {} not connected", server_name))?;
 
+        let stdout = match connection {
+            McpConnection::Stdio { stdout, .. } => stdout,
+            McpConnection::Sse { events, .. } => {
+                // Skip server notifications; only messages carrying an id answer our requests
+                loop {
+                    let message = events.recv().await
+                        .ok_or_else(|| anyhow::anyhow!("MCP server {} disconnected unexpectedly", server_name))?;
+                    if message.get("id").is_some() {
+                        return Ok(message);
+                    }
+                    debug!("[MCP] Skipping notification from {}: {}", server_name, message);
+                }
+            }
+            McpConnection::Http { pending, .. } => {
+                while let Some(message) = pending.pop_front() {
+                    if message.get("id").is_some() {
+                        return Ok(message);
+                    }
+                    debug!("[MCP] Skipping notification from {}: {}", server_name, message);
+                }
+                return Err(anyhow::anyhow!("MCP server {} sent no response", server_name));
+            }
+        };
+
         // Keep reading lines until we get a valid JSON response
         // Some MCP servers might output debug info to stdout
         let mut attempts = 0;
         loop {
             let mut line = String::new();
-            let bytes_read = connection.stdout.read_line(&mut line).await?;
+            let bytes_read = stdout.read_line(&mut line).await?;
 
             if bytes_read == 0 {
                 return Err(anyhow::anyhow!("MCP server {} disconnected unexpectedly", server_name));
@@ -478,9 +1612,9 @@ impl ToolExecutor {
                 continue;
             }
 
-            debug_println!("[MCP] Raw response from {}: {}", server_name,
+            debug!("[MCP] Raw response from {}: {}", server_name,
                 if trimmed.len() > 200 {
-                    format!("{}...", &trimmed[..200])
+                    format!("{}...", crate::ollama::truncate_at_char_boundary(trimmed, 200))
                 } else {
                     trimmed.to_string()
                 });
@@ -488,20 +1622,20 @@ impl ToolExecutor {
             // Try to parse as JSON
             match serde_json::from_str::<Value>(trimmed) {
                 Ok(response) => {
-                    debug_println!("[MCP] Successfully parsed JSON response");
+                    debug!("[MCP] Successfully parsed JSON response");
                     return Ok(response);
                 },
                 Err(e) => {
                     // If it's not JSON, it might be debug output
                     if trimmed.starts_with('{') || trimmed.starts_with('[') {
                         // Looks like JSON but failed to parse
-                        debug_println!("[MCP] Failed to parse JSON: {}", e);
+                        debug!("[MCP] Failed to parse JSON: {}", e);
                         if attempts > 5 {
                             return Err(anyhow::anyhow!("Failed to parse JSON response after retries"));
                         }
                     } else {
                         // Probably debug output, skip it
-                        debug_println!("[MCP] Skipping non-JSON output: {}", trimmed);
+                        debug!("[MCP] Skipping non-JSON output: {}", trimmed);
                     }
                 }
             }
@@ -513,49 +1647,6 @@ impl ToolExecutor {
         }
     }
 
-    fn extract_json_path(&self, json: &Value, path: &str) -> Result<Value, anyhow::Error> {
-        debug_println!("[JSON] Extracting path: {} from response", path);
-
-        let mut current = json.clone();
-        let parts: Vec<&str> = path.split('.').collect();
-
-        for part in parts {
-            // Handle array indexing like "results[0]"
-            if part.contains('[') && part.contains(']') {
-                let bracket_start = part.find('[').unwrap();
-                let bracket_end = part.find(']').unwrap();
-                let field_name = &part[..bracket_start];
-                let index_str = &part[bracket_start + 1..bracket_end];
-
-                // First get the field if it exists
-                if !field_name.is_empty() {
-                    current = current.get(field_name)
-                        .ok_or_else(|| anyhow::anyhow!("Field '{}' not found in JSON", field_name))?
-                        .clone();
-                }
-
-                // Then apply the index
-                if let Ok(index) = index_str.parse::<usize>() {
-                    current = current.as_array()
-                        .ok_or_else(|| anyhow::anyhow!("Expected array at '{}'", part))?
-                        .get(index)
-                        .ok_or_else(|| anyhow::anyhow!("Index {} out of bounds", index))?
-                        .clone();
-                } else {
-                    return Err(anyhow::anyhow!("Invalid array index: {}", index_str));
-                }
-            } else {
-                // Simple field access
-                current = current.get(part)
-                    .ok_or_else(|| anyhow::anyhow!("Field '{}' not found in JSON", part))?
-                    .clone();
-            }
-        }
-
-        debug_println!("[JSON] Successfully extracted value from path: {}", path);
-        Ok(current)
-    }
-
     fn parse_value_by_type(&self, value: &str, param_type: &str) -> Value {
         match param_type.to_lowercase().as_str() {
             "number" => {
@@ -604,10 +1695,26 @@ impl ToolExecutor {
         let params_json = serde_json::to_string(&params).unwrap_or_else(|_| "{}".to_string());
         self.record_tool_call("http", tool_name, &params_json).await;
 
+        // Serve identical calls from the cache (skips rate limiting too)
+        let cache_ttl = self.config.tools.http.iter()
+            .find(|t| t.name == tool_name)
+            .map(|t| t.cache_ttl_secs)
+            .unwrap_or(0);
+        let cache_key = ResponseCache::key(tool_name, &params);
+        if cache_ttl > 0 {
+            let cached = self.response_cache.lock().ok()
+                .and_then(|mut cache| cache.get(&cache_key, Duration::from_secs(cache_ttl)));
+            if let Some(cached) = cached {
+                debug!("[HTTP] Cache hit for tool: {} with params: {:?}", tool_name, params);
+                crate::progress::log_with(crate::progress::Kind::Http, format!("HTTP cache hit [tool: {}]", tool_name));
+                return Ok(cached);
+            }
+        }
+
         // Apply rate limiting
         self.apply_rate_limit(tool_name).await;
 
-        debug_println!("[HTTP] Executing tool: {} with params: {:?}", tool_name, params);
+        debug!("[HTTP] Executing tool: {} with params: {:?}", tool_name, params);
         let tool = self.config.tools.http.iter()
             .find(|t| t.name == tool_name)
             .ok_or_else(|| anyhow::anyhow!("HTTP tool '{}' not found", tool_name))?;
@@ -621,13 +1728,9 @@ impl ToolExecutor {
                 // Default values ALWAYS override what the LLM provides
                 // This allows us to force certain parameter values
                 match default {
-                    Value::String(s) if s.starts_with("${") && s.ends_with("}") => {
-                        // Environment variable substitution
-                        let key_name = &s[2..s.len()-1];
-                        let env_value = self.api_keys.get(key_name)
-                            .cloned()
-                            .or_else(|| std::env::var(key_name).ok())
-                            .unwrap_or_else(|| s.clone());
+                    Value::String(s) if s.contains("${") => {
+                        // API key / environment variable substitution
+                        let env_value = self.substitute_secrets(s).await;
                         self.parse_value_by_type(&env_value, &param_def.param_type)
                     },
                     _ => default.clone()
@@ -643,13 +1746,7 @@ impl ToolExecutor {
 
             // Check if this is a path parameter (path params need to be strings)
             if tool.path_params.contains(key) {
-                let string_value = match &value {
-                    Value::String(s) => s.clone(),
-                    Value::Number(n) => n.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    _ => serde_json::to_string(&value)?
-                };
-                path_param_values.insert(key.clone(), string_value);
+                path_param_values.insert(key.clone(), param_to_string(&value));
             } else {
                 final_params.insert(key.clone(), value);
             }
@@ -661,56 +1758,34 @@ impl ToolExecutor {
         // Replace {param_name} and :param_name placeholders in the URL
         for param_name in &tool.path_params {
             if let Some(value) = path_param_values.get(param_name) {
-                debug_println!("[HTTP] Replacing path parameter '{}' with: {}", param_name, value);
+                debug!("[HTTP] Replacing path parameter '{}' with: {}", param_name, value);
                 // Support both {param} and :param styles
                 final_endpoint = final_endpoint
                     .replace(&format!("{{{}}}", param_name), value)
                     .replace(&format!(":{}", param_name), value);
             } else if tool.parameters.get(param_name).map_or(false, |p| p.required) {
-                debug_println!("[HTTP] Warning: Required path parameter {} not found", param_name);
+                debug!("[HTTP] Warning: Required path parameter {} not found", param_name);
             }
         }
 
-        debug_println!("[HTTP] Final endpoint after path substitution: {}", final_endpoint);
+        debug!("[HTTP] Final endpoint after path substitution: {}", final_endpoint);
         // Record the resolved endpoint for UI verbosity
         crate::tools::note_current_source(&final_endpoint);
 
+        // Any valid HTTP method token is accepted, including custom ones (e.g. PROPFIND)
+        let method = reqwest::Method::from_bytes(tool.method.trim().to_uppercase().as_bytes())
+            .map_err(|_| anyhow::anyhow!("Unsupported HTTP method: {}", tool.method))?;
+
         // Process headers with environment variable substitution
-        let mut request_builder = match tool.method.as_str() {
-            "GET" => self.http_client.get(&final_endpoint),
-            "POST" => self.http_client.post(&final_endpoint),
-            "PUT" => self.http_client.put(&final_endpoint),
-            "DELETE" => self.http_client.delete(&final_endpoint),
-            "PATCH" => self.http_client.patch(&final_endpoint),
-            _ => return Err(anyhow::anyhow!("Unsupported HTTP method: {}", tool.method)),
-        };
+        let mut request_builder = self.http_client
+            .request(method.clone(), &final_endpoint)
+            .timeout(Duration::from_secs(tool.timeout_secs));
 
         // Add headers with variable substitution
         for (header_name, header_value) in &tool.headers {
             let processed_value = if header_value.contains("${") {
-                // Process environment variable substitution
-                let mut value = header_value.clone();
-
-                // Find all ${VAR_NAME} patterns and replace them
-                while let Some(start) = value.find("${") {
-                    if let Some(end) = value[start..].find('}') {
-                        let var_name = &value[start + 2..start + end];
-
-                        // Try api_keys first
-                        let replacement = if let Some(api_key) = self.api_keys.get(var_name) {
-                            api_key.clone()
-                        } else if let Ok(env_val) = std::env::var(var_name) {
-                            env_val
-                        } else {
-                            format!("${{{}}}", var_name)
-                        };
-
-                        value.replace_range(start..=start + end, &replacement);
-                    } else {
-                        break;
-                    }
-                }
-                value
+                // Replace ${VAR_NAME} / ${cmd:...} patterns with their secrets
+                self.substitute_secrets(header_value).await
             } else {
                 header_value.clone()
             };
@@ -718,41 +1793,45 @@ impl ToolExecutor {
             request_builder = request_builder.header(header_name, processed_value);
         }
 
-        debug_println!("[HTTP] Making {} request to: {} (tool: {})", tool.method, tool.endpoint, tool_name);
+        debug!("[HTTP] Making {} request to: {} (tool: {})", tool.method, tool.endpoint, tool_name);
         crate::progress::log_with(
             crate::progress::Kind::Http,
             format!("HTTP {} {} [tool: {}]", tool.method, host_from_url(&final_endpoint), tool_name),
         );
 
         // Add query parameters or JSON body based on method
-        let response = match tool.method.as_str() {
-            "GET" => {
-                // Convert Values to strings for query parameters
-                let query_params: HashMap<String, String> = final_params.iter()
-                    .map(|(k, v)| {
-                        let string_value = match v {
-                            Value::String(s) => s.clone(),
-                            Value::Number(n) => n.to_string(),
-                            Value::Bool(b) => b.to_string(),
-                            _ => serde_json::to_string(v).unwrap_or_default()
-                        };
-                        (k.clone(), string_value)
-                    })
-                    .collect();
-                debug_println!("[HTTP] Adding query parameters: {:?}", query_params);
-                request_builder
-                    .query(&query_params)
-                    .send()
-                    .await?
-            },
-            "POST" => {
-                debug_println!("[HTTP] Sending JSON body: {:?}", final_params);
-                request_builder
-                    .json(&final_params)
-                    .send()
-                    .await?
-            },
-            _ => unreachable!(),
+        let request = attach_params(request_builder, &method, tool, &final_params)?;
+        let mut attempt = 0;
+        let response = loop {
+            let attempt_request = request.try_clone()
+                .ok_or_else(|| anyhow::anyhow!("Request for tool '{}' cannot be sent", tool_name))?;
+            let response = match attempt_request.send().await {
+                Ok(response) => response,
+                Err(e) if e.is_timeout() => {
+                    return Err(self.timeout_error(tool, &final_endpoint).await);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let status_code = response.status().as_u16();
+            if attempt >= tool.retry_count || !tool.retry_on_status.iter().any(|p| status_matches(status_code, p)) {
+                break response;
+            }
+
+            let retry_after = response.headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok());
+            let delay = retry_delay(attempt, retry_after);
+            attempt += 1;
+            crate::progress::log_with(
+                crate::progress::Kind::Http,
+                format!(
+                    "HTTP {} {} → {}, retry {}/{} in {}s [tool: {}]",
+                    tool.method, host_from_url(&final_endpoint), status_code,
+                    attempt, tool.retry_count, delay.as_secs(), tool_name
+                ),
+            );
+            tokio::time::sleep(delay).await;
         };
 
         let status_code = response.status().as_u16();
@@ -760,33 +1839,20 @@ impl ToolExecutor {
             crate::progress::Kind::Http,
             format!("HTTP {} {} → {} [tool: {}]", tool.method, host_from_url(&final_endpoint), status_code, tool_name),
         );
-        debug_println!("[HTTP] Response status: {}", status_code);
+        debug!("[HTTP] Response status: {}", status_code);
 
-        // Check if status is in acceptable_status list (should be ignored)
-        if status_in_list(status_code, &tool.acceptable_status) {
-            debug_println!("[HTTP] Status {} is acceptable, ignoring response", status_code);
+        let disposition = classify_status(status_code, tool);
+        if disposition == StatusDisposition::Ignore {
+            debug!("[HTTP] Status {} is acceptable, ignoring response", status_code);
             return Ok(json!({"status": "ignored", "status_code": status_code}));
         }
 
-        // Check if status is in expected_status list
-        let is_expected = status_in_list(status_code, &tool.expected_status);
-
-        // Determine if we should throw an error
-        let should_error = if !tool.error_status.is_empty() {
-            // If error_status is specified, only error on those codes
-            status_in_list(status_code, &tool.error_status)
-        } else {
-            // Otherwise, error on any non-expected status
-            !is_expected
-        };
-
-        if should_error {
+        if disposition == StatusDisposition::Error {
             let status = response.status();
             let error_body = response.text().await.unwrap_or_else(|_| "Could not read error response".to_string());
             // Always log HTTP error responses to console for debugging
-            eprintln!("[HTTP Tool Error] Tool: {} | Status: {} | Response Body:\n{}",
+            warn!("HTTP tool error. Tool: {} | Status: {} | Response Body:\n{}",
                 tool_name, status_code, error_body);
-            debug_println!("[HTTP] Error response body: {}", error_body);
             return Err(anyhow::anyhow!(
                 "HTTP {} error for tool '{}':\nStatus: {}\nResponse:\n{}",
                 status_code, tool_name, status, error_body
@@ -800,10 +1866,18 @@ impl ToolExecutor {
 
         // Apply JSON path extraction if specified
         if let Some(path) = &tool.response_path {
-            result = self.extract_json_path(&result, path)?;
+            result = extract_json_path(&result, path)?;
+        }
+
+        let result = cap_result(result, tool.max_result_bytes.unwrap_or(self.config.tools.max_result_bytes));
+
+        if cache_ttl > 0 {
+            if let Ok(mut cache) = self.response_cache.lock() {
+                cache.insert(cache_key, result.clone());
+            }
         }
 
-        debug_println!("[HTTP] Tool {} executed successfully", tool_name);
+        debug!("[HTTP] Tool {} executed successfully", tool_name);
         Ok(result)
     }
 
@@ -819,7 +1893,7 @@ impl ToolExecutor {
         let rate_limit_key = format!("{}:{}", server_name, tool_name);
         self.apply_rate_limit(&rate_limit_key).await;
 
-        debug_println!("[MCP] Executing tool '{}' on server: {}", tool_name, server_name);
+        debug!("[MCP] Executing tool '{}' on server: {}", tool_name, server_name);
 
         // MCP tools are called with tools/call method
         let message = json!({
@@ -834,15 +1908,20 @@ impl ToolExecutor {
 
         self.send_mcp_message(server_name, &message).await?;
         let response = self.read_mcp_response(server_name).await?;
-        debug_println!("[MCP] Tool execution completed for: {}", server_name);
+        debug!("[MCP] Tool execution completed for: {}", server_name);
+
+        let max_result_bytes = self.config.tools.mcp.iter()
+            .find(|s| s.name == server_name)
+            .and_then(|s| s.max_result_bytes)
+            .unwrap_or(self.config.tools.max_result_bytes);
 
         // Extract the result from the response
         if let Some(result) = response.get("result") {
-            Ok(result.clone())
+            Ok(cap_result(result.clone(), max_result_bytes))
         } else if let Some(error) = response.get("error") {
             Err(anyhow::anyhow!("MCP error: {}", error))
         } else {
-            Ok(response)
+            Ok(cap_result(response, max_result_bytes))
         }
     }
 
@@ -862,38 +1941,64 @@ impl ToolExecutor {
             "memory_get_insights" => self.builtin_memory_get_insights(params).await,
             "memory_get_feedback" => self.builtin_memory_get_feedback(params).await,
             "memory_get_plan" => self.builtin_memory_get_plan(params).await,
+            "memory_get_by_agent" => self.builtin_memory_get_by_agent(params).await,
             "memory_stats" => self.builtin_memory_stats(params).await,
             "current_date" => self.builtin_current_date(params).await,
+            "web_search" => self.builtin_web_search(params).await,
+            "fetch_url" => self.builtin_fetch_url(params).await,
+            "read_file" => self.builtin_read_file(params).await,
+            "calculate" => self.builtin_calculate(params).await,
             _ => Err(anyhow::anyhow!("Unknown built-in tool: {}", tool_name)),
         }
     }
 
     async fn builtin_pdf_extract(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
         let url = params.get("url")
+            .or_else(|| params.get("path"))
             .ok_or_else(|| anyhow::anyhow!("Missing 'url' parameter for pdf_extract"))?;
 
-        debug_println!("[BuiltIn:PDF] Fetching PDF from: {}", url);
+        let pdf_bytes = if url.starts_with("http://") || url.starts_with("https://") {
+            debug!("[BuiltIn:PDF] Fetching PDF from: {}", url);
 
-        // Download PDF
-        let response = self.http_client.get(url).send().await?;
+            // Download PDF
+            let response = self.http_client.get(url).send().await?;
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Failed to download PDF: HTTP {}", response.status()));
-        }
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("Failed to download PDF: HTTP {}", response.status()));
+            }
+
+            response.bytes().await?.to_vec()
+        } else {
+            let path = resolve_local_pdf_path(url, self.config.tools.local_pdf_dir.as_deref())?;
+            debug!("[BuiltIn:PDF] Reading local PDF: {}", path.display());
+            tokio::fs::read(&path).await?
+        };
 
-        let pdf_bytes = response.bytes().await?;
+        let parse_page = |key: &str| -> Result<Option<u32>, anyhow::Error> {
+            params.get(key)
+                .map(|v| v.trim().parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("'{}' must be a positive page number, got '{}'", key, v)))
+                .transpose()
+        };
+        let start_page = parse_page("start_page")?;
+        let end_page = parse_page("end_page")?;
 
         // Extract text from PDF
-        let text = tokio::task::spawn_blocking(move || {
-            pdf_extract::extract_text_from_mem(&pdf_bytes)
+        let extraction = tokio::task::spawn_blocking(move || {
+            extract_pdf_text(&pdf_bytes, start_page, end_page)
         }).await??;
 
-        debug_println!("[BuiltIn:PDF] Extracted {} characters of text", text.len());
+        debug!("[BuiltIn:PDF] Extracted {} characters of text from pages {}-{} of {}",
+            extraction.text.len(), extraction.start_page, extraction.end_page, extraction.page_count);
 
+        let length = extraction.text.len();
         Ok(json!({
-            "text": text,
-            "length": text.len(),
-            "source": url
+            "text": cap_text(extraction.text, self.config.tools.max_result_bytes),
+            "length": length,
+            "source": url,
+            "page_count": extraction.page_count,
+            "start_page": extraction.start_page,
+            "end_page": extraction.end_page
         }))
     }
 
@@ -929,7 +2034,7 @@ impl ToolExecutor {
             Some(metadata)
         ).await?;
 
-        debug_println!("[Memory] Stored {} by {}: {} chars", memory_type_str, created_by, content.len());
+        debug!("[Memory] Stored {} by {}: {} chars", memory_type_str, created_by, content.len());
 
         Ok(json!({
             "success": true,
@@ -948,20 +2053,29 @@ impl ToolExecutor {
         let memory_type = params.get("type")
             .and_then(|t| crate::shared_memory::MemoryType::from_str(t));
 
+        let agent = params.get("agent").map(|a| a.trim()).filter(|a| !a.is_empty());
+
         let top_k = params.get("limit")
             .and_then(|s| s.parse::<usize>().ok())
             .unwrap_or(5);
 
-        let results = memory.search_similar(query, memory_type, top_k).await?;
+        let min_score = match params.get("min_score") {
+            Some(s) => s.trim().parse::<f32>()
+                .map_err(|_| anyhow::anyhow!("Invalid 'min_score' parameter: {}", s))?,
+            None => -1.0,  // Cosine similarity never goes below -1
+        };
+
+        let results = memory.search_similar(query, memory_type, agent, top_k, min_score).await?;
 
-        debug_println!("[Memory] Search for '{}' found {} results", query, results.len());
+        debug!("[Memory] Search for '{}' found {} results", query, results.len());
 
         Ok(json!({
-            "results": results.iter().map(|m| json!({
+            "results": results.iter().map(|(m, score)| json!({
                 "type": m.memory_type.as_str(),
                 "content": m.content,
                 "created_by": m.created_by,
-                "metadata": m.metadata
+                "metadata": m.metadata,
+                "score": score
             })).collect::<Vec<_>>(),
             "count": results.len()
         }))
@@ -1015,56 +2129,246 @@ impl ToolExecutor {
         }))
     }
 
-    async fn builtin_memory_get_feedback(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
-        let memory = self.shared_memory.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+    async fn builtin_memory_get_feedback(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let memory = self.shared_memory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+
+        let feedback = memory.get_by_type(crate::shared_memory::MemoryType::Feedback).await;
+
+        Ok(json!({
+            "feedback": feedback.iter().map(|m| json!({
+                "content": m.content,
+                "created_by": m.created_by,
+                "metadata": m.metadata
+            })).collect::<Vec<_>>(),
+            "count": feedback.len()
+        }))
+    }
+
+    async fn builtin_memory_get_plan(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let memory = self.shared_memory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+
+        let plans = memory.get_by_type(crate::shared_memory::MemoryType::Plan).await;
+
+        // Return the most recent plan
+        let plan = plans.first();
+
+        Ok(json!({
+            "plan": plan.map(|p| p.content.clone()).unwrap_or_else(|| "No plan found".to_string()),
+            "created_by": plan.map(|p| p.created_by.clone()).unwrap_or_else(|| "unknown".to_string())
+        }))
+    }
+
+    async fn builtin_memory_get_by_agent(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let memory = self.shared_memory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+
+        let agent = params.get("agent")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'agent' parameter"))?;
+
+        let memory_type = match params.get("type") {
+            Some(t) => Some(crate::shared_memory::MemoryType::from_str(t)
+                .ok_or_else(|| anyhow::anyhow!("Invalid memory type: {}", t))?),
+            None => None,
+        };
+
+        let memories = memory.get_by_agent(agent, memory_type).await;
+
+        Ok(json!({
+            "memories": memories.iter().map(|m| json!({
+                "type": m.memory_type.as_str(),
+                "content": m.content,
+                "metadata": m.metadata
+            })).collect::<Vec<_>>(),
+            "agent": agent,
+            "count": memories.len()
+        }))
+    }
+
+    async fn builtin_memory_stats(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let memory = self.shared_memory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+
+        let stats = memory.get_stats().await;
+
+        Ok(json!({
+            "total": stats.total_count,
+            "discoveries": stats.discovery_count,
+            "insights": stats.insight_count,
+            "deadends": stats.deadend_count,
+            "cached_queries": stats.query_result_count,
+            "plans": stats.plan_count,
+            "feedback": stats.feedback_count,
+            "context": stats.context_count
+        }))
+    }
+
+    async fn builtin_web_search(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let query = params.get("query")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'query' parameter for web_search"))?;
+        let search_config = &self.config.tools.web_search;
+        let limit = params.get("limit")
+            .and_then(|l| l.parse::<usize>().ok())
+            .unwrap_or(search_config.max_results)
+            .max(1);
+
+        self.apply_rate_limit("web_search").await;
+        debug!("[BuiltIn:Search] Searching {} for: {}", search_config.backend, query);
+
+        let results = match search_config.backend.as_str() {
+            "searxng" => {
+                let base = search_config.searxng_url.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("web_search backend is searxng but no searxng_url is configured"))?;
+                let response = self.http_client.get(format!("{}/search", base.trim_end_matches('/')))
+                    .query(&[("q", query.as_str()), ("format", "json")])
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("SearXNG search failed: HTTP {}", response.status()));
+                }
+                let body: Value = response.json().await?;
+                parse_searxng_results(&body, limit)
+            }
+            "duckduckgo" => {
+                let response = self.http_client.get(DUCKDUCKGO_HTML_URL)
+                    .query(&[("q", query.as_str())])
+                    .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64) bob-bar")
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("DuckDuckGo search failed: HTTP {}", response.status()));
+                }
+                let html = response.text().await?;
+                parse_duckduckgo_html(&html, limit)
+            }
+            other => return Err(anyhow::anyhow!("Unknown web_search backend: {}", other)),
+        };
+
+        debug!("[BuiltIn:Search] {} results for: {}", results.len(), query);
+
+        Ok(json!({
+            "query": query,
+            "results": results
+        }))
+    }
+
+    async fn builtin_calculate(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let expression = params.get("expression")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'expression' parameter for calculate"))?;
 
-        let feedback = memory.get_by_type(crate::shared_memory::MemoryType::Feedback).await;
+        let result = crate::calculator::evaluate(expression)
+            .map_err(|e| anyhow::anyhow!("Could not evaluate '{}': {}", expression, e))?;
+        debug!("[BuiltIn:Calculate] {} = {}", expression, result);
 
+        // Whole numbers come back as integers so the model doesn't quote "42.0"
+        let result = if result.fract() == 0.0 && result.abs() < 9_007_199_254_740_992.0 {
+            json!(result as i64)
+        } else {
+            json!(result)
+        };
         Ok(json!({
-            "feedback": feedback.iter().map(|m| json!({
-                "content": m.content,
-                "created_by": m.created_by,
-                "metadata": m.metadata
-            })).collect::<Vec<_>>(),
-            "count": feedback.len()
+            "result": result,
+            "expression": expression
         }))
     }
 
-    async fn builtin_memory_get_plan(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
-        let memory = self.shared_memory.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+    async fn builtin_read_file(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let requested = params.get("path")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter for read_file"))?;
 
-        let plans = memory.get_by_type(crate::shared_memory::MemoryType::Plan).await;
+        self.apply_rate_limit("read_file").await;
+        let path = resolve_read_path(requested, self.config.tools.allowed_read_dir.as_deref())?;
+        let metadata = tokio::fs::metadata(&path).await?;
+        if !metadata.is_file() {
+            return Err(anyhow::anyhow!("{} is not a regular file", path.display()));
+        }
+        debug!("[BuiltIn:ReadFile] Reading {} ({} bytes)", path.display(), metadata.len());
 
-        // Return the most recent plan
-        let plan = plans.first();
+        // Read at most one byte past the cap so truncation can be detected without loading huge files
+        let file = tokio::fs::File::open(&path).await?;
+        let mut bytes = Vec::new();
+        file.take(READ_FILE_MAX_BYTES as u64 + 1).read_to_end(&mut bytes).await?;
+        let (content, truncated) = decode_text_file(bytes, READ_FILE_MAX_BYTES)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
 
         Ok(json!({
-            "plan": plan.map(|p| p.content.clone()).unwrap_or_else(|| "No plan found".to_string()),
-            "created_by": plan.map(|p| p.created_by.clone()).unwrap_or_else(|| "unknown".to_string())
+            "content": content,
+            "path": path.display().to_string(),
+            "size": metadata.len(),
+            "truncated": truncated
         }))
     }
 
-    async fn builtin_memory_stats(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
-        let memory = self.shared_memory.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Shared memory not available"))?;
+    async fn builtin_fetch_url(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+        let url = params.get("url")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'url' parameter for fetch_url"))?;
+        let max_chars = params.get("max_chars")
+            .and_then(|m| m.parse::<usize>().ok())
+            .unwrap_or(FETCH_URL_MAX_CHARS)
+            .min(FETCH_URL_MAX_CHARS);
+
+        self.apply_rate_limit("fetch_url").await;
+        debug!("[BuiltIn:Fetch] Fetching page: {}", url);
+        note_current_source(url);
+
+        let mut response = self.http_client.get(url)
+            .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64) bob-bar")
+            .header("Accept", "text/html,application/xhtml+xml,text/plain;q=0.9")
+            .send()
+            .await?;
 
-        let stats = memory.get_stats().await;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to fetch page: HTTP {}", response.status()));
+        }
+
+        let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_lowercase();
+        if content_type.contains("application/pdf") {
+            return Err(anyhow::anyhow!("{} is a PDF; use pdf_extract instead", url));
+        }
+
+        // Read at most FETCH_URL_MAX_BYTES so huge pages can't stall the worker
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= FETCH_URL_MAX_BYTES {
+                body.truncate(FETCH_URL_MAX_BYTES);
+                break;
+            }
+        }
+        let page = String::from_utf8_lossy(&body);
+
+        let (title, text) = if content_type.starts_with("text/plain") {
+            (String::new(), page.into_owned())
+        } else {
+            extract_readable_text(&page)
+        };
+
+        let total_chars = text.chars().count();
+        let truncated = total_chars > max_chars;
+        let text = if truncated {
+            let mut cut: String = text.chars().take(max_chars).collect();
+            cut.push_str("\n\n[... page truncated ...]");
+            cut
+        } else {
+            text
+        };
+
+        debug!("[BuiltIn:Fetch] Extracted {} characters from {} (truncated: {})", total_chars, url, truncated);
 
         Ok(json!({
-            "total": stats.total_count,
-            "discoveries": stats.discovery_count,
-            "insights": stats.insight_count,
-            "deadends": stats.deadend_count,
-            "cached_queries": stats.query_result_count,
-            "plans": stats.plan_count,
-            "feedback": stats.feedback_count,
-            "context": stats.context_count
+            "text": text,
+            "title": title,
+            "url": url,
+            "length": total_chars,
+            "truncated": truncated
         }))
     }
 
-    async fn builtin_current_date(&self, _params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
+    async fn builtin_current_date(&self, params: HashMap<String, String>) -> Result<Value, anyhow::Error> {
         use std::time::{SystemTime, UNIX_EPOCH};
 
         let now = SystemTime::now();
@@ -1072,31 +2376,22 @@ impl ToolExecutor {
             .map_err(|e| anyhow::anyhow!("System time error: {}", e))?
             .as_secs();
 
-        // Convert timestamp to date components (UTC)
-        // Using algorithm from: https://howardhinnant.github.io/date_algorithms.html
-        let days_since_epoch = (timestamp / 86400) as i64;
-        let z = days_since_epoch + 719468; // Days from 0000-03-01 to 1970-01-01
-        let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
-        let doe = (z - era * 146097) as u32; // Day of era
-        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // Year of era
-        let y = yoe as i64 + era * 400;
-        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // Day of year
-        let mp = (5 * doy + 2) / 153;
-        let d = doy - (153 * mp + 2) / 5 + 1;
-        let m = if mp < 10 { mp + 3 } else { mp - 9 };
-        let year = if m <= 2 { y + 1 } else { y };
-        let month = m;
-        let day = d;
+        // Shift into the requested (or system local) timezone before splitting into fields
+        let zone = crate::timezone::resolve(params.get("timezone").map(|s| s.as_str()), timestamp as i64)?;
+        let local = timestamp as i64 + zone.offset_secs;
+        let (year, month, day) = crate::timezone::civil_from_days(local.div_euclid(86400));
 
         // Time components
-        let seconds_today = timestamp % 86400;
+        let seconds_today = local.rem_euclid(86400);
         let hour = seconds_today / 3600;
         let minute = (seconds_today % 3600) / 60;
         let second = seconds_today % 60;
 
-        // Format as ISO 8601: YYYY-MM-DDTHH:MM:SSZ
-        let iso8601 = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
-            year, month, day, hour, minute, second);
+        // Format as ISO 8601: YYYY-MM-DDTHH:MM:SS with Z or the numeric offset
+        let utc_offset = zone.offset_string();
+        let iso8601 = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+            year, month, day, hour, minute, second,
+            if zone.offset_secs == 0 { "Z" } else { utc_offset.as_str() });
 
         // Human-friendly format: Month DD, YYYY
         let month_names = ["January", "February", "March", "April", "May", "June",
@@ -1104,7 +2399,7 @@ impl ToolExecutor {
         let month_name = month_names[(month - 1) as usize];
         let friendly = format!("{} {}, {}", month_name, day, year);
 
-        debug_println!("[BuiltIn:Date] Current date: {}", iso8601);
+        debug!("[BuiltIn:Date] Current date: {}", iso8601);
 
         Ok(json!({
             "iso8601": iso8601,
@@ -1112,7 +2407,9 @@ impl ToolExecutor {
             "timestamp": timestamp,
             "year": year,
             "month": month,
-            "day": day
+            "day": day,
+            "timezone": zone.name,
+            "utc_offset": utc_offset
         }))
     }
 
@@ -1124,13 +2421,27 @@ impl ToolExecutor {
         for tool_name in &self.config.tools.builtin {
             let (description, parameters) = match tool_name.as_str() {
                 "pdf_extract" => (
-                    "Extracts text content from a PDF file at a given URL. Returns the full text content of the PDF document.".to_string(),
-                    vec![ParameterDescription {
-                        name: "url".to_string(),
-                        param_type: "string".to_string(),
-                        description: "URL of the PDF file to extract text from. Must be a valid HTTP/HTTPS URL pointing to a PDF document.".to_string(),
-                        required: true,
-                    }]
+                    "Extracts text content from a PDF file at a given URL or local path. Returns the text plus page_count; for long documents request a page range and paginate across calls.".to_string(),
+                    vec![
+                        ParameterDescription {
+                            name: "url".to_string(),
+                            param_type: "string".to_string(),
+                            description: "HTTP/HTTPS URL, file:// URL, or local filesystem path of the PDF document to extract text from.".to_string(),
+                            required: true,
+                        },
+                        ParameterDescription {
+                            name: "start_page".to_string(),
+                            param_type: "number".to_string(),
+                            description: "Optional first page to extract (1-based, default: 1)".to_string(),
+                            required: false,
+                        },
+                        ParameterDescription {
+                            name: "end_page".to_string(),
+                            param_type: "number".to_string(),
+                            description: "Optional last page to extract, inclusive (default: last page)".to_string(),
+                            required: false,
+                        },
+                    ]
                 ),
                 "memory_store" => (
                     "Store a new memory in shared memory for other agents to access. Types: discovery (key findings), insight (observations), deadend (failed approaches), context (general notes), feedback (agent feedback).".to_string(),
@@ -1162,7 +2473,7 @@ impl ToolExecutor {
                     ]
                 ),
                 "memory_search" => (
-                    "Search shared memory for similar content using semantic search. Returns top matching memories.".to_string(),
+                    "Search shared memory for similar content using semantic search. Returns top matching memories, best first, each with a cosine similarity score.".to_string(),
                     vec![
                         ParameterDescription {
                             name: "query".to_string(),
@@ -1182,6 +2493,18 @@ impl ToolExecutor {
                             description: "Maximum number of results (default: 5)".to_string(),
                             required: false,
                         },
+                        ParameterDescription {
+                            name: "agent".to_string(),
+                            param_type: "string".to_string(),
+                            description: "Optional: only memories written by this agent (e.g. your own name, or 'supervisor')".to_string(),
+                            required: false,
+                        },
+                        ParameterDescription {
+                            name: "min_score".to_string(),
+                            param_type: "number".to_string(),
+                            description: "Optional: drop results whose similarity score (-1 to 1) is below this, e.g. 0.5".to_string(),
+                            required: false,
+                        },
                     ]
                 ),
                 "memory_get_discoveries" => (
@@ -1204,13 +2527,87 @@ impl ToolExecutor {
                     "Get the research plan for this query. Shows the strategy and sub-questions assigned.".to_string(),
                     vec![]
                 ),
+                "memory_get_by_agent" => (
+                    "Get every memory written by one agent, oldest first. Use it to review your own earlier notes or one colleague's work.".to_string(),
+                    vec![
+                        ParameterDescription {
+                            name: "agent".to_string(),
+                            param_type: "string".to_string(),
+                            description: "Agent name whose memories to return".to_string(),
+                            required: true,
+                        },
+                        ParameterDescription {
+                            name: "type".to_string(),
+                            param_type: "string".to_string(),
+                            description: "Optional: filter by memory type (discovery, insight, deadend, etc)".to_string(),
+                            required: false,
+                        },
+                    ]
+                ),
                 "memory_stats" => (
                     "Get statistics about shared memory usage (counts of each memory type).".to_string(),
                     vec![]
                 ),
+                "web_search" => (
+                    "Searches the web and returns result titles, URLs and snippets. Use for current events, facts, and finding sources to cite.".to_string(),
+                    vec![
+                        ParameterDescription {
+                            name: "query".to_string(),
+                            param_type: "string".to_string(),
+                            description: "Search query using natural keywords. Be specific: include names, dates, or context.".to_string(),
+                            required: true,
+                        },
+                        ParameterDescription {
+                            name: "limit".to_string(),
+                            param_type: "number".to_string(),
+                            description: "Maximum number of results (default: 8)".to_string(),
+                            required: false,
+                        },
+                    ]
+                ),
+                "fetch_url" => (
+                    "Downloads a web page and returns its readable article text and title, with navigation and scripts removed. Use to read pages found via search. For PDFs use pdf_extract.".to_string(),
+                    vec![
+                        ParameterDescription {
+                            name: "url".to_string(),
+                            param_type: "string".to_string(),
+                            description: "HTTP/HTTPS URL of the page to read".to_string(),
+                            required: true,
+                        },
+                        ParameterDescription {
+                            name: "max_chars".to_string(),
+                            param_type: "number".to_string(),
+                            description: "Maximum characters of text to return (default and cap: 20000)".to_string(),
+                            required: false,
+                        },
+                    ]
+                ),
+                "calculate" => (
+                    "Evaluate an arithmetic expression exactly instead of computing it yourself. Supports + - * / % ^, parentheses, pi, e and sqrt, abs, exp, ln, log (base 10), log2, sin, cos, tan, asin, acos, atan, floor, ceil, round, pow, min, max. Returns result and expression.".to_string(),
+                    vec![ParameterDescription {
+                        name: "expression".to_string(),
+                        param_type: "string".to_string(),
+                        description: "Expression to evaluate, e.g. '(1250 - 980) / 980 * 100' or 'sqrt(2) * 3^4'".to_string(),
+                        required: true,
+                    }]
+                ),
+                "read_file" => (
+                    format!("Read a UTF-8 text file from the user's allowed directory. Returns content, the resolved path and size in bytes; files over {} KB are cut off with truncated: true.", READ_FILE_MAX_BYTES / 1024),
+                    vec![ParameterDescription {
+                        name: "path".to_string(),
+                        param_type: "string".to_string(),
+                        description: "Path of the file, relative to the allowed directory (absolute paths must be inside it)".to_string(),
+                        required: true,
+                    }]
+                ),
                 "current_date" => (
-                    "Get the current date and time. Returns both ISO 8601 format (iso8601) and human-friendly format (friendly: 'October 04, 2025'). Use friendly format for search queries and API calls that expect readable dates. No parameters required.".to_string(),
-                    vec![]
+                    "Get the current date and time. Returns both ISO 8601 format (iso8601) and human-friendly format (friendly: 'October 04, 2025'), plus the timezone and utc_offset used. Use friendly format for search queries and API calls that expect readable dates. Defaults to the system local timezone.".to_string(),
+                    vec![ParameterDescription {
+                        name: "timezone".to_string(),
+                        param_type: "string".to_string(),
+                        description: "Optional IANA timezone ('America/New_York', 'Europe/London') or fixed offset ('+05:30', 'UTC-8')".to_string(),
+                        required: false,
+                    }]
                 ),
                 _ => continue,
             };
@@ -1275,42 +2672,734 @@ pub struct ParameterDescription {
 
 #[allow(dead_code)]
 pub fn load_tools_config(path: &str) -> Result<ToolsConfig, anyhow::Error> {
-    debug_println!("[TOOLS] Loading tools configuration from: {}", path);
+    debug!("[TOOLS] Loading tools configuration from: {}", path);
     let contents = std::fs::read_to_string(path)?;
 
     // Handle empty or invalid JSON files
     if contents.trim().is_empty() {
-        debug_println!("[TOOLS] Configuration file is empty");
-        return Ok(ToolsConfig {
-            tools: Tools {
-                builtin: Vec::new(),
-                http: Vec::new(),
-                mcp: Vec::new(),
-            }
-        });
+        debug!("[TOOLS] Configuration file is empty");
+        return Ok(ToolsConfig { tools: Tools::default() });
     }
 
     let config: ToolsConfig = serde_json::from_str(&contents)
         .map_err(|e| anyhow::anyhow!("Failed to parse tools.json: {}", e))?;
 
-    debug_println!("[TOOLS] Successfully loaded {} HTTP tools and {} MCP servers",
+    debug!("[TOOLS] Successfully loaded {} HTTP tools and {} MCP servers",
         config.tools.http.len(),
         config.tools.mcp.len());
 
     Ok(config)
 }
 
-#[derive(Debug, Deserialize)]
-struct ApiKeysConfig {
-    keys: HashMap<String, String>,
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiKeysConfig {
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+    #[serde(default)]
+    pub env_file: Option<String>,  // dotenv file with more keys, relative to api_keys.toml
 }
 
-pub fn load_api_keys(path: &std::path::Path) -> Result<HashMap<String, String>, anyhow::Error> {
+pub fn load_api_keys(path: &std::path::Path) -> Result<ApiKeysConfig, anyhow::Error> {
     if !path.exists() {
-        return Ok(HashMap::new());
+        return Ok(ApiKeysConfig::default());
     }
 
     let contents = std::fs::read_to_string(path)?;
     let config: ApiKeysConfig = toml::from_str(&contents)?;
-    Ok(config.keys)
+    Ok(config)
+}
+
+/// `KEY=value` lines of a dotenv file. Blank lines, `#` comments and an `export ` prefix
+/// are skipped; values may be quoted, and unquoted values end at ` #`.
+fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    let mut keys = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) if value.len() > 1 && value.ends_with(quote) => &value[1..value.len() - 1],
+            _ => value.split(" #").next().unwrap_or(value).trim_end(),
+        };
+        keys.insert(key.trim().to_string(), value.to_string());
+    }
+    keys
+}
+
+/// Output of `${cmd:...}` secret commands, so each runs once per process
+static SECRET_COMMAND_OUTPUT: Lazy<StdMutex<HashMap<String, String>>> = Lazy::new(|| StdMutex::new(HashMap::new()));
+
+/// Run a secret command (e.g. `op read op://vault/api/token`) through the shell and return
+/// its stdout without the trailing newline. Only successful results are cached.
+async fn run_secret_command(command: &str) -> Result<String, anyhow::Error> {
+    if let Some(secret) = SECRET_COMMAND_OUTPUT.lock().ok().and_then(|cache| cache.get(command).cloned()) {
+        return Ok(secret);
+    }
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    let output = shell.stdin(Stdio::null()).output().await
+        .map_err(|e| anyhow::anyhow!("could not run `{}`: {}", command, e))?;
+    if !output.status.success() {
+        // stderr only: stdout would be the secret
+        return Err(anyhow::anyhow!("`{}` failed ({}): {}", command, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let secret = String::from_utf8(output.stdout)
+        .map_err(|_| anyhow::anyhow!("`{}` printed something that isn't UTF-8", command))?
+        .trim_end_matches(['\r', '\n'])
+        .to_string();
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("`{}` printed nothing", command));
+    }
+    if let Ok(mut cache) = SECRET_COMMAND_OUTPUT.lock() {
+        cache.insert(command.to_string(), secret.clone());
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{spawn_test_http_server, TestResponse};
+
+    fn build_request(method: &str, params: &[(&str, Value)]) -> reqwest::Request {
+        build_request_with(method, json!({}), params).unwrap()
+    }
+
+    /// A minimal HTTP tool definition with the fields in `extra` added or overridden
+    fn http_tool(method: &str, endpoint: &str, extra: Value) -> HttpTool {
+        let mut def = json!({"name": "t", "description": "d", "endpoint": endpoint, "method": method, "parameters": {}, "response_format": "json"});
+        def.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(def).unwrap()
+    }
+
+    /// Config with only `http` tools and no result cap
+    fn tools_config(http: Vec<HttpTool>) -> ToolsConfig {
+        ToolsConfig { tools: Tools { http, max_result_bytes: 0, ..Default::default() } }
+    }
+
+    fn build_request_with(method: &str, extra: Value, params: &[(&str, Value)]) -> anyhow::Result<reqwest::Request> {
+        let tool = http_tool(method, "http://example.com/api", extra);
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        let params: HashMap<String, Value> = params.iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        let builder = reqwest::Client::new().request(method.clone(), "http://example.com/api");
+        Ok(attach_params(builder, &method, &tool, &params)?.build().unwrap())
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let keys = parse_env_file("# secrets\n\nexport GITHUB_TOKEN=ghp_123\nQUOTED = \"a b # c\"\nSINGLE='x'\nPLAIN=value # comment\nnot a pair\n");
+        assert_eq!(keys.get("GITHUB_TOKEN").map(String::as_str), Some("ghp_123"));
+        assert_eq!(keys.get("QUOTED").map(String::as_str), Some("a b # c"));
+        assert_eq!(keys.get("SINGLE").map(String::as_str), Some("x"));
+        assert_eq!(keys.get("PLAIN").map(String::as_str), Some("value"));
+        assert_eq!(keys.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_substitute_secrets_sources_and_priority() {
+        let config = tools_config(Vec::new());
+        let mut executor = ToolExecutor::new(config, HashMap::from([("SHARED_KEY".to_string(), "from-toml".to_string())]));
+        executor.env_file_keys = HashMap::from([
+            ("SHARED_KEY".to_string(), "from-env-file".to_string()),
+            ("DOTENV_ONLY_KEY".to_string(), "dotenv".to_string()),
+        ]);
+
+        assert_eq!(executor.substitute_secrets("Bearer ${SHARED_KEY}").await, "Bearer from-toml");
+        assert_eq!(executor.substitute_secrets("${DOTENV_ONLY_KEY}-${DOTENV_ONLY_KEY}").await, "dotenv-dotenv");
+        // Unknown names stay as they are (and don't stop later substitutions)
+        assert_eq!(executor.substitute_secrets("${BOB_BAR_NO_SUCH_KEY} ${SHARED_KEY}").await, "${BOB_BAR_NO_SUCH_KEY} from-toml");
+        if cfg!(unix) {
+            assert_eq!(executor.substitute_secrets("token=${cmd:echo s3cret}").await, "token=s3cret");
+            assert_eq!(executor.substitute_secrets("${cmd:exit 3}").await, "${cmd:exit 3}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_tool_times_out() {
+        // Server that accepts connections but never answers
+        let server = spawn_test_http_server(vec![TestResponse::Silent]).await;
+
+        let tool: HttpTool = serde_json::from_value(json!({
+            "name": "slow",
+            "description": "never responds",
+            "endpoint": format!("{}/slow", server.url()),
+            "method": "GET",
+            "parameters": {},
+            "response_format": "json",
+            "timeout_secs": 1
+        })).unwrap();
+        let config = tools_config(vec![tool]);
+        let executor = ToolExecutor::new(config, HashMap::new());
+
+        let started = Instant::now();
+        let err = executor.execute_http_tool("slow", HashMap::new()).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err.to_string().contains("timed out after 1s"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_http_tool_timeout_defaults_to_30s() {
+        assert_eq!(http_tool("GET", "http://example.com", json!({})).timeout_secs, 30);
+    }
+
+    #[test]
+    fn test_extract_json_path_fields_and_indexes() {
+        let json = json!({"data": {"results": [{"value": 1}, {"value": 2}]}, "matrix": [[1, 2], [3, 4]]});
+        assert_eq!(extract_json_path(&json, "data.results[1].value").unwrap(), json!(2));
+        assert_eq!(extract_json_path(&json, "matrix[1][0]").unwrap(), json!(3));
+        assert!(extract_json_path(&json, "data.missing").is_err());
+        assert!(extract_json_path(&json, "data.results[5]").is_err());
+        assert!(extract_json_path(&json, "data[0]").is_err());
+        assert!(extract_json_path(&json, "data.results[x]").is_err());
+        assert!(extract_json_path(&json, "data.results[0").is_err());
+    }
+
+    #[test]
+    fn test_extract_json_path_wildcards_and_slices() {
+        let json = json!({"results": [
+            {"title": "a", "tags": [{"name": "x"}, {"name": "y"}]},
+            {"title": "b", "tags": []},
+            {"tags": [{"name": "z"}]},
+            {"title": "d", "tags": [{"name": "w"}]}
+        ]});
+        assert_eq!(extract_json_path(&json, "results[*].title").unwrap(), json!(["a", "b", "d"]));
+        assert_eq!(extract_json_path(&json, "results[0:2].title").unwrap(), json!(["a", "b"]));
+        assert_eq!(extract_json_path(&json, "results[2:].tags[0].name").unwrap(), json!(["z", "w"]));
+        assert_eq!(extract_json_path(&json, "results[:10]").unwrap().as_array().unwrap().len(), 4);
+        assert_eq!(extract_json_path(&json, "results[3:1]").unwrap(), json!([]));
+        // Nested wildcards flatten into one list
+        assert_eq!(extract_json_path(&json, "results[*].tags[*].name").unwrap(), json!(["x", "y", "z", "w"]));
+        assert_eq!(extract_json_path(&json!({"m": [[1, 2], [3]]}), "m[*][*]").unwrap(), json!([1, 2, 3]));
+        assert!(extract_json_path(&json, "results[0].title[*]").is_err());
+        assert!(extract_json_path(&json, "results[a:2]").is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_ladder_resets_after_window() {
+        let start = Instant::now();
+        let window = Duration::from_secs(10);
+        let mut usage = ToolUsage::new(start, None);
+        let delays: Vec<u64> = (0..7).map(|_| usage.ladder_delay(start, window)).collect();
+        assert_eq!(delays, vec![0, 100, 250, 500, 1000, 1500, 1500]);
+        assert_eq!(usage.ladder_delay(start + Duration::from_secs(11), window), 0);
+        assert_eq!(usage.ladder_delay(start + Duration::from_secs(12), window), 100);
+    }
+
+    #[test]
+    fn test_rate_limit_budget_spaces_out_calls() {
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        // 30 calls a minute: a burst of 30, then one every 2 seconds
+        let mut usage = ToolUsage::new(start, Some(30));
+        assert!((0..30).all(|_| usage.budget_delay(start, 30, window) == 0));
+        assert_eq!(usage.budget_delay(start, 30, window), 2000);
+        assert_eq!(usage.budget_delay(start, 30, window), 4000);
+
+        // Waiting refills the bucket, but never above the budget
+        let mut usage = ToolUsage::new(start, Some(2));
+        assert_eq!(usage.budget_delay(start, 2, window), 0);
+        assert_eq!(usage.budget_delay(start, 2, window), 0);
+        assert_eq!(usage.budget_delay(start, 2, window), 30000);
+        let later = start + Duration::from_secs(600);
+        assert_eq!(usage.budget_delay(later, 2, window), 0);
+        assert_eq!(usage.budget_delay(later, 2, window), 0);
+        assert_eq!(usage.budget_delay(later, 2, window), 30000);
+    }
+
+    #[test]
+    fn test_rate_limit_shared_by_host_or_group() {
+        let tool = |name: &str, endpoint: &str, group: Option<&str>| -> HttpTool {
+            serde_json::from_value(json!({
+                "name": name, "description": "d", "endpoint": endpoint, "method": "GET",
+                "parameters": {}, "response_format": "json", "rate_limit_group": group
+            })).unwrap()
+        };
+        let config = tools_config(vec![
+            tool("maps", "https://www.googleapis.com/maps/{id}", None),
+            tool("books", "https://googleapis.com:443/books/v1", None),
+            tool("weather", "https://api.weather.example/now", None),
+            tool("forecast", "https://forecast.example/v2", Some("weather-co")),
+            tool("alerts", "https://alerts.example/v1", Some("weather-co")),
+        ]);
+        assert_eq!(config.tools.http[0].rate_limit_key(), "googleapis.com");
+        assert_eq!(config.tools.http[3].rate_limit_key(), "weather-co");
+
+        let executor = ToolExecutor::new(config, HashMap::new());
+        let delays: Vec<u64> = ["maps", "books", "weather", "forecast", "alerts", "maps"]
+            .iter()
+            .map(|name| executor.calculate_rate_limit_delay(name))
+            .collect();
+        assert_eq!(delays, vec![0, 100, 0, 0, 100, 250]);
+    }
+
+    #[test]
+    fn test_parse_duckduckgo_html() {
+        let html = r#"
+            <div class="result results_links"><h2 class="result__title">
+              <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.rust-lang.org%2F&amp;rut=abc">Rust <b>Programming</b> Language</a>
+            </h2>
+            <a class="result__snippet" href="//duckduckgo.com/l/?uddg=x">A language empowering everyone &amp; more.</a></div>
+            <div class="result results_links"><h2 class="result__title">
+              <a rel="nofollow" class="result__a" href="https://duckduckgo.com/y.js?ad_provider=x">Sponsored</a>
+            </h2></div>
+            <div class="result results_links"><h2 class="result__title">
+              <a rel="nofollow" class="result__a" href="https://doc.rust-lang.org/book/">The Book</a>
+            </h2></div>
+        "#;
+        let results = parse_duckduckgo_html(html, 8);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], json!({
+            "title": "Rust Programming Language",
+            "url": "https://www.rust-lang.org/",
+            "snippet": "A language empowering everyone & more."
+        }));
+        assert_eq!(results[1]["url"], "https://doc.rust-lang.org/book/");
+        assert_eq!(results[1]["snippet"], "");
+
+        assert_eq!(parse_duckduckgo_html(html, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_page_range() {
+        assert_eq!(resolve_page_range(None, None, 300).unwrap(), (1, 300));
+        assert_eq!(resolve_page_range(Some(10), Some(20), 300).unwrap(), (10, 20));
+        assert_eq!(resolve_page_range(Some(0), Some(500), 300).unwrap(), (1, 300));
+        assert_eq!(resolve_page_range(Some(290), None, 300).unwrap(), (290, 300));
+        assert!(resolve_page_range(Some(301), None, 300).is_err());
+        assert!(resolve_page_range(Some(20), Some(10), 300).is_err());
+    }
+
+    #[test]
+    fn test_resolve_local_pdf_path_restricts_to_allowed_dir() {
+        let root = std::env::temp_dir().join(format!("bobbar-pdf-test-{}", std::process::id()));
+        let allowed = root.join("allowed");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::write(allowed.join("report.pdf"), b"%PDF").unwrap();
+        std::fs::write(root.join("secret.pdf"), b"%PDF").unwrap();
+        let allowed_str = allowed.to_str().unwrap();
+
+        let inside = resolve_local_pdf_path(allowed.join("report.pdf").to_str().unwrap(), Some(allowed_str)).unwrap();
+        assert!(inside.ends_with("report.pdf"));
+
+        let file_url = format!("file://{}", allowed.join("report.pdf").display());
+        assert!(resolve_local_pdf_path(&file_url, Some(allowed_str)).is_ok());
+
+        let traversal = allowed.join("..").join("secret.pdf");
+        assert!(resolve_local_pdf_path(traversal.to_str().unwrap(), Some(allowed_str)).is_err());
+        // Without a configured directory any readable path is allowed
+        assert!(resolve_local_pdf_path(traversal.to_str().unwrap(), None).is_ok());
+        assert!(resolve_local_pdf_path(root.join("missing.pdf").to_str().unwrap(), None).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_read_path_stays_in_allowed_dir() {
+        let root = std::env::temp_dir().join(format!("bobbar-read-test-{}", std::process::id()));
+        let allowed = root.join("allowed");
+        std::fs::create_dir_all(allowed.join("notes")).unwrap();
+        std::fs::write(allowed.join("notes/todo.txt"), "milk").unwrap();
+        std::fs::write(root.join("secret.txt"), "key").unwrap();
+        let allowed_str = allowed.to_str().unwrap();
+
+        assert!(resolve_read_path("notes/todo.txt", Some(allowed_str)).unwrap().ends_with("notes/todo.txt"));
+        assert!(resolve_read_path(allowed.join("notes/todo.txt").to_str().unwrap(), Some(allowed_str)).is_ok());
+        assert!(resolve_read_path("../secret.txt", Some(allowed_str)).is_err());
+        assert!(resolve_read_path(root.join("secret.txt").to_str().unwrap(), Some(allowed_str)).is_err());
+        assert!(resolve_read_path("notes/todo.txt", None).unwrap_err().to_string().contains("allowed_read_dir"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_decode_text_file() {
+        assert_eq!(decode_text_file(b"hello".to_vec(), 10).unwrap(), ("hello".to_string(), false));
+        assert_eq!(decode_text_file(b"hello".to_vec(), 4).unwrap(), ("hell".to_string(), true));
+        // "é" is two bytes; a cut between them drops the partial character
+        assert_eq!(decode_text_file("aé".as_bytes().to_vec(), 2).unwrap(), ("a".to_string(), true));
+        assert!(decode_text_file(vec![0x89, b'P', b'N', b'G'], 10).is_err());
+    }
+
+    #[test]
+    fn test_extract_readable_text() {
+        let html = r#"<html><head><title>Ignored &amp; Title</title>
+            <meta property="og:title" content="Real Title">
+            <style>body { color: red; }</style><script>var x = "<p>no</p>";</script></head>
+            <body><nav><a href="/">Home</a></nav>
+            <article><h1>Heading</h1><p>First   paragraph<br>continued.</p>
+            <!-- comment --><ul><li>one</li><li>two &lt;3</li></ul></article>
+            <footer>Copyright</footer></body></html>"#;
+        let (title, text) = extract_readable_text(html);
+        assert_eq!(title, "Real Title");
+        assert_eq!(text, "Heading\n\nFirst paragraph\ncontinued.\n\n- one\n\n- two <3");
+    }
+
+    #[test]
+    fn test_extract_readable_text_without_article() {
+        let (title, text) = extract_readable_text("<html><head><title>Plain</title></head><body><div>Body text</div></body></html>");
+        assert_eq!(title, "Plain");
+        assert_eq!(text, "Body text");
+    }
+
+    #[test]
+    fn test_parse_searxng_results() {
+        let response = json!({"results": [
+            {"title": "A", "url": "https://a.example", "content": "first"},
+            {"title": "B", "url": "https://b.example"}
+        ]});
+        let results = parse_searxng_results(&response, 8);
+        assert_eq!(results, vec![
+            json!({"title": "A", "url": "https://a.example", "snippet": "first"}),
+            json!({"title": "B", "url": "https://b.example", "snippet": ""}),
+        ]);
+        assert!(parse_searxng_results(&json!({}), 8).is_empty());
+    }
+
+    #[test]
+    fn test_web_search_config_defaults() {
+        let config: ToolsConfig = serde_json::from_str(r#"{"tools": {"http": [], "mcp": []}}"#).unwrap();
+        assert_eq!(config.tools.web_search.backend, "duckduckgo");
+        assert_eq!(config.tools.web_search.max_results, 8);
+        assert!(config.tools.web_search.searxng_url.is_none());
+    }
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"event: endpoint\nda").is_empty());
+        let events = parser.feed(b"ta: /messages?session=1\n\n: ping\n\ndata: {\"id\":1}\r\n\r\n");
+        assert_eq!(events, vec![
+            SseEvent { event: "endpoint".to_string(), data: "/messages?session=1".to_string() },
+            SseEvent { event: "message".to_string(), data: "{\"id\":1}".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_mcp_http_body() {
+        let json_body = parse_mcp_http_body("application/json", r#"{"jsonrpc":"2.0","id":1,"result":{}}"#);
+        assert_eq!(json_body, vec![json!({"jsonrpc": "2.0", "id": 1, "result": {}})]);
+
+        let sse_body = "event: message\ndata: {\"method\":\"notifications/progress\"}\n\nevent: message\ndata: {\"id\":2,\"result\":{}}\n";
+        let messages = parse_mcp_http_body("text/event-stream", sse_body);
+        assert_eq!(messages, vec![json!({"method": "notifications/progress"}), json!({"id": 2, "result": {}})]);
+
+        assert!(parse_mcp_http_body("application/json", "").is_empty());
+    }
+
+    #[test]
+    fn test_mcp_server_url_transport_config() {
+        let server: McpServer = serde_json::from_value(json!({
+            "name": "remote",
+            "transport": "sse",
+            "url": "http://localhost:8080/sse",
+            "description": "Remote MCP server"
+        })).unwrap();
+        assert_eq!(server.url.as_deref(), Some("http://localhost:8080/sse"));
+        assert!(server.command.is_empty());
+        assert!(server.env.is_empty());
+    }
+
+    #[test]
+    fn test_response_cache_key_ignores_param_order() {
+        let mut a = HashMap::new();
+        a.insert("q".to_string(), "rust".to_string());
+        a.insert("page".to_string(), "2".to_string());
+        let mut b = HashMap::new();
+        b.insert("page".to_string(), "2".to_string());
+        b.insert("q".to_string(), "rust".to_string());
+        assert_eq!(ResponseCache::key("search", &a), ResponseCache::key("search", &b));
+        assert_ne!(ResponseCache::key("search", &a), ResponseCache::key("other", &a));
+    }
+
+    #[test]
+    fn test_response_cache_hits_and_expires() {
+        let mut cache = ResponseCache::default();
+        cache.insert("k".to_string(), json!({"v": 1}));
+        assert_eq!(cache.get("k", Duration::from_secs(60)), Some(json!({"v": 1})));
+        assert_eq!(cache.get("k", Duration::ZERO), None);
+        // Expired entries are dropped
+        assert_eq!(cache.get("k", Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn test_response_cache_evicts_least_recently_used() {
+        let mut cache = ResponseCache::default();
+        for i in 0..RESPONSE_CACHE_CAPACITY {
+            cache.insert(format!("k{}", i), json!(i));
+        }
+        // Touch the oldest entry so k1 becomes the least recently used
+        assert!(cache.get("k0", Duration::from_secs(60)).is_some());
+        cache.insert("new".to_string(), json!("new"));
+        assert_eq!(cache.entries.len(), RESPONSE_CACHE_CAPACITY);
+        assert!(cache.entries.contains_key("k0"));
+        assert!(!cache.entries.contains_key("k1"));
+        assert!(cache.entries.contains_key("new"));
+    }
+
+    #[test]
+    fn test_status_patterns() {
+        assert!(status_matches(503, "5xx"));
+        assert!(status_matches(503, "5XX"));
+        assert!(status_matches(503, "500-599"));
+        assert!(status_matches(100, "1xx"));
+        assert!(!status_matches(404, "500-599"));
+        assert!(!status_matches(204, "20"));
+        assert!(status_matches(204, " 204 "));
+    }
+
+    #[test]
+    fn test_classify_status_precedence() {
+        let tool = |extra: Value| http_tool("GET", "http://localhost", extra);
+
+        let defaults = tool(json!({}));
+        assert_eq!(classify_status(100, &defaults), StatusDisposition::Error);
+        assert_eq!(classify_status(204, &defaults), StatusDisposition::Success);
+        assert_eq!(classify_status(301, &defaults), StatusDisposition::Success);
+        assert_eq!(classify_status(404, &defaults), StatusDisposition::Error);
+        assert_eq!(classify_status(503, &defaults), StatusDisposition::Error);
+
+        // An explicit error wildcard beats a broader acceptable range
+        let tuned = tool(json!({
+            "expected_status": ["2xx", "3xx"],
+            "acceptable_status": ["404", "500-599"],
+            "error_status": ["5xx"]
+        }));
+        assert_eq!(classify_status(100, &tuned), StatusDisposition::Success);
+        assert_eq!(classify_status(204, &tuned), StatusDisposition::Success);
+        assert_eq!(classify_status(301, &tuned), StatusDisposition::Success);
+        assert_eq!(classify_status(404, &tuned), StatusDisposition::Ignore);
+        assert_eq!(classify_status(503, &tuned), StatusDisposition::Error);
+    }
+
+    #[tokio::test]
+    async fn test_http_tool_serves_repeat_calls_from_cache() {
+        let server = spawn_test_http_server(vec![TestResponse::json("{\"ok\":true}")]).await;
+
+        let tool: HttpTool = serde_json::from_value(json!({
+            "name": "lookup",
+            "description": "d",
+            "endpoint": format!("{}/lookup", server.url()),
+            "method": "GET",
+            "parameters": {"q": {"type": "string", "description": "query", "required": true}},
+            "response_format": "json",
+            "cache_ttl_secs": 300
+        })).unwrap();
+        let config = tools_config(vec![tool]);
+        let mut executor = ToolExecutor::new(config, HashMap::new());
+
+        let mut params = HashMap::new();
+        params.insert("q".to_string(), "rust".to_string());
+        let first = executor.execute_http_tool("lookup", params.clone()).await.unwrap();
+        let second = executor.execute_http_tool("lookup", params.clone()).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(server.hits(), 1);
+
+        // A new research session busts the cache
+        executor.set_query_id("next".to_string());
+        executor.execute_http_tool("lookup", params).await.unwrap();
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[test]
+    fn test_retry_delay_prefers_retry_after() {
+        assert_eq!(retry_delay(0, None), Duration::from_secs(1));
+        assert_eq!(retry_delay(2, None), Duration::from_secs(4));
+        assert_eq!(retry_delay(2, Some(" 7 ")), Duration::from_secs(7));
+        assert_eq!(retry_delay(0, Some("Wed, 21 Oct 2015 07:28:00 GMT")), Duration::from_secs(1));
+        assert_eq!(retry_delay(0, Some("3600")), Duration::from_secs(MAX_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(30, None), Duration::from_secs(MAX_RETRY_DELAY_SECS));
+    }
+
+    #[tokio::test]
+    async fn test_http_tool_retries_retryable_status() {
+        let server = spawn_test_http_server(vec![
+            TestResponse::raw("HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+            TestResponse::json("{\"ok\":true}"),
+        ]).await;
+
+        let tool: HttpTool = serde_json::from_value(json!({
+            "name": "flaky",
+            "description": "d",
+            "endpoint": format!("{}/flaky", server.url()),
+            "method": "GET",
+            "parameters": {},
+            "response_format": "json",
+            "retry_count": 2
+        })).unwrap();
+        let config = tools_config(vec![tool]);
+        let executor = ToolExecutor::new(config, HashMap::new());
+
+        let result = executor.execute_http_tool("flaky", HashMap::new()).await.unwrap();
+        assert_eq!(result, json!({"ok": true}));
+        assert_eq!(server.hits(), 2);
+    }
+
+    fn body_json(request: &reqwest::Request) -> Value {
+        let bytes = request.body().and_then(|b| b.as_bytes()).expect("request has no body");
+        serde_json::from_slice(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_get_sends_query_params() {
+        let req = build_request("GET", &[("q", json!("rust"))]);
+        assert_eq!(req.url().query(), Some("q=rust"));
+        assert!(req.body().is_none());
+    }
+
+    #[test]
+    fn test_delete_sends_query_params() {
+        let req = build_request("DELETE", &[("id", json!(42))]);
+        assert_eq!(req.url().query(), Some("id=42"));
+        assert!(req.body().is_none());
+    }
+
+    #[test]
+    fn test_post_sends_json_body() {
+        let req = build_request("POST", &[("name", json!("bob"))]);
+        assert_eq!(req.url().query(), None);
+        assert_eq!(body_json(&req), json!({"name": "bob"}));
+    }
+
+    #[test]
+    fn test_put_sends_json_body() {
+        let req = build_request("PUT", &[("enabled", json!(true))]);
+        assert_eq!(req.url().query(), None);
+        assert_eq!(body_json(&req), json!({"enabled": true}));
+    }
+
+    #[test]
+    fn test_patch_sends_json_body() {
+        let req = build_request("PATCH", &[("count", json!(3))]);
+        assert_eq!(req.url().query(), None);
+        assert_eq!(body_json(&req), json!({"count": 3}));
+    }
+
+    #[test]
+    fn test_custom_method_sends_json_body() {
+        let req = build_request("PROPFIND", &[("depth", json!("1"))]);
+        assert_eq!(req.method().as_str(), "PROPFIND");
+        assert_eq!(body_json(&req), json!({"depth": "1"}));
+    }
+
+    #[test]
+    fn test_post_sends_form_body() {
+        let req = build_request_with("POST", json!({"body_format": "form"}), &[("q", json!("a b"))]).unwrap();
+        assert_eq!(req.headers()[reqwest::header::CONTENT_TYPE], "application/x-www-form-urlencoded");
+        assert_eq!(req.body().and_then(|b| b.as_bytes()), Some(&b"q=a+b"[..]));
+    }
+
+    #[test]
+    fn test_post_sends_raw_template_body() {
+        let extra = json!({
+            "body_format": "raw",
+            "body_template": "<q>{q}</q><n>{limit}</n>",
+            "parameters": {"limit": {"type": "number", "description": "max"}}
+        });
+        let req = build_request_with("POST", extra, &[("q", json!("rust"))]).unwrap();
+        assert_eq!(req.headers()[reqwest::header::CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(req.body().and_then(|b| b.as_bytes()), Some(&b"<q>rust</q><n></n>"[..]));
+
+        // One pass: JSON braces survive and a value that looks like a placeholder isn't expanded
+        let extra = json!({
+            "body_format": "raw",
+            "body_template": "{\"q\": \"{q}\", \"n\": \"{limit}\"}",
+            "parameters": {"limit": {"type": "number", "description": "max"}}
+        });
+        let req = build_request_with("POST", extra, &[("q", json!("{limit}")), ("limit", json!(5))]).unwrap();
+        assert_eq!(req.body().and_then(|b| b.as_bytes()), Some(&br#"{"q": "{limit}", "n": "5"}"#[..]));
+
+        let missing = build_request_with("POST", json!({"body_format": "raw"}), &[]);
+        assert!(missing.unwrap_err().to_string().contains("body_template"));
+        assert!(build_request_with("POST", json!({"body_format": "xml"}), &[]).is_err());
+    }
+
+    #[test]
+    fn test_tools_config_problems() {
+        let config: ToolsConfig = serde_json::from_value(json!({"tools": {
+            "http": [
+                {"name": "search", "description": "", "endpoint": "https://example.com", "method": "GET", "parameters": {}, "response_format": "json", "max_calls_per_window": 0},
+                {"name": "search", "description": "", "endpoint": " ", "method": "POST", "parameters": {}, "response_format": "json", "body_format": "raw", "rate_limit_window_secs": 0}
+            ],
+            "mcp": [
+                {"name": "fs", "transport": "stdio", "description": ""},
+                {"name": "remote", "transport": "websocket", "description": ""}
+            ],
+            "builtin": ["web_search", "web_serch"]
+        }})).unwrap();
+        assert_eq!(config.problems(), vec![
+            "HTTP tool \"search\" has max_calls_per_window 0 (omit it to use the default delays)".to_string(),
+            "tool \"search\" is defined more than once".to_string(),
+            "HTTP tool \"search\" has an empty \"endpoint\"".to_string(),
+            "HTTP tool \"search\" uses body_format \"raw\" but has no \"body_template\"".to_string(),
+            "HTTP tool \"search\" has rate_limit_window_secs 0".to_string(),
+            format!("unknown built-in tool \"web_serch\" (available: {})", BUILTIN_TOOLS.join(", ")),
+            "MCP server \"fs\" uses the stdio transport but has no \"command\"".to_string(),
+            "MCP server \"remote\" has unknown transport \"websocket\" (expected stdio, sse or http)".to_string(),
+        ]);
+
+        assert!(config.provides("search") && config.provides("web_search") && config.provides("fs:read_file"));
+        assert!(!config.provides("weather") && !config.provides("git:log"));
+    }
+
+    #[test]
+    fn test_mcp_server_changes_only_touch_edited_servers() {
+        let server = |name: &str, command: &str| McpServer {
+            name: name.to_string(),
+            transport: "stdio".to_string(),
+            command: command.to_string(),
+            args: Vec::new(),
+            description: String::new(),
+            env: HashMap::new(),
+            url: None,
+            max_result_bytes: None,
+        };
+        let old = vec![server("fs", "mcp-fs"), server("git", "mcp-git"), server("gone", "mcp-old")];
+        let new = vec![server("fs", "mcp-fs"), server("git", "mcp-git-v2"), server("web", "mcp-web")];
+
+        let (stop, start) = mcp_server_changes(&old, &new);
+        assert_eq!(stop, vec!["git".to_string(), "gone".to_string()]);
+        assert_eq!(start.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["git", "web"]);
+        assert_eq!(mcp_server_changes(&new, &new), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_cap_result_truncates_with_marker() {
+        let text = "x".repeat(50);
+        assert_eq!(cap_result(json!(text.clone()), 0), json!(text.clone()));
+        assert_eq!(cap_result(json!(text.clone()), 100), json!(text.clone()));
+        assert_eq!(cap_result(json!(text.clone()), 20), json!(format!("{}\n[truncated 30 bytes]", "x".repeat(20))));
+
+        // `{"text": ...}` results keep their other fields
+        let capped = cap_result(json!({"text": text, "source": "a.pdf"}), 10);
+        assert_eq!(capped["source"], "a.pdf");
+        assert!(capped["text"].as_str().unwrap().ends_with("[truncated 40 bytes]"));
+
+        // Other JSON becomes its cut-off serialization
+        let big = json!({"items": vec![1; 100]});
+        let capped = cap_result(big.clone(), 30);
+        let capped = capped.as_str().unwrap();
+        assert!(capped.starts_with("{\"items\":[1,1,"));
+        assert!(capped.ends_with(&format!("[truncated {} bytes]", big.to_string().len() - 30)));
+        assert_eq!(cap_result(big.clone(), 10_000), big);
+    }
+
+    #[test]
+    fn test_cap_text_respects_char_boundaries() {
+        let capped = cap_text("héllo".to_string(), 2);
+        assert_eq!(capped, "h\n[truncated 5 bytes]");
+    }
+
+    #[test]
+    fn test_max_result_bytes_defaults() {
+        let config: ToolsConfig = serde_json::from_value(json!({"tools": {"http": [], "mcp": []}})).unwrap();
+        assert_eq!(config.tools.max_result_bytes, 100_000);
+        let tool = http_tool("GET", "http://localhost", json!({"max_result_bytes": 2048}));
+        assert_eq!(tool.max_result_bytes, Some(2048));
+    }
 }
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Smallest window we will restore; anything below is treated as corrupt
+const MIN_SIZE: f32 = 200.0;
+
+/// Largest desktop extent (in logical pixels) a saved geometry may reach
+const MAX_EXTENT: f32 = 16384.0;
+
+/// How much of the window must stay reachable for a saved position to be reused
+const VISIBLE_MARGIN: f32 = 100.0;
+
+/// Window size and position saved between runs, in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub x: Option<f32>,
+    #[serde(default)]
+    pub y: Option<f32>,
+}
+
+impl Default for WindowGeometry {
+    fn default() -> Self {
+        WindowGeometry { width: 1200.0, height: 1200.0, x: None, y: None }
+    }
+}
+
+impl WindowGeometry {
+    /// Drop values that can't be on any reasonable desktop. iced doesn't expose the
+    /// monitor layout before the window opens, so the saved position only has to keep
+    /// `VISIBLE_MARGIN` of the window inside the desktop extent (monitors left of or
+    /// above the primary one have negative coordinates); otherwise it is centered again.
+    fn validated(self) -> Option<Self> {
+        let size_ok = |v: f32| v.is_finite() && (MIN_SIZE..=MAX_EXTENT).contains(&v);
+        if !size_ok(self.width) || !size_ok(self.height) {
+            return None;
+        }
+
+        let position_ok = match (self.x, self.y) {
+            (Some(x), Some(y)) => {
+                let on_desktop = |pos: f32, len: f32| {
+                    pos.is_finite() && pos + len > VISIBLE_MARGIN - MAX_EXTENT && pos < MAX_EXTENT - VISIBLE_MARGIN
+                };
+                on_desktop(x, self.width) && on_desktop(y, self.height)
+            }
+            _ => false,
+        };
+        Some(if position_ok { self } else { WindowGeometry { x: None, y: None, ..self } })
+    }
+}
+
+fn geometry_path(dir: &Path) -> PathBuf {
+    dir.join("window.json")
+}
+
+/// Geometry saved by the previous run, if it is still usable
+pub fn load(dir: &Path) -> Option<WindowGeometry> {
+    let contents = std::fs::read_to_string(geometry_path(dir)).ok()?;
+    serde_json::from_str::<WindowGeometry>(&contents).ok()?.validated()
+}
+
+pub fn save(dir: &Path, geometry: &WindowGeometry) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(geometry_path(dir), serde_json::to_string_pretty(geometry)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validated_keeps_reasonable_geometry() {
+        let geometry = WindowGeometry { width: 900.0, height: 700.0, x: Some(-300.0), y: Some(40.0) };
+        assert_eq!(geometry.validated(), Some(geometry));
+    }
+
+    #[test]
+    fn test_validated_recenters_offscreen_position() {
+        let far_left = WindowGeometry { width: 900.0, height: 700.0, x: Some(-20000.0), y: Some(40.0) };
+        assert_eq!(far_left.validated(), Some(WindowGeometry { x: None, y: None, ..far_left }));
+
+        let below = WindowGeometry { x: Some(10.0), y: Some(16300.0), ..far_left };
+        assert_eq!(below.validated().unwrap().x, None);
+
+        let missing_y = WindowGeometry { x: Some(10.0), y: None, ..far_left };
+        assert_eq!(missing_y.validated().unwrap().x, None);
+    }
+
+    #[test]
+    fn test_validated_rejects_bad_size() {
+        assert_eq!(WindowGeometry { width: 50.0, ..Default::default() }.validated(), None);
+        assert_eq!(WindowGeometry { height: f32::NAN, ..Default::default() }.validated(), None);
+    }
+}